@@ -2,6 +2,7 @@
 
 mod error;
 mod management;
+mod metrics;
 mod provision;
 mod watchdog;
 mod workload_manager;
@@ -96,7 +97,7 @@ async fn run() -> Result<(), EdgedError> {
     let image_use_data = ImagePruneData::new(&gc_dir, gc_settings.clone())
         .map_err(|err| EdgedError::from_err("Failed to set up image garbage collection", err))?;
 
-    let runtime = edgelet_docker::DockerModuleRuntime::make_runtime(
+    let mut runtime = edgelet_docker::DockerModuleRuntime::make_runtime(
         &settings,
         create_socket_channel_snd.clone(),
         image_use_data.clone(),
@@ -104,6 +105,10 @@ async fn run() -> Result<(), EdgedError> {
     .await
     .map_err(|err| EdgedError::from_err("Failed to initialize module runtime", err))?;
 
+    if let Some(notary) = content_trust(&settings).await? {
+        runtime = runtime.with_notary(notary);
+    }
+
     let (watchdog_tx, watchdog_rx) =
         tokio::sync::mpsc::unbounded_channel::<edgelet_core::WatchdogAction>();
 
@@ -147,11 +152,25 @@ async fn run() -> Result<(), EdgedError> {
     // appropriate hostname.
     let settings = settings.agent_upstream_resolve(&device_info.gateway_host);
 
+    let crash_loop =
+        edgelet_core::CrashLoopDetector::new(settings.watchdog().crash_loop_threshold());
+
+    let metrics_shutdown = if let Some(metrics_uri) = settings.listen().metrics_uri() {
+        Some(metrics::start(metrics_uri, tasks.clone()).await?)
+    } else {
+        None
+    };
+
+    let (image_prune_tx, image_prune_rx) =
+        tokio::sync::mpsc::unbounded_channel::<edgelet_core::ImagePruneRequest>();
+
     // Start management and workload sockets.
     let management_shutdown = management::start(
         &settings,
         runtime.clone(),
         watchdog_tx.clone(),
+        crash_loop.clone(),
+        image_prune_tx,
         tasks.clone(),
         settings.iotedge_max_requests().management,
     )
@@ -169,15 +188,27 @@ async fn run() -> Result<(), EdgedError> {
         &device_info,
         runtime.clone(),
         &identity_client,
+        crash_loop,
         watchdog_rx,
     );
 
     let edge_agent_bootstrap: String = settings.agent().config().image().to_string();
+    let data_root = if gc_settings.is_enabled() && gc_settings.disk_pressure().is_some() {
+        runtime
+            .data_root()
+            .await
+            .map_err(|err| log::warn!("Failed to get container engine data root: {err:?}"))
+            .ok()
+    } else {
+        None
+    };
     let image_gc = image_gc::image_garbage_collect(
         edge_agent_bootstrap,
         gc_settings.clone(),
+        data_root,
         &runtime,
         image_use_data,
+        image_prune_rx,
     );
 
     tokio::select! {
@@ -202,6 +233,13 @@ async fn run() -> Result<(), EdgedError> {
         .send(())
         .expect("workload API shutdown receiver was dropped");
 
+    if let Some(metrics_shutdown) = metrics_shutdown {
+        log::info!("Stopping metrics listener...");
+        metrics_shutdown
+            .send(())
+            .expect("metrics listener shutdown receiver was dropped");
+    }
+
     let shutdown_timeout = std::time::Duration::from_secs(10);
     let poll_period = std::time::Duration::from_millis(100);
     let mut wait_time = std::time::Duration::from_millis(0);
@@ -236,6 +274,46 @@ async fn run() -> Result<(), EdgedError> {
     }
 }
 
+/// Sets up Notary for the registries configured under content trust, using the root CA
+/// certs that were imported into certd for them.
+async fn content_trust(
+    settings: &edgelet_settings::docker::Settings,
+) -> Result<Option<edgelet_docker::Notary>, EdgedError> {
+    let Some(content_trust) = settings.moby_runtime().content_trust() else {
+        return Ok(None);
+    };
+    let Some(ca_certs) = content_trust.ca_certs() else {
+        return Ok(None);
+    };
+
+    let cert_connector = http_common::Connector::new(settings.endpoints().aziot_certd_url())
+        .map_err(|err| EdgedError::from_err("Invalid Cert Service URL", err))?;
+    let cert_client = aziot_cert_client_async::Client::new(
+        aziot_cert_common_http::ApiVersion::V2020_09_01,
+        cert_connector,
+        1,
+    );
+
+    let mut root_ca_certs = std::collections::BTreeMap::new();
+
+    for (hostname, cert_id) in ca_certs {
+        let cert = cert_client.get_cert(cert_id).await.map_err(|err| {
+            EdgedError::from_err(
+                format!("Failed to get content trust root CA for {hostname}"),
+                err,
+            )
+        })?;
+
+        root_ca_certs.insert(hostname.clone(), cert);
+    }
+
+    let notary_dir = std::path::Path::new(&settings.homedir()).join("notary");
+    let notary = edgelet_docker::Notary::new(&notary_dir, &root_ca_certs, content_trust.servers())
+        .map_err(|err| EdgedError::from_err("Failed to set up content trust", err))?;
+
+    Ok(Some(notary))
+}
+
 fn set_signal_handlers(
     shutdown_tx: tokio::sync::mpsc::UnboundedSender<edgelet_core::WatchdogAction>,
 ) {
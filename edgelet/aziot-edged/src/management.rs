@@ -8,6 +8,8 @@ pub(crate) async fn start<M>(
     settings: &impl edgelet_settings::RuntimeSettings,
     runtime: M,
     sender: tokio::sync::mpsc::UnboundedSender<edgelet_core::WatchdogAction>,
+    crash_loop: edgelet_core::CrashLoopDetector,
+    image_prune: tokio::sync::mpsc::UnboundedSender<edgelet_core::ImagePruneRequest>,
     tasks: std::sync::Arc<std::sync::atomic::AtomicUsize>,
     max_requests: usize,
 ) -> Result<tokio::sync::oneshot::Sender<()>, EdgedError>
@@ -24,8 +26,13 @@ where
         settings.endpoints().aziot_identityd_url(),
         runtime,
         sender,
+        crash_loop,
+        image_prune,
+        settings.allow_module_exec(),
     )
     .map_err(|err| EdgedError::from_err("Invalid Identity Service URL", err))?;
+    let service = edgelet_http_mgmt::Uploads::new(service);
+    let service = edgelet_http::Instrumented::new("management", service);
 
     let socket_name = Listen::get_management_systemd_socket_name();
     let mut incoming = connector
@@ -0,0 +1,156 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use crate::error::Error as EdgedError;
+
+enum Listener {
+    Tcp(tokio::net::TcpListener),
+    Unix(tokio::net::UnixListener),
+}
+
+/// Serves Prometheus metrics on `uri`, which is either a Unix socket or a loopback TCP
+/// address.
+pub(crate) async fn start(
+    uri: &url::Url,
+    tasks: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+) -> Result<tokio::sync::oneshot::Sender<()>, EdgedError> {
+    let listener = bind(uri).await?;
+
+    let (shutdown_tx, mut shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+
+    tasks.fetch_add(1, std::sync::atomic::Ordering::AcqRel);
+
+    tokio::spawn(async move {
+        log::info!("Starting metrics listener...");
+
+        loop {
+            let accepted = tokio::select! {
+                _ = &mut shutdown_rx => break,
+                accepted = accept(&listener) => accepted,
+            };
+
+            match accepted {
+                Ok(stream) => {
+                    tokio::spawn(serve(stream));
+                }
+                Err(err) => log::warn!("Failed to accept metrics connection: {err}"),
+            }
+        }
+
+        tasks.fetch_sub(1, std::sync::atomic::Ordering::AcqRel);
+        log::info!("Metrics listener stopped");
+    });
+
+    Ok(shutdown_tx)
+}
+
+async fn bind(uri: &url::Url) -> Result<Listener, EdgedError> {
+    match uri.scheme() {
+        "http" => {
+            let host = uri
+                .host()
+                .ok_or_else(|| EdgedError::new("Metrics URI has no host"))?;
+
+            let ip: std::net::IpAddr = match host {
+                url::Host::Domain("localhost") => std::net::Ipv4Addr::LOCALHOST.into(),
+                url::Host::Ipv4(ip) => ip.into(),
+                url::Host::Ipv6(ip) => ip.into(),
+                url::Host::Domain(_) => {
+                    return Err(EdgedError::new(
+                        "Metrics URI host must be localhost or an IP address",
+                    ));
+                }
+            };
+
+            // Metrics are not authenticated, so they are only served to local clients.
+            if !ip.is_loopback() {
+                return Err(EdgedError::new(format!(
+                    "Metrics URI host must be a loopback address, not {ip}"
+                )));
+            }
+
+            let port = uri
+                .port()
+                .ok_or_else(|| EdgedError::new("Metrics URI has no port"))?;
+
+            let listener = tokio::net::TcpListener::bind((ip, port))
+                .await
+                .map_err(|err| EdgedError::from_err("Failed to listen on metrics address", err))?;
+
+            Ok(Listener::Tcp(listener))
+        }
+
+        "unix" => {
+            let path = std::path::Path::new(uri.path());
+
+            // Remove the socket left behind by a previous run.
+            if let Err(err) = std::fs::remove_file(path)
+                && err.kind() != std::io::ErrorKind::NotFound
+            {
+                return Err(EdgedError::from_err(
+                    "Failed to remove old metrics socket",
+                    err,
+                ));
+            }
+
+            let listener = tokio::net::UnixListener::bind(path)
+                .map_err(|err| EdgedError::from_err("Failed to listen on metrics socket", err))?;
+
+            Ok(Listener::Unix(listener))
+        }
+
+        scheme => Err(EdgedError::new(format!(
+            "Unsupported metrics URI scheme {scheme}"
+        ))),
+    }
+}
+
+trait Stream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin {}
+
+impl<T> Stream for T where T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin {}
+
+async fn accept(listener: &Listener) -> std::io::Result<Box<dyn Stream>> {
+    match listener {
+        Listener::Tcp(listener) => {
+            let (stream, _) = listener.accept().await?;
+            Ok(Box::new(stream))
+        }
+        Listener::Unix(listener) => {
+            let (stream, _) = listener.accept().await?;
+            Ok(Box::new(stream))
+        }
+    }
+}
+
+async fn serve(stream: Box<dyn Stream>) {
+    let service =
+        hyper::service::service_fn(|req: hyper::Request<hyper::body::Incoming>| async move {
+            let res = if req.method() != hyper::Method::GET {
+                hyper::Response::builder()
+                    .status(hyper::StatusCode::METHOD_NOT_ALLOWED)
+                    .body(http_body_util::Full::default())
+            } else if req.uri().path() == "/metrics" {
+                hyper::Response::builder()
+                    .status(hyper::StatusCode::OK)
+                    .header(
+                        hyper::header::CONTENT_TYPE,
+                        "text/plain; version=0.0.4; charset=utf-8",
+                    )
+                    .body(http_body_util::Full::new(hyper::body::Bytes::from(
+                        edgelet_core::metrics().render(),
+                    )))
+            } else {
+                hyper::Response::builder()
+                    .status(hyper::StatusCode::NOT_FOUND)
+                    .body(http_body_util::Full::default())
+            };
+
+            Ok::<_, std::convert::Infallible>(res.expect("cannot fail to build hyper response"))
+        });
+
+    if let Err(err) = hyper::server::conn::http1::Builder::new()
+        .serve_connection(hyper_util::rt::TokioIo::new(stream), service)
+        .await
+    {
+        log::debug!("Failed to serve metrics connection: {err}");
+    }
+}
@@ -5,38 +5,66 @@ use edgelet_settings::RuntimeSettings;
 
 use crate::error::Error as EdgedError;
 
-pub(crate) async fn run_until_shutdown(
+pub(crate) async fn run_until_shutdown<M>(
     settings: edgelet_settings::docker::Settings,
     device_info: &aziot_identity_common::AzureIoTSpec,
-    runtime: edgelet_docker::DockerModuleRuntime<http_common::Connector>,
+    runtime: M,
     identity_client: &aziot_identity_client_async::Client,
+    crash_loop: edgelet_core::CrashLoopDetector,
     mut action_rx: tokio::sync::mpsc::UnboundedReceiver<edgelet_core::WatchdogAction>,
-) -> Result<edgelet_core::WatchdogAction, EdgedError> {
-    // Run the watchdog every 60 seconds while waiting for any running task to send a
-    // watchdog action.
-    let watchdog_period = std::time::Duration::from_mins(1);
-    let watchdog_retries = settings.watchdog().max_retries();
+) -> Result<edgelet_core::WatchdogAction, EdgedError>
+where
+    M: ModuleRuntime<Config = edgelet_settings::DockerConfig>,
+{
+    // Run the watchdog periodically while waiting for any running task to send a
+    // watchdog action. The period backs off while the watchdog keeps failing.
+    let watchdog_settings = settings.watchdog().clone();
+    let watchdog_retries = watchdog_settings.max_retries();
     let mut watchdog_errors = 0;
+    let mut last_error: Option<std::time::Instant> = None;
+    let mut unhealthy_restarts = UnhealthyRestarts::new();
 
-    let mut watchdog_timer = tokio::time::interval(watchdog_period);
-    watchdog_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // The first run happens immediately.
+    let mut watchdog_deadline = tokio::time::Instant::now();
 
-    log::info!("Starting watchdog with 60 second period...");
+    // Module events bring the next run forward when edgeAgent stops, rather than waiting
+    // out the period. The subscription is renewed by the next run if it ends.
+    let mut events = subscribe_events(&runtime).await;
 
-    loop {
-        let watchdog_next = watchdog_timer.tick();
-        tokio::pin!(watchdog_next);
+    log::info!(
+        "Starting watchdog with {} period...",
+        humantime::format_duration(watchdog_settings.period())
+    );
 
-        let action_next = action_rx.recv();
-        tokio::pin!(action_next);
+    loop {
+        tokio::select! {
+            () = tokio::time::sleep_until(watchdog_deadline) => {
+                if events.is_none() {
+                    events = subscribe_events(&runtime).await;
+                }
 
-        match futures_util::future::select(watchdog_next, action_next).await {
-            futures_util::future::Either::Left((_, _)) => {
-                if let Err(err) = watchdog(&settings, device_info, &runtime, identity_client).await
+                let result = watchdog(
+                    &settings,
+                    device_info,
+                    &runtime,
+                    identity_client,
+                    &crash_loop,
+                    &mut unhealthy_restarts,
+                )
+                .await;
+
+                // Forget errors that are older than the reset window.
+                if last_error.is_some_and(|last| last.elapsed() >= watchdog_settings.reset_window())
                 {
+                    watchdog_errors = 0;
+                    last_error = None;
+                }
+
+                if let Err(err) = result {
                     log::warn!("Error in watchdog: {err}");
 
                     watchdog_errors += 1;
+                    last_error = Some(std::time::Instant::now());
 
                     if watchdog_retries <= watchdog_errors {
                         return Err(EdgedError::new(
@@ -44,9 +72,53 @@ pub(crate) async fn run_until_shutdown(
                         ));
                     }
                 }
+
+                let period = watchdog_settings.backoff_period(watchdog_errors)
+                    + jitter(watchdog_settings.jitter());
+
+                if watchdog_errors > 0 {
+                    log::info!(
+                        "Watchdog has {watchdog_errors} recent error(s); next run in {}",
+                        humantime::format_duration(period)
+                    );
+                }
+
+                watchdog_deadline = tokio::time::Instant::now() + period;
+            }
+
+            event = next_event(&mut events) => {
+                let Some(event) = event else {
+                    log::info!("Module event subscription ended");
+                    continue;
+                };
+
+                match event.kind {
+                    edgelet_core::ModuleEventKind::Die => match event.exit_code {
+                        Some(exit_code) => {
+                            log::info!("Module {} exited with code {exit_code}", event.module);
+                        }
+                        None => log::info!("Module {} exited", event.module),
+                    },
+                    edgelet_core::ModuleEventKind::Oom => {
+                        log::warn!("Module {} ran out of memory", event.module);
+                    }
+                    edgelet_core::ModuleEventKind::Start | edgelet_core::ModuleEventKind::Stop => {
+                        log::debug!("Module {} event: {}", event.module, event.kind);
+                    }
+                }
+
+                // A stopped edgeAgent reports several events in quick succession, so wait
+                // briefly to handle them with a single run. Errors still back off the run.
+                if event.module == settings.agent().name()
+                    && event.kind != edgelet_core::ModuleEventKind::Start
+                    && watchdog_errors == 0
+                {
+                    watchdog_deadline =
+                        watchdog_deadline.min(tokio::time::Instant::now() + AGENT_EVENT_DELAY);
+                }
             }
 
-            futures_util::future::Either::Right((action, _)) => {
+            action = action_rx.recv() => {
                 let action = action.expect("shutdown channel closed");
                 log::info!("{action}");
 
@@ -73,23 +145,127 @@ pub(crate) async fn run_until_shutdown(
     }
 }
 
-async fn watchdog(
+/// Time to wait after edgeAgent stops before checking on it.
+const AGENT_EVENT_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+
+async fn subscribe_events<M>(
+    runtime: &M,
+) -> Option<tokio::sync::mpsc::Receiver<edgelet_core::ModuleEvent>>
+where
+    M: ModuleRuntime,
+{
+    match runtime.events().await {
+        Ok(events) => Some(events),
+        Err(err) => {
+            log::warn!("Failed to subscribe to module events: {err}");
+            None
+        }
+    }
+}
+
+/// The next module event, or `None` once the subscription ends. Never completes while
+/// there is no subscription.
+async fn next_event(
+    events: &mut Option<tokio::sync::mpsc::Receiver<edgelet_core::ModuleEvent>>,
+) -> Option<edgelet_core::ModuleEvent> {
+    let Some(receiver) = events else {
+        return std::future::pending().await;
+    };
+
+    let event = receiver.recv().await;
+    if event.is_none() {
+        *events = None;
+    }
+
+    event
+}
+
+/// Restart count and time of the last restart for each module restarted for being
+/// unhealthy.
+type UnhealthyRestarts = std::collections::HashMap<String, (u32, std::time::Instant)>;
+
+async fn watchdog<M>(
     settings: &edgelet_settings::docker::Settings,
     device_info: &aziot_identity_common::AzureIoTSpec,
-    runtime: &edgelet_docker::DockerModuleRuntime<http_common::Connector>,
+    runtime: &M,
     identity_client: &aziot_identity_client_async::Client,
-) -> Result<(), EdgedError> {
+    crash_loop: &edgelet_core::CrashLoopDetector,
+    unhealthy_restarts: &mut UnhealthyRestarts,
+) -> Result<(), EdgedError>
+where
+    M: ModuleRuntime<Config = edgelet_settings::DockerConfig>,
+{
     log::info!("Watchdog checking Edge runtime status");
     let agent_name = settings.agent().name();
 
-    if let Ok((_, agent_status)) = runtime.get(agent_name).await {
-        let agent_status = agent_status.status();
+    let agent_status = runtime
+        .get(agent_name)
+        .await
+        .ok()
+        .map(|(_, state)| *state.status());
+
+    let result = check_agent(
+        agent_status,
+        settings,
+        device_info,
+        runtime,
+        identity_client,
+    )
+    .await;
+
+    // The agent failed if it exited with an error or could not be brought back up.
+    match (agent_status, &result) {
+        (
+            Some(edgelet_core::ModuleStatus::Running | edgelet_core::ModuleStatus::Unhealthy),
+            Ok(()),
+        ) => crash_loop.record_running(),
+
+        (Some(edgelet_core::ModuleStatus::Failed | edgelet_core::ModuleStatus::Dead), _)
+        | (_, Err(_)) => {
+            if crash_loop.record_failure() {
+                log::warn!(
+                    "Edge runtime is crash looping after {} consecutive failures",
+                    crash_loop.status().consecutive_failures
+                );
+            }
+        }
+
+        _ => {}
+    }
+
+    result?;
+
+    let health_policy = settings.watchdog().health();
+
+    if health_policy.restart_unhealthy {
+        restart_unhealthy_modules(health_policy, runtime, unhealthy_restarts).await;
+    }
+
+    Ok(())
+}
 
+async fn check_agent<M>(
+    agent_status: Option<edgelet_core::ModuleStatus>,
+    settings: &edgelet_settings::docker::Settings,
+    device_info: &aziot_identity_common::AzureIoTSpec,
+    runtime: &M,
+    identity_client: &aziot_identity_client_async::Client,
+) -> Result<(), EdgedError>
+where
+    M: ModuleRuntime<Config = edgelet_settings::DockerConfig>,
+{
+    let agent_name = settings.agent().name();
+
+    if let Some(agent_status) = agent_status {
         match agent_status {
             edgelet_core::ModuleStatus::Running => {
                 log::info!("Edge runtime is running");
             }
 
+            edgelet_core::ModuleStatus::Unhealthy => {
+                log::info!("Edge runtime is running but unhealthy");
+            }
+
             edgelet_core::ModuleStatus::Stopped | edgelet_core::ModuleStatus::Failed => {
                 log::info!("Edge runtime status is {agent_status}, starting module now...");
 
@@ -99,6 +275,7 @@ async fn watchdog(
                     .map_err(|err| EdgedError::from_err("Failed to start Edge runtime", err))?;
 
                 log::info!("Started Edge runtime module {agent_name}");
+                edgelet_core::metrics().record_watchdog_restart(agent_name);
             }
 
             edgelet_core::ModuleStatus::Dead | edgelet_core::ModuleStatus::Unknown => {
@@ -121,10 +298,95 @@ async fn watchdog(
     Ok(())
 }
 
-async fn restart_modules(
-    settings: &edgelet_settings::docker::Settings,
-    runtime: &edgelet_docker::DockerModuleRuntime<http_common::Connector>,
-) {
+/// A random delay of up to `max`.
+fn jitter(max: std::time::Duration) -> std::time::Duration {
+    use std::hash::BuildHasher;
+
+    let Ok(max_nanos) = u64::try_from(max.as_nanos()) else {
+        return max;
+    };
+
+    if max_nanos == 0 {
+        return std::time::Duration::ZERO;
+    }
+
+    // Every RandomState is seeded with fresh random keys, so this is random enough for
+    // spreading out watchdog runs.
+    let random = std::collections::hash_map::RandomState::new().hash_one(0_u8);
+
+    std::time::Duration::from_nanos(random % max_nanos)
+}
+
+async fn restart_unhealthy_modules<M>(
+    policy: &edgelet_settings::watchdog::HealthPolicy,
+    runtime: &M,
+    unhealthy_restarts: &mut UnhealthyRestarts,
+) where
+    M: ModuleRuntime,
+{
+    let modules = match runtime.list_with_details().await {
+        Ok(modules) => modules,
+        Err(err) => {
+            log::warn!("Failed to list modules for health check: {err}");
+
+            return;
+        }
+    };
+
+    let now = std::time::Instant::now();
+
+    for (module, state) in modules {
+        let module_name = module.name();
+
+        if !policy.applies_to(module_name) {
+            continue;
+        }
+
+        if state.status() != &edgelet_core::ModuleStatus::Unhealthy {
+            // A module that has stayed out of trouble long enough starts over with the
+            // initial backoff.
+            if let Some((_, last_restart)) = unhealthy_restarts.get(module_name)
+                && now.duration_since(*last_restart) >= policy.max_backoff
+            {
+                unhealthy_restarts.remove(module_name);
+            }
+
+            continue;
+        }
+
+        let restarts = if let Some((restarts, last_restart)) = unhealthy_restarts.get(module_name) {
+            if policy.max_restarts <= *restarts {
+                log::warn!("{module_name} is still unhealthy after {restarts} restarts");
+
+                continue;
+            }
+
+            if now.duration_since(*last_restart) < policy.backoff(*restarts) {
+                continue;
+            }
+
+            *restarts
+        } else {
+            0
+        };
+
+        log::info!("{module_name} is unhealthy, restarting module now...");
+
+        if let Err(err) = runtime.restart(module_name).await {
+            log::warn!("Failed to restart unhealthy module {module_name}: {err}");
+        } else {
+            log::info!("Restarted unhealthy module {module_name}");
+            edgelet_core::metrics().record_watchdog_restart(module_name);
+        }
+
+        unhealthy_restarts.insert(module_name.to_owned(), (restarts + 1, now));
+    }
+}
+
+async fn restart_modules<M>(settings: &edgelet_settings::docker::Settings, runtime: &M)
+where
+    M: ModuleRuntime<Config = edgelet_settings::DockerConfig>,
+{
     let agent_name = settings.agent().name();
 
     // Check if edgeAgent is running. If edgeAgent does not exist or is not running,
@@ -177,12 +439,15 @@ async fn restart_modules(
     }
 }
 
-async fn create_and_start_agent(
+async fn create_and_start_agent<M>(
     settings: &edgelet_settings::docker::Settings,
     device_info: &aziot_identity_common::AzureIoTSpec,
-    runtime: &edgelet_docker::DockerModuleRuntime<http_common::Connector>,
+    runtime: &M,
     identity_client: &aziot_identity_client_async::Client,
-) -> Result<(), EdgedError> {
+) -> Result<(), EdgedError>
+where
+    M: ModuleRuntime<Config = edgelet_settings::DockerConfig>,
+{
     let agent_name = settings.agent().name();
     let mut agent_spec = settings.agent().clone();
 
@@ -193,7 +458,7 @@ async fn create_and_start_agent(
     log::info!("Creating and starting Edge runtime module {agent_name}...");
 
     if let edgelet_settings::module::ImagePullPolicy::OnCreate = agent_spec.image_pull_policy() {
-        edgelet_core::ModuleRegistry::pull(runtime.registry(), agent_spec.config())
+        edgelet_core::ModuleRegistry::pull(runtime.registry(), agent_spec.config(), None)
             .await
             .map_err(|err| EdgedError::from_err("Failed to pull Edge runtime module", err))?;
     }
@@ -211,6 +476,7 @@ async fn create_and_start_agent(
         .map_err(|err| EdgedError::from_err("Failed to start Edge runtime", err))?;
 
     log::info!("Started Edge runtime module {agent_name}");
+    edgelet_core::metrics().record_watchdog_restart(agent_name);
 
     Ok(())
 }
@@ -98,7 +98,7 @@ where
             })?;
         }
 
-        let service = self.service.clone();
+        let service = edgelet_http::Instrumented::new("workload", self.service.clone());
         tokio::spawn(async move {
             log::info!("Starting workload API...");
 
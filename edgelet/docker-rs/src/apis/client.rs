@@ -90,6 +90,8 @@ where
 pub trait DockerApi {
     fn system_info(&self) -> BoxFutureResult<'_, models::SystemInfo>;
 
+    fn system_version(&self) -> BoxFutureResult<'_, models::SystemVersion>;
+
     fn image_create<'a>(
         &'a self,
         from_image: &'a str,
@@ -102,6 +104,20 @@ pub trait DockerApi {
         platform: &'a str,
     ) -> BoxFutureResult<'a, ()>;
 
+    /// Same as `image_create`, but returns the stream of progress messages instead of waiting
+    /// for the pull to finish. Use `ImageCreateStream` to consume it.
+    fn image_create_stream<'a>(
+        &'a self,
+        from_image: &'a str,
+        from_src: &'a str,
+        repo: &'a str,
+        tag: &'a str,
+        message: &'a str,
+        input_image: &'a str,
+        x_registry_auth: &'a str,
+        platform: &'a str,
+    ) -> BoxFutureResult<'a, Incoming>;
+
     fn images_list<'a>(
         &'a self,
         all: bool,
@@ -116,6 +132,17 @@ pub trait DockerApi {
         no_prune: bool,
     ) -> BoxFutureResult<'a, ()>;
 
+    fn image_tag<'a>(
+        &'a self,
+        name: &'a str,
+        repo: &'a str,
+        tag: &'a str,
+    ) -> BoxFutureResult<'a, ()>;
+
+    /// Loads the images in a `docker save` or OCI layout tarball. Returns the references of
+    /// the loaded images as reported by the engine, which are tags, or IDs for untagged images.
+    fn image_load(&self, archive: BoxBody<Bytes, Infallible>) -> BoxFutureResult<'_, Vec<String>>;
+
     fn container_create<'a>(
         &'a self,
         name: &'a str,
@@ -145,6 +172,8 @@ pub trait DockerApi {
         filters: &'a str,
     ) -> BoxFutureResult<'a, Vec<models::ContainerSummary>>;
 
+    fn container_rename<'a>(&'a self, id: &'a str, name: &'a str) -> BoxFutureResult<'a, ()>;
+
     fn container_restart<'a>(
         &'a self,
         id: &'a str,
@@ -176,12 +205,53 @@ pub trait DockerApi {
         tail: &'a str,
     ) -> BoxFutureResult<'a, Incoming>;
 
+    fn container_exec_create<'a>(
+        &'a self,
+        id: &'a str,
+        body: models::ExecConfig,
+    ) -> BoxFutureResult<'a, models::IdResponse>;
+
+    /// Starts a command created by `container_exec_create` and returns its output. Without a
+    /// TTY, stdout and stderr are multiplexed; use `MultiplexedStream` to separate them.
+    fn container_exec_start<'a>(
+        &'a self,
+        id: &'a str,
+        body: models::ExecStartConfig,
+    ) -> BoxFutureResult<'a, Incoming>;
+
+    fn container_exec_inspect<'a>(
+        &'a self,
+        id: &'a str,
+    ) -> BoxFutureResult<'a, models::ExecInspectResponse>;
+
+    /// Returns a tar archive of the file or directory at `path` in the container.
+    fn container_archive<'a>(&'a self, id: &'a str, path: &'a str)
+    -> BoxFutureResult<'a, Incoming>;
+
+    /// Extracts a tar archive into the directory at `path` in the container.
+    fn put_container_archive<'a>(
+        &'a self,
+        id: &'a str,
+        path: &'a str,
+        archive: BoxBody<Bytes, Infallible>,
+    ) -> BoxFutureResult<'a, ()>;
+
     fn network_create(&self, network_config: models::NetworkConfig) -> BoxFutureResult<'_, ()>;
 
     fn network_list<'a>(
         &'a self,
         filters: &'a str,
     ) -> BoxFutureResult<'a, Vec<serde::de::IgnoredAny>>;
+
+    /// Returns the stream of events that match `filters`, starting from `since` if set. The
+    /// stream stays open until `until` if set, or until the engine shuts down otherwise. Use
+    /// `EventStream` to consume it.
+    fn system_events<'a>(
+        &'a self,
+        since: &'a str,
+        until: &'a str,
+        filters: &'a str,
+    ) -> BoxFutureResult<'a, Incoming>;
 }
 
 macro_rules! api_call {
@@ -288,6 +358,11 @@ where
         ok : [OK]
     }
 
+    api_call! {
+        system_version : get "/version" -> models::SystemVersion ;
+        ok : [OK]
+    }
+
     api_call! {
         image_delete : delete "/images/{name}" ;
         path : [ name: &'a str ] ;
@@ -301,6 +376,13 @@ where
         ok : [OK]
     }
 
+    api_call! {
+        image_tag : post "/images/{name}/tag" ;
+        path : [ name: &'a str ] ;
+        query : [ "repo" = (repo: &'a str), "tag" = (tag: &'a str) ] ;
+        ok : [CREATED]
+    }
+
     api_call! {
         container_create : post "/containers/create" ;
         query : [ "name" = (name: &'a str), "platform" = (platform: &'a str) ] ;
@@ -315,6 +397,13 @@ where
         ok : [NO_CONTENT]
     }
 
+    api_call! {
+        container_rename : post "/containers/{id}/rename" ;
+        path : [ id: &'a str ] ;
+        query : [ "name" = (name: &'a str) ] ;
+        ok : [NO_CONTENT]
+    }
+
     api_call! {
         container_restart : post "/containers/{id}/restart" ;
         path : [ id: &'a str ] ;
@@ -380,6 +469,20 @@ where
         ok : [OK]
     }
 
+    api_call! {
+        system_events : get "/events" -> Incoming ;
+        query : [
+            "since" = (since: &'a str),
+            "until" = (until: &'a str),
+            "filters" = (filters: &'a str)
+        ] ;
+        ok : [OK] ;
+        and_then(response) : {
+            ensure_json_content_type(&response)?;
+            Ok(response.into_body())
+        }
+    }
+
     api_call! {
         image_create : post "/images/create" ;
         query : [
@@ -396,39 +499,128 @@ where
         body : &'a str ;
         ok : [OK] ;
         and_then(response) : {
-            let (parts, body) = response.into_parts();
-
-            anyhow::ensure!(
-                parts.headers.get(hyper::header::CONTENT_TYPE)
-                    .ok_or_else(|| anyhow::anyhow!("expected Content-Type"))?
-                    .to_str()?
-                    .contains("application/json"),
-                "expected JSON Content-Type"
-            );
-
-            let response_bytes = body.collect().await?.to_bytes();
-            let mut last = serde_json::Deserializer::from_slice(&response_bytes)
-                .into_iter::<serde_json::Map<String, serde_json::Value>>()
-                .last()
-                .ok_or_else(|| {
-                    anyhow::anyhow!("received empty response from container runtime")
-                })??;
-
-            if let Some(detail) = last.remove("errorDetail") {
-                let fallback_msg = serde_json::to_string(&detail)?;
-                Err(anyhow::anyhow!(
-                    serde_json::from_value(detail)
-                        .unwrap_or(ApiError {
-                            code: hyper::StatusCode::INTERNAL_SERVER_ERROR,
-                            message: fallback_msg
-                        })
-                ))
-            } else {
-                Ok(())
-            }
+            ensure_json_content_type(&response)?;
+            let mut messages = ImageCreateStream::new(response.into_body());
+            while messages.next().await?.is_some() {}
+
+            Ok(())
+        }
+    }
+
+    api_call! {
+        image_create_stream : post "/images/create" -> Incoming ;
+        query : [
+            "fromImage" = (from_image: &'a str),
+            "fromSrc" = (from_src: &'a str),
+            "repo" = (repo: &'a str),
+            "tag" = (tag: &'a str),
+            "message" = (message: &'a str),
+            "platform" = (platform: &'a str)
+        ] ;
+        header : [
+            "x-registry-auth" = (x_registry_auth: &'a str)
+        ] ;
+        body : &'a str ;
+        ok : [OK] ;
+        and_then(response) : {
+            ensure_json_content_type(&response)?;
+            Ok(response.into_body())
         }
     }
 
+    fn image_load(&self, archive: BoxBody<Bytes, Infallible>) -> BoxFutureResult<'_, Vec<String>> {
+        Box::pin(async move {
+            let uri = (self.configuration.uri_composer)(
+                &self.configuration.base_path,
+                "/images/load?quiet=true",
+            )?;
+
+            let mut builder =
+                hyper::Request::post(&uri).header(hyper::header::CONTENT_TYPE, "application/x-tar");
+            if let Some(agent) = &self.configuration.user_agent {
+                builder = builder.header(hyper::header::USER_AGENT, agent);
+            }
+            let request = builder.body(archive)?;
+
+            // Large archives take longer to upload than other requests are allowed to take,
+            // so this request has no timeout.
+            let response = self.client.request(request).await?;
+
+            if response.status() != hyper::StatusCode::OK {
+                return Err(anyhow::anyhow!(
+                    ApiError::try_from_response(response).await?
+                ));
+            }
+
+            let response_bytes = response.into_body().collect().await?.to_bytes();
+            parse_image_load_response(&response_bytes)
+        })
+    }
+
+    api_call! {
+        container_exec_create : post "/containers/{id}/exec" -> models::IdResponse ;
+        path : [ id: &'a str ] ;
+        body : models::ExecConfig ;
+        ok : [CREATED]
+    }
+
+    api_call! {
+        container_exec_start : post "/exec/{id}/start" -> Incoming ;
+        path : [ id: &'a str ] ;
+        body : models::ExecStartConfig ;
+        ok : [OK] ;
+        and_then(response) : { Ok(response.into_body()) }
+    }
+
+    api_call! {
+        container_exec_inspect : get "/exec/{id}/json" -> models::ExecInspectResponse ;
+        path : [ id: &'a str ] ;
+        ok : [OK]
+    }
+
+    api_call! {
+        container_archive : get "/containers/{id}/archive" -> Incoming ;
+        path : [ id: &'a str ] ;
+        query : [ "path" = (path: &'a str) ] ;
+        ok : [OK] ;
+        and_then(response) : { Ok(response.into_body()) }
+    }
+
+    fn put_container_archive<'a>(
+        &'a self,
+        id: &'a str,
+        path: &'a str,
+        archive: BoxBody<Bytes, Infallible>,
+    ) -> BoxFutureResult<'a, ()> {
+        Box::pin(async move {
+            let query = url::form_urlencoded::Serializer::new(String::new())
+                .append_pair("path", path)
+                .finish();
+            let uri = (self.configuration.uri_composer)(
+                &self.configuration.base_path,
+                &format!("/containers/{id}/archive?{query}"),
+            )?;
+
+            let mut builder =
+                hyper::Request::put(&uri).header(hyper::header::CONTENT_TYPE, "application/x-tar");
+            if let Some(agent) = &self.configuration.user_agent {
+                builder = builder.header(hyper::header::USER_AGENT, agent);
+            }
+            let request = builder.body(archive)?;
+
+            // Like image_load, large archives need longer than the usual request timeout.
+            let response = self.client.request(request).await?;
+
+            if response.status() != hyper::StatusCode::OK {
+                return Err(anyhow::anyhow!(
+                    ApiError::try_from_response(response).await?
+                ));
+            }
+
+            Ok(())
+        })
+    }
+
     api_call! {
         container_logs : get "/containers/{id}/logs" -> Incoming ;
         path : [ id: &'a str ] ;
@@ -446,11 +638,287 @@ where
     }
 }
 
+fn ensure_json_content_type(response: &hyper::Response<Incoming>) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        response
+            .headers()
+            .get(hyper::header::CONTENT_TYPE)
+            .ok_or_else(|| anyhow::anyhow!("expected Content-Type"))?
+            .to_str()?
+            .contains("application/json"),
+        "expected JSON Content-Type"
+    );
+
+    Ok(())
+}
+
+/// Converts the `errorDetail` of a streamed message into an error. Details that are not in
+/// the expected `{code, message}` form are reported verbatim.
+fn error_from_detail(detail: serde_json::Value) -> anyhow::Error {
+    let fallback_msg = detail.to_string();
+    anyhow::anyhow!(serde_json::from_value(detail).unwrap_or(ApiError {
+        code: hyper::StatusCode::INTERNAL_SERVER_ERROR,
+        message: fallback_msg
+    }))
+}
+
+/// Reads the progress messages streamed by `/images/create` as they arrive.
+pub struct ImageCreateStream {
+    body: Incoming,
+    buf: Vec<u8>,
+    messages: std::collections::VecDeque<models::CreateImageInfo>,
+    received: bool,
+    finished: bool,
+}
+
+impl ImageCreateStream {
+    pub fn new(body: Incoming) -> Self {
+        ImageCreateStream {
+            body,
+            buf: Vec::new(),
+            messages: std::collections::VecDeque::new(),
+            received: false,
+            finished: false,
+        }
+    }
+
+    /// Returns the next progress message, or `None` once the pull has finished. Returns an
+    /// error if the engine reports that the pull failed.
+    pub async fn next(&mut self) -> anyhow::Result<Option<models::CreateImageInfo>> {
+        loop {
+            if let Some(message) = self.messages.pop_front() {
+                return Ok(Some(message));
+            }
+
+            if self.finished {
+                return Ok(None);
+            }
+
+            if let Some(frame) = self.body.frame().await {
+                if let Ok(data) = frame?.into_data() {
+                    self.buf.extend_from_slice(&data);
+                }
+
+                let messages = &mut self.messages;
+                let consumed =
+                    parse_image_create_messages(&self.buf, &mut self.received, &mut |message| {
+                        messages.push_back(message);
+                    })?;
+                self.buf.drain(..consumed);
+            } else {
+                self.finished = true;
+
+                anyhow::ensure!(
+                    self.buf.iter().all(u8::is_ascii_whitespace),
+                    "received incomplete response from container runtime"
+                );
+                anyhow::ensure!(
+                    self.received,
+                    "received empty response from container runtime"
+                );
+            }
+        }
+    }
+}
+
+/// Parses the complete messages at the start of `buf` and returns the number of bytes consumed.
+/// A message split across chunks is left in the buffer until the rest of it arrives.
+fn parse_image_create_messages(
+    buf: &[u8],
+    received: &mut bool,
+    on_progress: &mut impl FnMut(models::CreateImageInfo),
+) -> anyhow::Result<usize> {
+    let mut messages =
+        serde_json::Deserializer::from_slice(buf).into_iter::<models::CreateImageInfo>();
+
+    loop {
+        match messages.next() {
+            Some(Ok(mut message)) => {
+                if let Some(detail) = message.error_detail.take() {
+                    return Err(error_from_detail(detail));
+                }
+
+                *received = true;
+                on_progress(message);
+            }
+            Some(Err(err)) if err.is_eof() => break,
+            Some(Err(err)) => return Err(err.into()),
+            None => break,
+        }
+    }
+
+    Ok(messages.byte_offset())
+}
+
+/// Reads the events streamed by `/events` as they arrive.
+pub struct EventStream {
+    body: Incoming,
+    buf: Vec<u8>,
+    events: std::collections::VecDeque<models::EventMessage>,
+}
+
+impl EventStream {
+    pub fn new(body: Incoming) -> Self {
+        EventStream {
+            body,
+            buf: Vec::new(),
+            events: std::collections::VecDeque::new(),
+        }
+    }
+
+    /// Returns the next event, or `None` once the engine has ended the stream.
+    pub async fn next(&mut self) -> anyhow::Result<Option<models::EventMessage>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Some(event));
+            }
+
+            let Some(frame) = self.body.frame().await else {
+                return Ok(None);
+            };
+            if let Ok(data) = frame?.into_data() {
+                self.buf.extend_from_slice(&data);
+            }
+
+            let consumed = parse_events(&self.buf, &mut self.events)?;
+            self.buf.drain(..consumed);
+        }
+    }
+}
+
+/// Parses the complete events at the start of `buf` and returns the number of bytes consumed.
+fn parse_events(
+    buf: &[u8],
+    events: &mut std::collections::VecDeque<models::EventMessage>,
+) -> anyhow::Result<usize> {
+    let mut messages =
+        serde_json::Deserializer::from_slice(buf).into_iter::<models::EventMessage>();
+
+    loop {
+        match messages.next() {
+            Some(Ok(event)) => events.push_back(event),
+            Some(Err(err)) if err.is_eof() => break,
+            Some(Err(err)) => return Err(err.into()),
+            None => break,
+        }
+    }
+
+    Ok(messages.byte_offset())
+}
+
+/// The stream that a frame of a multiplexed stream belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdin,
+    Stdout,
+    Stderr,
+}
+
+/// Reads the frames of a multiplexed stdout and stderr stream, such as the output of an exec
+/// without a TTY. Each frame has an 8-byte header holding the stream and the frame's size.
+pub struct MultiplexedStream {
+    body: Incoming,
+    buf: Vec<u8>,
+    finished: bool,
+}
+
+impl MultiplexedStream {
+    pub fn new(body: Incoming) -> Self {
+        MultiplexedStream {
+            body,
+            buf: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Returns the next frame, or `None` once the stream has ended.
+    pub async fn next(&mut self) -> anyhow::Result<Option<(OutputStream, Bytes)>> {
+        loop {
+            if let Some((frame, consumed)) = parse_multiplexed_frame(&self.buf)? {
+                self.buf.drain(..consumed);
+                return Ok(Some(frame));
+            }
+
+            if self.finished {
+                anyhow::ensure!(
+                    self.buf.is_empty(),
+                    "received incomplete response from container runtime"
+                );
+                return Ok(None);
+            }
+
+            match self.body.frame().await {
+                Some(frame) => {
+                    if let Ok(data) = frame?.into_data() {
+                        self.buf.extend_from_slice(&data);
+                    }
+                }
+                None => self.finished = true,
+            }
+        }
+    }
+}
+
+/// Parses the frame at the start of `buf`, returning it and the number of bytes it took up,
+/// or `None` if the whole frame hasn't arrived yet.
+fn parse_multiplexed_frame(buf: &[u8]) -> anyhow::Result<Option<((OutputStream, Bytes), usize)>> {
+    let Some(header) = buf.get(..8) else {
+        return Ok(None);
+    };
+
+    let stream = match header[0] {
+        0 => OutputStream::Stdin,
+        1 => OutputStream::Stdout,
+        2 => OutputStream::Stderr,
+        other => anyhow::bail!("unknown stream {other} in multiplexed stream"),
+    };
+    let size = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    let end = 8 + usize::try_from(size)?;
+
+    let Some(payload) = buf.get(8..end) else {
+        return Ok(None);
+    };
+
+    Ok(Some(((stream, Bytes::copy_from_slice(payload)), end)))
+}
+
+/// Collects the loaded image references from the messages streamed by `/images/load`.
+fn parse_image_load_response(response: &[u8]) -> anyhow::Result<Vec<String>> {
+    let mut images = Vec::new();
+
+    for message in serde_json::Deserializer::from_slice(response)
+        .into_iter::<serde_json::Map<String, serde_json::Value>>()
+    {
+        let mut message = message?;
+
+        if let Some(detail) = message.remove("errorDetail") {
+            return Err(error_from_detail(detail));
+        }
+
+        if let Some(serde_json::Value::String(stream)) = message.get("stream") {
+            for line in stream.lines() {
+                if let Some(image) = line
+                    .strip_prefix("Loaded image: ")
+                    .or_else(|| line.strip_prefix("Loaded image ID: "))
+                {
+                    images.push(image.trim().to_owned());
+                }
+            }
+        }
+    }
+
+    Ok(images)
+}
+
 #[cfg(test)]
 mod tests {
     use edgelet_test_utils::JsonConnector;
 
-    use super::{ApiError, DockerApi, DockerApiClient};
+    use super::{
+        ApiError, DockerApi, DockerApiClient, EventStream, ImageCreateStream, OutputStream,
+        parse_events, parse_image_create_messages, parse_image_load_response,
+        parse_multiplexed_frame,
+    };
 
     #[tokio::test]
     async fn image_create_stream_ok() {
@@ -493,6 +961,132 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn image_create_stream_progress() {
+        let payload = format!(
+            "{}{}",
+            serde_json::to_string(&serde_json::json!({
+                "id": "LAYER",
+                "status": "Downloading",
+                "progressDetail": {"current": 512, "total": 1024}
+            }))
+            .unwrap(),
+            serde_json::to_string(&serde_json::json!({"status":"STATUS"})).unwrap(),
+        );
+        let client = DockerApiClient::new(JsonConnector::ok(&payload));
+        let body = client
+            .image_create_stream("", "", "", "", "", "", "", "")
+            .await
+            .unwrap();
+
+        let mut stream = ImageCreateStream::new(body);
+        let mut messages = Vec::new();
+        while let Some(message) = stream.next().await.unwrap() {
+            messages.push(message);
+        }
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].id.as_deref(), Some("LAYER"));
+        assert_eq!(messages[0].status.as_deref(), Some("Downloading"));
+        let detail = messages[0].progress_detail.as_ref().unwrap();
+        assert_eq!((detail.current, detail.total), (Some(512), Some(1024)));
+        assert_eq!(messages[1].status.as_deref(), Some("STATUS"));
+    }
+
+    #[test]
+    fn image_create_partial_message() {
+        let buf = br#"{"status":"ONE"}{"status":"TW"#;
+        let mut received = false;
+        let mut statuses = Vec::new();
+
+        let consumed = parse_image_create_messages(buf, &mut received, &mut |message| {
+            statuses.push(message.status.unwrap());
+        })
+        .unwrap();
+
+        assert!(received);
+        assert_eq!(statuses, vec!["ONE"]);
+        assert_eq!(&buf[consumed..], br#"{"status":"TW"#);
+    }
+
+    #[tokio::test]
+    async fn system_events() {
+        let payload = format!(
+            "{}\n{}\n",
+            serde_json::to_string(&serde_json::json!({
+                "Type": "container",
+                "Action": "die",
+                "Actor": {
+                    "ID": "ID",
+                    "Attributes": {"name": "edgeAgent", "exitCode": "137"}
+                },
+                "time": 1_700_000_000
+            }))
+            .unwrap(),
+            serde_json::to_string(&serde_json::json!({"Type":"image","Action":"pull"})).unwrap(),
+        );
+        let client = DockerApiClient::new(JsonConnector::ok(&payload));
+        let body = client.system_events("", "", "").await.unwrap();
+
+        let mut stream = EventStream::new(body);
+        let event = stream.next().await.unwrap().unwrap();
+        assert_eq!(event.r#type.as_deref(), Some("container"));
+        assert_eq!(event.action.as_deref(), Some("die"));
+        assert_eq!(event.time, Some(1_700_000_000));
+        let actor = event.actor.unwrap();
+        assert_eq!(actor.id.as_deref(), Some("ID"));
+        assert_eq!(actor.attributes["name"], "edgeAgent");
+        assert_eq!(actor.attributes["exitCode"], "137");
+
+        let event = stream.next().await.unwrap().unwrap();
+        assert_eq!(event.action.as_deref(), Some("pull"));
+        assert!(event.actor.is_none());
+
+        assert!(stream.next().await.unwrap().is_none());
+    }
+
+    #[test]
+    fn system_events_partial_message() {
+        let buf = br#"{"Action":"start"}
+{"Action":"st"#;
+        let mut events = std::collections::VecDeque::new();
+
+        let consumed = parse_events(buf, &mut events).unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action.as_deref(), Some("start"));
+        assert_eq!(&buf[consumed..], br#"{"Action":"st"#);
+    }
+
+    #[test]
+    fn multiplexed_frames() {
+        let mut buf = vec![1, 0, 0, 0, 0, 0, 0, 3];
+        buf.extend_from_slice(b"out");
+        buf.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 4]);
+        buf.extend_from_slice(b"er");
+
+        let (frame, consumed) = parse_multiplexed_frame(&buf).unwrap().unwrap();
+        assert_eq!(
+            frame,
+            (OutputStream::Stdout, bytes::Bytes::from_static(b"out"))
+        );
+        assert_eq!(consumed, 11);
+
+        // The second frame is missing part of its payload.
+        assert!(parse_multiplexed_frame(&buf[consumed..]).unwrap().is_none());
+        buf.extend_from_slice(b"r\n");
+        let (frame, _) = parse_multiplexed_frame(&buf[consumed..]).unwrap().unwrap();
+        assert_eq!(
+            frame,
+            (OutputStream::Stderr, bytes::Bytes::from_static(b"err\n"))
+        );
+
+        // Partial headers aren't parsed either.
+        assert!(parse_multiplexed_frame(&[1, 0, 0]).unwrap().is_none());
+
+        assert!(parse_multiplexed_frame(&[7, 0, 0, 0, 0, 0, 0, 0]).is_err());
+    }
+
     #[tokio::test]
     async fn image_create_stream_error_unrecognized_structure() {
         let payload = format!(
@@ -518,6 +1112,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn image_load_response() {
+        let response = format!(
+            "{}{}",
+            serde_json::to_string(
+                &serde_json::json!({"stream":"Loaded image: alpine:3.19\nLoaded image: busybox:latest\n"})
+            )
+            .unwrap(),
+            serde_json::to_string(&serde_json::json!({"stream":"Loaded image ID: sha256:0123\n"}))
+                .unwrap(),
+        );
+        assert_eq!(
+            parse_image_load_response(response.as_bytes()).unwrap(),
+            vec!["alpine:3.19", "busybox:latest", "sha256:0123"]
+        );
+
+        let response = serde_json::to_string(
+            &serde_json::json!({"errorDetail":{"code":400,"message":"invalid tar header"}}),
+        )
+        .unwrap();
+        assert_eq!(
+            parse_image_load_response(response.as_bytes())
+                .unwrap_err()
+                .downcast::<ApiError>()
+                .unwrap(),
+            ApiError {
+                code: hyper::StatusCode::BAD_REQUEST,
+                message: "invalid tar header".to_owned()
+            }
+        );
+    }
+
     #[tokio::test]
     async fn images_list_null_repo_tags() {
         let payload = format!(
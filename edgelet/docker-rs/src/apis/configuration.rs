@@ -17,6 +17,9 @@
 // - Docker version <-> API version mapping: https://docs.docker.com/reference/api/engine/#api-version-matrix
 // - API version changelog: https://docs.docker.com/reference/api/engine/version-history
 
+/// The Docker API version whose types this crate models.
+pub const API_VERSION: &str = "1.41";
+
 pub struct Configuration {
     pub base_path: String,
     pub user_agent: Option<String>,
@@ -26,7 +29,7 @@ pub struct Configuration {
 impl Default for Configuration {
     fn default() -> Self {
         Configuration {
-            base_path: "http://localhost/v1.41".to_owned(),
+            base_path: format!("http://localhost/v{API_VERSION}"),
             user_agent: Some("edgelet/0.1.0".to_owned()),
             uri_composer: Box::new(|base_path, path| Ok(format!("{base_path}{path}").parse()?)),
         }
@@ -1,4 +1,7 @@
 mod client;
 mod configuration;
-pub use self::client::{ApiError, DockerApi, DockerApiClient};
-pub use self::configuration::Configuration;
+pub use self::client::{
+    ApiError, DockerApi, DockerApiClient, EventStream, ImageCreateStream, MultiplexedStream,
+    OutputStream,
+};
+pub use self::configuration::{API_VERSION, Configuration};
@@ -28,6 +28,16 @@ pub struct ContainerInspectResponseState {
     pub started_at: Option<String>,
     #[serde(rename = "FinishedAt", skip_serializing_if = "Option::is_none")]
     pub finished_at: Option<String>,
+    #[serde(rename = "Health", skip_serializing_if = "Option::is_none")]
+    pub health: Option<ContainerHealth>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ContainerHealth {
+    #[serde(rename = "Status", skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(rename = "FailingStreak", skip_serializing_if = "Option::is_none")]
+    pub failing_streak: Option<i64>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
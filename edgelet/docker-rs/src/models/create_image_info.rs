@@ -0,0 +1,22 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+/// One of the progress messages streamed by `/images/create`.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct CreateImageInfo {
+    #[serde(rename = "id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "status", skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(rename = "progressDetail", skip_serializing_if = "Option::is_none")]
+    pub progress_detail: Option<ProgressDetail>,
+    #[serde(rename = "errorDetail", skip_serializing_if = "Option::is_none")]
+    pub error_detail: Option<serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ProgressDetail {
+    #[serde(rename = "current", skip_serializing_if = "Option::is_none")]
+    pub current: Option<i64>,
+    #[serde(rename = "total", skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+}
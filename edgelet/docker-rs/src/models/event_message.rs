@@ -0,0 +1,27 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+/// One of the events streamed by `/events`.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct EventMessage {
+    /// The type of object the event is about, such as `container` or `image`.
+    #[serde(rename = "Type", skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<String>,
+    #[serde(rename = "Action", skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    #[serde(rename = "Actor", skip_serializing_if = "Option::is_none")]
+    pub actor: Option<EventActor>,
+    /// Seconds since the Unix epoch.
+    #[serde(rename = "time", skip_serializing_if = "Option::is_none")]
+    pub time: Option<i64>,
+}
+
+/// The object an event is about.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct EventActor {
+    #[serde(rename = "ID", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// For containers, their name, image and labels, and details of the event such as the
+    /// exit code of a container that died.
+    #[serde(rename = "Attributes", default)]
+    pub attributes: std::collections::BTreeMap<String, String>,
+}
@@ -0,0 +1,45 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ExecConfig {
+    #[serde(rename = "AttachStdout", skip_serializing_if = "Option::is_none")]
+    pub attach_stdout: Option<bool>,
+    #[serde(rename = "AttachStderr", skip_serializing_if = "Option::is_none")]
+    pub attach_stderr: Option<bool>,
+    #[serde(rename = "Tty", skip_serializing_if = "Option::is_none")]
+    pub tty: Option<bool>,
+    #[serde(rename = "Env", skip_serializing_if = "Option::is_none")]
+    pub env: Option<Vec<String>>,
+    #[serde(rename = "Cmd")]
+    pub cmd: Vec<String>,
+    #[serde(rename = "WorkingDir", skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    #[serde(rename = "User", skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ExecStartConfig {
+    #[serde(rename = "Detach")]
+    pub detach: bool,
+    #[serde(rename = "Tty")]
+    pub tty: bool,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ExecInspectResponse {
+    #[serde(rename = "ID", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "Running", default)]
+    pub running: bool,
+    /// Not set while the command is running.
+    #[serde(rename = "ExitCode", skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i64>,
+}
+
+/// The response of requests that create an object.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct IdResponse {
+    #[serde(rename = "Id")]
+    pub id: String,
+}
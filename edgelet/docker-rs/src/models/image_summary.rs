@@ -6,4 +6,8 @@ pub struct ImageSummary {
     pub id: String,
     #[serde(rename = "RepoTags")]
     pub repo_tags: Option<Vec<String>>,
+    #[serde(rename = "RepoDigests")]
+    pub repo_digests: Option<Vec<String>>,
+    #[serde(rename = "Size", skip_serializing_if = "Option::is_none")]
+    pub size: Option<i64>,
 }
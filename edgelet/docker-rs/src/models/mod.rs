@@ -31,7 +31,7 @@ pub use self::container_create_body::{
 
 mod container_inspect_response;
 pub use self::container_inspect_response::{
-    ContainerInspectResponse, ContainerInspectResponseState, MountPoint,
+    ContainerHealth, ContainerInspectResponse, ContainerInspectResponseState, MountPoint,
 };
 
 mod container_summary;
@@ -40,6 +40,15 @@ pub use self::container_summary::ContainerSummary;
 mod container_top_response;
 pub use self::container_top_response::ContainerTopResponse;
 
+mod create_image_info;
+pub use self::create_image_info::{CreateImageInfo, ProgressDetail};
+
+mod event_message;
+pub use self::event_message::{EventActor, EventMessage};
+
+mod exec_config;
+pub use self::exec_config::{ExecConfig, ExecInspectResponse, ExecStartConfig, IdResponse};
+
 mod host_config;
 pub use self::host_config::{HostConfig, HostConfigPortBindings};
 
@@ -54,3 +63,6 @@ pub use self::network_config::NetworkConfig;
 
 mod system_info;
 pub use self::system_info::SystemInfo;
+
+mod system_version;
+pub use self::system_version::{ComponentVersion, SystemVersion};
@@ -0,0 +1,25 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct SystemVersion {
+    #[serde(rename = "Version", skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    #[serde(rename = "ApiVersion", skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+
+    #[serde(rename = "MinAPIVersion", skip_serializing_if = "Option::is_none")]
+    pub min_api_version: Option<String>,
+
+    #[serde(rename = "Components", skip_serializing_if = "Option::is_none")]
+    pub components: Option<Vec<ComponentVersion>>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ComponentVersion {
+    #[serde(rename = "Name")]
+    pub name: String,
+
+    #[serde(rename = "Version")]
+    pub version: String,
+}
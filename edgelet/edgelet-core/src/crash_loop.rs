@@ -0,0 +1,96 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Counts consecutive Edge Agent failures seen by the watchdog. Clones share the same
+/// count, so the management API can report what the watchdog records.
+#[derive(Clone, Debug)]
+pub struct CrashLoopDetector {
+    threshold: u32,
+    status: Arc<Mutex<CrashLoopStatus>>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct CrashLoopStatus {
+    pub consecutive_failures: u32,
+    pub crash_looping: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_failure: Option<DateTime<Utc>>,
+}
+
+impl Default for CrashLoopDetector {
+    fn default() -> Self {
+        CrashLoopDetector::new(3)
+    }
+}
+
+impl CrashLoopDetector {
+    /// The agent is considered to be crash looping once it has failed `threshold` times in
+    /// a row.
+    pub fn new(threshold: u32) -> Self {
+        CrashLoopDetector {
+            threshold,
+            status: Arc::new(Mutex::new(CrashLoopStatus::default())),
+        }
+    }
+
+    /// Records a failure. Returns `true` if this failure put the agent into a crash loop.
+    pub fn record_failure(&self) -> bool {
+        let mut status = self.status.lock().expect("crash loop status lock poisoned");
+
+        status.consecutive_failures = status.consecutive_failures.saturating_add(1);
+        status.last_failure = Some(Utc::now());
+
+        let was_crash_looping = status.crash_looping;
+        status.crash_looping = status.consecutive_failures >= self.threshold;
+
+        status.crash_looping && !was_crash_looping
+    }
+
+    /// Records that the agent is running, which ends any crash loop.
+    pub fn record_running(&self) {
+        let mut status = self.status.lock().expect("crash loop status lock poisoned");
+
+        status.consecutive_failures = 0;
+        status.crash_looping = false;
+    }
+
+    pub fn status(&self) -> CrashLoopStatus {
+        self.status
+            .lock()
+            .expect("crash loop status lock poisoned")
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CrashLoopDetector;
+
+    #[test]
+    fn crash_loop_threshold() {
+        let detector = CrashLoopDetector::new(2);
+        let shared = detector.clone();
+
+        assert!(!detector.record_failure());
+        assert!(!shared.status().crash_looping);
+
+        assert!(detector.record_failure());
+        assert!(!detector.record_failure());
+
+        let status = shared.status();
+        assert_eq!(status.consecutive_failures, 3);
+        assert!(status.crash_looping);
+        assert!(status.last_failure.is_some());
+
+        detector.record_running();
+
+        let status = shared.status();
+        assert_eq!(status.consecutive_failures, 0);
+        assert!(!status.crash_looping);
+        assert!(status.last_failure.is_some());
+    }
+}
@@ -3,16 +3,22 @@
 pub mod error;
 pub mod module;
 
+mod crash_loop;
+mod metrics;
 mod parse_since;
 mod virtualization;
 
 use std::sync::LazyLock;
 
+pub use crash_loop::{CrashLoopDetector, CrashLoopStatus};
 pub use error::Error;
+pub use metrics::{Metrics, metrics};
 pub use module::{
-    DiskInfo, LogOptions, LogTail, Module, ModuleAction, ModuleOperation, ModuleRegistry,
-    ModuleRuntime, ModuleRuntimeErrorReason, ModuleRuntimeState, ModuleStatus, ProvisioningInfo,
-    RegistryOperation, RuntimeOperation, SystemInfo, SystemResources,
+    DiskInfo, ExecOutput, ImagePruneRequest, ImageUsage, LogOptions, LogTail, Module, ModuleAction,
+    ModuleEvent, ModuleEventKind, ModuleOperation, ModuleRegistry, ModuleRuntime,
+    ModuleRuntimeErrorReason, ModuleRuntimeState, ModuleStats, ModuleStatus, ProvisioningInfo,
+    PrunedImage, PullProgress, RegistryOperation, RuntimeOperation, SnapshotInfo, SystemInfo,
+    SystemResources,
 };
 pub use parse_since::parse_since;
 
@@ -0,0 +1,283 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+/// Upper bounds of the request duration histogram buckets, in seconds.
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+/// Process-wide metrics about aziot-edged's own behavior.
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct RequestKey {
+    api: &'static str,
+    method: String,
+    route: String,
+    status: u16,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Histogram {
+    buckets: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests: Mutex<BTreeMap<RequestKey, u64>>,
+    request_durations: Mutex<BTreeMap<(&'static str, String), Histogram>>,
+    watchdog_restarts: Mutex<BTreeMap<String, u64>>,
+    image_gc_removals: AtomicU64,
+    edge_ca_renewals: AtomicU64,
+    reprovisions: AtomicU64,
+}
+
+impl Metrics {
+    /// Records a request served by `api`. `route` should be the route's path template
+    /// rather than the request path so that the number of series stays bounded.
+    pub fn record_request(
+        &self,
+        api: &'static str,
+        method: &str,
+        route: &str,
+        status: u16,
+        duration: Duration,
+    ) {
+        let key = RequestKey {
+            api,
+            method: method.to_owned(),
+            route: route.to_owned(),
+            status,
+        };
+        *self
+            .requests
+            .lock()
+            .expect("metrics lock poisoned")
+            .entry(key)
+            .or_default() += 1;
+
+        self.request_durations
+            .lock()
+            .expect("metrics lock poisoned")
+            .entry((api, route.to_owned()))
+            .or_default()
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn record_watchdog_restart(&self, module: &str) {
+        *self
+            .watchdog_restarts
+            .lock()
+            .expect("metrics lock poisoned")
+            .entry(module.to_owned())
+            .or_default() += 1;
+    }
+
+    pub fn record_image_gc_removal(&self) {
+        self.image_gc_removals.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_edge_ca_renewal(&self) {
+        self.edge_ca_renewals.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_reprovision(&self) {
+        self.reprovisions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "aziot_edged_http_requests_total",
+            "counter",
+            "Number of management and workload API requests.",
+        );
+        for (key, count) in self.requests.lock().expect("metrics lock poisoned").iter() {
+            let _ = writeln!(
+                out,
+                "aziot_edged_http_requests_total{{api=\"{}\",method=\"{}\",route=\"{}\",status=\"{}\"}} {count}",
+                key.api,
+                escape(&key.method),
+                escape(&key.route),
+                key.status,
+            );
+        }
+
+        header(
+            &mut out,
+            "aziot_edged_http_request_duration_seconds",
+            "histogram",
+            "Time taken to serve management and workload API requests.",
+        );
+        for ((api, route), histogram) in self
+            .request_durations
+            .lock()
+            .expect("metrics lock poisoned")
+            .iter()
+        {
+            let labels = format!("api=\"{api}\",route=\"{}\"", escape(route));
+
+            for (bucket, bound) in histogram.buckets.iter().zip(DURATION_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "aziot_edged_http_request_duration_seconds_bucket{{{labels},le=\"{bound}\"}} {bucket}",
+                );
+            }
+            let _ = writeln!(
+                out,
+                "aziot_edged_http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {}",
+                histogram.count,
+            );
+            let _ = writeln!(
+                out,
+                "aziot_edged_http_request_duration_seconds_sum{{{labels}}} {}",
+                histogram.sum,
+            );
+            let _ = writeln!(
+                out,
+                "aziot_edged_http_request_duration_seconds_count{{{labels}}} {}",
+                histogram.count,
+            );
+        }
+
+        header(
+            &mut out,
+            "aziot_edged_watchdog_restarts_total",
+            "counter",
+            "Number of modules started, recreated or restarted by the watchdog.",
+        );
+        for (module, count) in self
+            .watchdog_restarts
+            .lock()
+            .expect("metrics lock poisoned")
+            .iter()
+        {
+            let _ = writeln!(
+                out,
+                "aziot_edged_watchdog_restarts_total{{module=\"{}\"}} {count}",
+                escape(module),
+            );
+        }
+
+        counter(
+            &mut out,
+            "aziot_edged_image_gc_removals_total",
+            "Number of images removed by image garbage collection.",
+            &self.image_gc_removals,
+        );
+        counter(
+            &mut out,
+            "aziot_edged_edge_ca_renewals_total",
+            "Number of Edge CA certificate renewals.",
+            &self.edge_ca_renewals,
+        );
+        counter(
+            &mut out,
+            "aziot_edged_reprovisions_total",
+            "Number of device reprovisioning requests.",
+            &self.reprovisions,
+        );
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: &AtomicU64) {
+    header(out, name, "counter", help);
+    let _ = writeln!(out, "{name} {}", value.load(Ordering::Relaxed));
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Metrics;
+
+    #[test]
+    fn render() {
+        let metrics = Metrics::default();
+
+        metrics.record_request(
+            "management",
+            "GET",
+            "/modules",
+            200,
+            Duration::from_millis(20),
+        );
+        metrics.record_request(
+            "management",
+            "GET",
+            "/modules",
+            200,
+            Duration::from_secs(20),
+        );
+        metrics.record_watchdog_restart("edge\"Agent");
+        metrics.record_image_gc_removal();
+        metrics.record_reprovision();
+        metrics.record_reprovision();
+
+        let rendered = metrics.render();
+
+        assert!(rendered.contains(
+            "aziot_edged_http_requests_total{api=\"management\",method=\"GET\",route=\"/modules\",status=\"200\"} 2\n"
+        ));
+        assert!(rendered.contains(
+            "aziot_edged_http_request_duration_seconds_bucket{api=\"management\",route=\"/modules\",le=\"0.01\"} 0\n"
+        ));
+        assert!(rendered.contains(
+            "aziot_edged_http_request_duration_seconds_bucket{api=\"management\",route=\"/modules\",le=\"0.025\"} 1\n"
+        ));
+        assert!(rendered.contains(
+            "aziot_edged_http_request_duration_seconds_bucket{api=\"management\",route=\"/modules\",le=\"+Inf\"} 2\n"
+        ));
+        assert!(rendered.contains(
+            "aziot_edged_http_request_duration_seconds_count{api=\"management\",route=\"/modules\"} 2\n"
+        ));
+        assert!(
+            rendered.contains("aziot_edged_watchdog_restarts_total{module=\"edge\\\"Agent\"} 1\n")
+        );
+        assert!(rendered.contains("aziot_edged_image_gc_removals_total 1\n"));
+        assert!(rendered.contains("aziot_edged_edge_ca_renewals_total 0\n"));
+        assert!(rendered.contains("aziot_edged_reprovisions_total 2\n"));
+        assert!(rendered.contains("# TYPE aziot_edged_http_request_duration_seconds histogram\n"));
+    }
+}
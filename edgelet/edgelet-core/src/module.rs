@@ -22,6 +22,8 @@ pub enum ModuleStatus {
     #[default]
     Unknown,
     Running,
+    /// Running, but failing its health check.
+    Unhealthy,
     Stopped,
     Failed,
     Dead,
@@ -242,8 +244,24 @@ pub trait Module {
 pub trait ModuleRegistry {
     type Config;
 
-    async fn pull(&self, config: &Self::Config) -> anyhow::Result<()>;
+    /// Pulls the image in `config`. If `progress` is set, the progress of the pull is sent to
+    /// it as it is reported. The sender is dropped when the pull finishes.
+    async fn pull(
+        &self,
+        config: &Self::Config,
+        progress: Option<tokio::sync::mpsc::UnboundedSender<PullProgress>>,
+    ) -> anyhow::Result<()>;
     async fn remove(&self, name: &str) -> anyhow::Result<()>;
+
+    /// Loads the images in the image archive streamed in `archive` and returns the references
+    /// of the loaded images. If `digest` is set, one of the loaded images must have that digest.
+    async fn load(&self, archive: Incoming, digest: Option<&str>) -> anyhow::Result<Vec<String>>;
+
+    /// Returns the size in bytes of each image on the device, keyed by image ID.
+    async fn image_sizes(&self) -> anyhow::Result<std::collections::HashMap<String, u64>>;
+
+    /// Returns what is known about the use of each image deployed to the device.
+    async fn image_usage(&self) -> anyhow::Result<Vec<ImageUsage>>;
 }
 
 #[derive(Debug, Eq, PartialEq, Serialize)]
@@ -281,6 +299,9 @@ pub struct SystemInfo {
 
     pub provisioning: ProvisioningInfo,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edge_agent: Option<crate::CrashLoopStatus>,
+
     #[serde(default, flatten, skip_serializing_if = "BTreeMap::is_empty")]
     pub additional_properties: BTreeMap<String, String>,
 }
@@ -374,6 +395,8 @@ impl Default for SystemInfo {
                 always_reprovision_on_startup: false,
             },
 
+            edge_agent: None,
+
             additional_properties: BTreeMap::new(),
         }
     }
@@ -449,6 +472,127 @@ impl DiskInfo {
     }
 }
 
+/// Resource usage of a single module, derived from one sample of the container runtime's
+/// stats.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ModuleStats {
+    pub name: String,
+    pub read: Option<DateTime<Utc>>,
+    /// Percentage of the host's CPU used, where 100% is one fully used core.
+    pub cpu_percent: f64,
+    pub memory_usage: u64,
+    pub memory_limit: u64,
+    pub network_rx_bytes: u64,
+    pub network_tx_bytes: u64,
+    pub block_read_bytes: u64,
+    pub block_write_bytes: u64,
+    pub pids: u64,
+}
+
+/// An image that image garbage collection removed, or would remove in a dry run.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct PrunedImage {
+    pub id: String,
+    /// Seconds since a module last used the image.
+    pub last_used_secs: u64,
+    /// Size of the image in bytes, if known.
+    pub size: Option<u64>,
+}
+
+/// A saved set of modules that can be recreated with [`ModuleRuntime::restore_snapshot`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SnapshotInfo {
+    pub name: String,
+    pub created: DateTime<Utc>,
+    /// Names of the modules in the snapshot.
+    pub modules: Vec<String>,
+}
+
+/// What is known about the use of an image deployed to the device. Times are seconds since
+/// the Unix epoch.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ImageUsage {
+    pub id: String,
+    /// Names the image is tagged with on the device.
+    pub names: Vec<String>,
+    /// When the image was first pulled or loaded, if known.
+    pub first_pulled: Option<u64>,
+    /// When a module last used the image.
+    pub last_used: u64,
+    /// When a module using the image was last started, if known.
+    pub last_started: Option<u64>,
+    /// Number of times the image was pulled or loaded.
+    pub pull_count: u64,
+    /// Size of the image in bytes, if known.
+    pub size: Option<u64>,
+}
+
+/// Progress of an image pull, as reported by the container runtime.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct PullProgress {
+    /// The layer this update is about, if any.
+    pub id: Option<String>,
+    pub status: String,
+    /// Bytes of the layer downloaded or extracted so far.
+    pub current: Option<u64>,
+    /// Size of the layer in bytes.
+    pub total: Option<u64>,
+}
+
+/// A change in the state of a module, as reported by the container runtime.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ModuleEvent {
+    pub module: String,
+    pub kind: ModuleEventKind,
+    /// Exit code of a module that died.
+    pub exit_code: Option<i64>,
+    /// When the event happened, if known.
+    pub time: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModuleEventKind {
+    Start,
+    /// The module was stopped on request. It also dies, which is reported separately.
+    Stop,
+    /// The module's main process exited, whether on its own, by request, or by being killed.
+    Die,
+    /// A process of the module was killed for running out of memory.
+    Oom,
+}
+
+impl fmt::Display for ModuleEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleEventKind::Start => f.write_str("start"),
+            ModuleEventKind::Stop => f.write_str("stop"),
+            ModuleEventKind::Die => f.write_str("die"),
+            ModuleEventKind::Oom => f.write_str("oom"),
+        }
+    }
+}
+
+/// Output of a command run in a module. Output that isn't valid UTF-8 is converted lossily.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecOutput {
+    Stdout(String),
+    Stderr(String),
+    /// The command exited with this code. Always the last output of a command that ran.
+    Exit(i64),
+    /// The command's output or exit code could not be read. No output follows.
+    Error(String),
+}
+
+/// Request to run image garbage collection immediately.
+#[derive(Debug)]
+pub struct ImagePruneRequest {
+    /// Report the images that would be removed without removing them.
+    pub dry_run: bool,
+    pub reply: tokio::sync::oneshot::Sender<anyhow::Result<Vec<PrunedImage>>>,
+}
+
 pub trait ProvisioningResult {
     fn device_id(&self) -> &str;
     fn hub_name(&self) -> &str;
@@ -475,6 +619,37 @@ pub trait ModuleRuntime {
     async fn remove_all(&self) -> anyhow::Result<()>;
     async fn stop_all(&self, wait_before_kill: Option<Duration>) -> anyhow::Result<()>;
     async fn module_top(&self, id: &str) -> anyhow::Result<Vec<i32>>;
+    async fn module_stats(&self, id: &str) -> anyhow::Result<ModuleStats>;
+
+    /// Runs `command` in the module's container and returns its output as it is produced.
+    async fn exec(
+        &self,
+        id: &str,
+        command: Vec<String>,
+    ) -> anyhow::Result<tokio::sync::mpsc::Receiver<ExecOutput>>;
+
+    /// Returns a tar archive of the file or directory at `path` in the module's container.
+    async fn copy_from(&self, id: &str, path: &str) -> anyhow::Result<Incoming>;
+
+    /// Extracts the tar archive streamed in `archive` into the directory at `path` in the
+    /// module's container.
+    async fn copy_to(&self, id: &str, path: &str, archive: Incoming) -> anyhow::Result<()>;
+
+    /// Subscribes to events about modules as they happen. The receiver is closed if the
+    /// container runtime ends the subscription, for example because it restarted, and a new
+    /// subscription is needed to keep receiving events.
+    async fn events(&self) -> anyhow::Result<tokio::sync::mpsc::Receiver<ModuleEvent>>;
+
+    /// Saves the specs and image IDs of the running modules as the snapshot `name`, replacing
+    /// any snapshot of the same name.
+    async fn save_snapshot(&self, name: &str) -> anyhow::Result<SnapshotInfo>;
+
+    async fn list_snapshots(&self) -> anyhow::Result<Vec<SnapshotInfo>>;
+
+    /// Replaces all modules with the modules in the snapshot `name`, recreated from the images
+    /// they were running when it was saved. If the snapshot's modules can't all be created and
+    /// started, the modules that were there before are put back.
+    async fn restore_snapshot(&self, name: &str) -> anyhow::Result<()>;
 
     fn registry(&self) -> &Self::ModuleRegistry;
 
@@ -504,14 +679,20 @@ impl fmt::Display for ModuleOperation {
 // Useful for error contexts
 #[derive(Clone, Debug)]
 pub enum RegistryOperation {
+    LoadImages,
     PullImage(String),
+    RegistryPull(String),
     RemoveImage(String),
 }
 
 impl fmt::Display for RegistryOperation {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            RegistryOperation::LoadImages => write!(f, "load images from an image archive"),
             RegistryOperation::PullImage(name) => write!(f, "pull image {name:?}"),
+            RegistryOperation::RegistryPull(name) => {
+                write!(f, "pull image {name:?} from a content trust registry")
+            }
             RegistryOperation::RemoveImage(name) => write!(f, "remove image {name:?}"),
         }
     }
@@ -520,15 +701,23 @@ impl fmt::Display for RegistryOperation {
 // Useful for error contexts
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum RuntimeOperation {
+    CopyFromModule(String),
+    CopyToModule(String),
     CreateModule(String),
+    ExecModule(String),
     GetModule(String),
     GetModuleLogs(String),
+    GetModuleStats(String),
+    GetModuleEvents,
     GetSupportBundle,
     Init,
     ListImages,
     ListModules,
+    ListSnapshots,
     RemoveModule(String),
     RestartModule(String),
+    RestoreSnapshot(String),
+    SaveSnapshot(String),
     StartModule(String),
     StopModule(String),
     SystemInfo,
@@ -539,17 +728,29 @@ pub enum RuntimeOperation {
 impl fmt::Display for RuntimeOperation {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            RuntimeOperation::CopyFromModule(name) => {
+                write!(f, "copy files from module {name:?}")
+            }
+            RuntimeOperation::CopyToModule(name) => write!(f, "copy files to module {name:?}"),
             RuntimeOperation::CreateModule(name) => write!(f, "create module {name:?}"),
+            RuntimeOperation::ExecModule(name) => write!(f, "run command in module {name:?}"),
             RuntimeOperation::GetModule(name) => write!(f, "get module {name:?}"),
             RuntimeOperation::GetModuleLogs(name) => {
                 write!(f, "get logs for module {name:?}")
             }
+            RuntimeOperation::GetModuleStats(name) => {
+                write!(f, "get stats for module {name:?}")
+            }
+            RuntimeOperation::GetModuleEvents => write!(f, "get module events"),
             RuntimeOperation::GetSupportBundle => write!(f, "get support bundle"),
             RuntimeOperation::Init => write!(f, "initialize module runtime"),
             RuntimeOperation::ListModules => write!(f, "list modules"),
             RuntimeOperation::ListImages => write!(f, "list images"),
+            RuntimeOperation::ListSnapshots => write!(f, "list snapshots"),
             RuntimeOperation::RemoveModule(name) => write!(f, "remove module {name:?}"),
             RuntimeOperation::RestartModule(name) => write!(f, "restart module {name:?}"),
+            RuntimeOperation::RestoreSnapshot(name) => write!(f, "restore snapshot {name:?}"),
+            RuntimeOperation::SaveSnapshot(name) => write!(f, "save snapshot {name:?}"),
             RuntimeOperation::StartModule(name) => write!(f, "start module {name:?}"),
             RuntimeOperation::StopModule(name) => write!(f, "stop module {name:?}"),
             RuntimeOperation::SystemInfo => write!(f, "query system info"),
@@ -678,6 +879,8 @@ mod tests {
                 always_reprovision_on_startup: false,
             },
 
+            edge_agent: None,
+
             additional_properties: BTreeMap::new(),
         };
 
@@ -708,6 +911,8 @@ mod tests {
                 always_reprovision_on_startup: false,
             },
 
+            edge_agent: None,
+
             additional_properties: BTreeMap::from([
                 ("foo".to_owned(), "foofoo".to_owned()),
                 ("bar".to_owned(), "barbar".to_owned()),
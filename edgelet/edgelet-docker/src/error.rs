@@ -12,6 +12,9 @@ pub enum Error {
     #[error("initialization failure")]
     Initialization,
 
+    #[error("unsupported container engine: {0}")]
+    ContainerEngine(String),
+
     #[error("invalid module name: {0:?}")]
     InvalidModuleName(String),
 
@@ -21,6 +24,39 @@ pub enum Error {
     #[error("module operation error: {0}")]
     ModuleOperation(ModuleOperation),
 
+    #[error("content trust verification failed for image {0:?}: {1}")]
+    ContentTrust(String, String),
+
+    #[error("module {0:?} exceeds the device's resource limits: {1}")]
+    ModuleLimits(String, String),
+
+    #[error("module {0:?} violates the device's create options policy: {1}")]
+    PolicyViolation(String, String),
+
+    #[error("image {0:?} failed digest verification: {1}")]
+    ImageDigest(String, String),
+
+    #[error("could not get credentials for registry {0:?}: {1}")]
+    RegistryCredentials(String, String),
+
+    #[error("could not start the bandwidth-limited pull proxy for registry {0:?}")]
+    PullProxy(String),
+
+    #[error("invalid snapshot name {0:?}: use letters, digits, '-', '_' and '.'")]
+    InvalidSnapshotName(String),
+
+    #[error("snapshot {0:?} was not found")]
+    SnapshotNotFound(String),
+
+    #[error("no modules are running to include in a snapshot")]
+    EmptySnapshot,
+
+    #[error("module {0:?} cannot be included in a snapshot: {1}")]
+    SnapshotModule(String, String),
+
+    #[error("snapshot {0:?} cannot be restored: {1}")]
+    SnapshotRestore(String, String),
+
     #[error("registry operation error: {0}")]
     RegistryOperation(RegistryOperation),
 
@@ -30,6 +66,9 @@ pub enum Error {
     #[error("file operation error: {0}")]
     FileOperation(String),
 
+    #[error("the uploaded archive was not received completely")]
+    IncompleteUpload,
+
     #[error("failed to calculate current time epoch: {0}")]
     GetCurrentTimeEpoch(SystemTimeError),
 
@@ -1,5 +1,6 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+use std::collections::BTreeMap;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
@@ -7,17 +8,65 @@ use std::time::UNIX_EPOCH;
 use std::{collections::HashMap, collections::HashSet, fs, time::Duration};
 
 use edgelet_settings::base::image::ImagePruneSettings;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
 
 use crate::Error;
+use crate::notary::ImageReference;
+use crate::policy::glob_match;
+use crate::snapshot::SnapshotStore;
 
 const IMAGE_USE_FILENAME: &str = "image_use";
 const TMP_FILENAME: &str = "image_use_tmp";
+const BACKUP_FILENAME: &str = "image_use_backup";
+const CORRUPT_FILENAME: &str = "image_use_corrupt";
+const MODULE_IMAGES_FILENAME: &str = "module_images";
+const MODULE_IMAGES_TMP_FILENAME: &str = "module_images_tmp";
+
+// The first line of the image use file is "<IMAGE_USE_MAGIC> <version> <sha256 of the rest>".
+// Files without it are in the original format of one "<image id> <last used>" line per image.
+const IMAGE_USE_MAGIC: &str = "iotedge-image-use";
+const IMAGE_USE_VERSION: u32 = 2;
 
 #[derive(Debug, Clone)]
 struct ImagePruneInner {
-    image_use_filepath: String,
-    tmp_filepath: String,
+    homedir: PathBuf,
+    module_images_filepath: String,
+    module_images_tmp_filepath: String,
     settings: ImagePruneSettings,
+    images: HashMap<String, ImageUse>,
+}
+
+/// What is known about the use of a deployed image. Times are seconds since the Unix epoch.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub(crate) struct ImageUse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) first_pulled: Option<u64>,
+    pub(crate) last_used: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) last_started: Option<u64>,
+    #[serde(default)]
+    pub(crate) pull_count: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) size: Option<u64>,
+}
+
+// The image a module was last created from, and the different image it used before that.
+#[derive(Debug, Default, PartialEq)]
+struct ModuleImages {
+    current: String,
+    previous: Option<String>,
+}
+
+impl ModuleImages {
+    fn update(&mut self, image_id: &str) {
+        if self.current != image_id {
+            if !self.current.is_empty() {
+                self.previous = Some(std::mem::take(&mut self.current));
+            }
+            self.current = image_id.to_string();
+        }
+    }
 }
 
 /// <summary>
@@ -30,65 +79,96 @@ pub struct ImagePruneData {
 }
 
 impl ImagePruneData {
+    /// <summary>
+    /// Loads the image use data from `homedir`. If the data is corrupt, the last good copy is
+    /// used instead; if there is none, the corrupt file is set aside and tracking starts over.
     pub fn new(homedir: &Path, settings: ImagePruneSettings) -> Result<Self, Error> {
-        let fp: PathBuf = homedir.join(IMAGE_USE_FILENAME);
-        let tmp_fp: PathBuf = homedir.join(TMP_FILENAME);
-
-        let image_use_filepath = fp
+        let module_images_filepath = homedir
+            .join(MODULE_IMAGES_FILENAME)
             .to_str()
-            .ok_or_else(|| Error::FilepathCreationError(IMAGE_USE_FILENAME.into()))?;
-        let tmp_filepath = tmp_fp
+            .ok_or_else(|| Error::FilepathCreationError(MODULE_IMAGES_FILENAME.into()))?
+            .to_string();
+        let module_images_tmp_filepath = homedir
+            .join(MODULE_IMAGES_TMP_FILENAME)
             .to_str()
-            .ok_or_else(|| Error::FilepathCreationError(TMP_FILENAME.into()))?;
+            .ok_or_else(|| Error::FilepathCreationError(MODULE_IMAGES_TMP_FILENAME.into()))?
+            .to_string();
+
+        let inner = ImagePruneInner {
+            homedir: homedir.to_path_buf(),
+            module_images_filepath,
+            module_images_tmp_filepath,
+            settings,
+            images: load_image_use(homedir),
+        };
 
         Ok(Self {
-            inner: Arc::new(Mutex::new(ImagePruneInner {
-                image_use_filepath: image_use_filepath.to_string(),
-                tmp_filepath: tmp_filepath.to_string(),
-                settings,
-            })),
+            inner: Arc::new(Mutex::new(inner)),
         })
     }
 
     /// <summary>
     /// This method takes the `image_id` and adds (if the image is new) OR updates the last-used timestamp associated
     /// with this `image_id`. This state is maintained for use during image garbage collection.
-    /// This method is (currently) called whenever a new image is pulled, a container is created, or when a container is removed.
+    /// This method is (currently) called whenever a container is created or removed.
     pub fn record_image_use_timestamp(&self, image_id: &str) -> Result<(), Error> {
-        let guard = self
-            .inner
-            .lock()
-            .expect("Image garbage collection file operation failed");
-
-        // read persistence file into in-mem map
-        // this map now contains all images deployed to the device (through an IoT Edge deployment)
+        self.update_image_use(image_id, |image_use, current_time| {
+            image_use.last_used = current_time;
+        })
+    }
 
-        let mut image_map = match get_images_with_timestamp(guard.image_use_filepath.clone()) {
-            Ok(map) => map,
-            Err(e) => {
-                drop(guard);
-                log::warn!(
-                    "Could not read image garbage collection data. Latest time of use will not be updated for image: {image_id}. Error: {e}"
-                );
-                return Err(e);
+    /// <summary>
+    /// This method records that `image_id` was pulled or loaded, which also counts as a use
+    /// of the image. `size` is the size of the image in bytes, if known.
+    pub fn record_image_pull(&self, image_id: &str, size: Option<u64>) -> Result<(), Error> {
+        self.update_image_use(image_id, |image_use, current_time| {
+            image_use.first_pulled.get_or_insert(current_time);
+            image_use.last_used = current_time;
+            image_use.pull_count += 1;
+            if size.is_some() {
+                image_use.size = size;
             }
-        };
+        })
+    }
+
+    /// <summary>
+    /// This method records that a module using `image_id` was started, which also counts as
+    /// a use of the image.
+    pub fn record_image_start(&self, image_id: &str) -> Result<(), Error> {
+        self.update_image_use(image_id, |image_use, current_time| {
+            image_use.last_started = Some(current_time);
+            image_use.last_used = current_time;
+        })
+    }
+
+    fn update_image_use(
+        &self,
+        image_id: &str,
+        update: impl FnOnce(&mut ImageUse, u64),
+    ) -> Result<(), Error> {
+        let mut guard = self
+            .inner
+            .lock()
+            .map_err(|e| Error::LockError(e.to_string()))?;
 
         let current_time = std::time::SystemTime::now()
             .duration_since(UNIX_EPOCH)
-            .expect("Could not get EPOCH time");
-
-        image_map.insert(image_id.to_string(), current_time);
+            .map_err(Error::GetCurrentTimeEpoch)?
+            .as_secs();
 
-        // write entries back to file
-        let res = write_images_with_timestamp(
-            &image_map,
-            guard.tmp_filepath.clone(),
-            guard.image_use_filepath.clone(),
+        update(
+            guard.images.entry(image_id.to_string()).or_default(),
+            current_time,
         );
 
-        if res.is_ok() {
-            log::debug!("Image with ID {image_id} tracked in image garbage collection state.");
+        // The in-memory state stays authoritative, so a failed write is retried with the next update.
+        match write_image_use(&guard.images, &guard.homedir) {
+            Ok(()) => {
+                log::debug!("Image with ID {image_id} tracked in image garbage collection state.");
+            }
+            Err(e) => log::warn!(
+                "Could not persist image garbage collection data for image: {image_id}. Error: {e}"
+            ),
         }
 
         drop(guard);
@@ -96,6 +176,18 @@ impl ImagePruneData {
         Ok(())
     }
 
+    /// <summary>
+    /// This method returns everything that is known about the images deployed by IoT Edge,
+    /// keyed by image ID.
+    pub(crate) fn image_use(&self) -> Result<HashMap<String, ImageUse>, Error> {
+        let guard = self
+            .inner
+            .lock()
+            .map_err(|e| Error::LockError(e.to_string()))?;
+
+        Ok(guard.images.clone())
+    }
+
     /// <summary>
     /// This method is called during image garbage collection. It returns a map of images that
     /// will be deleted by the image garbage collector.
@@ -105,48 +197,49 @@ impl ImagePruneData {
         &self,
         in_use_image_ids: HashSet<String>,
     ) -> Result<HashMap<String, Duration>, Error> {
-        let guard = self
+        self.prune(in_use_image_ids, false)
+    }
+
+    /// <summary>
+    /// This method is called during a dry run of image garbage collection. It returns the same
+    /// map of images as `prune_images_from_file`, but does not update the persistence file.
+    pub fn prunable_images(
+        &self,
+        in_use_image_ids: HashSet<String>,
+    ) -> Result<HashMap<String, Duration>, Error> {
+        self.prune(in_use_image_ids, true)
+    }
+
+    fn prune(
+        &self,
+        in_use_image_ids: HashSet<String>,
+        dry_run: bool,
+    ) -> Result<HashMap<String, Duration>, Error> {
+        let mut guard = self
             .inner
             .lock()
             .map_err(|e| Error::LockError(e.to_string()))?;
 
-        let settings = guard.settings.clone();
-
-        // Read persistence file into in-mem map. This map now contains
-        // all images deployed to the device (through an IoT Edge deployment).
-        // If persistence file cannot be read we will return a new map so
-        // new persistence file will be created.
-        let iotedge_images_map = match get_images_with_timestamp(guard.image_use_filepath.clone()) {
-            Ok(map) => map,
-            Err(e) => {
-                drop(guard);
-                log::warn!(
-                    "Could not read image garbage collection data. Image garbage collection will not prune any images. {e}"
-                );
-                return Ok(HashMap::new());
-            }
-        };
-
         /* ============================== */
 
         // process maps
         let (images_to_delete, carry_over) = process_state(
-            iotedge_images_map,
+            guard.images.clone(),
             in_use_image_ids,
-            settings.image_age_cleanup_threshold(),
+            guard.settings.image_age_cleanup_threshold(),
         )?;
 
         /* ============================== */
 
         // write previously removed entries back to file
-        if let Err(e) = write_images_with_timestamp(
-            &carry_over,
-            guard.tmp_filepath.clone(),
-            guard.image_use_filepath.clone(),
-        ) {
-            log::warn!(
-                "Failed to update image auto pruning persistence file. File will be updated on next scheduled run. {e}"
-            );
+        if !dry_run {
+            guard.images = carry_over;
+
+            if let Err(e) = write_image_use(&guard.images, &guard.homedir) {
+                log::warn!(
+                    "Failed to update image auto pruning persistence file. File will be updated on next scheduled run. {e}"
+                );
+            }
         }
 
         /* ============================== */
@@ -154,79 +247,399 @@ impl ImagePruneData {
         drop(guard);
 
         // these are the images we need to prune; file has already been updated
-        Ok(images_to_delete)
+        Ok(images_to_delete
+            .into_iter()
+            .map(|(image_id, image_use)| (image_id, Duration::from_secs(image_use.last_used)))
+            .collect())
+    }
+
+    /// <summary>
+    /// This method is called when disk usage crosses the garbage collection high watermark.
+    /// It returns the tracked images that are not in `in_use_image_ids`, least recently used
+    /// first, regardless of their age. The persistence file is not modified; call
+    /// `forget_image` for each image that is actually removed.
+    pub fn least_recently_used_images(
+        &self,
+        in_use_image_ids: &HashSet<String>,
+    ) -> Result<Vec<String>, Error> {
+        let guard = self
+            .inner
+            .lock()
+            .map_err(|e| Error::LockError(e.to_string()))?;
+
+        let iotedge_images_map = last_used_times(&guard.images);
+
+        drop(guard);
+
+        Ok(sort_least_recently_used(
+            iotedge_images_map,
+            in_use_image_ids,
+        ))
+    }
+
+    /// <summary>
+    /// This method removes `image_id` from the garbage collection state, e.g. after the image
+    /// has been removed from the device.
+    pub fn forget_image(&self, image_id: &str) -> Result<(), Error> {
+        let mut guard = self
+            .inner
+            .lock()
+            .map_err(|e| Error::LockError(e.to_string()))?;
+
+        if guard.images.remove(image_id).is_some() {
+            write_image_use(&guard.images, &guard.homedir)?;
+        }
+
+        drop(guard);
+
+        Ok(())
+    }
+
+    /// <summary>
+    /// This method records that the module `module` was created from `image_id`. If the module
+    /// was previously created from a different image, that image is remembered so that it can
+    /// be retained for a rollback.
+    pub fn record_module_image(&self, module: &str, image_id: &str) -> Result<(), Error> {
+        let guard = self
+            .inner
+            .lock()
+            .map_err(|e| Error::LockError(e.to_string()))?;
+
+        let mut module_images = get_module_images(&guard.module_images_filepath)?;
+        module_images
+            .entry(module.to_string())
+            .or_default()
+            .update(image_id);
+
+        write_module_images(
+            &module_images,
+            &guard.module_images_tmp_filepath,
+            &guard.module_images_filepath,
+        )?;
+
+        drop(guard);
+
+        Ok(())
+    }
+
+    /// <summary>
+    /// This method returns the IDs of the images that the retention rules in the image garbage
+    /// collection settings keep on the device, whether or not they are in use. The images of
+    /// saved snapshots are always kept so that the snapshots can be restored.
+    /// `images` maps the name of each image on the device to its ID.
+    pub fn retained_image_ids(
+        &self,
+        images: &HashMap<String, String>,
+    ) -> Result<HashSet<String>, Error> {
+        let guard = self
+            .inner
+            .lock()
+            .map_err(|e| Error::LockError(e.to_string()))?;
+
+        let retention = guard.settings.retention();
+
+        let mut retained_image_ids: HashSet<String> = images
+            .iter()
+            .filter(|(name, _)| is_protected(name, retention.protected_images()))
+            .map(|(_, image_id)| image_id.clone())
+            .collect();
+
+        if let Some(keep_versions) = retention.keep_versions() {
+            retained_image_ids.extend(latest_versions(
+                images,
+                &last_used_times(&guard.images),
+                keep_versions.get(),
+            ));
+        }
+
+        if retention.keep_previous_deployment() {
+            for module_images in get_module_images(&guard.module_images_filepath)?.into_values() {
+                retained_image_ids.insert(module_images.current);
+                retained_image_ids.extend(module_images.previous);
+            }
+        }
+
+        retained_image_ids.extend(SnapshotStore::new(&guard.homedir).image_ids()?);
+
+        drop(guard);
+
+        Ok(retained_image_ids)
     }
 }
 
 /* ===================================== HELPER METHODS ==================================== */
 
-fn get_images_with_timestamp(
-    image_use_filepath: String,
-) -> Result<HashMap<String, Duration>, Error> {
-    if !std::path::Path::new(&image_use_filepath).exists() {
+// Reads the image use file in `homedir`, falling back to the backup written before the last
+// update if the file is missing or corrupt. If neither can be read, the file is moved aside
+// for inspection and an empty state is returned; images that are no longer tracked are never
+// removed by garbage collection, so this only delays their removal until they are used again.
+fn load_image_use(homedir: &Path) -> HashMap<String, ImageUse> {
+    let image_use_filepath = homedir.join(IMAGE_USE_FILENAME);
+    let backup_filepath = homedir.join(BACKUP_FILENAME);
+
+    let error = match read_image_use(&image_use_filepath) {
+        Ok(Some(images)) => return images,
+        Ok(None) => None,
+        Err(e) => Some(e),
+    };
+
+    match read_image_use(&backup_filepath) {
+        Ok(Some(images)) => {
+            log::warn!(
+                "Recovered image garbage collection data from {}",
+                backup_filepath.display()
+            );
+            return images;
+        }
+        Ok(None) => (),
+        Err(e) => log::warn!(
+            "Could not read image garbage collection backup data from {}: {e}",
+            backup_filepath.display()
+        ),
+    }
+
+    if let Some(e) = error {
+        let corrupt_filepath = homedir.join(CORRUPT_FILENAME);
+        log::error!(
+            "Could not read image garbage collection data; moving it to {} and starting over. {e}",
+            corrupt_filepath.display()
+        );
+        if let Err(e) = fs::rename(&image_use_filepath, &corrupt_filepath) {
+            log::warn!("Could not move corrupt image garbage collection data: {e}");
+        }
+    } else {
         log::info!(
-            "Image garbage collection data file not found; creating file at: {}",
-            image_use_filepath.as_str(),
+            "Image garbage collection data file not found; it will be created at: {}",
+            image_use_filepath.display()
         );
-        let _file = fs::File::create(image_use_filepath.clone()).map_err(Error::CreateFile)?;
     }
 
+    HashMap::new()
+}
+
+// Returns `None` if the file does not exist.
+fn read_image_use(image_use_filepath: &Path) -> Result<Option<HashMap<String, ImageUse>>, Error> {
     let contents = match fs::read_to_string(image_use_filepath) {
-        Ok(ct) => ct,
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
         Err(e) => {
-            let msg = format!("Could not read image persistence data: {e}");
-            log::error!("{msg}");
-            return Err(Error::FileOperation(msg));
+            return Err(Error::FileOperation(format!(
+                "Could not read image persistence data: {e}"
+            )));
         }
     };
 
-    let mut image_map: HashMap<String, Duration> = HashMap::new(); // all image pruning data
+    let Some(header) = contents
+        .strip_prefix(IMAGE_USE_MAGIC)
+        .and_then(|contents| contents.strip_prefix(' '))
+    else {
+        return parse_image_use_v1(&contents).map(Some);
+    };
+
+    let (header, body) = header.split_once('\n').unwrap_or((header, ""));
+    let (version, checksum) = header.split_once(' ').ok_or_else(|| {
+        Error::FileOperation("Image persistence data has a malformed header".to_owned())
+    })?;
+
+    if version != IMAGE_USE_VERSION.to_string() {
+        return Err(Error::FileOperation(format!(
+            "Image persistence data has unsupported version {version}"
+        )));
+    }
+
+    if checksum != image_use_checksum(body) {
+        return Err(Error::FileOperation(
+            "Image persistence data failed checksum verification".to_owned(),
+        ));
+    }
+
+    let images: HashMap<String, ImageUse> = serde_json::from_str(body).map_err(|e| {
+        Error::FileOperation(format!("Could not parse image persistence data: {e}"))
+    })?;
+
+    Ok(Some(images))
+}
+
+// The original format only tracked when each image was last used.
+fn parse_image_use_v1(contents: &str) -> Result<HashMap<String, ImageUse>, Error> {
+    let mut image_map: HashMap<String, ImageUse> = HashMap::new();
+
+    for line in contents.lines() {
+        let (image_id, last_used) = line.split_once(' ').ok_or_else(|| {
+            Error::FileOperation(format!("Malformed image persistence data: {line}"))
+        })?;
 
-    // TL;DR: this dumps pruning data into the image_map, where
-    // Key: Image hash, Value: Timestamp when image was last used (in epoch)
-    let contents = contents
-        .lines()
-        .map(|line| line.split(' ').collect::<Vec<&str>>())
-        .map(|vec| (vec[0].to_string(), vec[1]));
-    for (k, v) in contents {
         image_map.insert(
-            k,
-            Duration::from_secs(v.parse::<u64>().map_err(Error::ParseIntError)?),
+            image_id.to_string(),
+            ImageUse {
+                last_used: last_used.parse::<u64>().map_err(Error::ParseIntError)?,
+                ..ImageUse::default()
+            },
         );
     }
 
     Ok(image_map)
 }
 
-fn write_images_with_timestamp(
-    state_to_persist: &HashMap<String, Duration>,
-    temp_file: String,
-    image_use_filepath: String,
-) -> Result<(), Error> {
-    // write to a temp file and then rename/overwrite to image persistence file (to prevent file write failures or corruption)
-    let mut file = std::fs::File::create(temp_file.clone()).map_err(Error::CreateFile)?;
-
-    for (key, value) in state_to_persist {
-        let image_details = format!("{key} {}\n", value.as_secs());
-        let res = write!(file, "{image_details}");
-        if res.is_err() {
-            let msg = format!(
-                "Could not write image:{key} with timestamp:{} to store",
-                value.as_secs(),
-            );
-            return Err(Error::FileOperation(msg));
+fn image_use_checksum(body: &str) -> String {
+    hex::encode(sha2::Sha256::digest(body.as_bytes()))
+}
+
+// Writes the image use data to a temp file, syncs it, keeps the current file as a backup, and
+// then renames the temp file over the current file, so that a crash at any point leaves a
+// complete copy behind.
+fn write_image_use(images: &HashMap<String, ImageUse>, homedir: &Path) -> Result<(), Error> {
+    let image_use_filepath = homedir.join(IMAGE_USE_FILENAME);
+    let tmp_filepath = homedir.join(TMP_FILENAME);
+
+    // sorted, so that the file only changes when the data does
+    let images: BTreeMap<&String, &ImageUse> = images.iter().collect();
+    let body = serde_json::to_string(&images).map_err(|e| {
+        Error::FileOperation(format!("Could not serialize image persistence data: {e}"))
+    })?;
+
+    let mut file = fs::File::create(&tmp_filepath).map_err(Error::CreateFile)?;
+    write!(
+        file,
+        "{IMAGE_USE_MAGIC} {IMAGE_USE_VERSION} {}\n{body}",
+        image_use_checksum(&body)
+    )
+    .and_then(|()| file.sync_all())
+    .map_err(|e| Error::FileOperation(format!("Could not write image persistence data: {e}")))?;
+
+    if image_use_filepath.exists() {
+        fs::rename(&image_use_filepath, homedir.join(BACKUP_FILENAME)).map_err(|e| {
+            Error::FileOperation(format!("Could not back up garbage collection data {e}"))
+        })?;
+    }
+
+    fs::rename(&tmp_filepath, &image_use_filepath).map_err(|e| {
+        Error::FileOperation(format!("Could not update garbage collection data {e}"))
+    })?;
+
+    // make the renames durable
+    fs::File::open(homedir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(|e| Error::FileOperation(format!("Could not sync garbage collection data {e}")))?;
+
+    Ok(())
+}
+
+fn last_used_times(images: &HashMap<String, ImageUse>) -> HashMap<String, Duration> {
+    images
+        .iter()
+        .map(|(image_id, image_use)| (image_id.clone(), Duration::from_secs(image_use.last_used)))
+        .collect()
+}
+
+// This method returns the images that are not currently in use, ordered from least to most
+// recently used. Images with the same timestamp are ordered by ID so that the order is stable.
+fn sort_least_recently_used(
+    iotedge_images_map: HashMap<String, Duration>,
+    in_use_image_ids: &HashSet<String>,
+) -> Vec<String> {
+    let mut images: Vec<(String, Duration)> = iotedge_images_map
+        .into_iter()
+        .filter(|(image_id, _)| !in_use_image_ids.contains(image_id))
+        .collect();
+    images.sort_by(|(id1, time1), (id2, time2)| time1.cmp(time2).then_with(|| id1.cmp(id2)));
+
+    images.into_iter().map(|(image_id, _)| image_id).collect()
+}
+
+// An image is protected if a pattern matches either its full name or its name without the tag.
+fn is_protected(name: &str, protected_images: &[String]) -> bool {
+    let untagged_name = ImageReference::parse(name).name;
+
+    protected_images
+        .iter()
+        .any(|pattern| glob_match(pattern, name) || glob_match(pattern, &untagged_name))
+}
+
+// This method returns, for each repository, the IDs of the `keep_versions` most recently used
+// images. Images that are not tracked in the image use data are not counted.
+fn latest_versions(
+    images: &HashMap<String, String>,
+    iotedge_images_map: &HashMap<String, Duration>,
+    keep_versions: usize,
+) -> HashSet<String> {
+    let mut repositories: HashMap<(String, String), Vec<(Duration, &str)>> = HashMap::new();
+    for (name, image_id) in images {
+        if let Some(last_used) = iotedge_images_map.get(image_id) {
+            let reference = ImageReference::parse(name);
+            repositories
+                .entry((reference.registry, reference.repository))
+                .or_default()
+                .push((*last_used, image_id));
         }
     }
 
-    match fs::rename(temp_file, image_use_filepath) {
-        Ok(()) => {}
-        Err(err) => {
-            return Err(Error::FileOperation(format!(
-                "Could not update garbage collection data {err}"
-            )));
+    repositories
+        .into_values()
+        .flat_map(|mut versions| {
+            versions.sort_by(|(time1, id1), (time2, id2)| time2.cmp(time1).then(id1.cmp(id2)));
+            // an image tagged more than once is one version
+            versions.dedup_by(|(_, id1), (_, id2)| id1 == id2);
+            versions
+                .into_iter()
+                .take(keep_versions)
+                .map(|(_, image_id)| image_id.to_string())
+        })
+        .collect()
+}
+
+fn get_module_images(module_images_filepath: &str) -> Result<HashMap<String, ModuleImages>, Error> {
+    if !Path::new(module_images_filepath).exists() {
+        return Ok(HashMap::new());
+    }
+
+    let contents = fs::read_to_string(module_images_filepath)
+        .map_err(|e| Error::FileOperation(format!("Could not read module image data: {e}")))?;
+
+    // Each line is: module current_image_id [previous_image_id]
+    let mut module_images = HashMap::new();
+    for line in contents.lines() {
+        let mut fields = line.split(' ');
+        match (fields.next(), fields.next()) {
+            (Some(module), Some(current)) if !module.is_empty() && !current.is_empty() => {
+                module_images.insert(
+                    module.to_string(),
+                    ModuleImages {
+                        current: current.to_string(),
+                        previous: fields.next().map(ToString::to_string),
+                    },
+                );
+            }
+            _ => log::warn!("Ignoring malformed module image data: {line}"),
+        }
+    }
+
+    Ok(module_images)
+}
+
+fn write_module_images(
+    module_images: &HashMap<String, ModuleImages>,
+    temp_file: &str,
+    module_images_filepath: &str,
+) -> Result<(), Error> {
+    let mut contents = String::new();
+    for (module, images) in module_images {
+        contents.push_str(module);
+        contents.push(' ');
+        contents.push_str(&images.current);
+        if let Some(previous) = &images.previous {
+            contents.push(' ');
+            contents.push_str(previous);
         }
+        contents.push('\n');
     }
 
+    // write to a temp file and then rename/overwrite, as for the image use data
+    fs::write(temp_file, contents)
+        .map_err(|e| Error::FileOperation(format!("Could not write module image data: {e}")))?;
+    fs::rename(temp_file, module_images_filepath)
+        .map_err(|e| Error::FileOperation(format!("Could not update module image data: {e}")))?;
+
     Ok(())
 }
 
@@ -239,31 +652,37 @@ fn write_images_with_timestamp(
 // minimum age are marked for deletion.
 #[allow(clippy::type_complexity)]
 fn process_state(
-    mut iotedge_images_map: HashMap<String, Duration>,
+    mut iotedge_images_map: HashMap<String, ImageUse>,
     in_use_image_ids: HashSet<String>,
     image_age_cleanup_threshold: Duration,
-) -> Result<(HashMap<String, Duration>, HashMap<String, Duration>), Error> {
+) -> Result<(HashMap<String, ImageUse>, HashMap<String, ImageUse>), Error> {
     let current_time = std::time::SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map_err(Error::GetCurrentTimeEpoch)?;
 
-    let mut carry_over: HashMap<String, Duration> = HashMap::new(); // all images to NOT be deleted by pruning in this run
+    let mut carry_over: HashMap<String, ImageUse> = HashMap::new(); // all images to NOT be deleted by pruning in this run
 
     // then, based on ID, keep track of images currently being used (in map: carry_over)
     for image_id in in_use_image_ids {
         // Since in_use_image_ids contains *all* the images currently being used, we need to filter on whether said image
         // was deployed/managed by iotedge or no.
-        if iotedge_images_map.contains_key(&image_id) {
+        if let Some(image_use) = iotedge_images_map.get(&image_id) {
             // Since the images are currently being used, we update the timestamp to the current time
             // This avoids the case where a container crash just as pruning is kicking off removes a needed image
-            carry_over.insert(image_id, current_time);
+            let image_use = ImageUse {
+                last_used: current_time.as_secs(),
+                ..image_use.clone()
+            };
+            carry_over.insert(image_id, image_use);
         }
     }
 
     // track entries younger than min age
     for (key, value) in &iotedge_images_map {
-        if current_time.as_secs() - value.as_secs() < image_age_cleanup_threshold.as_secs() {
-            carry_over.insert(key.clone(), *value);
+        if current_time.as_secs().saturating_sub(value.last_used)
+            < image_age_cleanup_threshold.as_secs()
+        {
+            carry_over.insert(key.clone(), value.clone());
         }
     }
 
@@ -284,21 +703,43 @@ mod tests {
     };
 
     use chrono::{Timelike, Utc};
-    use edgelet_settings::base::image::ImagePruneSettings;
+    use edgelet_settings::base::image::{ImagePruneSettings, RetentionSettings};
     use nix::libc::sleep;
     use serial_test::serial;
 
     use crate::{
         ImagePruneData,
         image_prune_data::{
-            IMAGE_USE_FILENAME, TMP_FILENAME, get_images_with_timestamp, process_state,
+            BACKUP_FILENAME, CORRUPT_FILENAME, IMAGE_USE_FILENAME, ImageUse, ModuleImages,
+            is_protected, last_used_times, latest_versions, process_state, read_image_use,
+            sort_least_recently_used, write_image_use,
         },
     };
 
-    use super::write_images_with_timestamp;
-
     const TEST_FILE_DIR: &str = "test-data";
 
+    fn image_use_at(last_used: Duration) -> ImageUse {
+        ImageUse {
+            last_used: last_used.as_secs(),
+            ..ImageUse::default()
+        }
+    }
+
+    fn write_last_used(test_file_dir: &Path, image_map: &HashMap<String, Duration>) {
+        let images = image_map
+            .iter()
+            .map(|(image_id, last_used)| (image_id.clone(), image_use_at(*last_used)))
+            .collect();
+        write_image_use(&images, test_file_dir).unwrap();
+    }
+
+    fn read_last_used(test_file_dir: &Path) -> HashMap<String, Duration> {
+        let images = read_image_use(&test_file_dir.join(IMAGE_USE_FILENAME))
+            .unwrap()
+            .unwrap();
+        last_used_times(&images)
+    }
+
     /* =============================================================== PUBLIC API TESTS ============================================================ */
 
     #[tokio::test]
@@ -326,14 +767,7 @@ mod tests {
                 "sha256:a4d112e0884bd2ba078ab8222e099bc989cc65cd433dfbb74d6de7cee188g4g7",
             )
             .unwrap();
-        let images = get_images_with_timestamp(
-            test_file_dir
-                .join(IMAGE_USE_FILENAME)
-                .into_os_string()
-                .into_string()
-                .unwrap(),
-        )
-        .unwrap();
+        let images = read_last_used(&test_file_dir);
 
         assert!(images.contains_key(
             "sha256:a4d112e0884bd2ba078ab8222e099bc989cc65cd433dfbb74d6de7cee188g4g7"
@@ -352,14 +786,7 @@ mod tests {
                 "sha256:a4d112e0884bd2ba078ab8222e099bc989cc65cd433dfbb74d6de7cee188g4g7",
             )
             .unwrap();
-        let new_images = get_images_with_timestamp(
-            test_file_dir
-                .join(IMAGE_USE_FILENAME)
-                .into_os_string()
-                .into_string()
-                .unwrap(),
-        )
-        .unwrap();
+        let new_images = read_last_used(&test_file_dir);
         assert!(new_images.contains_key(
             "sha256:a4d112e0884bd2ba078ab8222e099bc989cc65cd433dfbb74d6de7cee188g4g7"
         ));
@@ -413,19 +840,7 @@ mod tests {
             time,
         );
 
-        let _write = write_images_with_timestamp(
-            &image_map,
-            test_file_dir
-                .join(TMP_FILENAME)
-                .into_os_string()
-                .into_string()
-                .unwrap(),
-            test_file_dir
-                .join(IMAGE_USE_FILENAME)
-                .into_os_string()
-                .into_string()
-                .unwrap(),
-        );
+        write_last_used(&test_file_dir, &image_map);
 
         let curr_time = (Utc::now().hour() * 60 + Utc::now().minute()).into();
         let settings = ImagePruneSettings::new(
@@ -462,6 +877,190 @@ mod tests {
         std::fs::remove_dir_all(test_file_dir).unwrap();
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_prunable_images_dry_run() {
+        // setup
+        // the persistence file only stores whole seconds
+        let time = Duration::from_secs(
+            std::time::SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Could not get EPOCH time")
+                .as_secs(),
+        );
+
+        let test_file_dir = std::env::current_dir().unwrap().join(TEST_FILE_DIR);
+        if test_file_dir.is_dir() {
+            std::fs::remove_dir_all(test_file_dir.clone()).unwrap();
+        }
+        std::fs::create_dir(Path::new(&test_file_dir)).unwrap();
+
+        let mut image_map: HashMap<String, Duration> = HashMap::new();
+        image_map.insert(
+            "sha256:670dcc86b69df89a9d5a9e1a7ae5b8f67619c1c74e19de8a35f57d6c06505fd4".to_string(),
+            time - Duration::from_hours(1),
+        );
+        image_map.insert(
+            "sha256:7a45202c8491b92b7e7a9a0cbf887079fcdb86ea3cb0b7a4cb8f5491281e985d".to_string(),
+            time - Duration::from_hours(1),
+        );
+
+        write_last_used(&test_file_dir, &image_map);
+
+        let curr_time = (Utc::now().hour() * 60 + Utc::now().minute()).into();
+        let settings = ImagePruneSettings::new(
+            Duration::from_secs(30),
+            Duration::from_secs(5),
+            curr_time,
+            true,
+        );
+        let image_use_data = ImagePruneData::new(&test_file_dir, settings).unwrap();
+
+        let mut in_use_image_ids: HashSet<String> = HashSet::new();
+        in_use_image_ids.insert(
+            "sha256:670dcc86b69df89a9d5a9e1a7ae5b8f67619c1c74e19de8a35f57d6c06505fd4".to_string(),
+        );
+
+        // dry run reports the unused image, but keeps tracking it
+        let images_to_delete = image_use_data
+            .prunable_images(in_use_image_ids.clone())
+            .unwrap();
+        assert!(images_to_delete.len() == 1);
+        assert!(images_to_delete.contains_key(
+            "sha256:7a45202c8491b92b7e7a9a0cbf887079fcdb86ea3cb0b7a4cb8f5491281e985d"
+        ));
+
+        let images = read_last_used(&test_file_dir);
+        assert_eq!(images, image_map);
+
+        // a real run reports the same image
+        let images_to_delete = image_use_data
+            .prune_images_from_file(in_use_image_ids)
+            .unwrap();
+        assert!(images_to_delete.len() == 1);
+
+        // cleanup
+        std::fs::remove_dir_all(test_file_dir).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_record_image_pull_and_start() {
+        let test_file_dir = std::env::current_dir().unwrap().join(TEST_FILE_DIR);
+        if test_file_dir.is_dir() {
+            std::fs::remove_dir_all(test_file_dir.clone()).unwrap();
+        }
+        std::fs::create_dir(Path::new(&test_file_dir)).unwrap();
+
+        let image_use_data =
+            ImagePruneData::new(&test_file_dir, ImagePruneSettings::default()).unwrap();
+
+        image_use_data
+            .record_image_pull("sha256:image", Some(1024))
+            .unwrap();
+        image_use_data
+            .record_image_pull("sha256:image", None)
+            .unwrap();
+        image_use_data.record_image_start("sha256:image").unwrap();
+
+        let images = read_image_use(&test_file_dir.join(IMAGE_USE_FILENAME))
+            .unwrap()
+            .unwrap();
+        let image_use = &images["sha256:image"];
+        assert_eq!(image_use.pull_count, 2);
+        assert_eq!(image_use.size, Some(1024));
+        assert!(image_use.first_pulled.is_some());
+        assert_eq!(image_use.last_started, Some(image_use.last_used));
+
+        // the data survives a restart
+        let image_use_data =
+            ImagePruneData::new(&test_file_dir, ImagePruneSettings::default()).unwrap();
+        assert_eq!(image_use_data.image_use().unwrap(), images);
+
+        // cleanup
+        std::fs::remove_dir_all(test_file_dir).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_load_original_format() {
+        let test_file_dir = std::env::current_dir().unwrap().join(TEST_FILE_DIR);
+        if test_file_dir.is_dir() {
+            std::fs::remove_dir_all(test_file_dir.clone()).unwrap();
+        }
+        std::fs::create_dir(Path::new(&test_file_dir)).unwrap();
+
+        std::fs::write(
+            test_file_dir.join(IMAGE_USE_FILENAME),
+            "sha256:image1 1700000000\nsha256:image2 1700000100\n",
+        )
+        .unwrap();
+
+        let image_use_data =
+            ImagePruneData::new(&test_file_dir, ImagePruneSettings::default()).unwrap();
+        let images = image_use_data.image_use().unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!(
+            images["sha256:image1"],
+            image_use_at(Duration::from_secs(1_700_000_000))
+        );
+
+        // the next update rewrites the file in the current format
+        image_use_data
+            .record_image_use_timestamp("sha256:image3")
+            .unwrap();
+        assert_eq!(read_last_used(&test_file_dir).len(), 3);
+
+        // cleanup
+        std::fs::remove_dir_all(test_file_dir).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_recover_corrupt_image_use() {
+        let test_file_dir = std::env::current_dir().unwrap().join(TEST_FILE_DIR);
+        if test_file_dir.is_dir() {
+            std::fs::remove_dir_all(test_file_dir.clone()).unwrap();
+        }
+        std::fs::create_dir(Path::new(&test_file_dir)).unwrap();
+
+        let image_use_data =
+            ImagePruneData::new(&test_file_dir, ImagePruneSettings::default()).unwrap();
+        image_use_data
+            .record_image_use_timestamp("sha256:image1")
+            .unwrap();
+        image_use_data
+            .record_image_use_timestamp("sha256:image2")
+            .unwrap();
+
+        // a torn write fails checksum verification, so the backup is used
+        let image_use_filepath = test_file_dir.join(IMAGE_USE_FILENAME);
+        let contents = std::fs::read_to_string(&image_use_filepath).unwrap();
+        std::fs::write(&image_use_filepath, &contents[..contents.len() - 5]).unwrap();
+
+        let image_use_data =
+            ImagePruneData::new(&test_file_dir, ImagePruneSettings::default()).unwrap();
+        let images = image_use_data.image_use().unwrap();
+        assert_eq!(images.len(), 1);
+        assert!(images.contains_key("sha256:image1"));
+
+        // without a usable backup, the corrupt file is set aside
+        std::fs::write(&image_use_filepath, "garbage").unwrap();
+        std::fs::remove_file(test_file_dir.join(BACKUP_FILENAME)).unwrap();
+
+        let image_use_data =
+            ImagePruneData::new(&test_file_dir, ImagePruneSettings::default()).unwrap();
+        assert!(image_use_data.image_use().unwrap().is_empty());
+        assert!(!image_use_filepath.exists());
+        assert_eq!(
+            std::fs::read_to_string(test_file_dir.join(CORRUPT_FILENAME)).unwrap(),
+            "garbage"
+        );
+
+        // cleanup
+        std::fs::remove_dir_all(test_file_dir).unwrap();
+    }
+
     /* =============================================================== MORE TESTS ============================================================ */
 
     #[test]
@@ -474,19 +1073,7 @@ mod tests {
         }
         std::fs::create_dir(Path::new(&test_file_dir)).unwrap();
 
-        let result = write_images_with_timestamp(
-            &HashMap::new(),
-            test_file_dir
-                .join(TMP_FILENAME)
-                .into_os_string()
-                .into_string()
-                .unwrap(),
-            test_file_dir
-                .join(IMAGE_USE_FILENAME)
-                .into_os_string()
-                .into_string()
-                .unwrap(),
-        );
+        let result = write_image_use(&HashMap::new(), &test_file_dir);
         assert!(result.is_ok());
         assert!(std::path::Path::new(&test_file_dir.join(IMAGE_USE_FILENAME)).exists());
 
@@ -496,7 +1083,7 @@ mod tests {
 
     #[test]
     #[serial]
-    fn test_get_write_images_with_timestamp() {
+    fn test_write_read_image_use() {
         // setup
         let test_file_dir = std::env::current_dir().unwrap().join(TEST_FILE_DIR);
         if test_file_dir.is_dir() {
@@ -513,29 +1100,9 @@ mod tests {
         hash_map.insert("test2".to_string(), current_time);
         hash_map.insert("test3".to_string(), current_time);
 
-        let result = write_images_with_timestamp(
-            &hash_map,
-            test_file_dir
-                .join(TMP_FILENAME)
-                .into_os_string()
-                .into_string()
-                .unwrap(),
-            test_file_dir
-                .join(IMAGE_USE_FILENAME)
-                .into_os_string()
-                .into_string()
-                .unwrap(),
-        );
-        assert!(result.is_ok());
+        write_last_used(&test_file_dir, &hash_map);
         // assert file not empty, verify file write
-        let result_map: HashMap<String, Duration> = get_images_with_timestamp(
-            test_file_dir
-                .join(IMAGE_USE_FILENAME)
-                .into_os_string()
-                .into_string()
-                .unwrap(),
-        )
-        .unwrap();
+        let result_map: HashMap<String, Duration> = read_last_used(&test_file_dir);
         assert!(result_map.len() == 3);
         assert!(result_map.contains_key(&"test1".to_string()));
         assert!(result_map.contains_key(&"test2".to_string()));
@@ -608,6 +1175,11 @@ mod tests {
             time - Duration::from_hours(24 * 8),
         );
 
+        let all_iotedge_images = all_iotedge_images
+            .into_iter()
+            .map(|(image_id, last_used)| (image_id, image_use_at(last_used)))
+            .collect();
+
         let (to_delete, carry_over) = process_state(
             all_iotedge_images,
             images_being_used,
@@ -617,4 +1189,198 @@ mod tests {
         assert!(to_delete.len() == 3);
         assert!(carry_over.len() == 4);
     }
+
+    #[test]
+    fn test_sort_least_recently_used() {
+        let time = std::time::SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Could not get EPOCH time");
+
+        let mut all_iotedge_images: HashMap<String, Duration> = HashMap::new();
+        all_iotedge_images.insert("in-use".to_string(), time - Duration::from_hours(24 * 9));
+        all_iotedge_images.insert("newest".to_string(), time);
+        all_iotedge_images.insert("oldest".to_string(), time - Duration::from_hours(24 * 8));
+        all_iotedge_images.insert("older-b".to_string(), time - Duration::from_hours(24));
+        all_iotedge_images.insert("older-a".to_string(), time - Duration::from_hours(24));
+
+        let mut images_being_used: HashSet<String> = HashSet::new();
+        images_being_used.insert("in-use".to_string());
+
+        assert_eq!(
+            sort_least_recently_used(all_iotedge_images, &images_being_used),
+            vec!["oldest", "older-a", "older-b", "newest"]
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_retained_image_ids() {
+        // setup
+        let time = std::time::SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Could not get EPOCH time");
+
+        let test_file_dir = std::env::current_dir().unwrap().join(TEST_FILE_DIR);
+        if test_file_dir.is_dir() {
+            std::fs::remove_dir_all(test_file_dir.clone()).unwrap();
+        }
+        std::fs::create_dir(Path::new(&test_file_dir)).unwrap();
+
+        let mut image_map: HashMap<String, Duration> = HashMap::new();
+        image_map.insert("sha256:module1".to_string(), time - Duration::from_hours(3));
+        image_map.insert("sha256:module2".to_string(), time - Duration::from_hours(2));
+        image_map.insert("sha256:module3".to_string(), time - Duration::from_hours(1));
+
+        write_last_used(&test_file_dir, &image_map);
+
+        let mut images: HashMap<String, String> = HashMap::new();
+        images.insert(
+            "mcr.microsoft.com/azureiotedge-agent:1.5".to_string(),
+            "sha256:agent".to_string(),
+        );
+        images.insert(
+            "contoso.azurecr.io/module:1".to_string(),
+            "sha256:module1".to_string(),
+        );
+        images.insert(
+            "contoso.azurecr.io/module:2".to_string(),
+            "sha256:module2".to_string(),
+        );
+        images.insert(
+            "contoso.azurecr.io/module:3".to_string(),
+            "sha256:module3".to_string(),
+        );
+
+        let curr_time = (Utc::now().hour() * 60 + Utc::now().minute()).into();
+        let settings = ImagePruneSettings::new(
+            Duration::from_secs(30),
+            Duration::from_secs(5),
+            curr_time,
+            true,
+        )
+        .with_retention(RetentionSettings::new(
+            vec!["mcr.microsoft.com/azureiotedge-*".to_string()],
+            std::num::NonZeroUsize::new(1),
+            true,
+        ));
+        let image_use_data = ImagePruneData::new(&test_file_dir, settings).unwrap();
+
+        image_use_data
+            .record_module_image("module", "sha256:module1")
+            .unwrap();
+        image_use_data
+            .record_module_image("module", "sha256:module2")
+            .unwrap();
+
+        let retained_image_ids = image_use_data.retained_image_ids(&images).unwrap();
+        let expected: HashSet<String> = [
+            "sha256:agent",
+            "sha256:module1",
+            "sha256:module2",
+            "sha256:module3",
+        ]
+        .into_iter()
+        .map(ToString::to_string)
+        .collect();
+        assert_eq!(retained_image_ids, expected);
+
+        // the rollback set moves with the module
+        image_use_data
+            .record_module_image("module", "sha256:module3")
+            .unwrap();
+        let retained_image_ids = image_use_data.retained_image_ids(&images).unwrap();
+        assert!(!retained_image_ids.contains("sha256:module1"));
+        assert!(retained_image_ids.contains("sha256:module2"));
+
+        // images of saved snapshots are kept
+        std::fs::create_dir(test_file_dir.join("snapshots")).unwrap();
+        std::fs::write(
+            test_file_dir.join("snapshots/good.json"),
+            r#"{
+                "name": "good",
+                "created": "2024-01-01T00:00:00Z",
+                "modules": [{
+                    "spec": {
+                        "name": "module",
+                        "type": "docker",
+                        "config": { "image": "contoso.azurecr.io/module:1" }
+                    },
+                    "imageId": "sha256:module1"
+                }]
+            }"#,
+        )
+        .unwrap();
+        let retained_image_ids = image_use_data.retained_image_ids(&images).unwrap();
+        assert!(retained_image_ids.contains("sha256:module1"));
+
+        // cleanup
+        std::fs::remove_dir_all(test_file_dir).unwrap();
+    }
+
+    #[test]
+    fn test_module_images_update() {
+        let mut module_images = ModuleImages::default();
+
+        module_images.update("sha256:1");
+        assert_eq!(module_images.current, "sha256:1");
+        assert_eq!(module_images.previous, None);
+
+        // recreating a module from the same image keeps the previous image
+        module_images.update("sha256:2");
+        module_images.update("sha256:2");
+        assert_eq!(module_images.current, "sha256:2");
+        assert_eq!(module_images.previous.as_deref(), Some("sha256:1"));
+    }
+
+    #[test]
+    fn test_is_protected() {
+        let protected_images = vec![
+            "mcr.microsoft.com/azureiotedge-*".to_string(),
+            "contoso.azurecr.io/**".to_string(),
+        ];
+
+        assert!(is_protected(
+            "mcr.microsoft.com/azureiotedge-hub:1.5",
+            &protected_images
+        ));
+        assert!(is_protected(
+            "contoso.azurecr.io/team/module:latest",
+            &protected_images
+        ));
+        assert!(!is_protected(
+            "mcr.microsoft.com/azureiotedge-simulated-temperature-sensor/extra:1.0",
+            &protected_images
+        ));
+        assert!(!is_protected("alpine:latest", &protected_images));
+    }
+
+    #[test]
+    fn test_latest_versions() {
+        let time = std::time::SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Could not get EPOCH time");
+
+        let mut images: HashMap<String, String> = HashMap::new();
+        images.insert("module:1".to_string(), "old".to_string());
+        images.insert("module:2".to_string(), "new".to_string());
+        images.insert("module:latest".to_string(), "new".to_string());
+        images.insert(
+            "docker.io/library/module:3".to_string(),
+            "newer".to_string(),
+        );
+        images.insert("other:1".to_string(), "other".to_string());
+        images.insert("untracked:1".to_string(), "untracked".to_string());
+
+        let mut all_iotedge_images: HashMap<String, Duration> = HashMap::new();
+        all_iotedge_images.insert("old".to_string(), time - Duration::from_hours(48));
+        all_iotedge_images.insert("new".to_string(), time - Duration::from_hours(24));
+        all_iotedge_images.insert("newer".to_string(), time);
+        all_iotedge_images.insert("other".to_string(), time - Duration::from_hours(72));
+
+        let expected: HashSet<String> = ["newer", "new", "other"]
+            .into_iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(latest_versions(&images, &all_iotedge_images, 2), expected);
+    }
 }
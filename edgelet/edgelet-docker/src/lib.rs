@@ -3,11 +3,17 @@
 mod error;
 mod image_prune_data;
 mod module;
+mod notary;
+mod policy;
+mod pull_proxy;
+mod registry;
 mod runtime;
+mod snapshot;
 
 pub use error::Error;
 pub use image_prune_data::ImagePruneData;
 pub use module::{DockerModule, MODULE_TYPE};
+pub use notary::Notary;
 pub use runtime::{DockerModuleRuntime, init_client};
 
 use tokio::sync::mpsc::UnboundedSender;
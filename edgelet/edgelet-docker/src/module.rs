@@ -60,10 +60,14 @@ pub fn runtime_state(
         let status = state
             .status
             .and_then(|status| match &*status {
-                "created" | "paused" | "restarting" => Some(ModuleStatus::Stopped),
-                "removing" | "exited" => status_from_exit_code(state.exit_code),
+                // "configured" and "stopped" are reported by Podman.
+                "created" | "configured" | "paused" | "restarting" => Some(ModuleStatus::Stopped),
+                "removing" | "exited" | "stopped" => status_from_exit_code(state.exit_code),
                 "dead" => Some(ModuleStatus::Dead),
-                "running" => Some(ModuleStatus::Running),
+                "running" => match state.health.as_ref().and_then(|h| h.status.as_deref()) {
+                    Some("unhealthy") => Some(ModuleStatus::Unhealthy),
+                    _ => Some(ModuleStatus::Running),
+                },
                 _ => None,
             })
             .unwrap_or_default();
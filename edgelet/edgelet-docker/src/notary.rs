@@ -0,0 +1,478 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Docker content trust.
+//!
+//! Registries listed under `[moby_runtime.content_trust.ca_certs]` have their tags resolved
+//! through the registry's Notary server, which is at the registry's hostname unless
+//! `[moby_runtime.content_trust.servers]` names another. The Notary client is pinned to the
+//! root CA configured for that registry, so a tag that is unsigned or whose trust data does
+//! not chain to that CA fails to resolve and the image is never pulled.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::error::Error;
+
+pub(crate) const DEFAULT_REGISTRY: &str = "docker.io";
+const DEFAULT_TAG: &str = "latest";
+
+#[derive(Clone, Debug)]
+pub struct Notary {
+    notary_bin: PathBuf,
+    // Registry hostname -> Notary client config file.
+    configs: BTreeMap<String, PathBuf>,
+}
+
+impl Notary {
+    /// Writes a Notary client configuration for each registry in `root_ca_certs` under
+    /// `home_dir`. `root_ca_certs` maps registry hostnames to the PEM of the root CA that
+    /// signs the registry's trust data. `servers` maps registry hostnames to their Notary
+    /// server; registries not in it are expected to serve their own trust data.
+    pub fn new(
+        home_dir: &Path,
+        root_ca_certs: &BTreeMap<String, Vec<u8>>,
+        servers: &BTreeMap<String, url::Url>,
+    ) -> anyhow::Result<Self> {
+        let mut configs = BTreeMap::new();
+
+        for (hostname, root_ca) in root_ca_certs {
+            let server = servers
+                .get(hostname)
+                .map_or_else(|| format!("https://{hostname}"), ToString::to_string);
+
+            let registry_dir = home_dir.join(hostname);
+            let trust_dir = registry_dir.join("trust");
+            std::fs::create_dir_all(&trust_dir)
+                .with_context(|| Error::FileOperation(format!("create {}", trust_dir.display())))?;
+
+            let root_ca_path = registry_dir.join("root-ca.pem");
+            std::fs::write(&root_ca_path, root_ca).with_context(|| {
+                Error::FileOperation(format!("write {}", root_ca_path.display()))
+            })?;
+
+            let config = serde_json::json!({
+                "trust_dir": trust_dir,
+                "remote_server": {
+                    "url": server,
+                },
+                "trust_pinning": {
+                    "ca": {
+                        "": root_ca_path,
+                    },
+                },
+            });
+            let config_path = registry_dir.join("config.json");
+            std::fs::write(&config_path, serde_json::to_vec_pretty(&config)?).with_context(
+                || Error::FileOperation(format!("write {}", config_path.display())),
+            )?;
+
+            configs.insert(hostname.clone(), config_path);
+        }
+
+        Ok(Self {
+            notary_bin: PathBuf::from("notary"),
+            configs,
+        })
+    }
+
+    /// Overrides the Notary client binary, which is otherwise looked up in `PATH`.
+    #[must_use]
+    pub fn with_notary_bin(mut self, notary_bin: impl Into<PathBuf>) -> Self {
+        self.notary_bin = notary_bin.into();
+        self
+    }
+
+    /// Resolves `image` to the digest signed for its tag.
+    ///
+    /// Returns `None` if the image's registry is not covered by content trust.
+    pub(crate) async fn resolve(
+        &self,
+        image: &str,
+        auth: Option<&docker::models::AuthConfig>,
+    ) -> anyhow::Result<Option<TrustedImage>> {
+        let reference = ImageReference::parse(image);
+
+        let Some(config_path) = self.configs.get(&reference.registry) else {
+            return Ok(None);
+        };
+
+        let Some(tag) = &reference.tag else {
+            return Err(Error::ContentTrust(
+                image.to_owned(),
+                "a tag is required to look up signed trust data".to_owned(),
+            )
+            .into());
+        };
+
+        let gun = format!("{}/{}", reference.registry, reference.repository);
+
+        let mut command = tokio::process::Command::new(&self.notary_bin);
+        command
+            .arg("-c")
+            .arg(config_path)
+            .arg("lookup")
+            .arg(&gun)
+            .arg(tag);
+
+        if let Some(docker::models::AuthConfig {
+            username: Some(username),
+            password: Some(password),
+            ..
+        }) = auth
+        {
+            let engine = base64::engine::general_purpose::STANDARD;
+            command.env(
+                "NOTARY_AUTH",
+                base64::Engine::encode(&engine, format!("{username}:{password}")),
+            );
+        }
+
+        let output = command.output().await.with_context(|| {
+            Error::ContentTrust(
+                image.to_owned(),
+                format!("could not run {}", self.notary_bin.display()),
+            )
+        })?;
+
+        if !output.status.success() {
+            return Err(Error::ContentTrust(
+                image.to_owned(),
+                String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+            )
+            .into());
+        }
+
+        let digest = parse_lookup_output(&String::from_utf8_lossy(&output.stdout), tag)
+            .ok_or_else(|| {
+                Error::ContentTrust(
+                    image.to_owned(),
+                    format!("no signed digest found for tag {tag}"),
+                )
+            })?;
+
+        if let Some(pinned) = &reference.digest
+            && pinned != &digest
+        {
+            return Err(Error::ContentTrust(
+                image.to_owned(),
+                format!("digest {pinned} does not match signed digest {digest}"),
+            )
+            .into());
+        }
+
+        Ok(Some(TrustedImage {
+            digest_reference: format!("{gun}@{digest}"),
+            repository: reference.name,
+            tag: tag.clone(),
+        }))
+    }
+}
+
+/// An image whose tag was resolved through Notary.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TrustedImage {
+    /// The signed digest reference, e.g. `contoso.azurecr.io/module@sha256:...`.
+    pub(crate) digest_reference: String,
+    /// The repository as written in the deployment, without tag or digest.
+    pub(crate) repository: String,
+    pub(crate) tag: String,
+}
+
+/// The parts of a Docker image reference that content trust cares about.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ImageReference {
+    pub(crate) name: String,
+    pub(crate) registry: String,
+    pub(crate) repository: String,
+    pub(crate) tag: Option<String>,
+    pub(crate) digest: Option<String>,
+}
+
+impl ImageReference {
+    pub(crate) fn parse(image: &str) -> Self {
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(digest.to_owned())),
+            None => (image, None),
+        };
+
+        // A tag separator is a ':' after the last '/'; an earlier one is a registry port.
+        let (name, tag) = match name.rsplit_once(':') {
+            Some((repo, tag)) if !tag.contains('/') => (repo, Some(tag.to_owned())),
+            _ => (name, None),
+        };
+        let tag = if tag.is_none() && digest.is_none() {
+            Some(DEFAULT_TAG.to_owned())
+        } else {
+            tag
+        };
+
+        // The first component is a registry only if it looks like a hostname.
+        let (registry, repository) = match name.split_once('/') {
+            Some((host, rest))
+                if host.contains('.') || host.contains(':') || host == "localhost" =>
+            {
+                (host.to_owned(), rest.to_owned())
+            }
+            Some(_) => (DEFAULT_REGISTRY.to_owned(), name.to_owned()),
+            None => (DEFAULT_REGISTRY.to_owned(), format!("library/{name}")),
+        };
+
+        ImageReference {
+            name: name.to_owned(),
+            registry,
+            repository,
+            tag,
+            digest,
+        }
+    }
+}
+
+/// Parses the output of `notary lookup <gun> <tag>`, which is `<tag> <digest> <size>`.
+fn parse_lookup_output(output: &str, tag: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+
+        if fields.next() != Some(tag) {
+            return None;
+        }
+
+        let digest = fields.next()?;
+        let hex = digest.strip_prefix("sha256:").unwrap_or(digest);
+
+        if hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            Some(format!("sha256:{hex}"))
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::BTreeMap;
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::{ImageReference, Notary, TrustedImage, parse_lookup_output};
+
+    pub(crate) const DIGEST: &str =
+        "sha256:0d5f5a015e5ec7b7cb45a6fbf0dc6a6c7c1be1bbac42a75a8ae3d4f6e3f00e2b";
+
+    fn reference(
+        name: &str,
+        registry: &str,
+        repository: &str,
+        tag: Option<&str>,
+    ) -> ImageReference {
+        ImageReference {
+            name: name.to_owned(),
+            registry: registry.to_owned(),
+            repository: repository.to_owned(),
+            tag: tag.map(ToOwned::to_owned),
+            digest: None,
+        }
+    }
+
+    #[test]
+    fn parse_image_reference() {
+        assert_eq!(
+            ImageReference::parse("alpine"),
+            reference("alpine", "docker.io", "library/alpine", Some("latest"))
+        );
+        assert_eq!(
+            ImageReference::parse("microsoft/azureiotedge-agent:1.5"),
+            reference(
+                "microsoft/azureiotedge-agent",
+                "docker.io",
+                "microsoft/azureiotedge-agent",
+                Some("1.5")
+            )
+        );
+        assert_eq!(
+            ImageReference::parse("mcr.microsoft.com/azureiotedge-agent:1.5"),
+            reference(
+                "mcr.microsoft.com/azureiotedge-agent",
+                "mcr.microsoft.com",
+                "azureiotedge-agent",
+                Some("1.5")
+            )
+        );
+        assert_eq!(
+            ImageReference::parse("localhost:5000/team/module"),
+            reference(
+                "localhost:5000/team/module",
+                "localhost:5000",
+                "team/module",
+                Some("latest")
+            )
+        );
+        assert_eq!(
+            ImageReference::parse(&format!("contoso.azurecr.io/module@{DIGEST}")),
+            ImageReference {
+                name: "contoso.azurecr.io/module".to_owned(),
+                registry: "contoso.azurecr.io".to_owned(),
+                repository: "module".to_owned(),
+                tag: None,
+                digest: Some(DIGEST.to_owned()),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_lookup() {
+        let hex = DIGEST.trim_start_matches("sha256:");
+
+        assert_eq!(
+            parse_lookup_output(&format!("1.0 {DIGEST} 528\n"), "1.0").as_deref(),
+            Some(DIGEST)
+        );
+        assert_eq!(
+            parse_lookup_output(&format!("1.0 {hex} 528\n"), "1.0").as_deref(),
+            Some(DIGEST)
+        );
+        assert_eq!(
+            parse_lookup_output(&format!("2.0 {DIGEST} 528\n"), "1.0"),
+            None
+        );
+        assert_eq!(parse_lookup_output("1.0 sha256:abc 528\n", "1.0"), None);
+        assert_eq!(parse_lookup_output("", "1.0"), None);
+    }
+
+    fn read_config(dir: &std::path::Path, hostname: &str) -> serde_json::Value {
+        serde_json::from_slice(&std::fs::read(dir.join(hostname).join("config.json")).unwrap())
+            .unwrap()
+    }
+
+    /// Sets up a Notary config for `contoso.azurecr.io` backed by a fake Notary client that
+    /// only has trust data for `contoso.azurecr.io/signed:1.0`.
+    pub(crate) fn test_notary(name: &str) -> Notary {
+        let dir =
+            std::env::temp_dir().join(format!("edgelet-notary-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let roots = BTreeMap::from([("contoso.azurecr.io".to_owned(), b"root".to_vec())]);
+        let notary = Notary::new(&dir, &roots, &BTreeMap::new()).unwrap();
+
+        let config = read_config(&dir, "contoso.azurecr.io");
+        assert_eq!(
+            config["remote_server"]["url"],
+            serde_json::json!("https://contoso.azurecr.io")
+        );
+        assert!(config["trust_pinning"]["ca"][""].is_string());
+
+        let notary_bin = dir.join("notary");
+        std::fs::write(
+            &notary_bin,
+            format!(
+                "#!/bin/sh\n\
+                 if [ \"$4\" = contoso.azurecr.io/signed ] && [ \"$5\" = 1.0 ]; then\n\
+                 echo \"1.0 {DIGEST} 528\"\n\
+                 else\n\
+                 echo \"fatal: No valid trust data for $5\" >&2\n\
+                 exit 1\n\
+                 fi\n"
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&notary_bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        notary.with_notary_bin(notary_bin)
+    }
+
+    /// Makes the fake Notary client of `notary` fail as if its server can't be reached.
+    pub(crate) fn take_offline(notary: &Notary) {
+        std::fs::write(
+            &notary.notary_bin,
+            "#!/bin/sh\n\
+             echo \"fatal: could not reach https://contoso.azurecr.io\" >&2\n\
+             exit 1\n",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn configured_server() {
+        let dir =
+            std::env::temp_dir().join(format!("edgelet-notary-server-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let roots = BTreeMap::from([
+            ("contoso.azurecr.io".to_owned(), b"root".to_vec()),
+            ("docker.io".to_owned(), b"root".to_vec()),
+        ]);
+        let servers = BTreeMap::from([(
+            "docker.io".to_owned(),
+            "https://notary.docker.io".parse().unwrap(),
+        )]);
+        Notary::new(&dir, &roots, &servers).unwrap();
+
+        assert_eq!(
+            read_config(&dir, "docker.io")["remote_server"]["url"],
+            serde_json::json!("https://notary.docker.io/")
+        );
+        assert_eq!(
+            read_config(&dir, "contoso.azurecr.io")["remote_server"]["url"],
+            serde_json::json!("https://contoso.azurecr.io")
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_signed_image() {
+        let notary = test_notary("signed");
+
+        assert_eq!(
+            notary
+                .resolve("contoso.azurecr.io/signed:1.0", None)
+                .await
+                .unwrap(),
+            Some(TrustedImage {
+                digest_reference: format!("contoso.azurecr.io/signed@{DIGEST}"),
+                repository: "contoso.azurecr.io/signed".to_owned(),
+                tag: "1.0".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_unsigned_image() {
+        let notary = test_notary("unsigned");
+
+        let err = notary
+            .resolve("contoso.azurecr.io/unsigned:1.0", None)
+            .await
+            .unwrap_err();
+        assert!(format!("{err}").contains("No valid trust data"));
+
+        let err = notary
+            .resolve("contoso.azurecr.io/signed@sha256:abc", None)
+            .await
+            .unwrap_err();
+        assert!(format!("{err}").contains("tag is required"));
+    }
+
+    #[tokio::test]
+    async fn resolve_mismatched_digest() {
+        let notary = test_notary("mismatched");
+        let pinned = "sha256:1111111111111111111111111111111111111111111111111111111111111111";
+
+        let err = notary
+            .resolve(&format!("contoso.azurecr.io/signed:1.0@{pinned}"), None)
+            .await
+            .unwrap_err();
+        assert!(format!("{err}").contains("does not match"));
+    }
+
+    #[tokio::test]
+    async fn resolve_untrusted_registry() {
+        let notary = test_notary("untrusted");
+
+        assert_eq!(
+            notary
+                .resolve("mcr.microsoft.com/module:1.0", None)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+}
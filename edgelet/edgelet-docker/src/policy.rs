@@ -0,0 +1,542 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Create options policy.
+//!
+//! The policy under `[moby_runtime.policy]` restricts the images that modules may use and the
+//! host resources that their create options may expose. It is evaluated after
+//! `allow_elevated_docker_permissions` has been applied, so it sees the create options that
+//! would actually be passed to the container engine.
+
+use docker::models::ContainerCreateBody;
+use edgelet_settings::{CreateOptionsPolicy, PolicyRule};
+
+use crate::error::Error;
+use crate::notary::ImageReference;
+
+/// Network mode that Docker uses when the create options don't set one.
+const DEFAULT_NETWORK_MODE: &str = "default";
+
+/// Host namespaces and the `HostConfig` properties that share them with the host.
+const HOST_NAMESPACES: &[(&str, &str)] = &[
+    ("pid", "PidMode"),
+    ("ipc", "IpcMode"),
+    ("uts", "UTSMode"),
+    ("userns", "UsernsMode"),
+    ("cgroupns", "CgroupnsMode"),
+];
+
+/// Checks that `module`'s image and create options are allowed by `policy`.
+pub(crate) fn check(
+    policy: &CreateOptionsPolicy,
+    module: &str,
+    image: &str,
+    create_options: &ContainerCreateBody,
+) -> Result<(), Error> {
+    if policy.is_default() {
+        return Ok(());
+    }
+
+    let violation = |reason: String| Error::PolicyViolation(module.to_owned(), reason);
+
+    let reference = ImageReference::parse(image);
+    if policy.require_digest && reference.digest.is_none() {
+        return Err(violation(format!(
+            "image {image:?} must be referenced by digest"
+        )));
+    }
+    if !allows(
+        &policy.registries,
+        &reference.registry,
+        str::to_ascii_lowercase,
+    ) {
+        return Err(violation(format!(
+            "registry {:?} is not allowed",
+            reference.registry
+        )));
+    }
+
+    let host_config = create_options.host_config.clone().unwrap_or_default();
+
+    // Privileged containers have every capability and device.
+    if host_config.privileged == Some(true)
+        && !(policy.capabilities.is_default() && policy.devices.is_default())
+    {
+        return Err(violation("privileged mode is not allowed".to_owned()));
+    }
+
+    let binds = host_config.binds.iter().flatten().filter_map(|bind| {
+        // Binds without an absolute source are named volumes.
+        let (source, _) = bind.split_once(':')?;
+        source.starts_with('/').then_some(source)
+    });
+    let mounts = host_config.mounts.iter().flatten().filter_map(|mount| {
+        match mount.r#type.as_deref() {
+            Some("bind") => mount.source.as_deref(),
+            // The local volume driver mounts its `device` option, which can be any host path.
+            Some("volume") => mount
+                .other_properties
+                .get("VolumeOptions")?
+                .get("DriverConfig")?
+                .get("Options")?
+                .get("device")?
+                .as_str(),
+            _ => None,
+        }
+    });
+    for source in binds.chain(mounts) {
+        if !allows(&policy.bind_mounts, source, normalize_path) {
+            return Err(violation(format!(
+                "bind mount of {source:?} is not allowed"
+            )));
+        }
+    }
+
+    let devices = host_config
+        .other_properties
+        .get("Devices")
+        .and_then(serde_json::Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|device| device.get("PathOnHost")?.as_str());
+    for device in devices {
+        if !allows(&policy.devices, device, normalize_path) {
+            return Err(violation(format!("device {device:?} is not allowed")));
+        }
+    }
+
+    let network_mode = host_config
+        .other_properties
+        .get("NetworkMode")
+        .and_then(serde_json::Value::as_str)
+        .filter(|network_mode| !network_mode.is_empty())
+        .unwrap_or(DEFAULT_NETWORK_MODE);
+    if !allows(&policy.network_modes, network_mode, str::to_owned) {
+        return Err(violation(format!(
+            "network mode {network_mode:?} is not allowed"
+        )));
+    }
+
+    for capability in host_config.cap_add.iter().flatten() {
+        // ALL grants capabilities that the rule may restrict.
+        let allowed = if normalize_capability(capability) == "ALL" {
+            policy.capabilities.is_default()
+        } else {
+            allows(&policy.capabilities, capability, normalize_capability)
+        };
+
+        if !allowed {
+            return Err(violation(format!(
+                "capability {capability:?} is not allowed"
+            )));
+        }
+    }
+
+    // The options below escape the container's confinement, so a policy denies them unless
+    // it explicitly allows them.
+
+    for (namespace, property) in HOST_NAMESPACES {
+        let mode = host_config
+            .other_properties
+            .get(*property)
+            .and_then(serde_json::Value::as_str);
+
+        if mode == Some("host")
+            && !allows_explicitly(&policy.host_namespaces, namespace, str::to_owned)
+        {
+            return Err(violation(format!(
+                "sharing the host's {namespace} namespace is not allowed"
+            )));
+        }
+    }
+
+    let mut security_options: Vec<_> = host_config
+        .other_properties
+        .get("SecurityOpt")
+        .and_then(serde_json::Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(serde_json::Value::as_str)
+        .map(normalize_security_option)
+        .collect();
+    if host_config.privileged == Some(true) {
+        security_options.push("privileged".to_owned());
+    }
+    if ["MaskedPaths", "ReadonlyPaths"]
+        .iter()
+        .any(|property| host_config.other_properties.contains_key(*property))
+    {
+        security_options.push("systempaths=unconfined".to_owned());
+    }
+    for option in security_options {
+        // no-new-privileges only adds confinement.
+        let allowed = matches!(
+            option.as_str(),
+            "no-new-privileges" | "no-new-privileges=true"
+        ) || allows_explicitly(&policy.security_options, &option, str::to_owned);
+
+        if !allowed {
+            return Err(violation(format!(
+                "security option {option:?} is not allowed"
+            )));
+        }
+    }
+
+    let volumes_from = host_config
+        .other_properties
+        .get("VolumesFrom")
+        .and_then(serde_json::Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(serde_json::Value::as_str);
+    for volumes_from in volumes_from {
+        // Entries are a container name, optionally followed by `:ro` or `:rw`.
+        let container = volumes_from
+            .split_once(':')
+            .map_or(volumes_from, |(container, _)| container);
+
+        if !allows_explicitly(&policy.volumes_from, container, str::to_owned) {
+            return Err(violation(format!(
+                "volumes from container {container:?} are not allowed"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `rule` allows `value`. The value and the rule's patterns are normalized with
+/// `normalize` before they are compared.
+fn allows(rule: &PolicyRule, value: &str, normalize: fn(&str) -> String) -> bool {
+    let value = normalize(value);
+    let matches = |patterns: &[String]| {
+        patterns
+            .iter()
+            .any(|pattern| glob_match(&normalize(pattern), &value))
+    };
+
+    !matches(&rule.deny) && rule.allow.as_deref().is_none_or(matches)
+}
+
+/// Like [`allows`], but `value` is denied if `rule` has no `allow` list.
+fn allows_explicitly(rule: &PolicyRule, value: &str, normalize: fn(&str) -> String) -> bool {
+    rule.allow.is_some() && allows(rule, value, normalize)
+}
+
+/// Resolves `.` and `..` components so that a path can't escape an allowed directory.
+/// Symlinks are not resolved.
+fn normalize_path(path: &str) -> String {
+    let mut components = Vec::new();
+
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+
+    format!("/{}", components.join("/"))
+}
+
+/// Docker also accepts security options in the older `key:value` form.
+fn normalize_security_option(option: &str) -> String {
+    match (option.find(':'), option.find('=')) {
+        (Some(colon), equals) if equals.is_none_or(|equals| colon < equals) => {
+            format!("{}={}", &option[..colon], &option[colon + 1..])
+        }
+        _ => option.to_owned(),
+    }
+}
+
+fn normalize_capability(capability: &str) -> String {
+    let capability = capability.to_ascii_uppercase();
+
+    match capability.strip_prefix("CAP_") {
+        Some(capability) => capability.to_owned(),
+        None => capability,
+    }
+}
+
+/// Matches `value` against a glob in which `**` matches any characters, `*` matches any
+/// characters other than `/` and `?` matches one character other than `/`.
+pub(crate) fn glob_match(pattern: &str, value: &str) -> bool {
+    fn matches(pattern: &[u8], value: &[u8]) -> bool {
+        match pattern {
+            [] => value.is_empty(),
+            [b'*', b'*', rest @ ..] => (0..=value.len()).any(|i| matches(rest, &value[i..])),
+            [b'*', rest @ ..] => (0..=value.len())
+                .take_while(|&i| i == 0 || value[i - 1] != b'/')
+                .any(|i| matches(rest, &value[i..])),
+            [b'?', rest @ ..] => {
+                matches!(value.first(), Some(c) if *c != b'/') && matches(rest, &value[1..])
+            }
+            [c, rest @ ..] => value.first() == Some(c) && matches(rest, &value[1..]),
+        }
+    }
+
+    matches(pattern.as_bytes(), value.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use docker::models::ContainerCreateBody;
+    use edgelet_settings::{CreateOptionsPolicy, PolicyRule};
+
+    use super::{check, glob_match, normalize_path, normalize_security_option};
+    use crate::error::Error;
+
+    const IMAGE: &str = "contoso.azurecr.io/module@sha256:0123";
+
+    fn rule(allow: Option<&[&str]>, deny: &[&str]) -> PolicyRule {
+        PolicyRule {
+            allow: allow.map(|allow| allow.iter().map(ToString::to_string).collect()),
+            deny: deny.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    fn create_options(host_config: &serde_json::Value) -> ContainerCreateBody {
+        serde_json::from_value(serde_json::json!({ "HostConfig": host_config })).unwrap()
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("/var/lib/modules/**", "/var/lib/modules/a/b"));
+        assert!(glob_match("/var/lib/*/data", "/var/lib/module/data"));
+        assert!(!glob_match("/var/lib/*/data", "/var/lib/a/b/data"));
+        assert!(glob_match("/dev/tty?", "/dev/tty0"));
+        assert!(!glob_match("/dev/tty?", "/dev/tty10"));
+        assert!(glob_match("*.azurecr.io", "contoso.azurecr.io"));
+        assert!(!glob_match("*.azurecr.io", "azurecr.io"));
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!("/etc", normalize_path("/var/lib/modules/../../../etc"));
+        assert_eq!("/var/lib", normalize_path("/var//lib/./"));
+        assert_eq!("/", normalize_path("/.."));
+
+        assert_eq!(
+            "seccomp=unconfined",
+            normalize_security_option("seccomp:unconfined")
+        );
+        assert_eq!(
+            "label=type:svirt_t",
+            normalize_security_option("label=type:svirt_t")
+        );
+        assert_eq!(
+            "no-new-privileges",
+            normalize_security_option("no-new-privileges")
+        );
+    }
+
+    #[test]
+    fn empty_policy_allows_everything() {
+        let create_options = create_options(&serde_json::json!({
+            "Privileged": true,
+            "Binds": ["/:/host"],
+            "CapAdd": ["ALL"],
+        }));
+
+        check(
+            &CreateOptionsPolicy::default(),
+            "testModule",
+            "alpine",
+            &create_options,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn allowed() {
+        let policy = CreateOptionsPolicy {
+            bind_mounts: rule(Some(&["/var/lib/modules/**"]), &[]),
+            devices: rule(Some(&["/dev/ttyUSB?"]), &[]),
+            network_modes: rule(Some(&["azure-iot-edge", "host"]), &[]),
+            capabilities: rule(None, &["SYS_ADMIN"]),
+            registries: rule(Some(&["*.azurecr.io"]), &[]),
+            host_namespaces: rule(Some(&["ipc"]), &[]),
+            security_options: rule(Some(&["apparmor=iotedge-*"]), &[]),
+            volumes_from: rule(Some(&["edgeHub"]), &[]),
+            require_digest: true,
+        };
+
+        let create_options = create_options(&serde_json::json!({
+            "Binds": ["/var/lib/modules/test:/data", "volume:/volume"],
+            "Mounts": [
+                { "Type": "bind", "Source": "/var/lib/modules/other", "Target": "/other" },
+                { "Type": "volume", "Source": "volume", "Target": "/volume2" },
+                {
+                    "Type": "volume",
+                    "Source": "bound",
+                    "Target": "/bound",
+                    "VolumeOptions": { "DriverConfig": { "Options": {
+                        "type": "none", "o": "bind", "device": "/var/lib/modules/bound",
+                    } } },
+                },
+            ],
+            "Devices": [{ "PathOnHost": "/dev/ttyUSB0", "PathInContainer": "/dev/ttyUSB0" }],
+            "NetworkMode": "azure-iot-edge",
+            "CapAdd": ["CAP_NET_ADMIN"],
+            "IpcMode": "host",
+            "PidMode": "private",
+            "SecurityOpt": ["apparmor:iotedge-module", "no-new-privileges"],
+            "VolumesFrom": ["edgeHub:ro"],
+        }));
+
+        check(&policy, "testModule", IMAGE, &create_options).unwrap();
+    }
+
+    #[test]
+    fn denied() {
+        let policy = CreateOptionsPolicy {
+            bind_mounts: rule(
+                Some(&["/var/lib/modules/**"]),
+                &["/var/lib/modules/secret/**"],
+            ),
+            devices: rule(Some(&[]), &[]),
+            network_modes: rule(None, &["host"]),
+            capabilities: rule(None, &["SYS_ADMIN"]),
+            registries: rule(None, &["docker.io"]),
+            host_namespaces: rule(Some(&["ipc"]), &[]),
+            security_options: rule(Some(&["apparmor=*"]), &["apparmor=unconfined"]),
+            volumes_from: rule(Some(&["edgeHub"]), &[]),
+            require_digest: false,
+        };
+
+        for (image, host_config) in [
+            ("alpine", serde_json::json!({})),
+            (IMAGE, serde_json::json!({ "Binds": ["/etc:/etc"] })),
+            (
+                IMAGE,
+                serde_json::json!({ "Binds": ["/var/lib/modules/../../../etc:/etc"] }),
+            ),
+            (
+                IMAGE,
+                serde_json::json!({ "Binds": ["/var/lib/modules/secret/key:/key:ro"] }),
+            ),
+            (
+                IMAGE,
+                serde_json::json!({ "Mounts": [{ "Type": "bind", "Source": "/etc" }] }),
+            ),
+            (
+                IMAGE,
+                serde_json::json!({ "Devices": [{ "PathOnHost": "/dev/sda" }] }),
+            ),
+            (IMAGE, serde_json::json!({ "NetworkMode": "host" })),
+            (IMAGE, serde_json::json!({ "CapAdd": ["cap_sys_admin"] })),
+            (IMAGE, serde_json::json!({ "CapAdd": ["ALL"] })),
+            (IMAGE, serde_json::json!({ "Privileged": true })),
+            (
+                IMAGE,
+                serde_json::json!({ "Mounts": [{
+                    "Type": "volume",
+                    "Target": "/etc",
+                    "VolumeOptions": { "DriverConfig": { "Options": {
+                        "type": "none", "o": "bind", "device": "/etc",
+                    } } },
+                }] }),
+            ),
+            (IMAGE, serde_json::json!({ "PidMode": "host" })),
+            (IMAGE, serde_json::json!({ "UTSMode": "host" })),
+            (
+                IMAGE,
+                serde_json::json!({ "SecurityOpt": ["apparmor=unconfined"] }),
+            ),
+            (
+                IMAGE,
+                serde_json::json!({ "SecurityOpt": ["apparmor:unconfined"] }),
+            ),
+            (
+                IMAGE,
+                serde_json::json!({ "SecurityOpt": ["seccomp=unconfined"] }),
+            ),
+            (IMAGE, serde_json::json!({ "MaskedPaths": [] })),
+            (IMAGE, serde_json::json!({ "VolumesFrom": ["other"] })),
+        ] {
+            let err =
+                check(&policy, "testModule", image, &create_options(&host_config)).unwrap_err();
+            assert!(
+                matches!(err, Error::PolicyViolation(..)),
+                "{image} {host_config}"
+            );
+        }
+    }
+
+    #[test]
+    fn escapes_denied_by_default() {
+        // A policy that only restricts registries still denies escapes from confinement.
+        let policy = CreateOptionsPolicy {
+            registries: rule(Some(&["*.azurecr.io"]), &[]),
+            ..Default::default()
+        };
+
+        for host_config in [
+            serde_json::json!({ "PidMode": "host" }),
+            serde_json::json!({ "IpcMode": "host" }),
+            serde_json::json!({ "UTSMode": "host" }),
+            serde_json::json!({ "UsernsMode": "host" }),
+            serde_json::json!({ "CgroupnsMode": "host" }),
+            serde_json::json!({ "SecurityOpt": ["seccomp=unconfined"] }),
+            serde_json::json!({ "SecurityOpt": ["apparmor=unconfined"] }),
+            serde_json::json!({ "SecurityOpt": ["label=disable"] }),
+            serde_json::json!({ "ReadonlyPaths": [] }),
+            serde_json::json!({ "Privileged": true }),
+            serde_json::json!({ "VolumesFrom": ["edgeHub"] }),
+        ] {
+            let err =
+                check(&policy, "testModule", IMAGE, &create_options(&host_config)).unwrap_err();
+            assert!(matches!(err, Error::PolicyViolation(..)), "{host_config}");
+        }
+
+        let create_options = create_options(&serde_json::json!({
+            "PidMode": "container:edgeHub",
+            "IpcMode": "private",
+            "SecurityOpt": ["no-new-privileges:true"],
+        }));
+        check(&policy, "testModule", IMAGE, &create_options).unwrap();
+    }
+
+    #[test]
+    fn require_digest() {
+        let policy = CreateOptionsPolicy {
+            require_digest: true,
+            ..Default::default()
+        };
+
+        let err = check(
+            &policy,
+            "testModule",
+            "contoso.azurecr.io/module:1.0",
+            &ContainerCreateBody::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::PolicyViolation(..)));
+
+        check(
+            &policy,
+            "testModule",
+            IMAGE,
+            &ContainerCreateBody::default(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn default_network_mode() {
+        let policy = CreateOptionsPolicy {
+            network_modes: rule(Some(&["azure-iot-edge"]), &[]),
+            ..Default::default()
+        };
+
+        let err = check(
+            &policy,
+            "testModule",
+            IMAGE,
+            &ContainerCreateBody::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::PolicyViolation(..)));
+    }
+}
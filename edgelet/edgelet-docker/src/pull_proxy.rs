@@ -0,0 +1,543 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Bandwidth limits for image pulls.
+//!
+//! The container engine downloads images itself and has no rate limit of its own. When
+//! `[moby_runtime.pulls] max_bandwidth` is set, each pull is routed through a registry proxy
+//! that listens on the loopback interface for the duration of the pull. The proxy forwards the
+//! engine's requests to the image's registry over HTTPS and hands the responses back no faster
+//! than the limit, so the engine can only download as fast as the proxy lets it.
+//!
+//! The engine treats registries on 127.0.0.0/8 as insecure and talks plain HTTP to the proxy.
+//! Registries redirect layer downloads to storage elsewhere; the proxy follows those redirects
+//! itself so that layers are limited wherever they are stored.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::num::NonZeroU64;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, ready};
+use std::time::Duration;
+
+use anyhow::Context as _;
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Body, Bytes, Frame, Incoming, SizeHint};
+use hyper::header::{AUTHORIZATION, CONNECTION, HOST, LOCATION};
+use hyper::{Request, Response, StatusCode};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::Connect;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+
+use crate::error::Error;
+use crate::notary::{DEFAULT_REGISTRY, ImageReference};
+
+// Docker Hub serves its registry API from a different host than its name.
+const DOCKER_HUB_API: &str = "https://registry-1.docker.io";
+
+// Registries redirect layer downloads to storage, which may redirect again.
+const MAX_REDIRECTS: usize = 5;
+
+type ProxyBody = UnsyncBoxBody<Bytes, hyper::Error>;
+
+/// A registry proxy for one pull. It stops, closing its connections, when dropped.
+pub(crate) struct PullProxy {
+    address: SocketAddr,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl PullProxy {
+    /// Starts a proxy for `registry` that serves no more than `max_bandwidth` bytes per
+    /// second. Returns `None` for registries on this device, which need no limit.
+    pub(crate) async fn for_registry(
+        registry: &str,
+        max_bandwidth: NonZeroU64,
+    ) -> anyhow::Result<Option<Self>> {
+        if is_local(registry) {
+            return Ok(None);
+        }
+
+        let upstream = if registry == DEFAULT_REGISTRY {
+            DOCKER_HUB_API.to_owned()
+        } else {
+            format!("https://{registry}")
+        };
+        let upstream =
+            url::Url::parse(&upstream).with_context(|| Error::PullProxy(registry.to_owned()))?;
+
+        let connector = hyper_openssl::client::legacy::HttpsConnector::new()
+            .with_context(|| Error::PullProxy(registry.to_owned()))?;
+        let client = Client::builder(TokioExecutor::new()).build(connector);
+
+        let proxy = Self::start(upstream, max_bandwidth, client)
+            .await
+            .with_context(|| Error::PullProxy(registry.to_owned()))?;
+
+        Ok(Some(proxy))
+    }
+
+    async fn start<C>(
+        upstream: url::Url,
+        max_bandwidth: NonZeroU64,
+        client: Client<C, Full<Bytes>>,
+    ) -> anyhow::Result<Self>
+    where
+        C: Connect + Clone + Send + Sync + 'static,
+    {
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let address = listener.local_addr()?;
+        let limiter = Arc::new(Mutex::new(Limiter::new(max_bandwidth)));
+
+        let server = tokio::spawn(async move {
+            // Dropping the set when the proxy stops closes its connections.
+            let mut connections = tokio::task::JoinSet::new();
+
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        log::warn!("Pull proxy stopped accepting connections: {e}");
+                        return;
+                    }
+                };
+
+                let upstream = upstream.clone();
+                let client = client.clone();
+                let limiter = limiter.clone();
+                let service = hyper::service::service_fn(move |request| {
+                    forward(request, upstream.clone(), client.clone(), limiter.clone())
+                });
+
+                connections.spawn(async move {
+                    // The engine tries HTTPS before plain HTTP, so failed connections are expected.
+                    if let Err(e) = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                    {
+                        log::debug!("Pull proxy connection failed: {e}");
+                    }
+                });
+
+                while connections.try_join_next().is_some() {}
+            }
+        });
+
+        Ok(PullProxy { address, server })
+    }
+
+    /// Returns the proxy's name for `image`, an image from the proxy's registry.
+    pub(crate) fn image(&self, image: &str) -> String {
+        let reference = ImageReference::parse(image);
+
+        match (&reference.digest, &reference.tag) {
+            (Some(digest), _) => format!("{}/{}@{digest}", self.address, reference.repository),
+            (None, Some(tag)) => format!("{}/{}:{tag}", self.address, reference.repository),
+            (None, None) => format!("{}/{}", self.address, reference.repository),
+        }
+    }
+}
+
+impl Drop for PullProxy {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// Whether `registry` is on this device.
+fn is_local(registry: &str) -> bool {
+    let host = registry.rsplit_once(':').map_or(registry, |(host, _)| host);
+
+    host == "localhost"
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+async fn forward<C>(
+    request: Request<Incoming>,
+    upstream: url::Url,
+    client: Client<C, Full<Bytes>>,
+    limiter: Arc<Mutex<Limiter>>,
+) -> Result<Response<ProxyBody>, std::convert::Infallible>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    match send(request, &upstream, &client).await {
+        Ok(mut response) => {
+            response.headers_mut().remove(CONNECTION);
+            Ok(response.map(|body| ThrottledBody::new(body, limiter).boxed_unsync()))
+        }
+
+        Err(e) => {
+            log::warn!("Pull proxy could not reach {upstream}: {e:?}");
+
+            let mut response = Response::new(
+                Full::default()
+                    .map_err(|never| match never {})
+                    .boxed_unsync(),
+            );
+            *response.status_mut() = StatusCode::BAD_GATEWAY;
+            Ok(response)
+        }
+    }
+}
+
+/// Sends `request` to `upstream`, following any redirects.
+async fn send<C>(
+    request: Request<Incoming>,
+    upstream: &url::Url,
+    client: &Client<C, Full<Bytes>>,
+) -> anyhow::Result<Response<Incoming>>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    let (parts, body) = request.into_parts();
+    let body = body.collect().await?.to_bytes();
+
+    let path = parts.uri.path_and_query().map_or("/", |path| path.as_str());
+    let mut url = url::Url::parse(&format!(
+        "{}{path}",
+        upstream.as_str().trim_end_matches('/')
+    ))?;
+
+    let mut headers = parts.headers;
+    headers.remove(HOST);
+    headers.remove(CONNECTION);
+
+    for _ in 0..=MAX_REDIRECTS {
+        let mut forwarded = Request::new(Full::new(body.clone()));
+        *forwarded.method_mut() = parts.method.clone();
+        *forwarded.uri_mut() = url.as_str().parse()?;
+        *forwarded.headers_mut() = headers.clone();
+
+        let response = client.request(forwarded).await?;
+
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .filter(|_| response.status().is_redirection());
+        let Some(location) = location else {
+            return Ok(response);
+        };
+
+        let next = url.join(location.to_str()?)?;
+
+        // Storage that layers are redirected to uses signed URLs and rejects the registry's
+        // credentials.
+        if next.origin() != url.origin() {
+            headers.remove(AUTHORIZATION);
+        }
+
+        url = next;
+    }
+
+    Err(anyhow::anyhow!("too many redirects from {upstream}"))
+}
+
+/// Spaces out the bytes sent through a proxy so that they average no more than its bandwidth
+/// limit.
+struct Limiter {
+    max_bandwidth: NonZeroU64,
+    next: tokio::time::Instant,
+}
+
+impl Limiter {
+    fn new(max_bandwidth: NonZeroU64) -> Self {
+        Limiter {
+            max_bandwidth,
+            next: tokio::time::Instant::now(),
+        }
+    }
+
+    /// Reserves the time to send `len` bytes after the bytes already reserved, and returns
+    /// when they have been paid for. Time spent idle is not saved up for later.
+    fn reserve(&mut self, len: usize, now: tokio::time::Instant) -> tokio::time::Instant {
+        let len = u64::try_from(len).unwrap_or(u64::MAX);
+        let nanos = u128::from(len) * 1_000_000_000 / u128::from(self.max_bandwidth.get());
+        let duration = Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX));
+
+        self.next = self.next.max(now) + duration;
+        self.next
+    }
+}
+
+/// A response body whose frames are held back until its proxy's limiter allows them.
+struct ThrottledBody {
+    body: Incoming,
+    limiter: Arc<Mutex<Limiter>>,
+    delayed: Option<(Frame<Bytes>, Pin<Box<tokio::time::Sleep>>)>,
+}
+
+impl ThrottledBody {
+    fn new(body: Incoming, limiter: Arc<Mutex<Limiter>>) -> Self {
+        ThrottledBody {
+            body,
+            limiter,
+            delayed: None,
+        }
+    }
+}
+
+impl Body for ThrottledBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        if this.delayed.is_none() {
+            let frame = match ready!(Body::poll_frame(Pin::new(&mut this.body), cx)) {
+                Some(Ok(frame)) => frame,
+                other => return Poll::Ready(other),
+            };
+
+            let Some(data) = frame.data_ref() else {
+                return Poll::Ready(Some(Ok(frame)));
+            };
+
+            let release = this
+                .limiter
+                .lock()
+                .expect("pull proxy limiter lock poisoned")
+                .reserve(data.len(), tokio::time::Instant::now());
+            this.delayed = Some((frame, Box::pin(tokio::time::sleep_until(release))));
+        }
+
+        let (_, delay) = this.delayed.as_mut().expect("a frame is delayed");
+        ready!(delay.as_mut().poll(cx));
+
+        let (frame, _) = this.delayed.take().expect("a frame is delayed");
+        Poll::Ready(Some(Ok(frame)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.delayed.is_none() && self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let mut hint = self.body.size_hint();
+
+        if let Some((frame, _)) = &self.delayed
+            && let Some(data) = frame.data_ref()
+        {
+            let len = u64::try_from(data.len()).unwrap_or(u64::MAX);
+            if let Some(upper) = hint.upper() {
+                hint.set_upper(upper.saturating_add(len));
+            }
+            hint.set_lower(hint.lower().saturating_add(len));
+        }
+
+        hint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+    use std::time::Duration;
+
+    use http_body_util::{BodyExt, Full};
+    use hyper::body::{Bytes, Incoming};
+    use hyper_util::client::legacy::Client;
+    use hyper_util::client::legacy::connect::HttpConnector;
+    use hyper_util::rt::TokioExecutor;
+
+    use super::{Limiter, PullProxy, is_local};
+
+    const LAYER_SIZE: usize = 2000;
+
+    /// A stand-in server's response status, headers and body.
+    type Reply = (hyper::StatusCode, Vec<(&'static str, String)>, Vec<u8>);
+
+    /// A stand-in for a registry that redirects layer downloads to storage on another port.
+    /// Storage refuses requests that carry the registry's credentials.
+    async fn start_registry() -> url::Url {
+        let storage = serve(|req| {
+            if req.headers().contains_key(hyper::header::AUTHORIZATION) {
+                (hyper::StatusCode::BAD_REQUEST, Vec::new(), Vec::new())
+            } else {
+                (hyper::StatusCode::OK, Vec::new(), vec![0; LAYER_SIZE])
+            }
+        })
+        .await;
+
+        serve(move |req| match req.uri().path() {
+            "/v2/" => (
+                hyper::StatusCode::UNAUTHORIZED,
+                vec![(
+                    "www-authenticate",
+                    r#"Bearer realm="https://auth.contoso.com/token""#.to_owned(),
+                )],
+                Vec::new(),
+            ),
+            "/v2/module/blobs/sha256:0123" => (
+                hyper::StatusCode::TEMPORARY_REDIRECT,
+                vec![("location", format!("{storage}layers/0123?sig=4567"))],
+                Vec::new(),
+            ),
+            _ => (hyper::StatusCode::NOT_FOUND, Vec::new(), Vec::new()),
+        })
+        .await
+    }
+
+    async fn serve<F>(respond: F) -> url::Url
+    where
+        F: Fn(&hyper::Request<Incoming>) -> Reply + Clone + Send + Sync + 'static,
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let respond = respond.clone();
+                let service = hyper::service::service_fn(move |req| {
+                    let (status, headers, body) = respond(&req);
+
+                    let mut response = hyper::Response::builder().status(status);
+                    for (name, value) in headers {
+                        response = response.header(name, value);
+                    }
+
+                    let response = response.body(Full::new(Bytes::from(body))).unwrap();
+                    async move { Ok::<_, std::convert::Infallible>(response) }
+                });
+
+                tokio::spawn(
+                    hyper::server::conn::http1::Builder::new()
+                        .serve_connection(hyper_util::rt::TokioIo::new(stream), service),
+                );
+            }
+        });
+
+        format!("http://{address}/").parse().unwrap()
+    }
+
+    async fn start_proxy(max_bandwidth: u64) -> PullProxy {
+        let upstream = start_registry().await;
+        let client = Client::builder(TokioExecutor::new()).build(HttpConnector::new());
+
+        PullProxy::start(upstream, NonZeroU64::new(max_bandwidth).unwrap(), client)
+            .await
+            .unwrap()
+    }
+
+    async fn get(
+        proxy: &PullProxy,
+        path: &str,
+        authorization: Option<&str>,
+    ) -> hyper::Response<Bytes> {
+        let client = Client::builder(TokioExecutor::new()).build(HttpConnector::new());
+
+        let mut request = hyper::Request::builder().uri(format!("http://{}{path}", proxy.address));
+        if let Some(authorization) = authorization {
+            request = request.header(hyper::header::AUTHORIZATION, authorization);
+        }
+        let request = request.body(Full::<Bytes>::default()).unwrap();
+
+        let response = client.request(request).await.unwrap();
+        let (parts, body) = response.into_parts();
+        let body = body.collect().await.unwrap().to_bytes();
+
+        hyper::Response::from_parts(parts, body)
+    }
+
+    #[tokio::test]
+    async fn forwards_requests() {
+        let proxy = start_proxy(1_000_000).await;
+
+        // Authentication challenges reach the engine unchanged.
+        let response = get(&proxy, "/v2/", None).await;
+        assert_eq!(hyper::StatusCode::UNAUTHORIZED, response.status());
+        assert_eq!(
+            r#"Bearer realm="https://auth.contoso.com/token""#,
+            response.headers()["www-authenticate"]
+        );
+
+        let response = get(&proxy, "/v2/other/manifests/1.0", None).await;
+        assert_eq!(hyper::StatusCode::NOT_FOUND, response.status());
+    }
+
+    #[tokio::test]
+    async fn follows_redirects() {
+        let proxy = start_proxy(1_000_000).await;
+
+        // The layer is downloaded by the proxy, without the registry's credentials.
+        let response = get(&proxy, "/v2/module/blobs/sha256:0123", Some("Bearer token")).await;
+        assert_eq!(hyper::StatusCode::OK, response.status());
+        assert_eq!(LAYER_SIZE, response.body().len());
+    }
+
+    #[tokio::test]
+    async fn limits_bandwidth() {
+        let proxy = start_proxy(4000).await;
+
+        let start = tokio::time::Instant::now();
+        let response = get(&proxy, "/v2/module/blobs/sha256:0123", None).await;
+        assert_eq!(LAYER_SIZE, response.body().len());
+        assert!(start.elapsed() >= Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn stops_when_dropped() {
+        let proxy = start_proxy(1_000_000).await;
+        let address = proxy.address;
+        drop(proxy);
+        tokio::task::yield_now().await;
+
+        assert!(tokio::net::TcpStream::connect(address).await.is_err());
+    }
+
+    #[test]
+    fn limiter() {
+        let mut limiter = Limiter::new(NonZeroU64::new(1000).unwrap());
+        let start = tokio::time::Instant::now();
+
+        assert_eq!(
+            start + Duration::from_millis(500),
+            limiter.reserve(500, start)
+        );
+
+        // Bytes wait for the ones reserved before them.
+        assert_eq!(
+            start + Duration::from_millis(1500),
+            limiter.reserve(1000, start + Duration::from_millis(100))
+        );
+
+        // Time spent idle is not saved up.
+        assert_eq!(
+            start + Duration::from_millis(5500),
+            limiter.reserve(500, start + Duration::from_secs(5))
+        );
+    }
+
+    #[tokio::test]
+    async fn image() {
+        let proxy = start_proxy(1_000_000).await;
+        let address = proxy.address;
+
+        assert_eq!(
+            format!("{address}/module:1.0"),
+            proxy.image("contoso.azurecr.io/module:1.0")
+        );
+        assert_eq!(
+            format!("{address}/library/ubuntu:latest"),
+            proxy.image("ubuntu")
+        );
+        assert_eq!(
+            format!("{address}/edge/module@sha256:0123"),
+            proxy.image("mirror.contoso.com/edge/module@sha256:0123")
+        );
+    }
+
+    #[test]
+    fn local_registries() {
+        assert!(is_local("localhost:5000"));
+        assert!(is_local("127.0.0.1:5000"));
+        assert!(is_local("localhost"));
+        assert!(!is_local("contoso.azurecr.io"));
+        assert!(!is_local("docker.io"));
+    }
+}
@@ -0,0 +1,501 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Registry mirrors and credential providers.
+//!
+//! Images referenced by tag from a registry listed under `[[moby_runtime.registry_mirrors]]`
+//! are pulled from its mirrors first and then tagged with the name the deployment uses, so
+//! modules are created from the same image name either way. Images referenced by digest
+//! cannot be tagged with their original name, so they always come from the registry itself.
+//!
+//! Modules whose deployment has no credentials for a registry get them from the
+//! `[[moby_runtime.credential_providers]]` that match the registry, resolved at pull time.
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Context;
+use tokio::io::AsyncWriteExt;
+
+use docker::models::AuthConfig;
+use edgelet_settings::{CredentialProvider, CredentialSource, RegistryMirror};
+
+use crate::error::Error;
+use crate::notary::{DEFAULT_REGISTRY, ImageReference};
+use crate::policy::glob_match;
+
+// Docker stores Docker Hub credentials under its v1 index URL.
+const DOCKER_HUB_SERVER: &str = "https://index.docker.io/v1/";
+
+const HELPER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A place to pull an image from.
+#[derive(Debug, PartialEq)]
+pub(crate) struct PullSource {
+    /// The image reference to pull.
+    pub(crate) image: String,
+    /// The registry that `image` is pulled from.
+    pub(crate) registry: String,
+    /// The repository and tag to give the image after it's pulled, if `image` is not the name
+    /// the deployment uses.
+    pub(crate) tag_as: Option<(String, String)>,
+    /// Whether `image` is a mirror's name for the image.
+    pub(crate) mirror: bool,
+}
+
+/// Returns the places to pull `image` from, in the order to try them.
+pub(crate) fn pull_sources(image: &str, registry_mirrors: &[RegistryMirror]) -> Vec<PullSource> {
+    let reference = ImageReference::parse(image);
+
+    let upstream = PullSource {
+        image: image.to_owned(),
+        registry: reference.registry.clone(),
+        tag_as: None,
+        mirror: false,
+    };
+
+    let (Some(tag), None) = (&reference.tag, &reference.digest) else {
+        return vec![upstream];
+    };
+
+    let Some(registry_mirror) = registry_mirrors
+        .iter()
+        .find(|registry_mirror| registry_mirror.registry == reference.registry)
+    else {
+        return vec![upstream];
+    };
+
+    let mut sources: Vec<_> = registry_mirror
+        .mirrors
+        .iter()
+        .map(|mirror| {
+            let mirror = mirror.trim_end_matches('/');
+            let registry = mirror.split_once('/').map_or(mirror, |(host, _)| host);
+
+            PullSource {
+                image: format!("{mirror}/{}:{tag}", reference.repository),
+                registry: registry.to_owned(),
+                tag_as: Some((reference.name.clone(), tag.clone())),
+                mirror: true,
+            }
+        })
+        .collect();
+
+    if registry_mirror.fallback || sources.is_empty() {
+        sources.push(upstream);
+    }
+
+    sources
+}
+
+/// Gets credentials for `registry` from the first of `providers` that matches the registry
+/// and has credentials for it.
+pub(crate) async fn resolve_credentials(
+    registry: &str,
+    providers: &[CredentialProvider],
+) -> anyhow::Result<Option<AuthConfig>> {
+    for provider in providers {
+        if !provider
+            .registries
+            .iter()
+            .any(|pattern| glob_match(pattern, registry))
+        {
+            continue;
+        }
+
+        let auth = match &provider.source {
+            CredentialSource::DockerConfig(path) => read_docker_config(path, registry).await?,
+            CredentialSource::Helper(helper) => run_helper(helper, registry).await?,
+        };
+
+        if auth.is_some() {
+            return Ok(auth);
+        }
+    }
+
+    Ok(None)
+}
+
+async fn read_docker_config(path: &Path, registry: &str) -> anyhow::Result<Option<AuthConfig>> {
+    let contents = tokio::fs::read(path).await.with_context(|| {
+        Error::RegistryCredentials(
+            registry.to_owned(),
+            format!("could not read {}", path.display()),
+        )
+    })?;
+
+    parse_docker_config(&contents, registry).with_context(|| {
+        Error::RegistryCredentials(
+            registry.to_owned(),
+            format!("could not parse {}", path.display()),
+        )
+    })
+}
+
+/// Finds the credentials for `registry` in the `auths` section of a Docker client
+/// `config.json`. Entries may be keyed by hostname or by URL.
+fn parse_docker_config(contents: &[u8], registry: &str) -> anyhow::Result<Option<AuthConfig>> {
+    #[derive(serde::Deserialize)]
+    struct DockerConfig {
+        #[serde(default)]
+        auths: std::collections::BTreeMap<String, DockerConfigAuth>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct DockerConfigAuth {
+        auth: Option<String>,
+        username: Option<String>,
+        password: Option<String>,
+        identitytoken: Option<String>,
+    }
+
+    let config: DockerConfig = serde_json::from_slice(contents)?;
+
+    let Some(entry) = config
+        .auths
+        .into_iter()
+        .find_map(|(server, auth)| (registry_hostname(&server) == registry).then_some(auth))
+    else {
+        return Ok(None);
+    };
+
+    let (username, password) = match entry.auth {
+        Some(auth) => {
+            let engine = base64::engine::general_purpose::STANDARD;
+            let auth = String::from_utf8(base64::Engine::decode(&engine, auth)?)?;
+            let (username, password) = auth
+                .split_once(':')
+                .context("auth is not in the form username:password")?;
+            (Some(username.to_owned()), Some(password.to_owned()))
+        }
+        None => (entry.username, entry.password),
+    };
+
+    Ok(Some(auth_config(
+        registry,
+        username,
+        password,
+        entry.identitytoken,
+    )))
+}
+
+/// Runs a Docker credential helper, which reads the registry's server URL on stdin and writes
+/// the credentials as JSON on stdout.
+async fn run_helper(helper: &Path, registry: &str) -> anyhow::Result<Option<AuthConfig>> {
+    let server = if registry == DEFAULT_REGISTRY {
+        DOCKER_HUB_SERVER
+    } else {
+        registry
+    };
+
+    let output = async {
+        let mut child = tokio::process::Command::new(helper)
+            .arg("get")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin.write_all(server.as_bytes()).await?;
+        drop(stdin);
+
+        child.wait_with_output().await
+    };
+
+    let output = tokio::time::timeout(HELPER_TIMEOUT, output)
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|output| output.map_err(Into::into))
+        .with_context(|| {
+            Error::RegistryCredentials(
+                registry.to_owned(),
+                format!("could not run {}", helper.display()),
+            )
+        })?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        // Helpers report missing credentials this way rather than with a separate status.
+        if stdout.contains("credentials not found") {
+            return Ok(None);
+        }
+
+        return Err(Error::RegistryCredentials(
+            registry.to_owned(),
+            format!(
+                "{} failed: {}",
+                helper.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        )
+        .into());
+    }
+
+    parse_helper_output(&stdout, registry).map(Some)
+}
+
+fn parse_helper_output(output: &str, registry: &str) -> anyhow::Result<AuthConfig> {
+    #[derive(serde::Deserialize)]
+    struct HelperCredentials {
+        #[serde(rename = "Username")]
+        username: String,
+        #[serde(rename = "Secret")]
+        secret: String,
+    }
+
+    let credentials: HelperCredentials = serde_json::from_str(output).with_context(|| {
+        Error::RegistryCredentials(
+            registry.to_owned(),
+            "credential helper returned malformed output".to_owned(),
+        )
+    })?;
+
+    // Helpers return identity tokens with this placeholder user name.
+    Ok(if credentials.username == "<token>" {
+        auth_config(registry, None, None, Some(credentials.secret))
+    } else {
+        auth_config(
+            registry,
+            Some(credentials.username),
+            Some(credentials.secret),
+            None,
+        )
+    })
+}
+
+fn auth_config(
+    registry: &str,
+    username: Option<String>,
+    password: Option<String>,
+    identity_token: Option<String>,
+) -> AuthConfig {
+    AuthConfig {
+        username,
+        password,
+        server_address: Some(registry.to_owned()),
+        other_properties: identity_token
+            .map(|token| ("identitytoken".to_owned(), serde_json::Value::String(token)))
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Returns the registry hostname of a `config.json` server entry, which may be a URL.
+fn registry_hostname(server: &str) -> &str {
+    let server = server
+        .strip_prefix("https://")
+        .or_else(|| server.strip_prefix("http://"))
+        .unwrap_or(server);
+    let hostname = server
+        .split_once('/')
+        .map_or(server, |(hostname, _)| hostname);
+
+    match hostname {
+        "index.docker.io" | "registry-1.docker.io" => DEFAULT_REGISTRY,
+        hostname => hostname,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use edgelet_settings::{CredentialProvider, CredentialSource, RegistryMirror};
+
+    use super::{PullSource, parse_docker_config, pull_sources, resolve_credentials};
+
+    fn mirror(registry: &str, mirrors: &[&str], fallback: bool) -> RegistryMirror {
+        RegistryMirror {
+            registry: registry.to_owned(),
+            mirrors: mirrors.iter().map(ToString::to_string).collect(),
+            fallback,
+        }
+    }
+
+    fn upstream(image: &str, registry: &str) -> PullSource {
+        PullSource {
+            image: image.to_owned(),
+            registry: registry.to_owned(),
+            tag_as: None,
+            mirror: false,
+        }
+    }
+
+    #[test]
+    fn pull_sources_mirrors() {
+        let mirrors = [
+            mirror(
+                "docker.io",
+                &["cache.local:5000/docker-hub", "backup.local"],
+                true,
+            ),
+            mirror("mcr.microsoft.com", &["cache.local:5000/mcr/"], false),
+        ];
+
+        assert_eq!(
+            pull_sources("alpine:3.19", &mirrors),
+            vec![
+                PullSource {
+                    image: "cache.local:5000/docker-hub/library/alpine:3.19".to_owned(),
+                    registry: "cache.local:5000".to_owned(),
+                    tag_as: Some(("alpine".to_owned(), "3.19".to_owned())),
+                    mirror: true,
+                },
+                PullSource {
+                    image: "backup.local/library/alpine:3.19".to_owned(),
+                    registry: "backup.local".to_owned(),
+                    tag_as: Some(("alpine".to_owned(), "3.19".to_owned())),
+                    mirror: true,
+                },
+                upstream("alpine:3.19", "docker.io"),
+            ]
+        );
+
+        // Without fallback, only the mirrors are used.
+        assert_eq!(
+            pull_sources("mcr.microsoft.com/azureiotedge-agent", &mirrors),
+            vec![PullSource {
+                image: "cache.local:5000/mcr/azureiotedge-agent:latest".to_owned(),
+                registry: "cache.local:5000".to_owned(),
+                tag_as: Some((
+                    "mcr.microsoft.com/azureiotedge-agent".to_owned(),
+                    "latest".to_owned()
+                )),
+                mirror: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn pull_sources_no_mirror() {
+        let mirrors = [mirror("docker.io", &["cache.local"], true)];
+
+        // Registry without mirrors
+        assert_eq!(
+            pull_sources("contoso.azurecr.io/module:1.0", &mirrors),
+            vec![upstream(
+                "contoso.azurecr.io/module:1.0",
+                "contoso.azurecr.io"
+            )]
+        );
+
+        // Image referenced by digest
+        let image =
+            "alpine@sha256:0d5f5a015e5ec7b7cb45a6fbf0dc6a6c7c1be1bbac42a75a8ae3d4f6e3f00e2b";
+        assert_eq!(
+            pull_sources(image, &mirrors),
+            vec![upstream(image, "docker.io")]
+        );
+    }
+
+    #[test]
+    fn docker_config() {
+        let config = serde_json::json!({
+            "auths": {
+                "https://index.docker.io/v1/": { "auth": "dXNlcjpwYXNz" },
+                "cache.local:5000": { "username": "cache", "password": "secret" },
+                "contoso.azurecr.io": { "identitytoken": "TOKEN" },
+            }
+        })
+        .to_string();
+
+        let auth = parse_docker_config(config.as_bytes(), "docker.io")
+            .unwrap()
+            .unwrap();
+        assert_eq!(auth.username.as_deref(), Some("user"));
+        assert_eq!(auth.password.as_deref(), Some("pass"));
+        assert_eq!(auth.server_address.as_deref(), Some("docker.io"));
+
+        let auth = parse_docker_config(config.as_bytes(), "cache.local:5000")
+            .unwrap()
+            .unwrap();
+        assert_eq!(auth.username.as_deref(), Some("cache"));
+        assert_eq!(auth.password.as_deref(), Some("secret"));
+
+        let auth = parse_docker_config(config.as_bytes(), "contoso.azurecr.io")
+            .unwrap()
+            .unwrap();
+        assert_eq!(auth.username, None);
+        assert_eq!(
+            auth.other_properties.get("identitytoken"),
+            Some(&serde_json::json!("TOKEN"))
+        );
+
+        assert!(
+            parse_docker_config(config.as_bytes(), "fabrikam.azurecr.io")
+                .unwrap()
+                .is_none()
+        );
+        assert!(parse_docker_config(b"not json", "docker.io").is_err());
+    }
+
+    #[tokio::test]
+    async fn credential_helper() {
+        let dir = std::env::temp_dir().join(format!(
+            "edgelet-registry-credential-helper-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // The helper echoes the server URL it was asked about as the user name.
+        let helper = dir.join("docker-credential-test");
+        std::fs::write(
+            &helper,
+            "#!/bin/sh\n\
+             [ \"$1\" = get ] || exit 1\n\
+             read -r server\n\
+             [ \"$server\" = unknown.local ] && { echo 'credentials not found in native keychain'; exit 1; }\n\
+             echo \"{\\\"ServerURL\\\":\\\"$server\\\",\\\"Username\\\":\\\"$server\\\",\\\"Secret\\\":\\\"pass\\\"}\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let providers = [
+            CredentialProvider {
+                registries: vec!["*.local".to_owned(), "docker.io".to_owned()],
+                source: CredentialSource::Helper(helper),
+            },
+            CredentialProvider {
+                registries: vec!["**".to_owned()],
+                source: CredentialSource::DockerConfig(dir.join("missing.json")),
+            },
+        ];
+
+        let auth = resolve_credentials("cache.local", &providers)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(auth.username.as_deref(), Some("cache.local"));
+        assert_eq!(auth.password.as_deref(), Some("pass"));
+
+        // Docker Hub is looked up by its index URL.
+        let auth = resolve_credentials("docker.io", &providers)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            auth.username.as_deref(),
+            Some("https://index.docker.io/v1/")
+        );
+
+        // Registries that no provider matches have no credentials.
+        let providers = &providers[..1];
+        assert!(
+            resolve_credentials("contoso.azurecr.io", providers)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // Nor do registries that the helper has no credentials for.
+        assert!(
+            resolve_credentials("unknown.local", providers)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
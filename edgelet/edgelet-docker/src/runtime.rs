@@ -1,11 +1,13 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::pin::Pin;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{process, str};
 
 use anyhow::Context;
+use http_body_util::combinators::BoxBody;
 use hyper::body::Incoming;
 use hyper_util::client::legacy::connect::Connect;
 use sysinfo::{Disks, Process, System};
@@ -13,27 +15,41 @@ use tokio::sync::Mutex;
 use tokio::sync::mpsc::UnboundedSender;
 use url::Url;
 
-use docker::apis::{Configuration, DockerApi, DockerApiClient};
-use docker::models::{ContainerCreateBody, ContainerTopResponse, Ipam, NetworkConfig};
+use docker::apis::{
+    Configuration, DockerApi, DockerApiClient, EventStream, ImageCreateStream, MultiplexedStream,
+    OutputStream,
+};
+use docker::models::{
+    ContainerCreateBody, ContainerTopResponse, ExecConfig, ExecStartConfig, Ipam, NetworkConfig,
+};
 use edgelet_core::{
-    DiskInfo, LogOptions, Module, ModuleAction, ModuleRegistry, ModuleRuntime, ModuleRuntimeState,
-    RegistryOperation, RuntimeOperation, SystemInfo as CoreSystemInfo, SystemResources, UrlExt,
+    DiskInfo, ExecOutput, ImageUsage, LogOptions, Module, ModuleAction, ModuleEvent,
+    ModuleEventKind, ModuleRegistry, ModuleRuntime, ModuleRuntimeState, ModuleStats, ModuleStatus,
+    PullProgress, RegistryOperation, RuntimeOperation, SnapshotInfo, SystemInfo as CoreSystemInfo,
+    SystemResources, UrlExt,
 };
 use edgelet_settings::{
-    DockerConfig, Ipam as CoreIpam, MobyNetwork, ModuleSpec, RuntimeSettings, Settings,
+    ContainerEngine, CreateOptionsPolicy, CredentialProvider, DockerConfig, Ipam as CoreIpam,
+    MobyNetwork, ModuleLimits, ModuleSpec, PullSettings, RegistryMirror, ResourceLimits,
+    RuntimeSettings, Settings,
 };
 use edgelet_utils::ensure_not_empty;
 use http_common::Connector;
 
 use crate::error::Error;
 use crate::module::{DockerModule, MODULE_TYPE as DOCKER_MODULE_TYPE, runtime_state};
-use crate::{ImagePruneData, MakeModuleRuntime};
+use crate::notary::{ImageReference, TrustedImage};
+use crate::pull_proxy::PullProxy;
+use crate::registry::{PullSource, pull_sources, resolve_credentials};
+use crate::snapshot::{Snapshot, SnapshotModule, SnapshotStore};
+use crate::{ImagePruneData, MakeModuleRuntime, Notary};
 
 type Deserializer = &'static mut serde_json::Deserializer<serde_json::de::IoRead<std::io::Empty>>;
 
 const OWNER_LABEL_KEY: &str = "net.azure-devices.edge.owner";
 const OWNER_LABEL_VALUE: &str = "Microsoft.Azure.Devices.Edge.Agent";
 const ORIGINAL_IMAGE_LABEL_KEY: &str = "net.azure-devices.edge.original-image";
+const RESTORE_BACKUP_SUFFIX: &str = ".pre-restore";
 const LABELS: &[&str] = &["net.azure-devices.edge.owner=Microsoft.Azure.Devices.Edge.Agent"];
 
 #[derive(Clone)]
@@ -44,6 +60,139 @@ pub struct DockerModuleRuntime<C> {
     allow_elevated_docker_permissions: bool,
     additional_info: BTreeMap<String, String>,
     image_use_data: ImagePruneData,
+    snapshots: SnapshotStore,
+    engine: ContainerEngine,
+    module_limits: ModuleLimits,
+    policy: CreateOptionsPolicy,
+    registry_mirrors: Vec<RegistryMirror>,
+    credential_providers: Vec<CredentialProvider>,
+    pulls: PullSettings,
+    pull_permits: Option<Arc<tokio::sync::Semaphore>>,
+    notary: Option<Notary>,
+    // Image reference -> the trusted image that was pulled for it.
+    trusted_images: Arc<std::sync::Mutex<HashMap<String, TrustedImage>>>,
+}
+
+impl<C> DockerModuleRuntime<C> {
+    /// Enforces content trust for the registries configured in `notary`.
+    #[must_use]
+    pub fn with_notary(mut self, notary: Notary) -> Self {
+        self.notary = Some(notary);
+        self
+    }
+
+    /// Returns the container engine's data root, which holds its images.
+    pub async fn data_root(&self) -> anyhow::Result<std::path::PathBuf> {
+        let docker_info = self
+            .client
+            .system_info()
+            .await
+            .context(Error::Docker)
+            .context(Error::RuntimeOperation(RuntimeOperation::SystemInfo))?;
+
+        docker_info
+            .docker_root_dir
+            .map(Into::into)
+            .ok_or(Error::Docker)
+            .context(Error::RuntimeOperation(RuntimeOperation::SystemInfo))
+    }
+
+    async fn resolve_trusted_image(
+        &self,
+        image: &str,
+        auth: Option<&docker::models::AuthConfig>,
+    ) -> anyhow::Result<Option<TrustedImage>> {
+        let Some(notary) = &self.notary else {
+            return Ok(None);
+        };
+
+        notary.resolve(image, auth).await.map_err(|e| {
+            log::warn!("{e:?}");
+            e
+        })
+    }
+
+    /// Returns the trusted image that was pulled for `image`, so that modules are created from
+    /// the digest that was verified and pulled without asking Notary again. Images that
+    /// haven't been pulled since aziot-edged started are resolved through Notary.
+    async fn pulled_trusted_image(
+        &self,
+        image: &str,
+        auth: Option<&docker::models::AuthConfig>,
+    ) -> anyhow::Result<Option<TrustedImage>> {
+        let pulled = self
+            .trusted_images
+            .lock()
+            .expect("trusted images lock poisoned")
+            .get(image)
+            .cloned();
+
+        match pulled {
+            Some(trusted_image) => Ok(Some(trusted_image)),
+            None => self.resolve_trusted_image(image, auth).await,
+        }
+    }
+
+    /// Checks that the local image for `config` is the one its digest pins, and that the
+    /// deployment's digest, if any, agrees with it.
+    async fn verify_image_digest(&self, config: &DockerConfig) -> anyhow::Result<()> {
+        let image = config.image();
+        let reference = ImageReference::parse(image);
+
+        let Some(digest) = &reference.digest else {
+            return Err(Error::ImageDigest(
+                image.to_owned(),
+                "image is not referenced by digest".to_owned(),
+            )
+            .into());
+        };
+
+        if let Some(expected) = config.digest()
+            && expected != digest
+        {
+            return Err(Error::ImageDigest(
+                image.to_owned(),
+                format!("deployment expects digest {expected}"),
+            )
+            .into());
+        }
+
+        let filters = serde_json::json!({ "reference": { image: true } }).to_string();
+        let images = self
+            .client
+            .images_list(false, &filters, true)
+            .await
+            .context(Error::Docker)
+            .map_err(|e| {
+                log::warn!("{e:?}");
+                e
+            })?;
+
+        let verified = images
+            .iter()
+            .flat_map(|image| image.repo_digests.iter().flatten())
+            .any(|repo_digest| is_repo_digest_of(repo_digest, &reference));
+        if !verified {
+            return Err(Error::ImageDigest(
+                image.to_owned(),
+                "no local image has this digest".to_owned(),
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `repo_digest`, an entry of an image's `RepoDigests`, names the same repository and
+/// digest as `reference`.
+fn is_repo_digest_of(repo_digest: &str, reference: &ImageReference) -> bool {
+    let repo_digest = ImageReference::parse(repo_digest);
+
+    repo_digest.digest.is_some()
+        && repo_digest.digest == reference.digest
+        && repo_digest.registry == reference.registry
+        && repo_digest.repository == reference.repository
 }
 
 fn merge_env(cur_env: Option<&[String]>, new_env: &BTreeMap<String, String>) -> Vec<String> {
@@ -76,45 +225,393 @@ impl<C> std::fmt::Debug for DockerModuleRuntime<C> {
     }
 }
 
-#[async_trait::async_trait]
-impl<C> ModuleRegistry for DockerModuleRuntime<C>
+impl<C> DockerModuleRuntime<C>
 where
     C: Clone + Connect + Send + Sync + 'static,
 {
-    type Config = DockerConfig;
+    /// Pulls `image` from each of `sources` in turn until one succeeds. Waits for one of the
+    /// `max_concurrent` pulls to finish first if that many are in progress.
+    async fn pull_any(
+        &self,
+        image: &str,
+        sources: &[PullSource],
+        auth: Option<&docker::models::AuthConfig>,
+        progress: Option<&tokio::sync::mpsc::UnboundedSender<PullProgress>>,
+    ) -> anyhow::Result<()> {
+        let _permit = match &self.pull_permits {
+            Some(permits) => Some(
+                permits
+                    .acquire()
+                    .await
+                    .expect("pull semaphore is never closed"),
+            ),
+            None => None,
+        };
 
-    async fn pull(&self, config: &Self::Config) -> anyhow::Result<()> {
-        let image = config.image().to_owned();
-        let is_content_trust_enabled = false;
+        let mut sources = sources.iter().peekable();
+        while let Some(source) = sources.next() {
+            match self.pull_from(image, source, auth, progress).await {
+                Ok(()) => return Ok(()),
+                Err(e) if sources.peek().is_some() => {
+                    log::warn!(
+                        "Could not pull image {image} from {}, trying the next source: {e:?}",
+                        source.registry
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        unreachable!("pull_sources always returns at least one source")
+    }
 
-        if is_content_trust_enabled {
-            log::info!("Pulling image via digest {image}...");
+    /// Pulls `image` from one source and, if the source's name for the image differs from
+    /// the deployment's, tags it with the deployment's name. `auth` holds the credentials for
+    /// the image's own registry; mirrors get theirs from the credential providers.
+    async fn pull_from(
+        &self,
+        image: &str,
+        source: &PullSource,
+        auth: Option<&docker::models::AuthConfig>,
+        progress: Option<&tokio::sync::mpsc::UnboundedSender<PullProgress>>,
+    ) -> anyhow::Result<()> {
+        let mirror_auth;
+        let auth = if source.mirror {
+            mirror_auth = resolve_credentials(&source.registry, &self.credential_providers).await?;
+            log::info!("Pulling image {image} via mirror {}...", source.image);
+            mirror_auth.as_ref()
         } else {
-            log::info!("Pulling image via tag {image}...");
-        }
+            if source.tag_as.is_some() {
+                log::info!("Pulling image via digest {}...", source.image);
+            } else {
+                log::info!("Pulling image via tag {image}...");
+            }
+            auth
+        };
 
-        let creds = match config.auth() {
+        let creds = match auth {
             Some(a) => {
-                let json = serde_json::to_string(&a).with_context(|| {
-                    Error::RegistryOperation(RegistryOperation::PullImage(image.clone()))
-                })?;
+                let json = serde_json::to_string(&a)?;
                 let engine = base64::engine::general_purpose::URL_SAFE;
                 base64::Engine::encode(&engine, json)
             }
             None => String::new(),
         };
 
-        self.client
-            .image_create(&image, "", "", "", "", "", &creds, "")
+        let proxy = match self.pulls.max_bandwidth {
+            Some(max_bandwidth) => PullProxy::for_registry(&source.registry, max_bandwidth)
+                .await
+                .map_err(|e| {
+                    log::warn!("{e:?}");
+                    e
+                })?,
+            None => None,
+        };
+        let pulled = proxy
+            .as_ref()
+            .map_or_else(|| source.image.clone(), |proxy| proxy.image(&source.image));
+
+        async {
+            let body = self
+                .client
+                .image_create_stream(&pulled, "", "", "", "", "", &creds, "")
+                .await?;
+
+            let mut messages = ImageCreateStream::new(body);
+
+            while let Some(info) = messages.next().await? {
+                let detail = info.progress_detail.unwrap_or_default();
+                let update = PullProgress {
+                    id: info.id,
+                    status: info.status.unwrap_or_default(),
+                    current: detail
+                        .current
+                        .and_then(|current| u64::try_from(current).ok()),
+                    total: detail.total.and_then(|total| u64::try_from(total).ok()),
+                };
+
+                if let Some(progress) = progress {
+                    // The receiver going away does not affect the pull.
+                    let _ = progress.send(update);
+                }
+            }
+
+            anyhow::Ok(())
+        }
+        .await
+        .context(Error::Docker)
+        .map_err(|e| {
+            log::warn!("{e:?}");
+            e
+        })?;
+
+        if proxy.is_some() {
+            self.name_proxied_image(&pulled, &source.image, &creds)
+                .await
+                .context(Error::Docker)
+                .map_err(|e| {
+                    log::warn!("{e:?}");
+                    e
+                })?;
+        }
+        drop(proxy);
+
+        if let Some((repository, tag)) = &source.tag_as {
+            self.client
+                .image_tag(&source.image, repository, tag)
+                .await
+                .context(Error::Docker)
+                .map_err(|e| {
+                    log::warn!("{e:?}");
+                    e
+                })?;
+        }
+
+        // The mirror's name for the image is not needed once it has the deployment's name.
+        // Removing a name that isn't the image's last one only untags it.
+        if source.mirror
+            && let Err(e) = self.client.image_delete(&source.image, false, true).await
+        {
+            log::warn!("Could not remove mirror tag {}: {e:?}", source.image);
+        }
+
+        Ok(())
+    }
+
+    /// Gives an image pulled through a bandwidth-limited proxy as `pulled` its own name
+    /// `image`, then removes the proxy's name for it. Images referenced by a tag are tagged.
+    /// Images referenced by a digest can only get the digest from their registry, so they are
+    /// pulled again directly; the engine already has their layers and only fetches the
+    /// manifest.
+    async fn name_proxied_image(
+        &self,
+        pulled: &str,
+        image: &str,
+        creds: &str,
+    ) -> anyhow::Result<()> {
+        let reference = ImageReference::parse(image);
+
+        if let (None, Some(tag)) = (&reference.digest, &reference.tag) {
+            self.client.image_tag(pulled, &reference.name, tag).await?;
+        } else {
+            let body = self
+                .client
+                .image_create_stream(image, "", "", "", "", "", creds, "")
+                .await?;
+
+            let mut messages = ImageCreateStream::new(body);
+            while messages.next().await?.is_some() {}
+        }
+
+        // Removing a name that isn't the image's last one only untags it.
+        if let Err(e) = self.client.image_delete(pulled, false, true).await {
+            log::warn!("Could not remove proxy tag {pulled}: {e:?}");
+        }
+
+        Ok(())
+    }
+
+    /// Stops the current modules and moves the ones the snapshot replaces out of the way
+    /// under a temporary name, then creates and starts the modules in the snapshot. Each
+    /// change is recorded in `restore` so that it can be rolled back if a later one fails.
+    async fn replace_modules(
+        &self,
+        snapshot: Snapshot,
+        tags: &HashMap<String, String>,
+        restore: &mut Restore,
+    ) -> anyhow::Result<()> {
+        for (module, state) in self.list_with_details().await? {
+            let name = module.name().to_owned();
+            let running = matches!(
+                state.status(),
+                ModuleStatus::Running | ModuleStatus::Unhealthy
+            );
+            let in_snapshot = snapshot
+                .modules
+                .iter()
+                .any(|snapshot_module| snapshot_module.spec.name() == name);
+
+            restore.replaced.push(ReplacedModule {
+                spec: self.snapshots.load_module_spec(&name)?,
+                name: name.clone(),
+                running,
+                backup: None,
+            });
+
+            if running {
+                self.stop(&name, None).await?;
+            }
+
+            if in_snapshot {
+                let backup = format!("{name}{RESTORE_BACKUP_SUFFIX}");
+
+                self.client
+                    .container_rename(&name, &backup)
+                    .await
+                    .context(Error::Docker)?;
+
+                restore
+                    .replaced
+                    .last_mut()
+                    .expect("module was just added")
+                    .backup = Some(backup);
+            }
+        }
+
+        for SnapshotModule { spec, image_id } in snapshot.modules {
+            // The deployment's tag may have moved to a different image since the snapshot was
+            // saved, so it's pointed back at the image the module was running.
+            let reference = ImageReference::parse(spec.config().image());
+            if let (Some(tag), None) = (reference.tag, reference.digest) {
+                let previous = tags.get(&format!("{}:{tag}", reference.name)).cloned();
+
+                self.client
+                    .image_tag(&image_id, &reference.name, &tag)
+                    .await
+                    .context(Error::Docker)?;
+
+                if let Some(previous) = previous {
+                    restore.retagged.push((reference.name, tag, previous));
+                }
+            }
+
+            let module_name = spec.name().to_owned();
+            restore.created.push(module_name.clone());
+            self.create(spec).await?;
+            self.start(&module_name).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Undoes the changes of a restore that failed, so that the modules that were running
+    /// before it are running again.
+    async fn roll_back(&self, restore: Restore) {
+        for name in restore.created.iter().rev() {
+            if let Err(e) = ModuleRuntime::remove(self, name).await {
+                log::warn!("Could not remove module {name} while rolling back: {e:?}");
+            }
+        }
+
+        for (repo, tag, image_id) in restore.retagged.iter().rev() {
+            if let Err(e) = self.client.image_tag(image_id, repo, tag).await {
+                log::warn!("Could not tag image {image_id} as {repo}:{tag} again: {e:?}");
+            }
+        }
+
+        for module in restore.replaced.iter().rev() {
+            if let Some(backup) = &module.backup {
+                if let Err(e) = self.client.container_rename(backup, &module.name).await {
+                    log::error!(
+                        "Could not rename module {backup} back to {}: {e:?}",
+                        module.name
+                    );
+                    continue;
+                }
+
+                // Removing the module created in its place also removed its spec.
+                let spec = match &module.spec {
+                    Some(spec) => self.snapshots.save_module_spec(spec),
+                    None => self.snapshots.remove_module_spec(&module.name),
+                };
+                if let Err(e) = spec {
+                    log::warn!("Could not restore the spec of module {}: {e}", module.name);
+                }
+            }
+
+            if module.running
+                && let Err(e) = self.start(&module.name).await
+            {
+                log::error!("Could not start module {} again: {e:?}", module.name);
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<C> ModuleRegistry for DockerModuleRuntime<C>
+where
+    C: Clone + Connect + Send + Sync + 'static,
+{
+    type Config = DockerConfig;
+
+    async fn pull(
+        &self,
+        config: &Self::Config,
+        progress: Option<tokio::sync::mpsc::UnboundedSender<PullProgress>>,
+    ) -> anyhow::Result<()> {
+        let image = config.image().to_owned();
+        let registry = ImageReference::parse(&image).registry;
+
+        // Credentials in the deployment take precedence over the credential providers. A
+        // provider failing is not fatal, since mirrors or anonymous access may still work.
+        let auth = match config.auth() {
+            Some(auth) => Some(auth.clone()),
+            None => resolve_credentials(&registry, &self.credential_providers)
+                .await
+                .unwrap_or_else(|e| {
+                    log::warn!("{e:?}");
+                    None
+                }),
+        };
+
+        let trusted_image = self
+            .resolve_trusted_image(&image, auth.as_ref())
             .await
-            .context(Error::Docker)
-            .map_err(|e| {
-                log::warn!("{e:?}");
-                e
-            })
             .with_context(|| {
+                Error::RegistryOperation(RegistryOperation::RegistryPull(image.clone()))
+            })?;
+
+        let sources = if let Some(trusted_image) = &trusted_image {
+            // Images pulled by digest are untagged, so point the deployment's tag at the
+            // verified image.
+            vec![PullSource {
+                image: trusted_image.digest_reference.clone(),
+                registry,
+                tag_as: Some((trusted_image.repository.clone(), trusted_image.tag.clone())),
+                mirror: false,
+            }]
+        } else {
+            pull_sources(&image, &self.registry_mirrors)
+        };
+
+        let mut retry = 0;
+        loop {
+            match self
+                .pull_any(&image, &sources, auth.as_ref(), progress.as_ref())
+                .await
+            {
+                Ok(()) => break,
+                Err(e) if retry < self.pulls.retries => {
+                    retry += 1;
+                    let backoff = self.pulls.backoff(retry);
+                    log::warn!(
+                        "Could not pull image {image}, retrying in {}s ({retry}/{}): {e:?}",
+                        backoff.as_secs(),
+                        self.pulls.retries
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        Error::RegistryOperation(RegistryOperation::PullImage(image.clone()))
+                    });
+                }
+            }
+        }
+
+        if self.policy.require_digest {
+            self.verify_image_digest(config).await.with_context(|| {
                 Error::RegistryOperation(RegistryOperation::PullImage(image.clone()))
             })?;
+        }
+
+        if let Some(trusted_image) = trusted_image {
+            self.trusted_images
+                .lock()
+                .expect("trusted images lock poisoned")
+                .insert(image.clone(), trusted_image);
+        }
 
         log::info!("Successfully pulled image {image}");
 
@@ -126,7 +623,15 @@ where
                         "No docker images present on device: {image} was just pulled, but not found on device"
                     );
                 } else if let Some(image_id) = image_name_to_id.get(config.image()) {
-                    self.image_use_data.record_image_use_timestamp(image_id)?;
+                    // the size is only informational
+                    let size = match self.image_sizes().await {
+                        Ok(image_sizes) => image_sizes.get(image_id).copied(),
+                        Err(e) => {
+                            log::warn!("Could not get size of image {image}: {e}");
+                            None
+                        }
+                    };
+                    self.image_use_data.record_image_pull(image_id, size)?;
                 } else {
                     log::warn!(
                         "Could not retrieve image id. {image} was not added to image garbage collection list and will not be garbage collected"
@@ -161,6 +666,145 @@ where
         log::info!("Successfully removed image {name}");
         Ok(())
     }
+
+    async fn load(&self, archive: Incoming, digest: Option<&str>) -> anyhow::Result<Vec<String>> {
+        log::info!("Loading images from an image archive...");
+
+        let operation = || Error::RegistryOperation(RegistryOperation::LoadImages);
+
+        if self.policy.require_digest && digest.is_none() {
+            return Err(Error::ImageDigest(
+                "image archive".to_owned(),
+                "a digest is required to load images on this device".to_owned(),
+            ))
+            .with_context(operation);
+        }
+
+        let (archive, received) = UploadBody::new(archive);
+
+        let loaded = self
+            .client
+            .image_load(BoxBody::new(archive))
+            .await
+            .context(Error::Docker)
+            .and_then(|loaded| received.check().map(|()| loaded))
+            .map_err(|e| {
+                log::warn!("{e:?}");
+                e
+            })
+            .with_context(operation)?;
+
+        let images = self
+            .client
+            .images_list(false, "", true)
+            .await
+            .context(Error::Docker)
+            .map_err(|e| {
+                log::warn!("{e:?}");
+                e
+            })
+            .with_context(operation)?;
+        let images: Vec<_> = images
+            .into_iter()
+            .filter(|image| {
+                loaded.iter().any(|reference| {
+                    image.id == *reference
+                        || image.repo_tags.iter().flatten().any(|tag| tag == reference)
+                })
+            })
+            .collect();
+
+        if let Some(digest) = digest {
+            let verified = images.iter().any(|image| {
+                image.id == digest
+                    || image.repo_digests.iter().flatten().any(|repo_digest| {
+                        ImageReference::parse(repo_digest).digest.as_deref() == Some(digest)
+                    })
+            });
+
+            if !verified {
+                return Err(Error::ImageDigest(
+                    loaded.join(", "),
+                    format!("no loaded image has digest {digest}"),
+                ))
+                .with_context(operation);
+            }
+        }
+
+        // Loaded images are garbage collected like pulled ones.
+        for image in &images {
+            let size = image.size.and_then(|size| u64::try_from(size).ok());
+            self.image_use_data.record_image_pull(&image.id, size)?;
+        }
+
+        log::info!("Successfully loaded images {}", loaded.join(", "));
+        Ok(loaded)
+    }
+
+    async fn image_sizes(&self) -> anyhow::Result<HashMap<String, u64>> {
+        let images = self
+            .client
+            .images_list(false, "", false)
+            .await
+            .context(Error::Docker)
+            .map_err(|e| {
+                log::warn!("{e:?}");
+                e
+            })
+            .context(Error::RuntimeOperation(RuntimeOperation::ListImages))?;
+
+        Ok(images
+            .into_iter()
+            .filter_map(|image| {
+                let size = u64::try_from(image.size?).ok()?;
+                Some((image.id, size))
+            })
+            .collect())
+    }
+
+    async fn image_usage(&self) -> anyhow::Result<Vec<ImageUsage>> {
+        let images = self
+            .client
+            .images_list(false, "", false)
+            .await
+            .context(Error::Docker)
+            .map_err(|e| {
+                log::warn!("{e:?}");
+                e
+            })
+            .context(Error::RuntimeOperation(RuntimeOperation::ListImages))?;
+        let images: HashMap<_, _> = images
+            .into_iter()
+            .map(|image| (image.id.clone(), image))
+            .collect();
+
+        let mut result: Vec<ImageUsage> = self
+            .image_use_data
+            .image_use()
+            .context(Error::RuntimeOperation(RuntimeOperation::ListImages))?
+            .into_iter()
+            .map(|(id, image_use)| {
+                let image = images.get(&id);
+                ImageUsage {
+                    names: image
+                        .and_then(|image| image.repo_tags.clone())
+                        .unwrap_or_default(),
+                    first_pulled: image_use.first_pulled,
+                    last_used: image_use.last_used,
+                    last_started: image_use.last_started,
+                    pull_count: image_use.pull_count,
+                    // prefer the engine's current view of the size
+                    size: image
+                        .and_then(|image| u64::try_from(image.size?).ok())
+                        .or(image_use.size),
+                    id,
+                }
+            })
+            .collect();
+        result.sort_by(|image1, image2| image1.id.cmp(&image2.id));
+
+        Ok(result)
+    }
 }
 
 #[async_trait::async_trait]
@@ -174,9 +818,11 @@ impl MakeModuleRuntime for DockerModuleRuntime<Connector> {
         create_socket_channel: UnboundedSender<ModuleAction>,
         image_use_data: ImagePruneData,
     ) -> anyhow::Result<Self::ModuleRuntime> {
-        log::info!("Initializing module runtime...");
+        let engine = settings.moby_runtime().engine();
+        log::info!("Initializing module runtime for container engine {engine}...");
 
         let client = init_client(settings.moby_runtime().uri())?;
+        check_engine(engine, &client).await?;
         create_network_if_missing(settings, &client).await?;
 
         // to avoid excessive FD usage, we will not allow sysinfo to keep files open.
@@ -191,6 +837,20 @@ impl MakeModuleRuntime for DockerModuleRuntime<Connector> {
             allow_elevated_docker_permissions: settings.allow_elevated_docker_permissions(),
             additional_info: settings.additional_info().clone(),
             image_use_data,
+            snapshots: SnapshotStore::new(settings.homedir()),
+            engine,
+            module_limits: settings.moby_runtime().module_limits().clone(),
+            policy: settings.moby_runtime().policy().clone(),
+            registry_mirrors: settings.moby_runtime().registry_mirrors().to_vec(),
+            credential_providers: settings.moby_runtime().credential_providers().to_vec(),
+            pulls: settings.moby_runtime().pulls().clone(),
+            pull_permits: settings
+                .moby_runtime()
+                .pulls()
+                .max_concurrent
+                .map(|max| Arc::new(tokio::sync::Semaphore::new(max.get()))),
+            notary: None,
+            trusted_images: Default::default(),
         };
 
         Ok(runtime)
@@ -223,6 +883,83 @@ pub fn init_client(docker_url: &Url) -> anyhow::Result<DockerApiClient<Connector
     Ok(DockerApiClient::new(connector).with_configuration(configuration))
 }
 
+/// Checks that the engine listening on the runtime URI is the configured one, and that it
+/// serves the version of the Docker API that edged is written against. Podman is only
+/// supported through its Docker-compatible API.
+async fn check_engine(
+    engine: ContainerEngine,
+    client: &DockerApiClient<Connector>,
+) -> anyhow::Result<()> {
+    let version = client
+        .system_version()
+        .await
+        .context(Error::Docker)
+        .map_err(|e| {
+            log::warn!("{e:?}");
+            e
+        })
+        .context(Error::RuntimeOperation(RuntimeOperation::Init))?;
+
+    check_engine_version(engine, &version)
+        .context(Error::RuntimeOperation(RuntimeOperation::Init))?;
+
+    log::info!(
+        "Container engine version {}, API version {}",
+        version.version.as_deref().unwrap_or("unknown"),
+        version.api_version.as_deref().unwrap_or("unknown"),
+    );
+
+    Ok(())
+}
+
+fn check_engine_version(
+    engine: ContainerEngine,
+    version: &docker::models::SystemVersion,
+) -> Result<(), Error> {
+    // Podman names its server component "Podman Engine"; Moby and Docker name theirs "Engine".
+    let detected = if version
+        .components
+        .iter()
+        .flatten()
+        .any(|component| component.name.starts_with("Podman"))
+    {
+        ContainerEngine::Podman
+    } else {
+        ContainerEngine::Moby
+    };
+
+    if detected != engine {
+        return Err(Error::ContainerEngine(format!(
+            "engine is set to {engine}, but the runtime URI is served by {detected}"
+        )));
+    }
+
+    let required = parse_api_version(docker::apis::API_VERSION).expect("API_VERSION is valid");
+    let max = version.api_version.as_deref().and_then(parse_api_version);
+    let min = version
+        .min_api_version
+        .as_deref()
+        .and_then(parse_api_version);
+
+    let supported = max.is_some_and(|max| required <= max) && min.is_none_or(|min| min <= required);
+    if !supported {
+        return Err(Error::ContainerEngine(format!(
+            "{detected} API versions {} to {} do not include version {}",
+            version.min_api_version.as_deref().unwrap_or("unknown"),
+            version.api_version.as_deref().unwrap_or("unknown"),
+            docker::apis::API_VERSION,
+        )));
+    }
+
+    Ok(())
+}
+
+/// Parses a Docker API version such as `1.41` into its major and minor parts.
+fn parse_api_version(version: &str) -> Option<(u32, u32)> {
+    let (major, minor) = version.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
 async fn create_network_if_missing(
     settings: &Settings,
     client: &DockerApiClient<Connector>,
@@ -263,6 +1000,12 @@ async fn create_network_if_missing(
     Ok(())
 }
 
+fn podman_short_name(name: &str) -> Option<&str> {
+    name.strip_prefix("docker.io/library/")
+        .or_else(|| name.strip_prefix("docker.io/"))
+        .or_else(|| name.strip_prefix("localhost/"))
+}
+
 fn get_ipv6_settings(network_configuration: &MobyNetwork) -> (bool, Option<Ipam>) {
     if let MobyNetwork::Network(network) = network_configuration {
         let ipv6 = network.ipv6().unwrap_or_default();
@@ -320,6 +1063,12 @@ where
             return Err(Error::InvalidModuleType(module.r#type().to_string()).into());
         }
 
+        // The spec is kept as it was deployed, before the device's settings change it, so
+        // that a snapshot recreates the module the same way.
+        let deployed_spec = deployed_spec(&module).with_context(|| {
+            Error::RuntimeOperation(RuntimeOperation::CreateModule(module.name().to_string()))
+        })?;
+
         unset_privileged(
             self.allow_elevated_docker_permissions,
             module.config_mut().create_options_mut(),
@@ -329,15 +1078,50 @@ where
             module.config_mut().create_options_mut(),
         );
 
-        let image = module.config().image().to_owned();
-        let is_content_trust_enabled = false;
+        crate::policy::check(
+            &self.policy,
+            module.name(),
+            module.config().image(),
+            module.config().create_options(),
+        )
+        .with_context(|| {
+            Error::RuntimeOperation(RuntimeOperation::CreateModule(module.name().to_string()))
+        })?;
 
-        if is_content_trust_enabled {
-            log::info!("Creating image via digest {}...", &image);
-        } else {
-            log::info!("Creating image via tag {}...", &image);
+        // The image may have been replaced since it was pulled.
+        if self.policy.require_digest {
+            self.verify_image_digest(module.config())
+                .await
+                .with_context(|| {
+                    Error::RuntimeOperation(RuntimeOperation::CreateModule(
+                        module.name().to_string(),
+                    ))
+                })?;
         }
 
+        let limits = self.module_limits.for_module(module.name());
+        let name = module.name().to_owned();
+        apply_module_limits(&name, &limits, module.config_mut().create_options_mut())
+            .with_context(|| Error::RuntimeOperation(RuntimeOperation::CreateModule(name)))?;
+
+        let trusted_image = self
+            .pulled_trusted_image(module.config().image(), module.config().auth())
+            .await
+            .with_context(|| {
+                Error::RuntimeOperation(RuntimeOperation::CreateModule(module.name().to_string()))
+            })?;
+
+        let image = if let Some(trusted_image) = trusted_image {
+            log::info!(
+                "Creating image via digest {}...",
+                trusted_image.digest_reference
+            );
+            trusted_image.digest_reference
+        } else {
+            log::info!("Creating image via tag {}...", module.config().image());
+            module.config().image().to_owned()
+        };
+
         log::debug!("Creating container {} with image {image}...", module.name());
 
         let mut create_options = module.config().create_options().clone();
@@ -367,17 +1151,40 @@ where
                 Error::RuntimeOperation(RuntimeOperation::CreateModule(module.name().to_string()))
             })?;
 
+        if let Err(e) = self.snapshots.save_module_spec(&deployed_spec) {
+            log::warn!(
+                "Could not save the spec of module {} for snapshots: {e}",
+                module.name()
+            );
+
+            // A spec left by an earlier module of the same name must not be used instead.
+            if let Err(e) = self.snapshots.remove_module_spec(module.name()) {
+                log::warn!("Could not remove the spec of module {}: {e}", module.name());
+            }
+        }
+
         // Now, get the image id of the image associated with the module we started
         let module_with_details = self.get(module.name()).await?;
 
+        let image_id = module_with_details
+            .0
+            .config()
+            .image_hash()
+            .ok_or(Error::GetImageId())?;
+
         // update image use timestamp for image garbage collection job later
-        self.image_use_data.record_image_use_timestamp(
-            module_with_details
-                .0
-                .config()
-                .image_hash()
-                .ok_or(Error::GetImageId())?,
-        )?;
+        self.image_use_data.record_image_use_timestamp(image_id)?;
+
+        // track the module's images so that image garbage collection can keep the previous one
+        if let Err(e) = self
+            .image_use_data
+            .record_module_image(module.name(), image_id)
+        {
+            log::warn!(
+                "Could not record image {image_id} of module {} for image garbage collection: {e}",
+                module.name()
+            );
+        }
 
         Ok(())
     }
@@ -470,7 +1277,21 @@ where
                 log::warn!("{e:?}");
                 e
             })
-            .with_context(|| Error::RuntimeOperation(RuntimeOperation::StartModule(id.to_owned())))
+            .with_context(|| {
+                Error::RuntimeOperation(RuntimeOperation::StartModule(id.to_owned()))
+            })?;
+
+        // record the start for the image's usage history
+        match self.get(id).await {
+            Ok((module, _)) => {
+                if let Some(image_id) = module.config().image_hash() {
+                    self.image_use_data.record_image_start(image_id)?;
+                }
+            }
+            Err(e) => log::warn!("Could not get image of module {id}: {e:?}"),
+        }
+
+        Ok(())
     }
 
     async fn stop(&self, id: &str, wait_before_kill: Option<Duration>) -> anyhow::Result<()> {
@@ -553,6 +1374,10 @@ where
                 Error::RuntimeOperation(RuntimeOperation::RemoveModule(id.to_owned()))
             })?;
 
+        if let Err(e) = self.snapshots.remove_module_spec(id) {
+            log::warn!("Could not remove the spec of module {id}: {e}");
+        }
+
         // update image use timestamp for image garbage collection job later
         self.image_use_data.record_image_use_timestamp(image_id)?;
 
@@ -742,7 +1567,7 @@ where
             })
             .context(Error::RuntimeOperation(RuntimeOperation::ListImages))?;
 
-        let result = images
+        let mut result: HashMap<String, String> = images
             .into_iter()
             .flat_map(|image| {
                 image
@@ -752,6 +1577,22 @@ where
                     .map(move |name| (name, image.id.clone()))
             })
             .collect();
+
+        // Podman reports fully-qualified names, so also index images by the short names
+        // deployments use to refer to them.
+        if self.engine == ContainerEngine::Podman {
+            let short_names: Vec<_> = result
+                .iter()
+                .filter_map(|(name, id)| {
+                    podman_short_name(name).map(|short| (short.to_owned(), id.clone()))
+                })
+                .collect();
+
+            for (name, id) in short_names {
+                result.entry(name).or_insert(id);
+            }
+        }
+
         Ok(result)
     }
 
@@ -829,6 +1670,336 @@ where
         Ok(pids)
     }
 
+    async fn module_stats(&self, id: &str) -> anyhow::Result<ModuleStats> {
+        // A non-streaming request waits for a second sample so that CPU usage can be
+        // computed from the difference.
+        let stats = self
+            .client
+            .container_stats(id, false, false)
+            .await
+            .context(Error::Docker)
+            .map_err(|e| {
+                log::warn!("{e:?}");
+                e
+            })
+            .with_context(|| {
+                Error::RuntimeOperation(RuntimeOperation::GetModuleStats(id.to_owned()))
+            })?;
+
+        Ok(parse_stats_response(id, &stats))
+    }
+
+    async fn exec(
+        &self,
+        id: &str,
+        command: Vec<String>,
+    ) -> anyhow::Result<tokio::sync::mpsc::Receiver<ExecOutput>> {
+        log::info!("Running command in module {id}...");
+
+        let operation = || Error::RuntimeOperation(RuntimeOperation::ExecModule(id.to_owned()));
+
+        let exec = self
+            .client
+            .container_exec_create(
+                id,
+                ExecConfig {
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    tty: Some(false),
+                    cmd: command,
+                    ..Default::default()
+                },
+            )
+            .await
+            .context(Error::Docker)
+            .map_err(|e| {
+                log::warn!("{e:?}");
+                e
+            })
+            .with_context(operation)?;
+
+        let body = self
+            .client
+            .container_exec_start(
+                &exec.id,
+                ExecStartConfig {
+                    detach: false,
+                    tty: false,
+                },
+            )
+            .await
+            .context(Error::Docker)
+            .map_err(|e| {
+                log::warn!("{e:?}");
+                e
+            })
+            .with_context(operation)?;
+
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let client = self.client.clone();
+
+        tokio::spawn(async move {
+            let result = async {
+                let mut output = MultiplexedStream::new(body);
+
+                while let Some((stream, data)) = output.next().await? {
+                    let data = String::from_utf8_lossy(&data).into_owned();
+                    let output = match stream {
+                        OutputStream::Stderr => ExecOutput::Stderr(data),
+                        OutputStream::Stdin | OutputStream::Stdout => ExecOutput::Stdout(data),
+                    };
+
+                    // The engine can't stop a running command, so it's left to finish on its
+                    // own once nobody reads its output.
+                    if tx.send(output).await.is_err() {
+                        return Ok(None);
+                    }
+                }
+
+                let exec = client.container_exec_inspect(&exec.id).await?;
+                let code = exec
+                    .exit_code
+                    .context("container runtime did not report the command's exit code")?;
+
+                anyhow::Ok(Some(code))
+            }
+            .await;
+
+            let last = match result {
+                Ok(Some(code)) => ExecOutput::Exit(code),
+                Ok(None) => return,
+                Err(e) => {
+                    log::warn!("Could not read command output: {e:?}");
+                    ExecOutput::Error(format!("{e:#}"))
+                }
+            };
+
+            let _ = tx.send(last).await;
+        });
+
+        Ok(rx)
+    }
+
+    async fn copy_from(&self, id: &str, path: &str) -> anyhow::Result<Incoming> {
+        log::info!("Copying {path} from module {id}...");
+
+        self.client
+            .container_archive(id, path)
+            .await
+            .context(Error::Docker)
+            .map_err(|e| {
+                log::warn!("{e:?}");
+                e
+            })
+            .with_context(|| {
+                Error::RuntimeOperation(RuntimeOperation::CopyFromModule(id.to_owned()))
+            })
+    }
+
+    async fn copy_to(&self, id: &str, path: &str, archive: Incoming) -> anyhow::Result<()> {
+        log::info!("Copying an archive to {path} in module {id}...");
+
+        let (archive, received) = UploadBody::new(archive);
+
+        self.client
+            .put_container_archive(id, path, BoxBody::new(archive))
+            .await
+            .context(Error::Docker)
+            .and_then(|()| received.check())
+            .map_err(|e| {
+                log::warn!("{e:?}");
+                e
+            })
+            .with_context(|| Error::RuntimeOperation(RuntimeOperation::CopyToModule(id.to_owned())))
+    }
+
+    async fn events(&self) -> anyhow::Result<tokio::sync::mpsc::Receiver<ModuleEvent>> {
+        log::debug!("Subscribing to module events...");
+
+        let filters = serde_json::json!({
+            "type": ["container"],
+            "label": LABELS,
+            "event": ["start", "stop", "die", "oom"],
+        })
+        .to_string();
+
+        let body = self
+            .client
+            .system_events("", "", &filters)
+            .await
+            .context(Error::Docker)
+            .map_err(|e| {
+                log::warn!("{e:?}");
+                e
+            })
+            .context(Error::RuntimeOperation(RuntimeOperation::GetModuleEvents))?;
+
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+
+        tokio::spawn(async move {
+            let mut events = EventStream::new(body);
+
+            loop {
+                let event = tokio::select! {
+                    event = events.next() => event,
+
+                    // Stop reading events once the subscriber goes away.
+                    () = tx.closed() => break,
+                };
+
+                match event {
+                    Ok(Some(event)) => {
+                        if let Some(event) = module_event(event)
+                            && tx.send(event).await.is_err()
+                        {
+                            break;
+                        }
+                    }
+                    Ok(None) => {
+                        log::debug!("Container runtime ended the module event subscription");
+                        break;
+                    }
+                    Err(e) => {
+                        log::warn!("Could not read module events: {e:?}");
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+
+    async fn save_snapshot(&self, name: &str) -> anyhow::Result<SnapshotInfo> {
+        log::info!("Saving snapshot {name}...");
+
+        let operation = || Error::RuntimeOperation(RuntimeOperation::SaveSnapshot(name.to_owned()));
+
+        let mut modules = Vec::new();
+
+        for (module, state) in self.list_with_details().await.with_context(operation)? {
+            if !matches!(
+                state.status(),
+                ModuleStatus::Running | ModuleStatus::Unhealthy
+            ) {
+                continue;
+            }
+
+            let image_id = module
+                .config()
+                .image_hash()
+                .ok_or(Error::GetImageId())
+                .with_context(operation)?
+                .to_owned();
+
+            let spec = self
+                .snapshots
+                .load_module_spec(module.name())
+                .with_context(operation)?
+                .ok_or_else(|| {
+                    Error::SnapshotModule(
+                        module.name().to_owned(),
+                        "its spec was not saved when it was created; redeploy it to include it"
+                            .to_owned(),
+                    )
+                })
+                .with_context(operation)?;
+
+            modules.push(SnapshotModule { spec, image_id });
+        }
+
+        if modules.is_empty() {
+            return Err(Error::EmptySnapshot).with_context(operation);
+        }
+
+        let snapshot = Snapshot {
+            name: name.to_owned(),
+            created: chrono::Utc::now(),
+            modules,
+        };
+        self.snapshots.save(&snapshot).with_context(operation)?;
+
+        log::info!("Saved snapshot {name}");
+
+        Ok(snapshot.info())
+    }
+
+    async fn list_snapshots(&self) -> anyhow::Result<Vec<SnapshotInfo>> {
+        let snapshots = self
+            .snapshots
+            .list()
+            .context(Error::RuntimeOperation(RuntimeOperation::ListSnapshots))?;
+
+        Ok(snapshots.iter().map(Snapshot::info).collect())
+    }
+
+    async fn restore_snapshot(&self, name: &str) -> anyhow::Result<()> {
+        log::info!("Restoring snapshot {name}...");
+
+        let operation =
+            || Error::RuntimeOperation(RuntimeOperation::RestoreSnapshot(name.to_owned()));
+
+        let snapshot = self.snapshots.load(name).with_context(operation)?;
+
+        // Nothing is changed unless every module in the snapshot can be recreated.
+        let images = self
+            .client
+            .images_list(false, "", false)
+            .await
+            .context(Error::Docker)
+            .with_context(operation)?;
+        let image_ids: HashSet<&str> = images.iter().map(|image| image.id.as_str()).collect();
+
+        if let Some(module) = snapshot
+            .modules
+            .iter()
+            .find(|module| !image_ids.contains(module.image_id.as_str()))
+        {
+            return Err(Error::SnapshotRestore(
+                name.to_owned(),
+                format!(
+                    "image {} of module {} is no longer on the device",
+                    module.image_id,
+                    module.spec.name()
+                ),
+            ))
+            .with_context(operation);
+        }
+
+        let tags: HashMap<String, String> = images
+            .iter()
+            .flat_map(|image| {
+                image
+                    .repo_tags
+                    .iter()
+                    .flatten()
+                    .map(|tag| (tag.clone(), image.id.clone()))
+            })
+            .collect();
+
+        let mut restore = Restore::default();
+
+        if let Err(e) = self.replace_modules(snapshot, &tags, &mut restore).await {
+            log::warn!("Could not restore snapshot {name}, rolling back: {e:?}");
+            self.roll_back(restore).await;
+
+            return Err(e).with_context(operation);
+        }
+
+        // The modules that were replaced are only removed once the snapshot is running.
+        for module in restore.replaced {
+            let id = module.backup.as_deref().unwrap_or(&module.name);
+
+            if let Err(e) = ModuleRuntime::remove(self, id).await {
+                log::warn!("Could not remove module {id} after restoring the snapshot: {e:?}");
+            }
+        }
+
+        log::info!("Restored snapshot {name}");
+
+        Ok(())
+    }
+
     fn registry(&self) -> &Self::ModuleRegistry {
         self
     }
@@ -836,12 +2007,68 @@ where
     fn error_code(error: &anyhow::Error) -> hyper::StatusCode {
         if let Some(error) = error.root_cause().downcast_ref::<docker::apis::ApiError>() {
             error.code
+        } else if let Some(error) = error.root_cause().downcast_ref::<Error>() {
+            match error {
+                Error::ModuleLimits(..) | Error::InvalidSnapshotName(_) => {
+                    hyper::StatusCode::BAD_REQUEST
+                }
+                Error::PolicyViolation(..) => hyper::StatusCode::FORBIDDEN,
+                Error::SnapshotNotFound(_) => hyper::StatusCode::NOT_FOUND,
+                Error::EmptySnapshot | Error::SnapshotModule(..) | Error::SnapshotRestore(..) => {
+                    hyper::StatusCode::CONFLICT
+                }
+                _ => hyper::StatusCode::INTERNAL_SERVER_ERROR,
+            }
         } else {
             hyper::StatusCode::INTERNAL_SERVER_ERROR
         }
     }
 }
 
+/// The changes made by a snapshot restore, in the order they were made.
+#[derive(Default)]
+struct Restore {
+    replaced: Vec<ReplacedModule>,
+
+    /// The tags that were moved to a snapshot's image, with the image they pointed at before.
+    retagged: Vec<(String, String, String)>,
+
+    created: Vec<String>,
+}
+
+/// A module that was deployed when a snapshot restore started.
+struct ReplacedModule {
+    name: String,
+    spec: Option<ModuleSpec<DockerConfig>>,
+    running: bool,
+
+    /// The temporary name of the module while a module of the same name from the snapshot
+    /// takes its place.
+    backup: Option<String>,
+}
+
+// Returns the module's spec as deployed, without the registry credentials, for snapshots.
+fn deployed_spec(module: &ModuleSpec<DockerConfig>) -> anyhow::Result<ModuleSpec<DockerConfig>> {
+    let config = module.config();
+    let config = DockerConfig::new(
+        config.image().to_owned(),
+        config.create_options().clone(),
+        config.digest().map(ToOwned::to_owned),
+        None,
+        config.allow_elevated_docker_permissions(),
+    )
+    .map_err(anyhow::Error::msg)?;
+
+    ModuleSpec::new(
+        module.name().to_owned(),
+        module.r#type().to_owned(),
+        config,
+        module.env().clone(),
+        module.image_pull_policy(),
+    )
+    .map_err(anyhow::Error::msg)
+}
+
 fn total_memory_bytes(system_resources: &System) -> u64 {
     system_resources.total_memory()
 }
@@ -885,7 +2112,123 @@ where
         })
         .collect::<Result<_, _>>();
 
-    pids
+    pids
+}
+
+fn module_event(event: docker::models::EventMessage) -> Option<ModuleEvent> {
+    let kind = match event.action.as_deref()? {
+        "start" => ModuleEventKind::Start,
+        "stop" => ModuleEventKind::Stop,
+        "die" => ModuleEventKind::Die,
+        "oom" => ModuleEventKind::Oom,
+        _ => return None,
+    };
+
+    let mut attributes = event.actor?.attributes;
+
+    Some(ModuleEvent {
+        module: attributes.remove("name")?,
+        kind,
+        exit_code: attributes
+            .get("exitCode")
+            .and_then(|exit_code| exit_code.parse().ok()),
+        time: event
+            .time
+            .and_then(|time| chrono::DateTime::from_timestamp(time, 0)),
+    })
+}
+
+fn parse_stats_response(name: &str, stats: &serde_json::Value) -> ModuleStats {
+    let u64_at = |pointer: &str| {
+        stats
+            .pointer(pointer)
+            .and_then(serde_json::Value::as_u64)
+            .unwrap_or_default()
+    };
+
+    // CPU usage is the share of the system's CPU time used by the container between the
+    // previous sample and this one, scaled by the number of CPUs.
+    let cpu_delta = u64_at("/cpu_stats/cpu_usage/total_usage")
+        .saturating_sub(u64_at("/precpu_stats/cpu_usage/total_usage"));
+    let system_delta = u64_at("/cpu_stats/system_cpu_usage")
+        .saturating_sub(u64_at("/precpu_stats/system_cpu_usage"));
+    let online_cpus = match u64_at("/cpu_stats/online_cpus") {
+        0 => stats
+            .pointer("/cpu_stats/cpu_usage/percpu_usage")
+            .and_then(serde_json::Value::as_array)
+            .map_or(1, |percpu| percpu.len() as u64),
+        online_cpus => online_cpus,
+    };
+    #[allow(clippy::cast_precision_loss)]
+    let cpu_percent = if cpu_delta == 0 || system_delta == 0 {
+        0.0
+    } else {
+        cpu_delta as f64 / system_delta as f64 * online_cpus as f64 * 100.0
+    };
+
+    // Page cache is reclaimable, so it is not counted as used memory. cgroup v1 reports
+    // it as "cache" and cgroup v2 as "inactive_file".
+    let cache = match u64_at("/memory_stats/stats/cache") {
+        0 => u64_at("/memory_stats/stats/inactive_file"),
+        cache => cache,
+    };
+    let memory_usage = u64_at("/memory_stats/usage").saturating_sub(cache);
+
+    let (network_rx_bytes, network_tx_bytes) = stats
+        .get("networks")
+        .and_then(serde_json::Value::as_object)
+        .map_or((0, 0), |networks| {
+            networks.values().fold((0, 0), |(rx, tx), network| {
+                let bytes = |key| {
+                    network
+                        .get(key)
+                        .and_then(serde_json::Value::as_u64)
+                        .unwrap_or_default()
+                };
+                (rx + bytes("rx_bytes"), tx + bytes("tx_bytes"))
+            })
+        });
+
+    let (block_read_bytes, block_write_bytes) = stats
+        .pointer("/blkio_stats/io_service_bytes_recursive")
+        .and_then(serde_json::Value::as_array)
+        .map_or((0, 0), |entries| {
+            entries.iter().fold((0, 0), |(read, write), entry| {
+                let op = entry
+                    .get("op")
+                    .and_then(serde_json::Value::as_str)
+                    .unwrap_or_default();
+                let value = entry
+                    .get("value")
+                    .and_then(serde_json::Value::as_u64)
+                    .unwrap_or_default();
+
+                if op.eq_ignore_ascii_case("read") {
+                    (read + value, write)
+                } else if op.eq_ignore_ascii_case("write") {
+                    (read, write + value)
+                } else {
+                    (read, write)
+                }
+            })
+        });
+
+    ModuleStats {
+        name: name.to_owned(),
+        read: stats
+            .get("read")
+            .and_then(serde_json::Value::as_str)
+            .and_then(|read| chrono::DateTime::parse_from_rfc3339(read).ok())
+            .map(|read| read.with_timezone(&chrono::Utc)),
+        cpu_percent,
+        memory_usage,
+        memory_limit: u64_at("/memory_stats/limit"),
+        network_rx_bytes,
+        network_tx_bytes,
+        block_read_bytes,
+        block_write_bytes,
+        pids: u64_at("/pids_stats/current"),
+    }
 }
 
 // Disallow adding privileged and other capabilities if allow_elevated_docker_permissions is false
@@ -939,14 +2282,364 @@ fn drop_unsafe_privileges(
     host_config.cap_drop = Some(caps_to_drop);
 }
 
+/// Request body that streams an archive uploaded by a client to the engine. An error receiving
+/// the upload ends the body early. The engine may accept the truncated archive, so the error is
+/// also recorded in the body's `UploadReceived`.
+struct UploadBody {
+    archive: Incoming,
+    received: UploadReceived,
+}
+
+impl UploadBody {
+    fn new(archive: Incoming) -> (Self, UploadReceived) {
+        let received = UploadReceived(Arc::new(std::sync::atomic::AtomicBool::new(true)));
+
+        let body = UploadBody {
+            archive,
+            received: received.clone(),
+        };
+
+        (body, received)
+    }
+}
+
+impl hyper::body::Body for UploadBody {
+    type Data = hyper::body::Bytes;
+    type Error = std::convert::Infallible;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<hyper::body::Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        match hyper::body::Body::poll_frame(Pin::new(&mut this.archive), cx) {
+            std::task::Poll::Ready(Some(Ok(frame))) => std::task::Poll::Ready(Some(Ok(frame))),
+            std::task::Poll::Ready(Some(Err(err))) => {
+                log::warn!("Failed to receive archive: {err}");
+                this.received
+                    .0
+                    .store(false, std::sync::atomic::Ordering::Release);
+                std::task::Poll::Ready(None)
+            }
+            std::task::Poll::Ready(None) => std::task::Poll::Ready(None),
+            std::task::Poll::Pending => std::task::Poll::Pending,
+        }
+    }
+}
+
+/// Whether an `UploadBody` received all of its archive.
+#[derive(Clone)]
+struct UploadReceived(Arc<std::sync::atomic::AtomicBool>);
+
+impl UploadReceived {
+    fn check(&self) -> anyhow::Result<()> {
+        if self.0.load(std::sync::atomic::Ordering::Acquire) {
+            Ok(())
+        } else {
+            Err(Error::IncompleteUpload.into())
+        }
+    }
+}
+
+/// Caps the module's resources at the device's limits. Limits that the module doesn't set
+/// are applied, and requests above a limit are rejected.
+fn apply_module_limits(
+    module: &str,
+    limits: &ResourceLimits,
+    create_options: &mut ContainerCreateBody,
+) -> Result<(), Error> {
+    if limits.is_empty() {
+        return Ok(());
+    }
+
+    let host_config = create_options.host_config.get_or_insert_default();
+
+    if let Some(limit) = limits.memory {
+        host_config.memory = Some(cap_limit(module, "Memory", host_config.memory, limit)?);
+    }
+
+    for (key, limit) in [("CpuShares", limits.cpu_shares), ("PidsLimit", limits.pids)] {
+        let Some(limit) = limit else {
+            continue;
+        };
+
+        let requested = host_config
+            .other_properties
+            .get(key)
+            .map(|requested| {
+                requested.as_i64().ok_or_else(|| {
+                    Error::ModuleLimits(module.to_owned(), format!("{key} must be an integer"))
+                })
+            })
+            .transpose()?;
+
+        let value = cap_limit(module, key, requested, limit)?;
+        host_config
+            .other_properties
+            .insert(key.to_owned(), value.into());
+    }
+
+    if let Some(limit) = limits.log_size {
+        cap_log_size(module, host_config, limit)?;
+    }
+
+    Ok(())
+}
+
+fn cap_limit(module: &str, key: &str, requested: Option<i64>, limit: u64) -> Result<i64, Error> {
+    let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+
+    match requested {
+        None => Ok(limit),
+
+        // Docker treats zero and negative values as unlimited.
+        Some(requested) if requested <= 0 => Err(Error::ModuleLimits(
+            module.to_owned(),
+            format!("{key} must be set to at most {limit}, not unlimited"),
+        )),
+
+        Some(requested) if requested > limit => Err(Error::ModuleLimits(
+            module.to_owned(),
+            format!("{key} {requested} is greater than the limit of {limit}"),
+        )),
+
+        Some(requested) => Ok(requested),
+    }
+}
+
+fn cap_log_size(
+    module: &str,
+    host_config: &mut docker::models::HostConfig,
+    limit: u64,
+) -> Result<(), Error> {
+    let log_config = host_config
+        .other_properties
+        .entry("LogConfig".to_owned())
+        .or_insert_with(|| serde_json::json!({}))
+        .as_object_mut()
+        .ok_or_else(|| {
+            Error::ModuleLimits(module.to_owned(), "LogConfig must be an object".to_owned())
+        })?;
+
+    // Without a log driver, the daemon's default driver is used, which may not support a
+    // size limit. Use json-file so that the limit can be enforced.
+    let log_type = match log_config.get("Type").and_then(serde_json::Value::as_str) {
+        Some(log_type) if !log_type.is_empty() => log_type.to_owned(),
+        _ => {
+            log_config.insert("Type".to_owned(), "json-file".into());
+            "json-file".to_owned()
+        }
+    };
+
+    // Other drivers don't write log files on the device.
+    if log_type != "json-file" && log_type != "local" {
+        return Ok(());
+    }
+
+    let options = log_config
+        .entry("Config")
+        .or_insert_with(|| serde_json::json!({}))
+        .as_object_mut()
+        .ok_or_else(|| {
+            Error::ModuleLimits(
+                module.to_owned(),
+                "LogConfig.Config must be an object".to_owned(),
+            )
+        })?;
+
+    if let Some(requested) = options.get("max-size") {
+        let requested = requested
+            .as_str()
+            .and_then(parse_docker_size)
+            .ok_or_else(|| {
+                Error::ModuleLimits(
+                    module.to_owned(),
+                    format!("invalid log max-size {requested}"),
+                )
+            })?;
+
+        if requested > limit {
+            return Err(Error::ModuleLimits(
+                module.to_owned(),
+                format!("log max-size {requested} is greater than the limit of {limit}"),
+            ));
+        }
+    } else {
+        options.insert("max-size".to_owned(), limit.to_string().into());
+    }
+
+    Ok(())
+}
+
+/// Parses a size such as "10m" the way Docker parses log options, with binary multiples.
+fn parse_docker_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (value, unit) = size.split_at(split);
+
+    let value: f64 = value.parse().ok()?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return None,
+    };
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    let bytes = (value * multiplier as f64) as u64;
+
+    Some(bytes)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
     use std::process::{Command, Stdio};
 
     use docker::models::HostConfig;
 
     use super::*;
 
+    #[test]
+    fn module_event_from_docker_event() {
+        let event: docker::models::EventMessage = serde_json::from_value(serde_json::json!({
+            "Type": "container",
+            "Action": "die",
+            "Actor": {
+                "ID": "0123",
+                "Attributes": {
+                    "name": "edgeAgent",
+                    "image": "mcr.microsoft.com/azureiotedge-agent:1.5",
+                    "exitCode": "137"
+                }
+            },
+            "time": 1_700_000_000
+        }))
+        .unwrap();
+
+        assert_eq!(
+            Some(ModuleEvent {
+                module: "edgeAgent".to_owned(),
+                kind: ModuleEventKind::Die,
+                exit_code: Some(137),
+                time: chrono::DateTime::from_timestamp(1_700_000_000, 0),
+            }),
+            module_event(event)
+        );
+
+        let event: docker::models::EventMessage = serde_json::from_value(serde_json::json!({
+            "Type": "container",
+            "Action": "oom",
+            "Actor": { "ID": "0123", "Attributes": { "name": "edgeHub" } }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            Some(ModuleEvent {
+                module: "edgeHub".to_owned(),
+                kind: ModuleEventKind::Oom,
+                exit_code: None,
+                time: None,
+            }),
+            module_event(event)
+        );
+
+        // Other actions and events without a container name are not module events.
+        let event: docker::models::EventMessage = serde_json::from_value(serde_json::json!({
+            "Type": "container",
+            "Action": "pause",
+            "Actor": { "ID": "0123", "Attributes": { "name": "edgeHub" } }
+        }))
+        .unwrap();
+        assert_eq!(None, module_event(event));
+
+        let event: docker::models::EventMessage = serde_json::from_value(serde_json::json!({
+            "Type": "container",
+            "Action": "start",
+            "Actor": { "ID": "0123" }
+        }))
+        .unwrap();
+        assert_eq!(None, module_event(event));
+    }
+
+    #[test]
+    fn parse_stats_response_computes_usage() {
+        let stats = serde_json::json!({
+            "read": "2024-01-02T03:04:05.123456789Z",
+            "pids_stats": { "current": 7 },
+            "cpu_stats": {
+                "cpu_usage": { "total_usage": 300 },
+                "system_cpu_usage": 2000,
+                "online_cpus": 2
+            },
+            "precpu_stats": {
+                "cpu_usage": { "total_usage": 100 },
+                "system_cpu_usage": 1000
+            },
+            "memory_stats": {
+                "usage": 1000,
+                "limit": 4000,
+                "stats": { "inactive_file": 200 }
+            },
+            "networks": {
+                "eth0": { "rx_bytes": 10, "tx_bytes": 20 },
+                "eth1": { "rx_bytes": 1, "tx_bytes": 2 }
+            },
+            "blkio_stats": {
+                "io_service_bytes_recursive": [
+                    { "major": 8, "minor": 0, "op": "read", "value": 512 },
+                    { "major": 8, "minor": 0, "op": "write", "value": 1024 },
+                    { "major": 8, "minor": 16, "op": "Read", "value": 512 }
+                ]
+            }
+        });
+
+        let stats = parse_stats_response("testModule", &stats);
+
+        assert_eq!("testModule", stats.name);
+        assert!(stats.read.is_some());
+        assert!((stats.cpu_percent - 40.0).abs() < f64::EPSILON);
+        assert_eq!(800, stats.memory_usage);
+        assert_eq!(4000, stats.memory_limit);
+        assert_eq!(11, stats.network_rx_bytes);
+        assert_eq!(22, stats.network_tx_bytes);
+        assert_eq!(1024, stats.block_read_bytes);
+        assert_eq!(1024, stats.block_write_bytes);
+        assert_eq!(7, stats.pids);
+    }
+
+    #[test]
+    fn parse_stats_response_stopped_container() {
+        // Stopped containers report empty stats.
+        let stats = serde_json::json!({
+            "read": "0001-01-01T00:00:00Z",
+            "cpu_stats": { "cpu_usage": { "total_usage": 0 } },
+            "precpu_stats": { "cpu_usage": { "total_usage": 0 } },
+            "memory_stats": {},
+            "blkio_stats": { "io_service_bytes_recursive": null }
+        });
+
+        let stats = parse_stats_response("testModule", &stats);
+
+        assert_eq!(
+            ModuleStats {
+                name: "testModule".to_owned(),
+                read: stats.read,
+                ..Default::default()
+            },
+            stats
+        );
+    }
+
     #[test]
     fn parse_top_response_returns_pid_array() {
         let response = ContainerTopResponse {
@@ -1079,6 +2772,130 @@ mod tests {
         assert_eq!(create_options.host_config.as_ref().unwrap().cap_add, None);
     }
 
+    #[test]
+    fn apply_module_limits_fills_in_limits() {
+        let limits = ResourceLimits {
+            memory: Some(1024),
+            cpu_shares: Some(512),
+            pids: Some(100),
+            log_size: Some(10 << 20),
+        };
+
+        let mut create_options = ContainerCreateBody::default();
+        apply_module_limits("testModule", &limits, &mut create_options).unwrap();
+
+        let host_config = create_options.host_config.unwrap();
+        assert_eq!(Some(1024), host_config.memory);
+        assert_eq!(
+            Some(&serde_json::json!(512)),
+            host_config.other_properties.get("CpuShares")
+        );
+        assert_eq!(
+            Some(&serde_json::json!(100)),
+            host_config.other_properties.get("PidsLimit")
+        );
+        assert_eq!(
+            Some(&serde_json::json!({
+                "Type": "json-file",
+                "Config": { "max-size": "10485760" },
+            })),
+            host_config.other_properties.get("LogConfig")
+        );
+    }
+
+    #[test]
+    fn apply_module_limits_keeps_smaller_requests() {
+        let limits = ResourceLimits {
+            memory: Some(1024),
+            pids: Some(100),
+            log_size: Some(10 << 20),
+            ..Default::default()
+        };
+
+        let mut create_options: ContainerCreateBody = serde_json::from_value(serde_json::json!({
+            "HostConfig": {
+                "Memory": 512,
+                "PidsLimit": 50,
+                "LogConfig": { "Type": "local", "Config": { "max-size": "1m" } },
+            }
+        }))
+        .unwrap();
+        apply_module_limits("testModule", &limits, &mut create_options).unwrap();
+
+        let host_config = create_options.host_config.unwrap();
+        assert_eq!(Some(512), host_config.memory);
+        assert_eq!(
+            Some(&serde_json::json!(50)),
+            host_config.other_properties.get("PidsLimit")
+        );
+        assert_eq!(
+            Some(&serde_json::json!({ "Type": "local", "Config": { "max-size": "1m" } })),
+            host_config.other_properties.get("LogConfig")
+        );
+        assert!(!host_config.other_properties.contains_key("CpuShares"));
+    }
+
+    #[test]
+    fn apply_module_limits_rejects_larger_requests() {
+        let limits = ResourceLimits {
+            memory: Some(1024),
+            pids: Some(100),
+            log_size: Some(10 << 20),
+            ..Default::default()
+        };
+
+        for host_config in [
+            serde_json::json!({ "Memory": 2048 }),
+            serde_json::json!({ "Memory": 0 }),
+            serde_json::json!({ "PidsLimit": -1 }),
+            serde_json::json!({ "PidsLimit": "many" }),
+            serde_json::json!({ "LogConfig": { "Config": { "max-size": "1g" } } }),
+            serde_json::json!({ "LogConfig": { "Config": { "max-size": "big" } } }),
+        ] {
+            let mut create_options: ContainerCreateBody =
+                serde_json::from_value(serde_json::json!({ "HostConfig": host_config })).unwrap();
+
+            let err = apply_module_limits("testModule", &limits, &mut create_options).unwrap_err();
+            assert!(matches!(err, Error::ModuleLimits(..)), "{host_config}");
+        }
+    }
+
+    #[test]
+    fn repo_digest_matches_reference() {
+        let digest = "sha256:0123456789abcdef";
+
+        let reference = ImageReference::parse(&format!("alpine@{digest}"));
+        assert!(is_repo_digest_of(&format!("alpine@{digest}"), &reference));
+        assert!(is_repo_digest_of(
+            &format!("docker.io/library/alpine@{digest}"),
+            &reference
+        ));
+        assert!(!is_repo_digest_of("alpine@sha256:fedcba", &reference));
+        assert!(!is_repo_digest_of(
+            &format!("contoso.azurecr.io/alpine@{digest}"),
+            &reference
+        ));
+
+        let reference = ImageReference::parse(&format!("contoso.azurecr.io/module:1.0@{digest}"));
+        assert!(is_repo_digest_of(
+            &format!("contoso.azurecr.io/module@{digest}"),
+            &reference
+        ));
+
+        let reference = ImageReference::parse("contoso.azurecr.io/module:1.0");
+        assert!(!is_repo_digest_of("contoso.azurecr.io/module", &reference));
+    }
+
+    #[test]
+    fn parse_docker_size_units() {
+        assert_eq!(Some(100), parse_docker_size("100"));
+        assert_eq!(Some(10 << 10), parse_docker_size("10k"));
+        assert_eq!(Some(10 << 20), parse_docker_size("10M"));
+        assert_eq!(Some(3 << 29), parse_docker_size("1.5g"));
+        assert_eq!(None, parse_docker_size("10x"));
+        assert_eq!(None, parse_docker_size("m"));
+    }
+
     #[test]
     fn drop_unsafe_privileges_works() {
         let mut create_options = ContainerCreateBody {
@@ -1120,6 +2937,89 @@ mod tests {
         );
     }
 
+    fn system_version(
+        component: &str,
+        min_api_version: &str,
+        api_version: &str,
+    ) -> docker::models::SystemVersion {
+        docker::models::SystemVersion {
+            version: Some("1.0".to_owned()),
+            api_version: Some(api_version.to_owned()),
+            min_api_version: Some(min_api_version.to_owned()),
+            components: Some(vec![docker::models::ComponentVersion {
+                name: component.to_owned(),
+                version: "1.0".to_owned(),
+            }]),
+        }
+    }
+
+    #[test]
+    fn engine_version_check() {
+        // The configured engine must be the one serving the API.
+        check_engine_version(
+            ContainerEngine::Moby,
+            &system_version("Engine", "1.24", "1.43"),
+        )
+        .unwrap();
+        check_engine_version(
+            ContainerEngine::Podman,
+            &system_version("Podman Engine", "1.24", "1.41"),
+        )
+        .unwrap();
+        check_engine_version(
+            ContainerEngine::Moby,
+            &system_version("Podman Engine", "1.24", "1.41"),
+        )
+        .unwrap_err();
+        check_engine_version(
+            ContainerEngine::Podman,
+            &system_version("Engine", "1.24", "1.43"),
+        )
+        .unwrap_err();
+
+        // The API must include the version edged is written against.
+        check_engine_version(
+            ContainerEngine::Moby,
+            &system_version("Engine", "1.12", "1.40"),
+        )
+        .unwrap_err();
+        check_engine_version(
+            ContainerEngine::Moby,
+            &system_version("Engine", "1.44", "1.51"),
+        )
+        .unwrap_err();
+        check_engine_version(
+            ContainerEngine::Moby,
+            &docker::models::SystemVersion::default(),
+        )
+        .unwrap_err();
+
+        // Minor versions compare numerically.
+        assert_eq!(parse_api_version("1.41"), Some((1, 41)));
+        assert!(parse_api_version("1.9") < parse_api_version("1.41"));
+        assert_eq!(parse_api_version("latest"), None);
+    }
+
+    #[test]
+    fn podman_short_name_strips_default_registries() {
+        assert_eq!(
+            podman_short_name("docker.io/library/alpine:latest"),
+            Some("alpine:latest")
+        );
+        assert_eq!(
+            podman_short_name("docker.io/contoso/module:1.0"),
+            Some("contoso/module:1.0")
+        );
+        assert_eq!(
+            podman_short_name("localhost/module:1.0"),
+            Some("module:1.0")
+        );
+        assert_eq!(
+            podman_short_name("mcr.microsoft.com/azureiotedge-agent:1.5"),
+            None
+        );
+    }
+
     // Compare the total memory returned by the 'total_memory_bytes()' helper method
     // to the value in /proc/meminfo
     #[test]
@@ -1156,4 +3056,301 @@ mod tests {
         // Compare
         assert_eq!(total_memory_bytes, expected_total_memory_bytes);
     }
+
+    /// A stand-in for the container engine, and the registries it pulls from, listening on a
+    /// Unix socket. It has no images of its own and records the images it is asked to pull and
+    /// to create containers from.
+    struct StandInEngine {
+        uri: Url,
+        pulled: Arc<std::sync::Mutex<Vec<String>>>,
+        created: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl StandInEngine {
+        fn start(dir: &Path) -> Self {
+            let socket = dir.join("engine.sock");
+            let _ = std::fs::remove_file(&socket);
+            let listener = tokio::net::UnixListener::bind(&socket).unwrap();
+
+            let pulled = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let created = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let engine_pulled = pulled.clone();
+            let engine_created = created.clone();
+
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let pulled = engine_pulled.clone();
+                    let created = engine_created.clone();
+                    let service = hyper::service::service_fn(move |req| {
+                        let pulled = pulled.clone();
+                        let created = created.clone();
+                        async move {
+                            let response = Self::respond(req, &pulled, &created).await;
+                            Ok::<_, std::convert::Infallible>(response)
+                        }
+                    });
+
+                    tokio::spawn(
+                        hyper::server::conn::http1::Builder::new()
+                            .serve_connection(hyper_util::rt::TokioIo::new(stream), service),
+                    );
+                }
+            });
+
+            StandInEngine {
+                uri: format!("unix://{}", socket.display()).parse().unwrap(),
+                pulled,
+                created,
+            }
+        }
+
+        async fn respond(
+            req: hyper::Request<Incoming>,
+            pulled: &std::sync::Mutex<Vec<String>>,
+            created: &std::sync::Mutex<Vec<String>>,
+        ) -> hyper::Response<http_body_util::Full<hyper::body::Bytes>> {
+            let path = req.uri().path().to_owned();
+            let query: HashMap<String, String> =
+                url::form_urlencoded::parse(req.uri().query().unwrap_or_default().as_bytes())
+                    .into_owned()
+                    .collect();
+
+            let (status, body) = if path.ends_with("/images/create") {
+                pulled.lock().unwrap().push(query["fromImage"].clone());
+                (
+                    hyper::StatusCode::OK,
+                    r#"{"status":"Downloaded newer image"}"#.to_owned(),
+                )
+            } else if path.ends_with("/tag") {
+                (hyper::StatusCode::CREATED, String::new())
+            } else if path.ends_with("/images/json") {
+                (hyper::StatusCode::OK, "[]".to_owned())
+            } else if path.ends_with("/containers/create") {
+                let body = http_body_util::BodyExt::collect(req.into_body())
+                    .await
+                    .unwrap()
+                    .to_bytes();
+                let body: ContainerCreateBody = serde_json::from_slice(&body).unwrap();
+                created.lock().unwrap().push(body.image.unwrap());
+                (hyper::StatusCode::CREATED, r#"{"Id":"0123"}"#.to_owned())
+            } else if let Some(name) = path
+                .strip_suffix("/json")
+                .and_then(|path| path.rsplit_once("/containers/"))
+                .map(|(_, name)| name)
+            {
+                let body = serde_json::json!({
+                    "Id": "0123",
+                    "Name": format!("/{name}"),
+                    "Image": "sha256:4567",
+                });
+                (hyper::StatusCode::OK, body.to_string())
+            } else {
+                (
+                    hyper::StatusCode::NOT_FOUND,
+                    r#"{"message":"not found"}"#.to_owned(),
+                )
+            };
+
+            hyper::Response::builder()
+                .status(status)
+                .header(hyper::header::CONTENT_TYPE, "application/json")
+                .body(body.into())
+                .unwrap()
+        }
+
+        fn pulled(&self) -> Vec<String> {
+            self.pulled.lock().unwrap().clone()
+        }
+
+        fn created(&self) -> Vec<String> {
+            self.created.lock().unwrap().clone()
+        }
+
+        fn runtime(&self, dir: &Path) -> DockerModuleRuntime<Connector> {
+            DockerModuleRuntime {
+                client: init_client(&self.uri).unwrap(),
+                system_resources: Arc::new(Mutex::new(System::new())),
+                create_socket_channel: tokio::sync::mpsc::unbounded_channel().0,
+                allow_elevated_docker_permissions: false,
+                additional_info: BTreeMap::new(),
+                image_use_data: ImagePruneData::new(dir, Default::default()).unwrap(),
+                snapshots: SnapshotStore::new(dir),
+                engine: ContainerEngine::Moby,
+                module_limits: ModuleLimits::default(),
+                policy: CreateOptionsPolicy::default(),
+                registry_mirrors: Vec::new(),
+                credential_providers: Vec::new(),
+                pulls: PullSettings::default(),
+                pull_permits: None,
+                notary: None,
+                trusted_images: Default::default(),
+            }
+        }
+    }
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("edgelet-runtime-{name}-{}", process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn pull_from_content_trust_registry() {
+        let dir = test_dir("content-trust");
+        let engine = StandInEngine::start(&dir);
+        let runtime = engine
+            .runtime(&dir)
+            .with_notary(crate::notary::tests::test_notary("runtime"));
+
+        let config = |image: &str| {
+            DockerConfig::new(
+                image.to_owned(),
+                ContainerCreateBody::default(),
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+        };
+
+        // Unsigned images are refused before the engine is asked to pull anything.
+        let err = runtime
+            .pull(&config("contoso.azurecr.io/unsigned:1.0"), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::RegistryOperation(RegistryOperation::RegistryPull(image)))
+                if image == "contoso.azurecr.io/unsigned:1.0"
+        ));
+        assert!(format!("{err:#}").contains("No valid trust data"));
+        assert!(engine.pulled().is_empty());
+
+        // Signed images are pulled by their signed digest.
+        runtime
+            .pull(&config("contoso.azurecr.io/signed:1.0"), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            engine.pulled(),
+            [format!(
+                "contoso.azurecr.io/signed@{}",
+                crate::notary::tests::DIGEST
+            )]
+        );
+
+        // Registries without content trust are pulled from as before.
+        runtime
+            .pull(&config("mcr.microsoft.com/module:1.0"), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            engine.pulled().last().unwrap(),
+            "mcr.microsoft.com/module:1.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_from_pulled_trusted_image() {
+        let dir = test_dir("content-trust-create");
+        let engine = StandInEngine::start(&dir);
+        let notary = crate::notary::tests::test_notary("runtime-create");
+        let runtime = engine.runtime(&dir).with_notary(notary.clone());
+
+        let spec = |name: &str, image: &str| {
+            let config = DockerConfig::new(
+                image.to_owned(),
+                ContainerCreateBody::default(),
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+
+            ModuleSpec::new(
+                name.to_owned(),
+                DOCKER_MODULE_TYPE.to_owned(),
+                config,
+                BTreeMap::new(),
+                Default::default(),
+            )
+            .unwrap()
+        };
+
+        let signed = spec("signed", "contoso.azurecr.io/signed:1.0");
+        runtime.pull(signed.config(), None).await.unwrap();
+
+        // Modules are created from the digest that was pulled without asking Notary again, so
+        // they can be created while it can't be reached.
+        crate::notary::tests::take_offline(&notary);
+
+        runtime.create(signed).await.unwrap();
+        assert_eq!(
+            engine.created(),
+            [format!(
+                "contoso.azurecr.io/signed@{}",
+                crate::notary::tests::DIGEST
+            )]
+        );
+
+        // Images that weren't pulled are still resolved through Notary.
+        let err = runtime
+            .create(spec("other", "contoso.azurecr.io/other:1.0"))
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("could not reach"));
+        assert_eq!(1, engine.created().len());
+    }
+
+    #[tokio::test]
+    async fn pull_through_bandwidth_limited_proxy() {
+        let dir = test_dir("pull-proxy");
+        let engine = StandInEngine::start(&dir);
+        let mut runtime = engine.runtime(&dir);
+        runtime.pulls.max_bandwidth = std::num::NonZeroU64::new(262_144);
+
+        let config = |image: &str| {
+            DockerConfig::new(
+                image.to_owned(),
+                ContainerCreateBody::default(),
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+        };
+
+        // Images referenced by tag are pulled only through the proxy.
+        runtime
+            .pull(&config("contoso.azurecr.io/module:1.0"), None)
+            .await
+            .unwrap();
+        let pulled = engine.pulled();
+        assert_eq!(1, pulled.len());
+        assert!(pulled[0].starts_with("127.0.0.1:"));
+        assert!(pulled[0].ends_with("/module:1.0"));
+
+        // Images referenced by digest are pulled again directly to get their digest.
+        let digest = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        runtime
+            .pull(
+                &config(&format!("contoso.azurecr.io/module@{digest}")),
+                None,
+            )
+            .await
+            .unwrap();
+        let pulled = engine.pulled();
+        assert_eq!(3, pulled.len());
+        assert!(pulled[1].starts_with("127.0.0.1:"));
+        assert!(pulled[1].ends_with(&format!("/module@{digest}")));
+        assert_eq!(format!("contoso.azurecr.io/module@{digest}"), pulled[2]);
+
+        // Registries on the device are pulled from directly.
+        runtime
+            .pull(&config("localhost:5000/module:1.0"), None)
+            .await
+            .unwrap();
+        assert_eq!("localhost:5000/module:1.0", engine.pulled()[3]);
+    }
 }
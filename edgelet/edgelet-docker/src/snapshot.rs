@@ -0,0 +1,418 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use edgelet_core::SnapshotInfo;
+use edgelet_settings::{DockerConfig, ModuleSpec};
+
+use crate::Error;
+
+const SNAPSHOTS_DIRNAME: &str = "snapshots";
+const MODULE_SPECS_DIRNAME: &str = "module-specs";
+const SNAPSHOT_EXTENSION: &str = "json";
+const MAX_NAME_LEN: usize = 64;
+
+/// The modules that were running when a snapshot was saved.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Snapshot {
+    pub(crate) name: String,
+    pub(crate) created: DateTime<Utc>,
+    pub(crate) modules: Vec<SnapshotModule>,
+}
+
+/// A module as it was deployed, and the ID of the image it was running so that the same image
+/// is used even if its tag has since moved.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SnapshotModule {
+    pub(crate) spec: ModuleSpec<DockerConfig>,
+    pub(crate) image_id: String,
+}
+
+impl Snapshot {
+    pub(crate) fn info(&self) -> SnapshotInfo {
+        SnapshotInfo {
+            name: self.name.clone(),
+            created: self.created,
+            modules: self
+                .modules
+                .iter()
+                .map(|module| module.spec.name().to_owned())
+                .collect(),
+        }
+    }
+}
+
+/// Snapshots are kept as one file per snapshot in the `snapshots` directory of `homedir`.
+///
+/// The specs of the modules as they were deployed are kept in the `module-specs` directory,
+/// one file per module, so that a snapshot can recreate them. They include the modules'
+/// environment variables, so they are only kept in aziot-edged's home directory and are
+/// only readable by aziot-edged.
+#[derive(Clone, Debug)]
+pub(crate) struct SnapshotStore {
+    dir: PathBuf,
+    specs_dir: PathBuf,
+}
+
+impl SnapshotStore {
+    pub(crate) fn new(homedir: &Path) -> Self {
+        SnapshotStore {
+            dir: homedir.join(SNAPSHOTS_DIRNAME),
+            specs_dir: homedir.join(MODULE_SPECS_DIRNAME),
+        }
+    }
+
+    /// Replaces any snapshot of the same name.
+    pub(crate) fn save(&self, snapshot: &Snapshot) -> Result<(), Error> {
+        let path = self.path(&snapshot.name)?;
+
+        write_file(&self.dir, &path, snapshot)
+    }
+
+    pub(crate) fn load(&self, name: &str) -> Result<Snapshot, Error> {
+        let path = self.path(name)?;
+
+        read_file(&path)?.ok_or_else(|| Error::SnapshotNotFound(name.to_owned()))
+    }
+
+    /// Saves the spec of a module as it was deployed, replacing the spec of any earlier
+    /// module of the same name.
+    pub(crate) fn save_module_spec(&self, spec: &ModuleSpec<DockerConfig>) -> Result<(), Error> {
+        let path = self.module_spec_path(spec.name())?;
+
+        write_file(&self.specs_dir, &path, spec)
+    }
+
+    /// Returns the spec of the module as it was deployed, or `None` if it wasn't saved.
+    pub(crate) fn load_module_spec(
+        &self,
+        name: &str,
+    ) -> Result<Option<ModuleSpec<DockerConfig>>, Error> {
+        let path = self.module_spec_path(name)?;
+
+        read_file(&path)
+    }
+
+    pub(crate) fn remove_module_spec(&self, name: &str) -> Result<(), Error> {
+        let path = self.module_spec_path(name)?;
+
+        match std::fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(Error::FileOperation(format!(
+                "could not remove {}: {e}",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Returns the snapshots that can be read, oldest first.
+    pub(crate) fn list(&self) -> Result<Vec<Snapshot>, Error> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(Error::FileOperation(format!(
+                    "could not read {}: {e}",
+                    self.dir.display()
+                )));
+            }
+        };
+
+        let mut snapshots: Vec<Snapshot> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext == SNAPSHOT_EXTENSION)
+            })
+            .filter_map(|path| {
+                let name = path.file_stem()?.to_str()?;
+
+                // A snapshot that can't be read is skipped rather than hiding all the others.
+                self.load(name)
+                    .map_err(|e| log::warn!("Skipping snapshot {}: {e}", path.display()))
+                    .ok()
+            })
+            .collect();
+
+        snapshots.sort_by(|a, b| a.created.cmp(&b.created).then(a.name.cmp(&b.name)));
+
+        Ok(snapshots)
+    }
+
+    /// Returns the IDs of the images that snapshots need, which image garbage collection
+    /// keeps so that the snapshots can still be restored.
+    pub(crate) fn image_ids(&self) -> Result<HashSet<String>, Error> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .flat_map(|snapshot| snapshot.modules)
+            .map(|module| module.image_id)
+            .collect())
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf, Error> {
+        validate_name(name)?;
+
+        Ok(self.dir.join(format!("{name}.{SNAPSHOT_EXTENSION}")))
+    }
+
+    fn module_spec_path(&self, name: &str) -> Result<PathBuf, Error> {
+        if !is_file_name(name) {
+            return Err(Error::FileOperation(format!(
+                "module name {name:?} cannot be used as a file name"
+            )));
+        }
+
+        Ok(self.specs_dir.join(format!("{name}.{SNAPSHOT_EXTENSION}")))
+    }
+}
+
+/// Writes `value` to a temp file and renames it over `path`, so that the file is never left
+/// partially written.
+fn write_file<T>(dir: &Path, path: &Path, value: &T) -> Result<(), Error>
+where
+    T: Serialize,
+{
+    let tmp_path = path.with_extension("tmp");
+
+    std::fs::create_dir_all(dir)
+        .map_err(|e| Error::FileOperation(format!("could not create {}: {e}", dir.display())))?;
+
+    let contents = serde_json::to_vec_pretty(value).map_err(|e| {
+        Error::FileOperation(format!("could not serialize {}: {e}", path.display()))
+    })?;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp_path)
+        .map_err(Error::CreateFile)?;
+    file.write_all(&contents)
+        .and_then(|()| file.sync_all())
+        .map_err(|e| {
+            Error::FileOperation(format!("could not write {}: {e}", tmp_path.display()))
+        })?;
+
+    std::fs::rename(&tmp_path, path).map_err(|e| {
+        Error::FileOperation(format!(
+            "could not rename {} to {}: {e}",
+            tmp_path.display(),
+            path.display()
+        ))
+    })
+}
+
+/// Returns `None` if the file doesn't exist.
+fn read_file<T>(path: &Path) -> Result<Option<T>, Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let contents = match std::fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(Error::FileOperation(format!(
+                "could not read {}: {e}",
+                path.display()
+            )));
+        }
+    };
+
+    serde_json::from_slice(&contents)
+        .map(Some)
+        .map_err(|e| Error::FileOperation(format!("could not parse {}: {e}", path.display())))
+}
+
+// Snapshot names are used as file names, so they are limited to characters that can't
+// name a different directory.
+fn validate_name(name: &str) -> Result<(), Error> {
+    if name.len() <= MAX_NAME_LEN && is_file_name(name) {
+        Ok(())
+    } else {
+        Err(Error::InvalidSnapshotName(name.to_owned()))
+    }
+}
+
+fn is_file_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use edgelet_settings::module::ImagePullPolicy;
+    use serial_test::serial;
+
+    use super::{Snapshot, SnapshotModule, SnapshotStore, validate_name};
+    use crate::Error;
+
+    const TEST_FILE_DIR: &str = "test-data-snapshots";
+
+    fn snapshot(name: &str, created: i64, image_ids: &[&str]) -> Snapshot {
+        Snapshot {
+            name: name.to_owned(),
+            created: chrono::DateTime::from_timestamp(created, 0).unwrap(),
+            modules: image_ids
+                .iter()
+                .enumerate()
+                .map(|(i, image_id)| SnapshotModule {
+                    spec: edgelet_settings::ModuleSpec::new(
+                        format!("module{i}"),
+                        "docker".to_owned(),
+                        edgelet_settings::DockerConfig::new(
+                            format!("example.azurecr.io/module{i}:1.0"),
+                            docker::models::ContainerCreateBody::default(),
+                            None,
+                            None,
+                            false,
+                        )
+                        .unwrap(),
+                        BTreeMap::from([("KEY".to_owned(), "value".to_owned())]),
+                        ImagePullPolicy::default(),
+                    )
+                    .unwrap(),
+                    image_id: (*image_id).to_owned(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn snapshot_names() {
+        for name in ["good", "known-good_1.2", "A"] {
+            validate_name(name).unwrap();
+        }
+
+        for name in ["", ".hidden", "..", "a/b", "a b", &"a".repeat(65)] {
+            assert!(matches!(
+                validate_name(name),
+                Err(Error::InvalidSnapshotName(_))
+            ));
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn save_load_list() {
+        let test_file_dir = std::env::current_dir().unwrap().join(TEST_FILE_DIR);
+        if test_file_dir.is_dir() {
+            std::fs::remove_dir_all(&test_file_dir).unwrap();
+        }
+        let store = SnapshotStore::new(&test_file_dir);
+
+        // No snapshots have been saved yet.
+        assert!(store.list().unwrap().is_empty());
+        assert!(matches!(
+            store.load("good"),
+            Err(Error::SnapshotNotFound(_))
+        ));
+
+        store
+            .save(&snapshot("newer", 200, &["sha256:2", "sha256:3"]))
+            .unwrap();
+        store.save(&snapshot("good", 100, &["sha256:1"])).unwrap();
+
+        let loaded = store.load("newer").unwrap();
+        assert_eq!(2, loaded.modules.len());
+        assert_eq!("module1", loaded.modules[1].spec.name());
+        assert_eq!(
+            "example.azurecr.io/module1:1.0",
+            loaded.modules[1].spec.config().image()
+        );
+        assert_eq!("value", loaded.modules[1].spec.env()["KEY"]);
+        assert_eq!("sha256:3", loaded.modules[1].image_id);
+
+        // Saving a snapshot again replaces it.
+        store.save(&snapshot("good", 300, &["sha256:4"])).unwrap();
+
+        let infos: Vec<_> = store
+            .list()
+            .unwrap()
+            .iter()
+            .map(|snapshot| (snapshot.info().name, snapshot.info().modules))
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "newer".to_owned(),
+                    vec!["module0".to_owned(), "module1".to_owned()]
+                ),
+                ("good".to_owned(), vec!["module0".to_owned()]),
+            ],
+            infos
+        );
+
+        let image_ids = store.image_ids().unwrap();
+        assert_eq!(3, image_ids.len());
+        assert!(image_ids.contains("sha256:4"));
+        assert!(!image_ids.contains("sha256:1"));
+
+        // Files that aren't snapshots are ignored.
+        std::fs::write(test_file_dir.join("snapshots/broken.json"), "{").unwrap();
+        std::fs::write(test_file_dir.join("snapshots/notes.txt"), "").unwrap();
+        assert_eq!(2, store.list().unwrap().len());
+
+        // cleanup
+        std::fs::remove_dir_all(test_file_dir).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn module_specs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let test_file_dir = std::env::current_dir().unwrap().join(TEST_FILE_DIR);
+        if test_file_dir.is_dir() {
+            std::fs::remove_dir_all(&test_file_dir).unwrap();
+        }
+        let store = SnapshotStore::new(&test_file_dir);
+
+        assert!(store.load_module_spec("module0").unwrap().is_none());
+
+        let snapshot = snapshot("good", 100, &["sha256:1"]);
+        let spec = &snapshot.modules[0].spec;
+        store.save_module_spec(spec).unwrap();
+
+        let loaded = store.load_module_spec("module0").unwrap().unwrap();
+        assert_eq!("example.azurecr.io/module0:1.0", loaded.config().image());
+        assert_eq!("value", loaded.env()["KEY"]);
+
+        // Specs include environment variables, so only aziot-edged can read them.
+        let mode = std::fs::metadata(test_file_dir.join("module-specs/module0.json"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(0o600, mode & 0o777);
+
+        // Module specs aren't snapshots.
+        assert!(store.list().unwrap().is_empty());
+
+        store.remove_module_spec("module0").unwrap();
+        assert!(store.load_module_spec("module0").unwrap().is_none());
+
+        // Removing a spec that wasn't saved is not an error.
+        store.remove_module_spec("module0").unwrap();
+
+        assert!(store.load_module_spec("../module0").is_err());
+
+        // cleanup
+        std::fs::remove_dir_all(test_file_dir).unwrap();
+    }
+}
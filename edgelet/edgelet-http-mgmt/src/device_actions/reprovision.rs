@@ -51,7 +51,11 @@ where
             .reprovision
             .send(edgelet_core::WatchdogAction::Reprovision)
         {
-            Ok(()) => Ok(http_common::server::response::no_content()),
+            Ok(()) => {
+                edgelet_core::metrics().record_reprovision();
+
+                Ok(http_common::server::response::no_content())
+            }
             Err(_) => Err(edgelet_http::error::server_error(
                 "failed to send reprovision request",
             )),
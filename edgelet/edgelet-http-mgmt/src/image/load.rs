@@ -0,0 +1,98 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+pub(crate) struct Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+    pid: libc::pid_t,
+    digest: Option<String>,
+}
+
+const PATH: &str = "/images/load";
+
+#[async_trait::async_trait]
+impl<M> http_common::server::Route for Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    type ApiVersion = edgelet_http::ApiVersion;
+    fn api_version() -> &'static dyn http_common::DynRangeBounds<Self::ApiVersion> {
+        &((edgelet_http::ApiVersion::V2022_08_03)..)
+    }
+
+    type Service = crate::Service<M>;
+    fn from_uri(
+        service: &Self::Service,
+        path: &str,
+        query: &[(std::borrow::Cow<'_, str>, std::borrow::Cow<'_, str>)],
+        extensions: &http::Extensions,
+    ) -> Option<Self> {
+        if path != PATH {
+            return None;
+        }
+
+        let pid = extensions.get::<Option<libc::pid_t>>().copied()??;
+
+        Some(Route {
+            runtime: service.runtime.clone(),
+            pid,
+            digest: edgelet_http::find_query("digest", query),
+        })
+    }
+
+    type DeleteBody = serde::de::IgnoredAny;
+
+    // The archive is only accepted as an upload. See crate::upload.
+    type PostBody = serde::de::IgnoredAny;
+
+    type PutBody = serde::de::IgnoredAny;
+}
+
+#[async_trait::async_trait]
+impl<M> crate::upload::Upload for Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    fn method() -> &'static http::Method {
+        &http::Method::POST
+    }
+
+    async fn upload(self, body: hyper::body::Incoming) -> http_common::server::RouteResponse {
+        edgelet_http::auth_agent_or_host(self.pid, &self.runtime).await?;
+
+        let runtime = self.runtime.lock().await;
+
+        let images =
+            edgelet_core::ModuleRegistry::load(runtime.registry(), body, self.digest.as_deref())
+                .await
+                .map_err(|err| edgelet_http::error::runtime_error(&*runtime, &err))?;
+
+        let res = edgelet_http::LoadImagesResponse { images };
+        let res = http_common::server::response::json(hyper::StatusCode::OK, &res);
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_test_utils::{test_route_err, test_route_ok};
+
+    #[test]
+    fn parse_uri() {
+        // Valid URI
+        let route = test_route_ok!(super::PATH, ("digest", "sha256:1234"));
+        assert_eq!(nix::unistd::getpid().as_raw(), route.pid);
+        assert_eq!(Some("sha256:1234"), route.digest.as_deref());
+
+        let route = test_route_ok!(super::PATH);
+        assert_eq!(None, route.digest);
+
+        // Extra character at beginning of URI
+        test_route_err!(&format!("a{}", super::PATH));
+
+        // Extra character at end of URI
+        test_route_err!(&format!("{}a", super::PATH));
+    }
+}
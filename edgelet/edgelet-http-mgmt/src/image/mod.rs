@@ -0,0 +1,5 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+pub(super) mod list;
+pub(super) mod load;
+pub(super) mod prune;
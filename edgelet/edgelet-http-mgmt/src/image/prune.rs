@@ -0,0 +1,147 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+pub(crate) struct Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+    image_prune: tokio::sync::mpsc::UnboundedSender<edgelet_core::ImagePruneRequest>,
+    pid: libc::pid_t,
+    dry_run: Option<String>,
+}
+
+const PATH: &str = "/images/prune";
+
+#[async_trait::async_trait]
+impl<M> http_common::server::Route for Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    type ApiVersion = edgelet_http::ApiVersion;
+    fn api_version() -> &'static dyn http_common::DynRangeBounds<Self::ApiVersion> {
+        &((edgelet_http::ApiVersion::V2022_08_03)..)
+    }
+
+    type Service = crate::Service<M>;
+    fn from_uri(
+        service: &Self::Service,
+        path: &str,
+        query: &[(std::borrow::Cow<'_, str>, std::borrow::Cow<'_, str>)],
+        extensions: &http::Extensions,
+    ) -> Option<Self> {
+        if path != PATH {
+            return None;
+        }
+
+        let pid = extensions.get::<Option<libc::pid_t>>().copied()??;
+
+        let dry_run = edgelet_http::find_query("dryRun", query);
+
+        Some(Route {
+            runtime: service.runtime.clone(),
+            image_prune: service.image_prune.clone(),
+            pid,
+            dry_run,
+        })
+    }
+
+    type DeleteBody = serde::de::IgnoredAny;
+
+    type PostBody = serde::de::IgnoredAny;
+    async fn post(self, _body: Option<Self::PostBody>) -> http_common::server::RouteResponse {
+        edgelet_http::auth_agent_or_host(self.pid, &self.runtime).await?;
+
+        let dry_run = self.dry_run()?;
+
+        // The image garbage collection task runs the prune so that it never overlaps a
+        // scheduled run.
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.image_prune
+            .send(edgelet_core::ImagePruneRequest {
+                dry_run,
+                reply: reply_tx,
+            })
+            .map_err(|_| edgelet_http::error::server_error("failed to send image prune request"))?;
+
+        let images = reply_rx
+            .await
+            .map_err(|_| edgelet_http::error::server_error("image prune request was dropped"))?
+            .map_err(|err| edgelet_http::error::server_error(format!("{err:#}")))?;
+
+        let res = edgelet_http::PruneImagesResponse { images };
+        let res = http_common::server::response::json(hyper::StatusCode::OK, &res);
+
+        Ok(res)
+    }
+
+    type PutBody = serde::de::IgnoredAny;
+}
+
+impl<M> Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    fn dry_run(&self) -> Result<bool, http_common::server::Error> {
+        match &self.dry_run {
+            Some(dry_run) => std::str::FromStr::from_str(dry_run)
+                .map_err(|_| edgelet_http::error::bad_request("invalid parameter: dryRun")),
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http_common::server::Route;
+
+    use edgelet_test_utils::{test_route_err, test_route_ok};
+
+    #[test]
+    fn parse_uri() {
+        // Valid URI
+        let route = test_route_ok!(super::PATH);
+        assert_eq!(nix::unistd::getpid().as_raw(), route.pid);
+
+        // Extra character at beginning of URI
+        test_route_err!(&format!("a{}", super::PATH));
+
+        // Extra character at end of URI
+        test_route_err!(&format!("{}a", super::PATH));
+    }
+
+    #[test]
+    fn parse_query_dry_run() {
+        // Default value when not provided
+        let route = test_route_ok!(super::PATH);
+        assert!(!route.dry_run().unwrap());
+
+        // Valid value
+        let route = test_route_ok!(super::PATH, ("dryRun", "true"));
+        assert!(route.dry_run().unwrap());
+
+        let route = test_route_ok!(super::PATH, ("dryRun", "false"));
+        assert!(!route.dry_run().unwrap());
+
+        // Invalid value
+        let route = test_route_ok!(super::PATH, ("dryRun", "invalid"));
+        assert!(route.dry_run().is_err());
+    }
+
+    #[tokio::test]
+    async fn auth() {
+        // Other modules are not authorized.
+        let route = test_route_ok!(super::PATH);
+
+        {
+            let pid = nix::unistd::getpid().as_raw();
+
+            let mut runtime = route.runtime.lock().await;
+            runtime
+                .module_auth
+                .insert("otherModule".to_string(), vec![pid]);
+        }
+
+        let response = route.post(None).await.unwrap_err();
+        assert_eq!(hyper::StatusCode::FORBIDDEN, response.status_code);
+    }
+}
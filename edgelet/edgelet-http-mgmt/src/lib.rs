@@ -2,8 +2,13 @@
 
 mod device_actions;
 mod identity;
+mod image;
 mod module;
+mod snapshot;
 mod system_info;
+mod upload;
+
+pub use upload::Uploads;
 
 #[cfg(not(test))]
 use aziot_identity_client_async::Client as IdentityClient;
@@ -11,6 +16,13 @@ use aziot_identity_client_async::Client as IdentityClient;
 #[cfg(test)]
 use test_common::client::IdentityClient;
 
+/// Progress of the image pulls started through the API, tagged with the module name.
+type PullProgressSender =
+    tokio::sync::broadcast::Sender<(String, edgelet_http::ModulePullProgress)>;
+
+// Enough for a burst of layer updates from a few concurrent pulls.
+const PULL_PROGRESS_CAPACITY: usize = 256;
+
 #[derive(Clone)]
 pub struct Service<M>
 where
@@ -19,6 +31,10 @@ where
     identity: std::sync::Arc<tokio::sync::Mutex<IdentityClient>>,
     runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
     reprovision: tokio::sync::mpsc::UnboundedSender<edgelet_core::WatchdogAction>,
+    crash_loop: edgelet_core::CrashLoopDetector,
+    image_prune: tokio::sync::mpsc::UnboundedSender<edgelet_core::ImagePruneRequest>,
+    pull_progress: PullProgressSender,
+    allow_module_exec: bool,
 }
 
 impl<M> Service<M>
@@ -30,6 +46,9 @@ where
         identity_socket: &url::Url,
         runtime: M,
         reprovision: tokio::sync::mpsc::UnboundedSender<edgelet_core::WatchdogAction>,
+        crash_loop: edgelet_core::CrashLoopDetector,
+        image_prune: tokio::sync::mpsc::UnboundedSender<edgelet_core::ImagePruneRequest>,
+        allow_module_exec: bool,
     ) -> Result<Self, http_common::ConnectorError> {
         let connector = http_common::Connector::new(identity_socket)?;
 
@@ -42,10 +61,16 @@ where
         let identity = std::sync::Arc::new(tokio::sync::Mutex::new(identity));
         let runtime = std::sync::Arc::new(tokio::sync::Mutex::new(runtime));
 
+        let (pull_progress, _) = tokio::sync::broadcast::channel(PULL_PROGRESS_CAPACITY);
+
         Ok(Service {
             identity,
             runtime,
             reprovision,
+            crash_loop,
+            image_prune,
+            pull_progress,
+            allow_module_exec,
         })
     }
 
@@ -63,10 +88,18 @@ where
         let (reprovision_tx, _) =
             tokio::sync::mpsc::unbounded_channel::<edgelet_core::WatchdogAction>();
 
+        // Likewise, image prune requests fail because the receiver is dropped.
+        let (image_prune_tx, _) =
+            tokio::sync::mpsc::unbounded_channel::<edgelet_core::ImagePruneRequest>();
+
         Service {
             identity,
             runtime,
             reprovision: reprovision_tx,
+            crash_loop: edgelet_core::CrashLoopDetector::default(),
+            image_prune: image_prune_tx,
+            pull_progress: tokio::sync::broadcast::channel(PULL_PROGRESS_CAPACITY).0,
+            allow_module_exec: true,
         }
     }
 
@@ -87,11 +120,18 @@ where
         let (reprovision_tx, reprovision_rx) =
             tokio::sync::mpsc::unbounded_channel::<edgelet_core::WatchdogAction>();
 
+        let (image_prune_tx, _) =
+            tokio::sync::mpsc::unbounded_channel::<edgelet_core::ImagePruneRequest>();
+
         (
             Service {
                 identity,
                 runtime,
                 reprovision: reprovision_tx,
+                crash_loop: edgelet_core::CrashLoopDetector::default(),
+                image_prune: image_prune_tx,
+                pull_progress: tokio::sync::broadcast::channel(PULL_PROGRESS_CAPACITY).0,
+                allow_module_exec: true,
             },
             reprovision_rx,
         )
@@ -109,9 +149,20 @@ http_common::make_service! {
     routes: [
         module::create_or_list::Route<M>,
         module::delete_or_get_or_update::Route<M>,
+        module::archive::Route<M>,
+        module::exec::Route<M>,
         module::restart_or_start_or_stop::Route<M>,
         module::logs::Route<M>,
         module::prepare_update::Route<M>,
+        module::pull::Route<M>,
+        module::stats::Route<M>,
+
+        image::list::Route<M>,
+        image::load::Route<M>,
+        image::prune::Route<M>,
+
+        snapshot::list_or_save::Route<M>,
+        snapshot::restore::Route<M>,
 
         identity::create_or_list::Route<M>,
         identity::delete_or_update::Route<M>,
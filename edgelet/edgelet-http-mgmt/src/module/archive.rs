@@ -0,0 +1,195 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use http_body_util::{BodyExt as _, combinators::BoxBody};
+
+pub(crate) struct Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+    allow_module_exec: bool,
+    pid: libc::pid_t,
+    module: String,
+    path: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl<M> http_common::server::Route for Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    type ApiVersion = edgelet_http::ApiVersion;
+    fn api_version() -> &'static dyn http_common::DynRangeBounds<Self::ApiVersion> {
+        &((edgelet_http::ApiVersion::V2022_08_03)..)
+    }
+
+    type Service = crate::Service<M>;
+    fn from_uri(
+        service: &Self::Service,
+        path: &str,
+        query: &[(std::borrow::Cow<'_, str>, std::borrow::Cow<'_, str>)],
+        extensions: &http::Extensions,
+    ) -> Option<Self> {
+        let uri_regex = regex::Regex::new("^/modules/(?P<module>[^/]+)/archive$")
+            .expect("hard-coded regex must compile");
+        let captures = uri_regex.captures(path)?;
+
+        let module = &captures["module"];
+        let module = percent_encoding::percent_decode_str(module)
+            .decode_utf8()
+            .ok()?;
+
+        let pid = extensions.get::<Option<libc::pid_t>>().copied()??;
+
+        Some(Route {
+            runtime: service.runtime.clone(),
+            allow_module_exec: service.allow_module_exec,
+            pid,
+            module: module.into_owned(),
+            path: edgelet_http::find_query("path", query),
+        })
+    }
+
+    type DeleteBody = serde::de::IgnoredAny;
+
+    async fn get(self) -> http_common::server::RouteResponse {
+        let path = self.authorize().await?;
+
+        let archive = {
+            let runtime = self.runtime.lock().await;
+
+            runtime
+                .copy_from(&self.module, &path)
+                .await
+                .map_err(|err| edgelet_http::error::runtime_error(&*runtime, &err))?
+        };
+
+        let res = hyper::Response::builder()
+            .status(hyper::StatusCode::OK)
+            .header(hyper::header::CONTENT_TYPE, "application/x-tar")
+            .body(BoxBody::new(archive.map_err(Into::into)))
+            .expect("cannot fail to build hyper response");
+        Ok(res)
+    }
+
+    type PostBody = serde::de::IgnoredAny;
+
+    // Archives to copy into the module are only accepted as uploads. See crate::upload.
+    type PutBody = serde::de::IgnoredAny;
+}
+
+#[async_trait::async_trait]
+impl<M> crate::upload::Upload for Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    fn method() -> &'static http::Method {
+        &http::Method::PUT
+    }
+
+    async fn upload(self, body: hyper::body::Incoming) -> http_common::server::RouteResponse {
+        let path = self.authorize().await?;
+
+        let runtime = self.runtime.lock().await;
+
+        runtime
+            .copy_to(&self.module, &path, body)
+            .await
+            .map_err(|err| edgelet_http::error::runtime_error(&*runtime, &err))?;
+
+        Ok(http_common::server::response::no_content())
+    }
+}
+
+impl<M> Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    /// Checks that the caller may copy files and returns the path in the module to copy.
+    async fn authorize(&self) -> Result<String, http_common::server::Error> {
+        if !self.allow_module_exec {
+            return Err(super::EXEC_DISABLED);
+        }
+
+        edgelet_http::auth_agent_or_host(self.pid, &self.runtime).await?;
+
+        match &self.path {
+            Some(path) if !path.is_empty() => Ok(path.clone()),
+            _ => Err(edgelet_http::error::bad_request("missing parameter: path")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http_common::server::Route;
+
+    use edgelet_test_utils::{test_route_err, test_route_ok};
+
+    const TEST_PATH: &str = "/modules/testModule/archive";
+
+    #[test]
+    fn parse_uri() {
+        // Valid URI
+        let route = test_route_ok!(TEST_PATH, ("path", "/tmp/a%20b"));
+        assert_eq!("testModule", &route.module);
+        assert_eq!(nix::unistd::getpid().as_raw(), route.pid);
+        assert_eq!(Some("/tmp/a b"), route.path.as_deref());
+
+        // Path is checked by the request handlers.
+        let route = test_route_ok!(TEST_PATH);
+        assert_eq!(None, route.path);
+
+        // Missing module name
+        test_route_err!("/modules//archive");
+
+        // Extra character at beginning of URI
+        test_route_err!(&format!("a{TEST_PATH}"));
+
+        // Extra character at end of URI
+        test_route_err!(&format!("{TEST_PATH}a"));
+    }
+
+    #[tokio::test]
+    async fn disabled() {
+        let mut route = test_route_ok!(TEST_PATH, ("path", "/tmp"));
+        route.allow_module_exec = false;
+        let response = route.authorize().await.unwrap_err();
+        assert_eq!(hyper::StatusCode::FORBIDDEN, response.status_code);
+
+        let mut route = test_route_ok!(TEST_PATH, ("path", "/tmp"));
+        route.allow_module_exec = false;
+        let response = route.get().await.unwrap_err();
+        assert_eq!(hyper::StatusCode::FORBIDDEN, response.status_code);
+    }
+
+    #[tokio::test]
+    async fn auth() {
+        // Other modules are not authorized.
+        let route = test_route_ok!(TEST_PATH, ("path", "/tmp"));
+
+        {
+            let pid = nix::unistd::getpid().as_raw();
+
+            let mut runtime = route.runtime.lock().await;
+            runtime
+                .module_auth
+                .insert("otherModule".to_string(), vec![pid]);
+        }
+
+        let response = route.get().await.unwrap_err();
+        assert_eq!(hyper::StatusCode::FORBIDDEN, response.status_code);
+    }
+
+    #[tokio::test]
+    async fn bad_request() {
+        // Missing path
+        let route = test_route_ok!(TEST_PATH);
+        let response = route.get().await.unwrap_err();
+        assert_eq!(hyper::StatusCode::BAD_REQUEST, response.status_code);
+
+        let route = test_route_ok!(TEST_PATH, ("path", ""));
+        let response = route.authorize().await.unwrap_err();
+        assert_eq!(hyper::StatusCode::BAD_REQUEST, response.status_code);
+    }
+}
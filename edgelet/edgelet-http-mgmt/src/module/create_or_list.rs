@@ -5,6 +5,7 @@ where
     M: edgelet_core::ModuleRuntime + Send + Sync,
 {
     runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+    pull_progress: crate::PullProgressSender,
     pid: libc::pid_t,
 }
 
@@ -39,6 +40,7 @@ where
 
         Some(Route {
             runtime: service.runtime.clone(),
+            pull_progress: service.pull_progress.clone(),
             pid,
         })
     }
@@ -72,7 +74,7 @@ where
 
         let runtime = self.runtime.lock().await;
 
-        super::create_module(&*runtime, body).await?;
+        super::create_module(&*runtime, body, &self.pull_progress).await?;
         let res = http_common::server::response::json(hyper::StatusCode::CREATED, &details);
 
         Ok(res)
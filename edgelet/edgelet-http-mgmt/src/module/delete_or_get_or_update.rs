@@ -5,6 +5,7 @@ where
     M: edgelet_core::ModuleRuntime + Send + Sync,
 {
     runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+    pull_progress: crate::PullProgressSender,
     pid: libc::pid_t,
     module: String,
     start: Option<String>,
@@ -44,6 +45,7 @@ where
 
         Some(Route {
             runtime: service.runtime.clone(),
+            pull_progress: service.pull_progress.clone(),
             pid,
             module: module.to_owned(),
             start,
@@ -138,7 +140,7 @@ where
             .await
             .map_err(|err| edgelet_http::error::runtime_error(&*runtime, &err))?;
 
-        super::create_module(&*runtime, body.clone()).await?;
+        super::create_module(&*runtime, body.clone(), &self.pull_progress).await?;
 
         let details = if start {
             match runtime.start(&self.module).await {
@@ -0,0 +1,174 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use http_body_util::{BodyExt as _, combinators::BoxBody};
+
+pub(crate) struct Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+    allow_module_exec: bool,
+    pid: libc::pid_t,
+    module: String,
+}
+
+#[async_trait::async_trait]
+impl<M> http_common::server::Route for Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    type ApiVersion = edgelet_http::ApiVersion;
+    fn api_version() -> &'static dyn http_common::DynRangeBounds<Self::ApiVersion> {
+        &((edgelet_http::ApiVersion::V2022_08_03)..)
+    }
+
+    type Service = crate::Service<M>;
+    fn from_uri(
+        service: &Self::Service,
+        path: &str,
+        _query: &[(std::borrow::Cow<'_, str>, std::borrow::Cow<'_, str>)],
+        extensions: &http::Extensions,
+    ) -> Option<Self> {
+        let uri_regex = regex::Regex::new("^/modules/(?P<module>[^/]+)/exec$")
+            .expect("hard-coded regex must compile");
+        let captures = uri_regex.captures(path)?;
+
+        let module = &captures["module"];
+        let module = percent_encoding::percent_decode_str(module)
+            .decode_utf8()
+            .ok()?;
+
+        let pid = extensions.get::<Option<libc::pid_t>>().copied()??;
+
+        Some(Route {
+            runtime: service.runtime.clone(),
+            allow_module_exec: service.allow_module_exec,
+            pid,
+            module: module.into_owned(),
+        })
+    }
+
+    type DeleteBody = serde::de::IgnoredAny;
+
+    type PostBody = edgelet_http::ExecRequest;
+    async fn post(self, body: Option<Self::PostBody>) -> http_common::server::RouteResponse {
+        if !self.allow_module_exec {
+            return Err(super::EXEC_DISABLED);
+        }
+
+        edgelet_http::auth_agent_or_host(self.pid, &self.runtime).await?;
+
+        let Some(body) = body else {
+            return Err(edgelet_http::error::bad_request("missing request body"));
+        };
+
+        if body.command.is_empty() {
+            return Err(edgelet_http::error::bad_request(
+                "command must not be empty",
+            ));
+        }
+
+        // The runtime is only locked to start the command, not for as long as it runs.
+        let mut output = {
+            let runtime = self.runtime.lock().await;
+
+            runtime
+                .exec(&self.module, body.command)
+                .await
+                .map_err(|err| edgelet_http::error::runtime_error(&*runtime, &err))?
+        };
+
+        // Each output of the command is written as one line of JSON. The stream ends after the
+        // line with the command's exit code, or the error that kept it from being read.
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+
+        tokio::spawn(async move {
+            while let Some(output) = output.recv().await {
+                if tx.send(super::to_line(&output)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let res = hyper::Response::builder()
+            .status(hyper::StatusCode::OK)
+            .header(hyper::header::CONTENT_TYPE, "application/x-ndjson")
+            .body(BoxBody::new(super::NdjsonBody(rx).map_err(Into::into)))
+            .expect("cannot fail to build hyper response");
+        Ok(res)
+    }
+
+    type PutBody = serde::de::IgnoredAny;
+}
+
+#[cfg(test)]
+mod tests {
+    use http_common::server::Route;
+
+    use edgelet_test_utils::{test_route_err, test_route_ok};
+
+    const TEST_PATH: &str = "/modules/testModule/exec";
+
+    fn request(command: &[&str]) -> Option<edgelet_http::ExecRequest> {
+        Some(edgelet_http::ExecRequest {
+            command: command.iter().map(ToString::to_string).collect(),
+        })
+    }
+
+    #[test]
+    fn parse_uri() {
+        // Valid URI
+        let route = test_route_ok!(TEST_PATH);
+        assert_eq!("testModule", &route.module);
+        assert_eq!(nix::unistd::getpid().as_raw(), route.pid);
+
+        // Missing module name
+        test_route_err!("/modules//exec");
+
+        // Extra character at beginning of URI
+        test_route_err!(&format!("a{TEST_PATH}"));
+
+        // Extra character at end of URI
+        test_route_err!(&format!("{TEST_PATH}a"));
+    }
+
+    #[tokio::test]
+    async fn disabled() {
+        let mut route = test_route_ok!(TEST_PATH);
+        route.allow_module_exec = false;
+
+        let response = route.post(request(&["ls"])).await.unwrap_err();
+        assert_eq!(hyper::StatusCode::FORBIDDEN, response.status_code);
+    }
+
+    #[tokio::test]
+    async fn auth() {
+        // Other modules are not authorized.
+        let route = test_route_ok!(TEST_PATH);
+
+        {
+            let pid = nix::unistd::getpid().as_raw();
+
+            let mut runtime = route.runtime.lock().await;
+            runtime
+                .module_auth
+                .insert("otherModule".to_string(), vec![pid]);
+        }
+
+        let response = route.post(request(&["ls"])).await.unwrap_err();
+        assert_eq!(hyper::StatusCode::FORBIDDEN, response.status_code);
+    }
+
+    #[tokio::test]
+    async fn bad_request() {
+        // Missing body
+        let route = test_route_ok!(TEST_PATH);
+        let response = route.post(None).await.unwrap_err();
+        assert_eq!(hyper::StatusCode::BAD_REQUEST, response.status_code);
+
+        // Empty command
+        let route = test_route_ok!(TEST_PATH);
+        let response = route.post(request(&[])).await.unwrap_err();
+        assert_eq!(hyper::StatusCode::BAD_REQUEST, response.status_code);
+    }
+}
@@ -4,14 +4,19 @@ pub(super) mod create_or_list;
 pub(super) mod delete_or_get_or_update;
 pub(super) mod restart_or_start_or_stop;
 
+pub(super) mod archive;
+pub(super) mod exec;
 pub(super) mod logs;
 pub(super) mod prepare_update;
+pub(super) mod pull;
+pub(super) mod stats;
 
 use edgelet_core::ModuleRegistry;
 
 async fn create_module<M>(
     runtime: &M,
     module: edgelet_http::ModuleSpec,
+    pull_progress: &crate::PullProgressSender,
 ) -> Result<(), http_common::server::Error>
 where
     M: edgelet_core::ModuleRuntime,
@@ -24,7 +29,7 @@ where
             message: err.into(),
         })?;
 
-    pull_image(runtime, &module).await?;
+    pull_image(runtime, &module, pull_progress).await?;
 
     runtime
         .create(module)
@@ -37,17 +42,14 @@ where
 async fn pull_image<M>(
     runtime: &M,
     module: &edgelet_settings::ModuleSpec<<M as edgelet_core::ModuleRuntime>::Config>,
+    pull_progress: &crate::PullProgressSender,
 ) -> Result<(), http_common::server::Error>
 where
     M: edgelet_core::ModuleRuntime,
 {
     match module.image_pull_policy() {
         edgelet_settings::module::ImagePullPolicy::OnCreate => {
-            runtime
-                .registry()
-                .pull(module.config())
-                .await
-                .map_err(|err| edgelet_http::error::runtime_error(runtime, &err))?;
+            pull_with_progress(runtime, module.name(), module.config(), pull_progress).await?;
 
             log::debug!("Successfully pulled new image for module {}", module.name());
         }
@@ -61,3 +63,81 @@ where
 
     Ok(())
 }
+
+/// Pulls the image in `config` for the module `name`, and sends its progress to anyone
+/// following pulls of the module.
+async fn pull_with_progress<M>(
+    runtime: &M,
+    name: &str,
+    config: &<M as edgelet_core::ModuleRuntime>::Config,
+    pull_progress: &crate::PullProgressSender,
+) -> Result<(), http_common::server::Error>
+where
+    M: edgelet_core::ModuleRuntime,
+{
+    // Sending fails when nobody is following the pull, which is fine.
+    let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+    let forward_progress = async {
+        while let Some(progress) = progress_rx.recv().await {
+            let _ = pull_progress.send((
+                name.to_owned(),
+                edgelet_http::ModulePullProgress {
+                    progress: Some(progress),
+                    done: false,
+                    error: None,
+                },
+            ));
+        }
+    };
+
+    let (result, ()) = tokio::join!(
+        runtime.registry().pull(config, Some(progress_tx)),
+        forward_progress
+    );
+
+    let _ = pull_progress.send((
+        name.to_owned(),
+        edgelet_http::ModulePullProgress {
+            progress: None,
+            done: true,
+            error: result.as_ref().err().map(|err| format!("{err:#}")),
+        },
+    ));
+
+    result.map_err(|err| edgelet_http::error::runtime_error(runtime, &err))
+}
+
+/// Error returned by the routes that run commands in or copy files to and from modules
+/// while `allow_module_exec` is not set.
+const EXEC_DISABLED: http_common::server::Error = http_common::server::Error {
+    status_code: http::StatusCode::FORBIDDEN,
+    message: std::borrow::Cow::Borrowed(
+        "running commands in and copying files to and from modules is disabled on this device",
+    ),
+};
+
+/// Writes a value as one line of a newline-delimited JSON stream.
+fn to_line(value: &impl serde::Serialize) -> hyper::body::Bytes {
+    let mut line = serde_json::to_vec(value).expect("cannot fail to serialize line");
+    line.push(b'\n');
+
+    line.into()
+}
+
+/// Response body that forwards the lines of a newline-delimited JSON stream as a task
+/// produces them. The body ends when the task drops its sender.
+struct NdjsonBody(tokio::sync::mpsc::Receiver<hyper::body::Bytes>);
+
+impl hyper::body::Body for NdjsonBody {
+    type Data = hyper::body::Bytes;
+    type Error = std::convert::Infallible;
+
+    fn poll_frame(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<hyper::body::Frame<Self::Data>, Self::Error>>> {
+        self.0
+            .poll_recv(cx)
+            .map(|line| line.map(|line| Ok(hyper::body::Frame::data(line))))
+    }
+}
@@ -5,6 +5,7 @@ where
     M: edgelet_core::ModuleRuntime + Send + Sync,
 {
     runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+    pull_progress: crate::PullProgressSender,
     pid: libc::pid_t,
     module: String,
 }
@@ -40,6 +41,7 @@ where
 
         Some(Route {
             runtime: service.runtime.clone(),
+            pull_progress: service.pull_progress.clone(),
             pid,
             module: module.into_owned(),
         })
@@ -70,7 +72,7 @@ where
                 message: err.into(),
             })?;
 
-        super::pull_image(&*runtime, &module).await?;
+        super::pull_image(&*runtime, &module, &self.pull_progress).await?;
 
         Ok(http_common::server::response::no_content())
     }
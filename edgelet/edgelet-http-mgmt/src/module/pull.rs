@@ -0,0 +1,168 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use http_body_util::{BodyExt as _, combinators::BoxBody};
+
+pub(crate) struct Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+    pull_progress: crate::PullProgressSender,
+    pid: libc::pid_t,
+    module: String,
+}
+
+#[async_trait::async_trait]
+impl<M> http_common::server::Route for Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync + 'static,
+    <M as edgelet_core::ModuleRuntime>::Config: Sync,
+{
+    type ApiVersion = edgelet_http::ApiVersion;
+    fn api_version() -> &'static dyn http_common::DynRangeBounds<Self::ApiVersion> {
+        &((edgelet_http::ApiVersion::V2022_08_03)..)
+    }
+
+    type Service = crate::Service<M>;
+    fn from_uri(
+        service: &Self::Service,
+        path: &str,
+        _query: &[(std::borrow::Cow<'_, str>, std::borrow::Cow<'_, str>)],
+        extensions: &http::Extensions,
+    ) -> Option<Self> {
+        let uri_regex = regex::Regex::new("^/modules/(?P<module>[^/]+)/pull$")
+            .expect("hard-coded regex must compile");
+        let captures = uri_regex.captures(path)?;
+
+        let module = &captures["module"];
+        let module = percent_encoding::percent_decode_str(module)
+            .decode_utf8()
+            .ok()?;
+
+        let pid = extensions.get::<Option<libc::pid_t>>().copied()??;
+
+        Some(Route {
+            runtime: service.runtime.clone(),
+            pull_progress: service.pull_progress.clone(),
+            pid,
+            module: module.into_owned(),
+        })
+    }
+
+    type DeleteBody = serde::de::IgnoredAny;
+
+    async fn get(self) -> http_common::server::RouteResponse {
+        // Follows the next pull of the module's image, or the one in progress. Updates from
+        // before the request are not replayed, so a pull that is already running is joined
+        // partway through. Each update is written as one line of JSON, and the stream ends
+        // after the line that reports the pull finished.
+        //
+        // This route does not lock the runtime, which is held by the pull for its duration.
+        let mut updates = self.pull_progress.subscribe();
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+
+        tokio::spawn(async move {
+            loop {
+                let update = tokio::select! {
+                    update = updates.recv() => update,
+
+                    // Stop waiting for a pull once the client goes away.
+                    () = tx.closed() => break,
+                };
+
+                let update = match update {
+                    Ok((module, update)) if module == self.module => update,
+                    Ok(_) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::debug!(
+                            "Skipped {skipped} pull progress updates for module {}",
+                            self.module
+                        );
+                        continue;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                };
+
+                let done = update.done;
+                if tx.send(super::to_line(&update)).await.is_err() || done {
+                    break;
+                }
+            }
+        });
+
+        let res = hyper::Response::builder()
+            .status(hyper::StatusCode::OK)
+            .header(hyper::header::CONTENT_TYPE, "application/x-ndjson")
+            .body(BoxBody::new(super::NdjsonBody(rx).map_err(Into::into)))
+            .expect("cannot fail to build hyper response");
+        Ok(res)
+    }
+
+    type PostBody = serde::de::IgnoredAny;
+    async fn post(self, _body: Option<Self::PostBody>) -> http_common::server::RouteResponse {
+        // Pulls the image the module was created with again, for example to pick up a new
+        // image for the same tag. The progress is reported to the requests following the pull.
+        edgelet_http::auth_agent_or_host(self.pid, &self.runtime).await?;
+
+        let runtime = self.runtime.lock().await;
+
+        let config = {
+            let (module, _) = runtime
+                .get(&self.module)
+                .await
+                .map_err(|err| edgelet_http::error::runtime_error(&*runtime, &err))?;
+
+            edgelet_core::Module::config(&module).clone()
+        };
+
+        super::pull_with_progress(&*runtime, &self.module, &config, &self.pull_progress).await?;
+
+        log::debug!("Successfully pulled image for module {}", self.module);
+
+        Ok(http_common::server::response::no_content())
+    }
+
+    type PutBody = serde::de::IgnoredAny;
+}
+
+#[cfg(test)]
+mod tests {
+    use http_common::server::Route;
+
+    use edgelet_test_utils::{test_route_err, test_route_ok};
+
+    #[test]
+    fn parse_uri() {
+        // Valid URI
+        let route = test_route_ok!("/modules/testModule/pull");
+        assert_eq!("testModule", &route.module);
+        assert_eq!(nix::unistd::getpid().as_raw(), route.pid);
+
+        // Missing module name
+        test_route_err!("/modules//pull");
+
+        // Extra character at beginning of URI
+        test_route_err!("a/modules/testModule/pull");
+
+        // Extra character at end of URI
+        test_route_err!("/modules/testModule/pulla");
+    }
+
+    #[tokio::test]
+    async fn auth() {
+        // Other modules are not authorized to start a pull.
+        let route = test_route_ok!("/modules/testModule/pull");
+
+        {
+            let pid = nix::unistd::getpid().as_raw();
+
+            let mut runtime = route.runtime.lock().await;
+            runtime
+                .module_auth
+                .insert("otherModule".to_string(), vec![pid]);
+        }
+
+        let response = route.post(None).await.unwrap_err();
+        assert_eq!(hyper::StatusCode::FORBIDDEN, response.status_code);
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use http_body_util::{BodyExt as _, combinators::BoxBody};
+
+pub(crate) struct Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+    module: String,
+
+    stream: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl<M> http_common::server::Route for Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync + 'static,
+{
+    type ApiVersion = edgelet_http::ApiVersion;
+    fn api_version() -> &'static dyn http_common::DynRangeBounds<Self::ApiVersion> {
+        &((edgelet_http::ApiVersion::V2022_08_03)..)
+    }
+
+    type Service = crate::Service<M>;
+    fn from_uri(
+        service: &Self::Service,
+        path: &str,
+        query: &[(std::borrow::Cow<'_, str>, std::borrow::Cow<'_, str>)],
+        _extensions: &http::Extensions,
+    ) -> Option<Self> {
+        let uri_regex = regex::Regex::new("^/modules/(?P<module>[^/]+)/stats$")
+            .expect("hard-coded regex must compile");
+        let captures = uri_regex.captures(path)?;
+
+        let module = &captures["module"];
+        let module = percent_encoding::percent_decode_str(module)
+            .decode_utf8()
+            .ok()?;
+
+        let stream = edgelet_http::find_query("stream", query);
+
+        Some(Route {
+            runtime: service.runtime.clone(),
+            module: module.into_owned(),
+
+            stream,
+        })
+    }
+
+    type DeleteBody = serde::de::IgnoredAny;
+
+    async fn get(self) -> http_common::server::RouteResponse {
+        let stream = self.stream()?;
+
+        // Query the first sample before responding so that errors such as a missing
+        // module are reported with the right status code.
+        let stats = module_stats(&self.runtime, &self.module).await?;
+
+        if !stream {
+            return Ok(http_common::server::response::json(
+                hyper::StatusCode::OK,
+                &stats,
+            ));
+        }
+
+        // Each sample is written as one line of JSON. The runtime takes about a second to
+        // produce a sample, which paces the stream. The stream ends when the client goes
+        // away or the module's stats can no longer be queried, for example because it was
+        // removed.
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+
+        tokio::spawn(async move {
+            let mut stats = stats;
+
+            loop {
+                if tx.send(super::to_line(&stats)).await.is_err() {
+                    break;
+                }
+
+                stats = match module_stats(&self.runtime, &self.module).await {
+                    Ok(stats) => stats,
+                    Err(err) => {
+                        log::info!(
+                            "Ending stats stream for module {}: {}",
+                            self.module,
+                            err.message
+                        );
+                        break;
+                    }
+                };
+            }
+        });
+
+        let res = hyper::Response::builder()
+            .status(hyper::StatusCode::OK)
+            .header(hyper::header::CONTENT_TYPE, "application/x-ndjson")
+            .body(BoxBody::new(super::NdjsonBody(rx).map_err(Into::into)))
+            .expect("cannot fail to build hyper response");
+        Ok(res)
+    }
+
+    type PostBody = serde::de::IgnoredAny;
+
+    type PutBody = serde::de::IgnoredAny;
+}
+
+impl<M> Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    fn stream(&self) -> Result<bool, http_common::server::Error> {
+        match &self.stream {
+            Some(stream) => std::str::FromStr::from_str(stream)
+                .map_err(|_| edgelet_http::error::bad_request("invalid parameter: stream")),
+            None => Ok(false),
+        }
+    }
+}
+
+async fn module_stats<M>(
+    runtime: &tokio::sync::Mutex<M>,
+    module: &str,
+) -> Result<edgelet_core::ModuleStats, http_common::server::Error>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    let runtime = runtime.lock().await;
+
+    runtime
+        .module_stats(module)
+        .await
+        .map_err(|err| edgelet_http::error::runtime_error(&*runtime, &err))
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_test_utils::{test_route_err, test_route_ok};
+
+    #[test]
+    fn parse_uri() {
+        // Valid URI
+        let route = test_route_ok!("/modules/testModule/stats");
+        assert_eq!("testModule", &route.module);
+
+        // Missing module name
+        test_route_err!("/modules//stats");
+
+        // Extra character at beginning of URI
+        test_route_err!("a/modules/testModule/stats");
+
+        // Extra character at end of URI
+        test_route_err!("/modules/testModule/statsa");
+    }
+
+    #[test]
+    fn parse_query_stream() {
+        let uri = "/modules/testModule/stats";
+
+        // Default value when not provided
+        let route = test_route_ok!(uri);
+        assert!(!route.stream().unwrap());
+
+        // Valid value
+        let route = test_route_ok!(uri, ("stream", "true"));
+        assert!(route.stream().unwrap());
+
+        let route = test_route_ok!(uri, ("stream", "false"));
+        assert!(!route.stream().unwrap());
+
+        // Invalid value
+        let route = test_route_ok!(uri, ("stream", "invalid"));
+        assert!(route.stream().is_err());
+    }
+}
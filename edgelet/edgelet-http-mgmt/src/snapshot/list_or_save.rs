@@ -0,0 +1,127 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+pub(crate) struct Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+    pid: libc::pid_t,
+}
+
+const PATH: &str = "/snapshots";
+
+#[async_trait::async_trait]
+impl<M> http_common::server::Route for Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    type ApiVersion = edgelet_http::ApiVersion;
+    fn api_version() -> &'static dyn http_common::DynRangeBounds<Self::ApiVersion> {
+        &((edgelet_http::ApiVersion::V2022_08_03)..)
+    }
+
+    type Service = crate::Service<M>;
+    fn from_uri(
+        service: &Self::Service,
+        path: &str,
+        _query: &[(std::borrow::Cow<'_, str>, std::borrow::Cow<'_, str>)],
+        extensions: &http::Extensions,
+    ) -> Option<Self> {
+        if path != PATH {
+            return None;
+        }
+
+        let pid = extensions.get::<Option<libc::pid_t>>().copied()??;
+
+        Some(Route {
+            runtime: service.runtime.clone(),
+            pid,
+        })
+    }
+
+    type DeleteBody = serde::de::IgnoredAny;
+
+    async fn get(self) -> http_common::server::RouteResponse {
+        edgelet_http::auth_agent_or_host(self.pid, &self.runtime).await?;
+
+        let runtime = self.runtime.lock().await;
+
+        let snapshots = runtime
+            .list_snapshots()
+            .await
+            .map_err(|err| edgelet_http::error::runtime_error(&*runtime, &err))?;
+
+        let res = edgelet_http::ListSnapshotsResponse { snapshots };
+        let res = http_common::server::response::json(hyper::StatusCode::OK, &res);
+
+        Ok(res)
+    }
+
+    type PostBody = edgelet_http::SaveSnapshotRequest;
+    async fn post(self, body: Option<Self::PostBody>) -> http_common::server::RouteResponse {
+        edgelet_http::auth_agent_or_host(self.pid, &self.runtime).await?;
+
+        let Some(body) = body else {
+            return Err(edgelet_http::error::bad_request("missing request body"));
+        };
+
+        let runtime = self.runtime.lock().await;
+
+        let snapshot = runtime
+            .save_snapshot(&body.name)
+            .await
+            .map_err(|err| edgelet_http::error::runtime_error(&*runtime, &err))?;
+
+        let res = http_common::server::response::json(hyper::StatusCode::CREATED, &snapshot);
+
+        Ok(res)
+    }
+
+    type PutBody = serde::de::IgnoredAny;
+}
+
+#[cfg(test)]
+mod tests {
+    use http_common::server::Route;
+
+    use edgelet_test_utils::{test_route_err, test_route_ok};
+
+    #[test]
+    fn parse_uri() {
+        // Valid URI
+        let route = test_route_ok!(super::PATH);
+        assert_eq!(nix::unistd::getpid().as_raw(), route.pid);
+
+        // Extra character at beginning of URI
+        test_route_err!(&format!("a{}", super::PATH));
+
+        // Extra character at end of URI
+        test_route_err!(&format!("{}a", super::PATH));
+    }
+
+    #[tokio::test]
+    async fn auth() {
+        // Other modules are not authorized.
+        let route = test_route_ok!(super::PATH);
+
+        {
+            let pid = nix::unistd::getpid().as_raw();
+
+            let mut runtime = route.runtime.lock().await;
+            runtime
+                .module_auth
+                .insert("otherModule".to_string(), vec![pid]);
+        }
+
+        let response = route.get().await.unwrap_err();
+        assert_eq!(hyper::StatusCode::FORBIDDEN, response.status_code);
+    }
+
+    #[tokio::test]
+    async fn bad_request() {
+        // Missing body
+        let route = test_route_ok!(super::PATH);
+        let response = route.post(None).await.unwrap_err();
+        assert_eq!(hyper::StatusCode::BAD_REQUEST, response.status_code);
+    }
+}
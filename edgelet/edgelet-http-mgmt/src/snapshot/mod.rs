@@ -0,0 +1,4 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+pub(super) mod list_or_save;
+pub(super) mod restore;
@@ -0,0 +1,110 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+pub(crate) struct Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+    pid: libc::pid_t,
+    name: String,
+}
+
+#[async_trait::async_trait]
+impl<M> http_common::server::Route for Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    type ApiVersion = edgelet_http::ApiVersion;
+    fn api_version() -> &'static dyn http_common::DynRangeBounds<Self::ApiVersion> {
+        &((edgelet_http::ApiVersion::V2022_08_03)..)
+    }
+
+    type Service = crate::Service<M>;
+    fn from_uri(
+        service: &Self::Service,
+        path: &str,
+        _query: &[(std::borrow::Cow<'_, str>, std::borrow::Cow<'_, str>)],
+        extensions: &http::Extensions,
+    ) -> Option<Self> {
+        let uri_regex = regex::Regex::new("^/snapshots/(?P<name>[^/]+)/restore$")
+            .expect("hard-coded regex must compile");
+        let captures = uri_regex.captures(path)?;
+
+        let name = &captures["name"];
+        let name = percent_encoding::percent_decode_str(name)
+            .decode_utf8()
+            .ok()?;
+
+        let pid = extensions.get::<Option<libc::pid_t>>().copied()??;
+
+        Some(Route {
+            runtime: service.runtime.clone(),
+            pid,
+            name: name.into_owned(),
+        })
+    }
+
+    type DeleteBody = serde::de::IgnoredAny;
+
+    type PostBody = serde::de::IgnoredAny;
+    async fn post(self, _body: Option<Self::PostBody>) -> http_common::server::RouteResponse {
+        edgelet_http::auth_agent_or_host(self.pid, &self.runtime).await?;
+
+        // The runtime stays locked until all modules are recreated so that no other request
+        // sees or changes a partially restored set of modules.
+        let runtime = self.runtime.lock().await;
+
+        runtime
+            .restore_snapshot(&self.name)
+            .await
+            .map_err(|err| edgelet_http::error::runtime_error(&*runtime, &err))?;
+
+        Ok(http_common::server::response::no_content())
+    }
+
+    type PutBody = serde::de::IgnoredAny;
+}
+
+#[cfg(test)]
+mod tests {
+    use http_common::server::Route;
+
+    use edgelet_test_utils::{test_route_err, test_route_ok};
+
+    const TEST_PATH: &str = "/snapshots/known-good/restore";
+
+    #[test]
+    fn parse_uri() {
+        // Valid URI
+        let route = test_route_ok!(TEST_PATH);
+        assert_eq!("known-good", &route.name);
+        assert_eq!(nix::unistd::getpid().as_raw(), route.pid);
+
+        // Missing snapshot name
+        test_route_err!("/snapshots//restore");
+
+        // Extra character at beginning of URI
+        test_route_err!(&format!("a{TEST_PATH}"));
+
+        // Extra character at end of URI
+        test_route_err!(&format!("{TEST_PATH}a"));
+    }
+
+    #[tokio::test]
+    async fn auth() {
+        // Other modules are not authorized.
+        let route = test_route_ok!(TEST_PATH);
+
+        {
+            let pid = nix::unistd::getpid().as_raw();
+
+            let mut runtime = route.runtime.lock().await;
+            runtime
+                .module_auth
+                .insert("otherModule".to_string(), vec![pid]);
+        }
+
+        let response = route.post(None).await.unwrap_err();
+        assert_eq!(hyper::StatusCode::FORBIDDEN, response.status_code);
+    }
+}
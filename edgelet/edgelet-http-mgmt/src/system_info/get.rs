@@ -5,6 +5,7 @@ where
     M: edgelet_core::ModuleRuntime + Send + Sync,
 {
     runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+    crash_loop: edgelet_core::CrashLoopDetector,
 }
 
 const PATH: &str = "/systeminfo";
@@ -32,6 +33,7 @@ where
 
         Some(Route {
             runtime: service.runtime.clone(),
+            crash_loop: service.crash_loop.clone(),
         })
     }
 
@@ -41,10 +43,14 @@ where
         let runtime = self.runtime.lock().await;
 
         match runtime.system_info().await {
-            Ok(sysinfo) => Ok(http_common::server::response::json(
-                hyper::StatusCode::OK,
-                &sysinfo,
-            )),
+            Ok(mut sysinfo) => {
+                sysinfo.edge_agent = Some(self.crash_loop.status());
+
+                Ok(http_common::server::response::json(
+                    hyper::StatusCode::OK,
+                    &sysinfo,
+                ))
+            }
             Err(err) => Err(edgelet_http::error::server_error(err)),
         }
     }
@@ -0,0 +1,128 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use hyper::service::Service as _;
+
+/// Content type of the archives streamed to [`Upload`] routes.
+const CONTENT_TYPE: &str = "application/x-tar";
+
+/// A route that takes a tar archive as its request body. The archive is streamed to the
+/// route as it's received instead of being read and parsed as JSON.
+#[async_trait::async_trait]
+pub(crate) trait Upload: http_common::server::Route {
+    fn method() -> &'static http::Method;
+
+    async fn upload(self, body: hyper::body::Incoming) -> http_common::server::RouteResponse;
+}
+
+/// The management API. Requests with an archive body are served by the [`Upload`] routes and
+/// all others by [`crate::Service`].
+#[derive(Clone)]
+pub struct Uploads<M>
+where
+    M: edgelet_core::ModuleRuntime,
+{
+    service: crate::Service<M>,
+}
+
+impl<M> Uploads<M>
+where
+    M: edgelet_core::ModuleRuntime,
+{
+    pub fn new(service: crate::Service<M>) -> Self {
+        Uploads { service }
+    }
+}
+
+type ServiceResponse<M> =
+    <crate::Service<M> as hyper::service::Service<hyper::Request<hyper::body::Incoming>>>::Response;
+type ServiceError<M> =
+    <crate::Service<M> as hyper::service::Service<hyper::Request<hyper::body::Incoming>>>::Error;
+
+impl<M> hyper::service::Service<hyper::Request<hyper::body::Incoming>> for Uploads<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync + 'static,
+    <M as edgelet_core::ModuleRuntime>::Config: serde::de::DeserializeOwned + Sync,
+{
+    type Response = ServiceResponse<M>;
+    type Error = ServiceError<M>;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn call(&self, req: hyper::Request<hyper::body::Incoming>) -> Self::Future {
+        let is_upload = req
+            .headers()
+            .get(hyper::header::CONTENT_TYPE)
+            .is_some_and(|content_type| content_type == CONTENT_TYPE);
+
+        if !is_upload {
+            return Box::pin(self.service.call(req));
+        }
+
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            let res = match upload(&service, req).await {
+                Ok(res) => res,
+                Err(err) => err.to_http_response(),
+            };
+
+            Ok(res)
+        })
+    }
+}
+
+async fn upload<M>(
+    service: &crate::Service<M>,
+    req: hyper::Request<hyper::body::Incoming>,
+) -> http_common::server::RouteResponse
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync + 'static,
+    <M as edgelet_core::ModuleRuntime>::Config: serde::de::DeserializeOwned + Sync,
+{
+    let (parts, body) = req.into_parts();
+
+    let query: Vec<_> =
+        url::form_urlencoded::parse(parts.uri.query().unwrap_or_default().as_bytes()).collect();
+
+    let api_version = edgelet_http::find_query("api-version", &query)
+        .ok_or_else(|| edgelet_http::error::bad_request("missing parameter: api-version"))?;
+    let api_version: edgelet_http::ApiVersion = api_version
+        .parse()
+        .map_err(|()| edgelet_http::error::bad_request("invalid api-version"))?;
+
+    if let Some(route) =
+        route::<M, crate::image::load::Route<M>>(service, &parts, &query, api_version)
+    {
+        return route.upload(body).await;
+    }
+
+    if let Some(route) =
+        route::<M, crate::module::archive::Route<M>>(service, &parts, &query, api_version)
+    {
+        return route.upload(body).await;
+    }
+
+    Err(http_common::server::Error {
+        status_code: hyper::StatusCode::NOT_FOUND,
+        message: "not found".into(),
+    })
+}
+
+/// Matches the request against the upload route `R`.
+fn route<M, R>(
+    service: &crate::Service<M>,
+    parts: &http::request::Parts,
+    query: &[(std::borrow::Cow<'_, str>, std::borrow::Cow<'_, str>)],
+    api_version: edgelet_http::ApiVersion,
+) -> Option<R>
+where
+    M: edgelet_core::ModuleRuntime,
+    R: Upload<Service = crate::Service<M>, ApiVersion = edgelet_http::ApiVersion>,
+{
+    if parts.method != *R::method() || !R::api_version().contains(&api_version) {
+        return None;
+    }
+
+    R::from_uri(service, parts.uri.path(), query, &parts.extensions)
+}
@@ -16,10 +16,12 @@ use test_common::client::KeyEngine;
 
 pub(crate) struct EdgeCaRenewal {
     rotate_key: bool,
+    key_algorithm: edgelet_settings::base::KeyAlgorithm,
     temp_cert: String,
     cert_client: std::sync::Arc<tokio::sync::Mutex<CertClient>>,
     key_client: std::sync::Arc<tokio::sync::Mutex<KeyClient>>,
     key_connector: http_common::Connector,
+    cert_tracker: std::sync::Arc<crate::module::cert::tracker::CertTracker>,
     renewal_tx: tokio::sync::mpsc::UnboundedSender<edgelet_core::WatchdogAction>,
 }
 
@@ -30,16 +32,19 @@ impl EdgeCaRenewal {
         cert_client: std::sync::Arc<tokio::sync::Mutex<CertClient>>,
         key_client: std::sync::Arc<tokio::sync::Mutex<KeyClient>>,
         key_connector: http_common::Connector,
+        cert_tracker: std::sync::Arc<crate::module::cert::tracker::CertTracker>,
         renewal_tx: tokio::sync::mpsc::UnboundedSender<edgelet_core::WatchdogAction>,
     ) -> Self {
         let temp_cert = format!("{}-temp", config.edge_ca_cert);
 
         EdgeCaRenewal {
             rotate_key,
+            key_algorithm: config.edge_ca_key_algorithm,
             temp_cert,
             cert_client,
             key_client,
             key_connector,
+            cert_tracker,
             renewal_tx,
         }
     }
@@ -105,8 +110,11 @@ impl cert_renewal::CertInterface for EdgeCaRenewal {
                     })?;
                 }
 
+                let preference =
+                    key_preference(self.key_algorithm).map_err(cert_renewal::Error::fatal_error)?;
+
                 let key_handle = key_client
-                    .create_key_pair_if_not_exists(&key_id, Some("rsa-2048:*"))
+                    .create_key_pair_if_not_exists(&key_id, Some(&preference))
                     .await
                     .map_err(|_| {
                         cert_renewal::Error::retryable_error("failed to generate temp key")
@@ -231,6 +239,11 @@ impl cert_renewal::CertInterface for EdgeCaRenewal {
         }
 
         log::info!("Edge CA was renewed");
+        edgelet_core::metrics().record_edge_ca_renewal();
+
+        // Module certs were issued by the old Edge CA, so modules waiting for renewal should
+        // request new ones.
+        self.cert_tracker.edge_ca_renewed();
 
         // Modules should be restarted so that they request new server certs.
         if let Err(err) = self
@@ -277,6 +290,17 @@ pub(crate) fn keys(
     Ok((private_key, public_key))
 }
 
+/// Returns the key algorithm preference for Edge CA keys created in keyd.
+pub(crate) fn key_preference(
+    algorithm: edgelet_settings::base::KeyAlgorithm,
+) -> Result<String, String> {
+    let name = algorithm
+        .keyd_name()
+        .ok_or_else(|| format!("edge CA keys cannot use the {algorithm} algorithm"))?;
+
+    Ok(format!("{name}:*"))
+}
+
 pub(crate) fn extensions()
 -> Result<openssl::stack::Stack<openssl::x509::X509Extension>, openssl::error::ErrorStack> {
     let mut csr_extensions = openssl::stack::Stack::new()?;
@@ -339,6 +363,7 @@ mod tests {
             cert_client,
             key_client,
             key_connector,
+            std::sync::Arc::default(),
             renewal_tx,
         )
     }
@@ -397,4 +422,22 @@ mod tests {
 
         renewal.get_key("test-key").await.unwrap_err();
     }
+
+    #[test]
+    fn key_preference() {
+        use edgelet_settings::base::KeyAlgorithm;
+
+        assert_eq!(
+            "rsa-2048:*",
+            super::key_preference(KeyAlgorithm::default()).unwrap()
+        );
+        assert_eq!(
+            "ec-p384:*",
+            super::key_preference(KeyAlgorithm::EcP384).unwrap()
+        );
+
+        // keyd can't generate these keys.
+        super::key_preference(KeyAlgorithm::Rsa3072).unwrap_err();
+        super::key_preference(KeyAlgorithm::Ed25519).unwrap_err();
+    }
 }
@@ -35,6 +35,7 @@ where
     renewal_engine: Option<
         std::sync::Arc<tokio::sync::Mutex<cert_renewal::RenewalEngine<edge_ca::EdgeCaRenewal>>>,
     >,
+    cert_tracker: std::sync::Arc<module::cert::tracker::CertTracker>,
     config: WorkloadConfig,
 }
 
@@ -94,6 +95,7 @@ where
             runtime,
             renewal_tx,
             renewal_engine,
+            cert_tracker: std::sync::Arc::default(),
             config,
         })
     }
@@ -103,8 +105,10 @@ where
         let key_handle = {
             let key_client = self.key_client.lock().await;
 
+            let preference = edge_ca::key_preference(self.config.edge_ca_key_algorithm)?;
+
             key_client
-                .create_key_pair_if_not_exists(&self.config.edge_ca_key, Some("rsa-2048:*"))
+                .create_key_pair_if_not_exists(&self.config.edge_ca_key, Some(&preference))
                 .await
                 .map_err(|err| err.to_string())?
         };
@@ -161,6 +165,7 @@ where
                 self.cert_client.clone(),
                 self.key_client.clone(),
                 self.key_connector.clone(),
+                self.cert_tracker.clone(),
                 self.renewal_tx.clone(),
             );
 
@@ -211,6 +216,9 @@ where
             edge_ca_subject: aziot_certd_config::CertSubject::CommonName(
                 "aziot-edge CA test-device".to_string(),
             ),
+            edge_ca_key_algorithm: edgelet_settings::base::KeyAlgorithm::default(),
+
+            module_cert_policy: Vec::new(),
         };
 
         // We won't use the renewal sender, but it must be created to construct the
@@ -227,6 +235,7 @@ where
             runtime,
             renewal_tx,
             renewal_engine: None,
+            cert_tracker: std::sync::Arc::default(),
             config,
         }
     }
@@ -243,11 +252,14 @@ http_common::make_service! {
         module::list::Route<M>,
 
         module::cert::identity::Route<M>,
+        module::cert::renewal::Route<M>,
         module::cert::server::Route<M>,
 
         module::data::decrypt::Route<M>,
         module::data::encrypt::Route<M>,
+        module::data::public_key::Route<M>,
         module::data::sign::Route<M>,
+        module::data::verify::Route<M>,
 
         trust_bundle::Route<M>,
     ],
@@ -267,6 +279,9 @@ struct WorkloadConfig {
     edge_ca_key: String,
     edge_ca_auto_renew: Option<cert_renewal::AutoRenewConfig>,
     edge_ca_subject: aziot_certd_config::CertSubject,
+    edge_ca_key_algorithm: edgelet_settings::base::KeyAlgorithm,
+
+    module_cert_policy: Vec<edgelet_settings::base::cert::ModuleCertRule>,
 }
 
 impl WorkloadConfig {
@@ -298,6 +313,9 @@ impl WorkloadConfig {
         let edge_ca_subject = settings.edge_ca_subject().clone().unwrap_or_else(|| {
             aziot_certd_config::CertSubject::CommonName(format!("aziot-edge CA {device_id}"))
         });
+        let edge_ca_key_algorithm = settings.edge_ca_key_algorithm().unwrap_or_default();
+
+        let module_cert_policy = settings.module_cert_policy().to_vec();
 
         WorkloadConfig {
             hub_name: device_info.hub_name.clone(),
@@ -310,6 +328,9 @@ impl WorkloadConfig {
             edge_ca_key,
             edge_ca_auto_renew,
             edge_ca_subject,
+            edge_ca_key_algorithm,
+
+            module_cert_policy,
         }
     }
 }
@@ -344,7 +365,10 @@ mod tests {
                 edge_ca_auto_renew: None,
                 edge_ca_subject: aziot_certd_config::CertSubject::CommonName(
                     "aziot-edge CA test-device".to_string(),
-                )
+                ),
+                edge_ca_key_algorithm: edgelet_settings::base::KeyAlgorithm::Rsa2048,
+
+                module_cert_policy: Vec::new(),
             },
             config
         );
@@ -368,8 +392,14 @@ mod tests {
             edge_ca_subject: Some(aziot_certd_config::CertSubject::CommonName(
                 "aziot-edge CA test-device".to_string(),
             )),
+            edge_ca_key_algorithm: Some(edgelet_settings::base::KeyAlgorithm::EcP384),
             trust_bundle: Some("test-trust-bundle".to_string()),
             manifest_trust_bundle: Some("test-manifest-trust-bundle".to_string()),
+            module_cert_policy: vec![edgelet_settings::base::cert::ModuleCertRule {
+                modules: vec!["testModule".to_string()],
+                dns_names: vec!["*.test.local".to_string()],
+                ..Default::default()
+            }],
         };
 
         // Check that values from settings are used when provided.
@@ -387,7 +417,14 @@ mod tests {
                 edge_ca_auto_renew: None,
                 edge_ca_subject: aziot_certd_config::CertSubject::CommonName(
                     "aziot-edge CA test-device".to_string(),
-                )
+                ),
+                edge_ca_key_algorithm: edgelet_settings::base::KeyAlgorithm::EcP384,
+
+                module_cert_policy: vec![edgelet_settings::base::cert::ModuleCertRule {
+                    modules: vec!["testModule".to_string()],
+                    dns_names: vec!["*.test.local".to_string()],
+                    ..Default::default()
+                }],
             },
             config
         );
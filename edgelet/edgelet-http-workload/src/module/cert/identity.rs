@@ -11,6 +11,12 @@ where
     runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
 }
 
+#[derive(Debug, Default, serde::Deserialize)]
+pub(crate) struct IdentityCertificateRequest {
+    #[serde(default, rename = "keyAlgorithm")]
+    key_algorithm: edgelet_settings::base::KeyAlgorithm,
+}
+
 #[async_trait::async_trait]
 impl<M> http_common::server::Route for Route<M>
 where
@@ -47,6 +53,7 @@ where
         let api = super::CertApi::new(
             service.key_client.clone(),
             service.cert_client.clone(),
+            service.cert_tracker.clone(),
             &service.config,
         );
 
@@ -61,10 +68,13 @@ where
 
     type DeleteBody = serde::de::IgnoredAny;
 
-    type PostBody = serde::de::IgnoredAny;
-    async fn post(self, _body: Option<Self::PostBody>) -> http_common::server::RouteResponse {
+    type PostBody = IdentityCertificateRequest;
+    async fn post(self, body: Option<Self::PostBody>) -> http_common::server::RouteResponse {
         edgelet_http::auth_caller(&self.module_id, self.pid, &self.runtime).await?;
 
+        // The request body is optional for compatibility with clients that don't send one.
+        let key_algorithm = body.unwrap_or_default().key_algorithm;
+
         let cert_id = format!("aziot-edged/module/{}:identity", &self.module_id);
 
         let subject_alt_names = vec![super::SubjectAltName::Dns(self.module_uri)];
@@ -74,7 +84,17 @@ where
         })?;
 
         self.api
-            .issue_cert(cert_id, self.module_id, subject_alt_names, csr_extensions)
+            .issue_cert(
+                cert_id,
+                super::tracker::TrackedCertId::new(
+                    &self.module_id,
+                    super::tracker::CertType::Identity,
+                ),
+                self.module_id,
+                subject_alt_names,
+                csr_extensions,
+                key_algorithm,
+            )
             .await
     }
 
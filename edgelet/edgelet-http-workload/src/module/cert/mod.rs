@@ -1,7 +1,10 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 pub(crate) mod identity;
+mod policy;
+pub(crate) mod renewal;
 pub(crate) mod server;
+pub(crate) mod tracker;
 
 use std::error::Error as StdError;
 
@@ -39,11 +42,23 @@ pub(crate) struct CertificateResponse {
 pub(crate) enum SubjectAltName {
     Dns(String),
     Ip(String),
+    Uri(String),
+}
+
+impl SubjectAltName {
+    fn value(&self) -> &str {
+        match self {
+            SubjectAltName::Dns(value) | SubjectAltName::Ip(value) | SubjectAltName::Uri(value) => {
+                value
+            }
+        }
+    }
 }
 
 struct CertApi {
     key_client: std::sync::Arc<tokio::sync::Mutex<KeyClient>>,
     cert_client: std::sync::Arc<tokio::sync::Mutex<CertClient>>,
+    tracker: std::sync::Arc<tracker::CertTracker>,
 
     edge_ca_cert: String,
     edge_ca_key: String,
@@ -53,11 +68,13 @@ impl CertApi {
     pub fn new(
         key_client: std::sync::Arc<tokio::sync::Mutex<KeyClient>>,
         cert_client: std::sync::Arc<tokio::sync::Mutex<CertClient>>,
+        tracker: std::sync::Arc<tracker::CertTracker>,
         config: &crate::WorkloadConfig,
     ) -> Self {
         CertApi {
             key_client,
             cert_client,
+            tracker,
             edge_ca_cert: config.edge_ca_cert.clone(),
             edge_ca_key: config.edge_ca_key.clone(),
         }
@@ -66,14 +83,16 @@ impl CertApi {
     pub async fn issue_cert(
         self,
         cert_id: String,
+        tracked_id: tracker::TrackedCertId,
         common_name: String,
         subject_alt_names: Vec<SubjectAltName>,
         extensions: openssl::stack::Stack<openssl::x509::X509Extension>,
+        key_algorithm: edgelet_settings::base::KeyAlgorithm,
     ) -> Result<
         hyper::Response<BoxBody<Bytes, Box<dyn StdError + Send + Sync>>>,
         http_common::server::Error,
     > {
-        let keys = new_keys()
+        let keys = new_keys(key_algorithm)
             .map_err(|_| edgelet_http::error::server_error("failed to generate csr keys"))?;
         let private_key = key_to_pem(&keys.0);
 
@@ -100,7 +119,9 @@ impl CertApi {
             .create_cert(&cert_id, &csr, &edge_ca_key_handle)
             .await?;
 
-        let expiration = get_expiration(&cert)?;
+        let (not_before, expiration) = get_validity(&cert)?;
+        self.tracker.track(tracked_id, not_before, expiration);
+        let expiration = expiration.to_rfc3339();
 
         let response = CertificateResponse {
             private_key: PrivateKey::Key { bytes: private_key },
@@ -136,15 +157,25 @@ impl CertApi {
     }
 }
 
-fn new_keys() -> Result<
+fn new_keys(
+    algorithm: edgelet_settings::base::KeyAlgorithm,
+) -> Result<
     (
         openssl::pkey::PKey<openssl::pkey::Private>,
         openssl::pkey::PKey<openssl::pkey::Public>,
     ),
     openssl::error::ErrorStack,
 > {
-    let rsa = openssl::rsa::Rsa::generate(2048)?;
-    let private_key = openssl::pkey::PKey::from_rsa(rsa)?;
+    use edgelet_settings::base::KeyAlgorithm;
+
+    let private_key = match algorithm {
+        KeyAlgorithm::Rsa2048 => openssl::pkey::PKey::from_rsa(openssl::rsa::Rsa::generate(2048)?)?,
+        KeyAlgorithm::Rsa3072 => openssl::pkey::PKey::from_rsa(openssl::rsa::Rsa::generate(3072)?)?,
+        KeyAlgorithm::Rsa4096 => openssl::pkey::PKey::from_rsa(openssl::rsa::Rsa::generate(4096)?)?,
+        KeyAlgorithm::EcP256 => new_ec_key(openssl::nid::Nid::X9_62_PRIME256V1)?,
+        KeyAlgorithm::EcP384 => new_ec_key(openssl::nid::Nid::SECP384R1)?,
+        KeyAlgorithm::Ed25519 => openssl::pkey::PKey::generate_ed25519()?,
+    };
 
     let public_key = private_key.public_key_to_pem()?;
     let public_key = openssl::pkey::PKey::public_key_from_pem(&public_key)?;
@@ -152,6 +183,19 @@ fn new_keys() -> Result<
     Ok((private_key, public_key))
 }
 
+fn new_ec_key(
+    curve: openssl::nid::Nid,
+) -> Result<openssl::pkey::PKey<openssl::pkey::Private>, openssl::error::ErrorStack> {
+    let mut group = openssl::ec::EcGroup::from_curve_name(curve)?;
+
+    // Keys are given to modules as PEM, which should name the curve rather than list its
+    // parameters.
+    group.set_asn1_flag(openssl::ec::Asn1Flag::NAMED_CURVE);
+
+    let ec = openssl::ec::EcKey::generate(&group)?;
+    openssl::pkey::PKey::from_ec_key(ec)
+}
+
 pub(crate) fn new_csr(
     subject: &openssl::x509::X509NameRef,
     keys: (
@@ -177,6 +221,7 @@ pub(crate) fn new_csr(
             match name {
                 SubjectAltName::Dns(name) => names.dns(&name),
                 SubjectAltName::Ip(name) => names.ip(&name),
+                SubjectAltName::Uri(name) => names.uri(&name),
             };
         }
 
@@ -186,14 +231,38 @@ pub(crate) fn new_csr(
 
     csr.add_extensions(&extensions)?;
 
-    csr.sign(&private_key, openssl::hash::MessageDigest::sha256())?;
+    csr.sign(&private_key, csr_digest(&public_key)?)?;
 
     let csr = csr.build().to_pem()?;
 
     Ok(csr)
 }
 
-fn get_expiration(cert: &str) -> Result<String, http_common::server::Error> {
+/// Ed25519 keys sign the CSR without a separate digest. P-384 keys use SHA-384 to match the
+/// strength of the curve, and all other keys use SHA-256.
+fn csr_digest(
+    key: &openssl::pkey::PKeyRef<openssl::pkey::Public>,
+) -> Result<openssl::hash::MessageDigest, openssl::error::ErrorStack> {
+    let digest = match key.id() {
+        openssl::pkey::Id::ED25519 => openssl::hash::MessageDigest::null(),
+        openssl::pkey::Id::EC
+            if key.ec_key()?.group().curve_name() == Some(openssl::nid::Nid::SECP384R1) =>
+        {
+            openssl::hash::MessageDigest::sha384()
+        }
+        _ => openssl::hash::MessageDigest::sha256(),
+    };
+
+    Ok(digest)
+}
+
+/// Returns the start and end of the cert's validity period.
+fn get_validity(
+    cert: &str,
+) -> Result<
+    (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>),
+    http_common::server::Error,
+> {
     let cert = openssl::x509::X509::from_pem(cert.as_bytes())
         .map_err(|_| edgelet_http::error::server_error("failed to parse cert"))?;
 
@@ -201,11 +270,14 @@ fn get_expiration(cert: &str) -> Result<String, http_common::server::Error> {
     //
     // Its Display impl uses ASN1_TIME_print, so we convert it into a String and parse it back
     // into a chrono::DateTime<chrono::Utc>
-    let expiration = cert.not_after().to_string();
-    let expiration = chrono::NaiveDateTime::parse_from_str(&expiration, "%b %e %H:%M:%S %Y GMT")
-        .expect("cert not_after should parse");
-    let expiration = chrono::TimeZone::from_utc_datetime(&chrono::Utc, &expiration);
-    Ok(expiration.to_rfc3339())
+    let parse = |time: &openssl::asn1::Asn1TimeRef| {
+        let time =
+            chrono::NaiveDateTime::parse_from_str(&time.to_string(), "%b %e %H:%M:%S %Y GMT")
+                .expect("cert validity should parse");
+        chrono::TimeZone::from_utc_datetime(&chrono::Utc, &time)
+    };
+
+    Ok((parse(cert.not_before()), parse(cert.not_after())))
 }
 
 fn key_to_pem(key: &openssl::pkey::PKey<openssl::pkey::Private>) -> String {
@@ -233,14 +305,19 @@ mod tests {
         super::CertApi {
             key_client,
             cert_client,
+            tracker: std::sync::Arc::default(),
 
             edge_ca_cert: "test-device-cert".to_string(),
             edge_ca_key: "test-device-key".to_string(),
         }
     }
 
-    #[tokio::test]
-    async fn issue_cert() {
+    async fn issue_cert(
+        key_algorithm: edgelet_settings::base::KeyAlgorithm,
+    ) -> (
+        openssl::x509::X509,
+        openssl::pkey::PKey<openssl::pkey::Private>,
+    ) {
         let api = test_api();
 
         let (_, issuer_key) = {
@@ -254,10 +331,12 @@ mod tests {
         let response = api
             .issue_cert(
                 "testCertificate".to_string(),
+                super::tracker::TrackedCertId::new("testModule", super::tracker::CertType::Server),
                 "testCertificate".to_string(),
                 // This test won't check these fields, so it doesn't matter what's passed here.
                 vec![],
                 extensions,
+                key_algorithm,
             )
             .await
             .unwrap();
@@ -284,5 +363,62 @@ mod tests {
 
         // Check certificate is signed by issuer key.
         assert!(cert.verify(&issuer_key).unwrap());
+
+        (cert, private_key)
+    }
+
+    #[tokio::test]
+    async fn issue_cert_default() {
+        let (_, private_key) = issue_cert(edgelet_settings::base::KeyAlgorithm::default()).await;
+
+        assert_eq!(openssl::pkey::Id::RSA, private_key.id());
+        assert_eq!(2048, private_key.bits());
+    }
+
+    #[tokio::test]
+    async fn issue_cert_key_algorithms() {
+        use edgelet_settings::base::KeyAlgorithm;
+
+        for (key_algorithm, id, bits) in [
+            (KeyAlgorithm::Rsa3072, openssl::pkey::Id::RSA, 3072),
+            (KeyAlgorithm::EcP256, openssl::pkey::Id::EC, 256),
+            (KeyAlgorithm::EcP384, openssl::pkey::Id::EC, 384),
+            (KeyAlgorithm::Ed25519, openssl::pkey::Id::ED25519, 256),
+        ] {
+            let (_, private_key) = issue_cert(key_algorithm).await;
+
+            assert_eq!(id, private_key.id(), "{key_algorithm}");
+            assert_eq!(bits, private_key.bits(), "{key_algorithm}");
+        }
+    }
+
+    #[test]
+    fn csr_digest() {
+        use edgelet_settings::base::KeyAlgorithm;
+
+        for (key_algorithm, digest) in [
+            (
+                KeyAlgorithm::Rsa2048,
+                openssl::hash::MessageDigest::sha256(),
+            ),
+            (KeyAlgorithm::EcP256, openssl::hash::MessageDigest::sha256()),
+            (KeyAlgorithm::EcP384, openssl::hash::MessageDigest::sha384()),
+            (KeyAlgorithm::Ed25519, openssl::hash::MessageDigest::null()),
+        ] {
+            let (private_key, public_key) = super::new_keys(key_algorithm).unwrap();
+            assert_eq!(
+                digest.type_(),
+                super::csr_digest(&public_key).unwrap().type_(),
+                "{key_algorithm}"
+            );
+
+            // The CSR must be signed by the generated key.
+            let subject = openssl::x509::X509Name::builder().unwrap().build();
+            let extensions = openssl::stack::Stack::new().unwrap();
+            let csr =
+                super::new_csr(&subject, (private_key, public_key), vec![], extensions).unwrap();
+            let csr = openssl::x509::X509Req::from_pem(&csr).unwrap();
+            assert!(csr.verify(&csr.public_key().unwrap()).unwrap());
+        }
     }
 }
@@ -0,0 +1,231 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Module certificate policy.
+//!
+//! The rules under `[[module_cert_policy]]` restrict which additional names modules may put in
+//! their server certificates, and whether those certificates may be used as client certificates.
+
+use edgelet_settings::base::cert::ModuleCertRule;
+
+use super::SubjectAltName;
+
+/// Checks that `module_id` may request the additional `names` and, if `client_auth` is set,
+/// client authentication. Returns an error response naming the first value that isn't allowed.
+pub(crate) fn check(
+    policy: &[ModuleCertRule],
+    module_id: &str,
+    names: &[SubjectAltName],
+    client_auth: bool,
+) -> Result<(), http_common::server::Error> {
+    let rules: Vec<&ModuleCertRule> = policy
+        .iter()
+        .filter(|rule| {
+            rule.modules
+                .iter()
+                .any(|pattern| glob_match(pattern, module_id, b'/'))
+        })
+        .collect();
+
+    for name in names {
+        let allowed = match name {
+            SubjectAltName::Dns(name) => rules.iter().any(|rule| {
+                rule.dns_names.iter().any(|pattern| {
+                    glob_match(
+                        &pattern.to_ascii_lowercase(),
+                        &name.to_ascii_lowercase(),
+                        b'.',
+                    )
+                })
+            }),
+            SubjectAltName::Ip(address) => {
+                let address: std::net::IpAddr = address
+                    .parse()
+                    .map_err(|_| forbidden(format!("{address:?} is not an IP address")))?;
+
+                rules.iter().any(|rule| {
+                    rule.ip_addresses
+                        .iter()
+                        .any(|range| ip_in_range(address, range))
+                })
+            }
+            SubjectAltName::Uri(uri) => rules.iter().any(|rule| {
+                rule.uris
+                    .iter()
+                    .any(|pattern| glob_match(pattern, uri, b'/'))
+            }),
+        };
+
+        if !allowed {
+            return Err(forbidden(format!(
+                "module {module_id} may not request the name {}",
+                name.value()
+            )));
+        }
+    }
+
+    if client_auth && !rules.iter().any(|rule| rule.client_auth) {
+        return Err(forbidden(format!(
+            "module {module_id} may not request client authentication"
+        )));
+    }
+
+    Ok(())
+}
+
+fn forbidden(message: String) -> http_common::server::Error {
+    http_common::server::Error {
+        status_code: http::StatusCode::FORBIDDEN,
+        message: message.into(),
+    }
+}
+
+/// Matches `value` against a glob in which `*` matches any characters other than `separator`
+/// and `?` matches one character other than `separator`.
+fn glob_match(pattern: &str, value: &str, separator: u8) -> bool {
+    fn matches(pattern: &[u8], value: &[u8], separator: u8) -> bool {
+        match pattern {
+            [] => value.is_empty(),
+            [b'*', rest @ ..] => (0..=value.len())
+                .take_while(|&i| i == 0 || value[i - 1] != separator)
+                .any(|i| matches(rest, &value[i..], separator)),
+            [b'?', rest @ ..] => {
+                matches!(value.first(), Some(c) if *c != separator)
+                    && matches(rest, &value[1..], separator)
+            }
+            [c, rest @ ..] => value.first() == Some(c) && matches(rest, &value[1..], separator),
+        }
+    }
+
+    matches(pattern.as_bytes(), value.as_bytes(), separator)
+}
+
+/// Checks whether `address` is `range`, which is either an address or a CIDR range.
+/// Ranges that can't be parsed contain no addresses.
+fn ip_in_range(address: std::net::IpAddr, range: &str) -> bool {
+    let (network, prefix_len) = match range.split_once('/') {
+        Some((network, prefix_len)) => match prefix_len.parse::<u32>() {
+            Ok(prefix_len) => (network, Some(prefix_len)),
+            Err(_) => return false,
+        },
+        None => (range, None),
+    };
+
+    let Ok(network) = network.parse::<std::net::IpAddr>() else {
+        return false;
+    };
+
+    let (address, network, bits) = match (address, network) {
+        (std::net::IpAddr::V4(address), std::net::IpAddr::V4(network)) => (
+            u128::from(u32::from(address)) << 96,
+            u128::from(u32::from(network)) << 96,
+            32,
+        ),
+        (std::net::IpAddr::V6(address), std::net::IpAddr::V6(network)) => {
+            (u128::from(address), u128::from(network), 128)
+        }
+        _ => return false,
+    };
+
+    let prefix_len = prefix_len.unwrap_or(bits);
+    if prefix_len > bits {
+        return false;
+    }
+
+    let mask = u128::MAX.checked_shl(128 - prefix_len).unwrap_or(0);
+
+    address & mask == network & mask
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_settings::base::cert::ModuleCertRule;
+
+    use super::{SubjectAltName, check, glob_match, ip_in_range};
+
+    fn policy() -> Vec<ModuleCertRule> {
+        vec![
+            ModuleCertRule {
+                modules: vec!["web*".to_string()],
+                dns_names: vec!["*.contoso.local".to_string(), "contoso.com".to_string()],
+                ip_addresses: vec!["10.0.0.0/8".to_string(), "fd00::1".to_string()],
+                uris: vec!["spiffe://contoso/web/*".to_string()],
+                client_auth: false,
+            },
+            ModuleCertRule {
+                modules: vec!["webProxy".to_string()],
+                client_auth: true,
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("*.contoso.local", "a.contoso.local", b'.'));
+        assert!(!glob_match("*.contoso.local", "a.b.contoso.local", b'.'));
+        assert!(!glob_match("*.contoso.local", "contoso.local", b'.'));
+        assert!(glob_match("web?", "web1", b'/'));
+        assert!(!glob_match("web?", "web10", b'/'));
+    }
+
+    #[test]
+    fn ip_ranges() {
+        let address = |address: &str| address.parse().unwrap();
+
+        assert!(ip_in_range(address("10.1.2.3"), "10.0.0.0/8"));
+        assert!(!ip_in_range(address("11.1.2.3"), "10.0.0.0/8"));
+        assert!(ip_in_range(address("192.168.1.1"), "192.168.1.1"));
+        assert!(ip_in_range(address("192.168.1.1"), "0.0.0.0/0"));
+        assert!(ip_in_range(address("fd00::abcd"), "fd00::/64"));
+        assert!(!ip_in_range(address("fd01::abcd"), "fd00::/64"));
+
+        // Addresses of a different family are never in range.
+        assert!(!ip_in_range(address("::ffff:10.0.0.1"), "10.0.0.0/8"));
+
+        // Invalid ranges
+        assert!(!ip_in_range(address("10.0.0.1"), "10.0.0.0/33"));
+        assert!(!ip_in_range(address("10.0.0.1"), "10.0.0.0/a"));
+        assert!(!ip_in_range(address("10.0.0.1"), "contoso"));
+    }
+
+    #[test]
+    fn allowed() {
+        let names = [
+            SubjectAltName::Dns("api.CONTOSO.local".to_string()),
+            SubjectAltName::Dns("contoso.com".to_string()),
+            SubjectAltName::Ip("10.20.30.40".to_string()),
+            SubjectAltName::Ip("fd00::1".to_string()),
+            SubjectAltName::Uri("spiffe://contoso/web/api".to_string()),
+        ];
+
+        check(&policy(), "webApi", &names, false).unwrap();
+        check(&policy(), "webProxy", &names, true).unwrap();
+
+        // Modules that no rule matches may still request certs without additional names.
+        check(&policy(), "otherModule", &[], false).unwrap();
+    }
+
+    #[test]
+    fn denied() {
+        for name in [
+            SubjectAltName::Dns("a.b.contoso.local".to_string()),
+            SubjectAltName::Dns("contoso.org".to_string()),
+            SubjectAltName::Ip("192.168.0.1".to_string()),
+            SubjectAltName::Uri("spiffe://contoso/web/api/admin".to_string()),
+        ] {
+            let err = check(&policy(), "webApi", &[name], false).unwrap_err();
+            assert_eq!(http::StatusCode::FORBIDDEN, err.status_code);
+        }
+
+        // Names are only allowed for modules that the rule matches.
+        let name = SubjectAltName::Dns("api.contoso.local".to_string());
+        let err = check(&policy(), "otherModule", &[name], false).unwrap_err();
+        assert_eq!(http::StatusCode::FORBIDDEN, err.status_code);
+
+        // Client authentication must be allowed for the module.
+        let err = check(&policy(), "webApi", &[], true).unwrap_err();
+        assert_eq!(http::StatusCode::FORBIDDEN, err.status_code);
+        let err = check(&[], "webProxy", &[], true).unwrap_err();
+        assert_eq!(http::StatusCode::FORBIDDEN, err.status_code);
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+/// How long a request waits for a certificate to become due if it doesn't set a timeout.
+const DEFAULT_TIMEOUT: std::time::Duration = std::time::Duration::from_mins(5);
+
+/// Longest timeout that a request may set.
+const MAX_TIMEOUT: std::time::Duration = std::time::Duration::from_hours(1);
+
+pub(crate) struct Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    module_id: String,
+    timeout: Option<String>,
+    pid: libc::pid_t,
+    tracker: std::sync::Arc<super::tracker::CertTracker>,
+    runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+}
+
+#[derive(Debug, serde::Serialize)]
+#[cfg_attr(test, derive(serde::Deserialize))]
+pub(crate) struct CertRenewalResponse {
+    certificates: Vec<super::tracker::DueCert>,
+}
+
+#[async_trait::async_trait]
+impl<M> http_common::server::Route for Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    type ApiVersion = edgelet_http::ApiVersion;
+    fn api_version() -> &'static dyn http_common::DynRangeBounds<Self::ApiVersion> {
+        &((edgelet_http::ApiVersion::V2022_08_03)..)
+    }
+
+    type Service = crate::Service<M>;
+    fn from_uri(
+        service: &Self::Service,
+        path: &str,
+        query: &[(std::borrow::Cow<'_, str>, std::borrow::Cow<'_, str>)],
+        extensions: &http::Extensions,
+    ) -> Option<Self> {
+        let uri_regex = regex::Regex::new("^/modules/(?P<moduleId>[^/]+)/certificate/renewal$")
+            .expect("hard-coded regex must compile");
+        let captures = uri_regex.captures(path)?;
+
+        let module_id = &captures["moduleId"];
+        let module_id = percent_encoding::percent_decode_str(module_id)
+            .decode_utf8()
+            .ok()?;
+
+        let pid = extensions.get::<Option<libc::pid_t>>().copied()??;
+
+        Some(Route {
+            module_id: module_id.into_owned(),
+            timeout: edgelet_http::find_query("timeout", query),
+            pid,
+            tracker: service.cert_tracker.clone(),
+            runtime: service.runtime.clone(),
+        })
+    }
+
+    type DeleteBody = serde::de::IgnoredAny;
+
+    /// Waits until any of the module's certificates should be requested again, and returns
+    /// them. Returns no content if none are due before the timeout.
+    async fn get(self) -> http_common::server::RouteResponse {
+        edgelet_http::auth_caller(&self.module_id, self.pid, &self.runtime).await?;
+
+        let timeout = match self.timeout {
+            Some(timeout) => timeout
+                .parse::<u64>()
+                .map(std::time::Duration::from_secs)
+                .map_err(|_| edgelet_http::error::bad_request("invalid parameter: timeout"))?
+                .min(MAX_TIMEOUT),
+            None => DEFAULT_TIMEOUT,
+        };
+
+        let certificates = self.tracker.wait(&self.module_id, timeout).await;
+
+        if certificates.is_empty() {
+            Ok(http_common::server::response::no_content())
+        } else {
+            let response = CertRenewalResponse { certificates };
+
+            Ok(http_common::server::response::json(
+                hyper::StatusCode::OK,
+                &response,
+            ))
+        }
+    }
+
+    type PostBody = serde::de::IgnoredAny;
+
+    type PutBody = serde::de::IgnoredAny;
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::BodyExt as _;
+
+    use http_common::server::Route;
+
+    use edgelet_test_utils::{test_route_err, test_route_ok};
+
+    use crate::module::cert::tracker::{CertType, RenewalReason, TrackedCertId};
+
+    const TEST_PATH: &str = "/modules/testModule/certificate/renewal";
+
+    const MODULE_NAME: &str = "testModule";
+
+    async fn authorized_route(timeout: &str) -> super::Route<edgelet_test_utils::runtime::Runtime> {
+        let route = test_route_ok!(TEST_PATH, ("timeout", timeout));
+        {
+            let pid = nix::unistd::getpid().as_raw();
+            let mut runtime = route.runtime.lock().await;
+            runtime.module_auth = std::collections::BTreeMap::new();
+            runtime
+                .module_auth
+                .insert(MODULE_NAME.to_string(), vec![pid]);
+        }
+
+        route
+    }
+
+    #[test]
+    fn parse_uri() {
+        // Valid URI
+        let route = test_route_ok!(TEST_PATH, ("timeout", "10"));
+        assert_eq!(MODULE_NAME, &route.module_id);
+        assert_eq!(Some("10"), route.timeout.as_deref());
+        assert_eq!(nix::unistd::getpid().as_raw(), route.pid);
+
+        // Timeout is optional.
+        let route = test_route_ok!(TEST_PATH);
+        assert_eq!(None, route.timeout);
+
+        // Missing module ID
+        test_route_err!("/modules//certificate/renewal");
+
+        // Extra character at beginning of URI
+        test_route_err!(&format!("a{TEST_PATH}"));
+
+        // Extra character at end of URI
+        test_route_err!(&format!("{TEST_PATH}a"));
+    }
+
+    #[tokio::test]
+    async fn auth() {
+        async fn get(
+            mut route: super::Route<edgelet_test_utils::runtime::Runtime>,
+        ) -> http_common::server::RouteResponse {
+            // Don't wait for certificates once auth succeeds.
+            route.timeout = Some("0".to_string());
+
+            route.get().await
+        }
+
+        edgelet_test_utils::test_auth_caller!(TEST_PATH, MODULE_NAME, get);
+    }
+
+    #[tokio::test]
+    async fn bad_timeout() {
+        let route = authorized_route("soon").await;
+
+        let response = route.get().await.unwrap_err();
+        assert_eq!(hyper::StatusCode::BAD_REQUEST, response.status_code);
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let route = authorized_route("0").await;
+
+        let response = route.get().await.unwrap();
+        assert_eq!(hyper::StatusCode::NO_CONTENT, response.status());
+    }
+
+    #[tokio::test]
+    async fn due() {
+        let route = authorized_route("60").await;
+
+        let now = chrono::Utc::now();
+        let expiration = now + chrono::TimeDelta::hours(1);
+        route.tracker.track(
+            TrackedCertId::new(MODULE_NAME, CertType::Server),
+            now - chrono::TimeDelta::days(1),
+            expiration,
+        );
+
+        let response = route.get().await.unwrap();
+        assert_eq!(hyper::StatusCode::OK, response.status());
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let response: super::CertRenewalResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(1, response.certificates.len());
+        assert_eq!(CertType::Server, response.certificates[0].cert_type);
+        assert_eq!(RenewalReason::Expiring, response.certificates[0].reason);
+    }
+}
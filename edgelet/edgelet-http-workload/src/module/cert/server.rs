@@ -10,6 +10,7 @@ where
     gen_id: String,
     pid: libc::pid_t,
     api: super::CertApi,
+    policy: Vec<edgelet_settings::base::cert::ModuleCertRule>,
     runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
 }
 
@@ -17,6 +18,37 @@ where
 pub(crate) struct ServerCertificateRequest {
     #[serde(rename = "commonName")]
     common_name: String,
+
+    #[serde(default, rename = "keyAlgorithm")]
+    key_algorithm: edgelet_settings::base::KeyAlgorithm,
+
+    /// Names to add to the module ID and common name, if the module cert policy allows them.
+    #[serde(default, rename = "subjectAltNames")]
+    subject_alt_names: SubjectAltNames,
+
+    /// Server certificates can always be used for server authentication, so this only needs
+    /// to be set to request client authentication as well.
+    #[serde(default, rename = "extendedKeyUsage")]
+    extended_key_usage: Vec<ExtendedKeyUsage>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub(crate) struct SubjectAltNames {
+    #[serde(default)]
+    dns: Vec<String>,
+
+    #[serde(default)]
+    ip: Vec<String>,
+
+    #[serde(default)]
+    uri: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum ExtendedKeyUsage {
+    ServerAuth,
+    ClientAuth,
 }
 
 #[async_trait::async_trait]
@@ -57,6 +89,7 @@ where
         let api = super::CertApi::new(
             service.key_client.clone(),
             service.cert_client.clone(),
+            service.cert_tracker.clone(),
             &service.config,
         );
 
@@ -65,6 +98,7 @@ where
             gen_id: gen_id.into_owned(),
             pid,
             api,
+            policy: service.config.module_cert_policy.clone(),
             runtime: service.runtime.clone(),
         })
     }
@@ -75,10 +109,10 @@ where
     async fn post(self, body: Option<Self::PostBody>) -> http_common::server::RouteResponse {
         edgelet_http::auth_caller(&self.module_id, self.pid, &self.runtime).await?;
 
-        let common_name = match body {
-            Some(body) => body.common_name,
-            None => return Err(edgelet_http::error::bad_request("missing request body")),
+        let Some(body) = body else {
+            return Err(edgelet_http::error::bad_request("missing request body"));
         };
+        let common_name = body.common_name;
 
         // Remove any leading '$' from modules like '$edgeAgent' and '$edgeHub' for consistency
         // with previous versions.
@@ -98,26 +132,92 @@ where
         // Server certificates have the module ID and certificate CN as the SANs.
         let module_id_san = super::SubjectAltName::Dns(module_id.to_string());
 
-        let subject_alt_names = vec![common_name_san, module_id_san];
+        let additional_names = additional_names(body.subject_alt_names)?;
+        let client_auth = body
+            .extended_key_usage
+            .contains(&ExtendedKeyUsage::ClientAuth);
+        super::policy::check(&self.policy, module_id, &additional_names, client_auth)?;
+
+        let mut subject_alt_names = vec![common_name_san, module_id_san];
+        subject_alt_names.extend(additional_names);
 
-        let csr_extensions = server_cert_extensions().map_err(|_| {
+        let csr_extensions = server_cert_extensions(client_auth).map_err(|_| {
             edgelet_http::error::server_error("failed to set server csr extensions")
         })?;
 
         self.api
-            .issue_cert(cert_id, common_name, subject_alt_names, csr_extensions)
+            .issue_cert(
+                cert_id,
+                super::tracker::TrackedCertId::new(module_id, super::tracker::CertType::Server),
+                common_name,
+                subject_alt_names,
+                csr_extensions,
+                body.key_algorithm,
+            )
             .await
     }
 
     type PutBody = serde::de::IgnoredAny;
 }
 
-fn server_cert_extensions()
--> Result<openssl::stack::Stack<openssl::x509::X509Extension>, openssl::error::ErrorStack> {
+/// Checks that the requested names are well-formed so that the policy only sees names that
+/// can be put in a certificate.
+fn additional_names(
+    names: SubjectAltNames,
+) -> Result<Vec<super::SubjectAltName>, http_common::server::Error> {
+    let mut subject_alt_names = Vec::new();
+
+    for name in names.dns {
+        let valid = !name.is_empty()
+            && name.len() <= 253
+            && name.split('.').all(|label| {
+                !label.is_empty()
+                    && label.len() <= 63
+                    && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            });
+
+        if !valid {
+            return Err(edgelet_http::error::bad_request(
+                "invalid DNS name in subjectAltNames",
+            ));
+        }
+
+        subject_alt_names.push(super::SubjectAltName::Dns(name));
+    }
+
+    for address in names.ip {
+        if std::net::IpAddr::from_str(&address).is_err() {
+            return Err(edgelet_http::error::bad_request(
+                "invalid IP address in subjectAltNames",
+            ));
+        }
+
+        subject_alt_names.push(super::SubjectAltName::Ip(address));
+    }
+
+    for uri in names.uri {
+        if url::Url::parse(&uri).is_err() {
+            return Err(edgelet_http::error::bad_request(
+                "invalid URI in subjectAltNames",
+            ));
+        }
+
+        subject_alt_names.push(super::SubjectAltName::Uri(uri));
+    }
+
+    Ok(subject_alt_names)
+}
+
+fn server_cert_extensions(
+    client_auth: bool,
+) -> Result<openssl::stack::Stack<openssl::x509::X509Extension>, openssl::error::ErrorStack> {
     let mut csr_extensions = openssl::stack::Stack::new()?;
 
     let mut ext_key_usage = openssl::x509::extension::ExtendedKeyUsage::new();
     ext_key_usage.server_auth();
+    if client_auth {
+        ext_key_usage.client_auth();
+    }
 
     let ext_key_usage = ext_key_usage.build()?;
     csr_extensions.push(ext_key_usage)?;
@@ -139,14 +239,33 @@ mod tests {
 
     const MODULE_NAME: &str = "testModule";
 
+    fn request() -> super::ServerCertificateRequest {
+        super::ServerCertificateRequest {
+            common_name: MODULE_NAME.to_string(),
+            key_algorithm: edgelet_settings::base::KeyAlgorithm::default(),
+            subject_alt_names: super::SubjectAltNames::default(),
+            extended_key_usage: Vec::new(),
+        }
+    }
+
     async fn post(
         route: super::Route<edgelet_test_utils::runtime::Runtime>,
     ) -> http_common::server::RouteResponse {
-        let body = super::ServerCertificateRequest {
-            common_name: MODULE_NAME.to_string(),
-        };
+        route.post(Some(request())).await
+    }
 
-        route.post(Some(body)).await
+    async fn authorized_route() -> super::Route<edgelet_test_utils::runtime::Runtime> {
+        let route = edgelet_test_utils::test_route_ok!(TEST_PATH);
+        {
+            let pid = nix::unistd::getpid().as_raw();
+            let mut runtime = route.runtime.lock().await;
+            runtime.module_auth = std::collections::BTreeMap::new();
+            runtime
+                .module_auth
+                .insert(MODULE_NAME.to_string(), vec![pid]);
+        }
+
+        route
     }
 
     #[test]
@@ -177,15 +296,7 @@ mod tests {
 
     #[tokio::test]
     async fn verifysans() {
-        let route = edgelet_test_utils::test_route_ok!(TEST_PATH);
-        {
-            let pid = nix::unistd::getpid().as_raw();
-            let mut runtime = route.runtime.lock().await;
-            runtime.module_auth = std::collections::BTreeMap::new();
-            runtime
-                .module_auth
-                .insert(MODULE_NAME.to_string(), vec![pid]);
-        }
+        let route = authorized_route().await;
 
         let response = post(route).await.unwrap();
         let body = response.into_body().collect().await.unwrap().to_bytes();
@@ -202,4 +313,87 @@ mod tests {
             assert_eq!(MODULE_NAME.to_lowercase(), name.to_lowercase());
         }
     }
+
+    #[tokio::test]
+    async fn additional_sans() {
+        let mut route = authorized_route().await;
+        route.policy = vec![edgelet_settings::base::cert::ModuleCertRule {
+            modules: vec![MODULE_NAME.to_string()],
+            dns_names: vec!["*.contoso.local".to_string()],
+            ip_addresses: vec!["10.0.0.0/8".to_string()],
+            uris: vec!["spiffe://contoso/*".to_string()],
+            client_auth: true,
+        }];
+
+        let mut body = request();
+        body.subject_alt_names = super::SubjectAltNames {
+            dns: vec!["api.contoso.local".to_string()],
+            ip: vec!["10.0.0.1".to_string()],
+            uri: vec!["spiffe://contoso/testModule".to_string()],
+        };
+        body.extended_key_usage = vec![super::ExtendedKeyUsage::ClientAuth];
+
+        let response = route.post(Some(body)).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let cert = serde_json::from_slice::<CertificateResponse>(&body)
+            .unwrap()
+            .certificate;
+        let cert = openssl::x509::X509::from_pem(cert.as_bytes()).unwrap();
+
+        let sans = cert.subject_alt_names().unwrap();
+        let dns: Vec<_> = sans.iter().filter_map(|san| san.dnsname()).collect();
+        let ips: Vec<_> = sans.iter().filter_map(|san| san.ipaddress()).collect();
+        let uris: Vec<_> = sans.iter().filter_map(|san| san.uri()).collect();
+
+        assert!(dns.contains(&"api.contoso.local"));
+        assert_eq!(vec![&[10, 0, 0, 1][..]], ips);
+        assert_eq!(vec!["spiffe://contoso/testModule"], uris);
+    }
+
+    #[tokio::test]
+    async fn additional_sans_rejected() {
+        // Names that the policy doesn't allow
+        let route = authorized_route().await;
+        let mut body = request();
+        body.subject_alt_names.dns = vec!["api.contoso.local".to_string()];
+        let response = route.post(Some(body)).await.unwrap_err();
+        assert_eq!(hyper::StatusCode::FORBIDDEN, response.status_code);
+
+        // Client authentication that the policy doesn't allow
+        let route = authorized_route().await;
+        let mut body = request();
+        body.extended_key_usage = vec![super::ExtendedKeyUsage::ClientAuth];
+        let response = route.post(Some(body)).await.unwrap_err();
+        assert_eq!(hyper::StatusCode::FORBIDDEN, response.status_code);
+
+        // Names that aren't valid
+        for (dns, ip, uri) in [
+            ("under_score.contoso.local", "", ""),
+            ("a..contoso.local", "", ""),
+            ("", "10.0.0.256", ""),
+            ("", "", "not a uri"),
+        ] {
+            let route = authorized_route().await;
+            let mut body = request();
+            body.subject_alt_names = super::SubjectAltNames {
+                dns: [dns]
+                    .into_iter()
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect(),
+                ip: [ip]
+                    .into_iter()
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect(),
+                uri: [uri]
+                    .into_iter()
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect(),
+            };
+            let response = route.post(Some(body)).await.unwrap_err();
+            assert_eq!(hyper::StatusCode::BAD_REQUEST, response.status_code);
+        }
+    }
 }
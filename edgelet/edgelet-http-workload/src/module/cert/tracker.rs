@@ -0,0 +1,287 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Tracking of the certificates issued to modules, so that modules can wait to be told when to
+//! request new ones instead of checking their certificates' expiry themselves.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+/// Certificates are due for renewal after this percentage of their lifetime, which matches
+/// the default threshold of Edge CA auto renewal.
+const RENEWAL_THRESHOLD_PERCENT: i32 = 80;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum CertType {
+    Identity,
+    Server,
+}
+
+/// The module that a certificate was issued to, and which of its certificates it is. A module
+/// has at most one tracked certificate of each type; requesting a new one replaces the old one.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct TrackedCertId {
+    pub(crate) module_id: String,
+    pub(crate) cert_type: CertType,
+}
+
+impl TrackedCertId {
+    pub(crate) fn new(module_id: &str, cert_type: CertType) -> Self {
+        TrackedCertId {
+            // Modules like '$edgeHub' are tracked under the same name as their container.
+            module_id: module_id.trim_start_matches('$').to_string(),
+            cert_type,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum RenewalReason {
+    /// The certificate has passed the renewal threshold of its lifetime.
+    Expiring,
+
+    /// The Edge CA that issued the certificate has been renewed.
+    EdgeCaRenewed,
+}
+
+/// A certificate that its module should request again.
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(test, derive(serde::Deserialize))]
+pub(crate) struct DueCert {
+    #[serde(rename = "type")]
+    pub(crate) cert_type: CertType,
+    pub(crate) expiration: DateTime<Utc>,
+    pub(crate) reason: RenewalReason,
+}
+
+#[derive(Debug)]
+struct TrackedCert {
+    expiration: DateTime<Utc>,
+    renew_at: DateTime<Utc>,
+    edge_ca_renewed: bool,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct CertTracker {
+    certs: std::sync::Mutex<BTreeMap<TrackedCertId, TrackedCert>>,
+
+    /// Notified whenever tracked certificates change, so that waiting modules check them again.
+    changed: tokio::sync::Notify,
+}
+
+impl CertTracker {
+    /// Records a certificate issued to a module, replacing the module's previous certificate
+    /// of the same type.
+    pub(crate) fn track(
+        &self,
+        id: TrackedCertId,
+        not_before: DateTime<Utc>,
+        expiration: DateTime<Utc>,
+    ) {
+        let renew_at = not_before + (expiration - not_before) * RENEWAL_THRESHOLD_PERCENT / 100;
+
+        self.certs
+            .lock()
+            .expect("cert tracker lock poisoned")
+            .insert(
+                id,
+                TrackedCert {
+                    expiration,
+                    renew_at,
+                    edge_ca_renewed: false,
+                },
+            );
+        self.changed.notify_waiters();
+    }
+
+    /// Marks every tracked certificate as due, since they were issued by the previous Edge CA.
+    pub(crate) fn edge_ca_renewed(&self) {
+        for cert in self
+            .certs
+            .lock()
+            .expect("cert tracker lock poisoned")
+            .values_mut()
+        {
+            cert.edge_ca_renewed = true;
+        }
+        self.changed.notify_waiters();
+    }
+
+    /// Waits until any of the module's certificates are due, or until `timeout` passes.
+    /// Returns the due certificates, which is empty if the wait timed out.
+    pub(crate) async fn wait(&self, module_id: &str, timeout: std::time::Duration) -> Vec<DueCert> {
+        let module_id = module_id.trim_start_matches('$');
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            // Start listening for changes before checking, so that a change made between the
+            // check and the wait isn't missed.
+            let changed = self.changed.notified();
+            let mut changed = std::pin::pin!(changed);
+            changed.as_mut().enable();
+
+            let (due, next_renewal) = self.due(module_id, Utc::now());
+            if !due.is_empty() {
+                return due;
+            }
+
+            let now = tokio::time::Instant::now();
+            if now >= deadline {
+                return Vec::new();
+            }
+
+            let wake = next_renewal
+                .map(|next_renewal| now + next_renewal)
+                .map_or(deadline, |next_renewal| next_renewal.min(deadline));
+
+            tokio::select! {
+                () = changed => {},
+                () = tokio::time::sleep_until(wake) => {},
+            }
+        }
+    }
+
+    /// Returns the module's certificates that are due, and how long it is until the next one
+    /// will be.
+    fn due(
+        &self,
+        module_id: &str,
+        now: DateTime<Utc>,
+    ) -> (Vec<DueCert>, Option<std::time::Duration>) {
+        let certs = self.certs.lock().expect("cert tracker lock poisoned");
+
+        let mut due = Vec::new();
+        let mut next_renewal: Option<std::time::Duration> = None;
+
+        for (id, cert) in certs.iter().filter(|(id, _)| id.module_id == module_id) {
+            let reason = if cert.edge_ca_renewed {
+                RenewalReason::EdgeCaRenewed
+            } else if cert.renew_at <= now {
+                RenewalReason::Expiring
+            } else {
+                let until_renewal = (cert.renew_at - now).to_std().unwrap_or_default();
+                next_renewal = Some(next_renewal.map_or(until_renewal, |n| n.min(until_renewal)));
+
+                continue;
+            };
+
+            due.push(DueCert {
+                cert_type: id.cert_type,
+                expiration: cert.expiration,
+                reason,
+            });
+        }
+
+        (due, next_renewal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use super::{CertTracker, CertType, DueCert, RenewalReason, TrackedCertId};
+
+    #[test]
+    fn due() {
+        let tracker = CertTracker::default();
+        let now = Utc::now();
+
+        // Server cert is 90% through its lifetime, identity cert is 50% through.
+        tracker.track(
+            TrackedCertId::new("$edgeHub", CertType::Server),
+            now - TimeDelta::days(9),
+            now + TimeDelta::days(1),
+        );
+        tracker.track(
+            TrackedCertId::new("edgeHub", CertType::Identity),
+            now - TimeDelta::days(5),
+            now + TimeDelta::days(5),
+        );
+        tracker.track(
+            TrackedCertId::new("otherModule", CertType::Server),
+            now - TimeDelta::days(9),
+            now + TimeDelta::days(1),
+        );
+
+        let (due, next_renewal) = tracker.due("edgeHub", now);
+        assert_eq!(
+            vec![DueCert {
+                cert_type: CertType::Server,
+                expiration: now + TimeDelta::days(1),
+                reason: RenewalReason::Expiring,
+            }],
+            due
+        );
+        assert_eq!(Some(TimeDelta::days(3).to_std().unwrap()), next_renewal);
+
+        // A new cert replaces the old one.
+        tracker.track(
+            TrackedCertId::new("edgeHub", CertType::Server),
+            now,
+            now + TimeDelta::days(10),
+        );
+        let (due, _) = tracker.due("edgeHub", now);
+        assert!(due.is_empty());
+
+        // All certs are due after the Edge CA is renewed.
+        tracker.edge_ca_renewed();
+        let (due, next_renewal) = tracker.due("edgeHub", now);
+        assert_eq!(2, due.len());
+        assert!(
+            due.iter()
+                .all(|cert| cert.reason == RenewalReason::EdgeCaRenewed)
+        );
+        assert_eq!(None, next_renewal);
+
+        // Modules without certs have nothing due.
+        assert_eq!((Vec::new(), None), tracker.due("newModule", now));
+    }
+
+    #[tokio::test]
+    async fn wait() {
+        let tracker = std::sync::Arc::new(CertTracker::default());
+        let now = Utc::now();
+
+        tracker.track(
+            TrackedCertId::new("testModule", CertType::Server),
+            now,
+            now + TimeDelta::days(10),
+        );
+
+        // Nothing is due before the timeout.
+        let due = tracker
+            .wait("testModule", std::time::Duration::from_millis(10))
+            .await;
+        assert!(due.is_empty());
+
+        // Waiting modules are woken when the Edge CA is renewed.
+        let waiter = {
+            let tracker = tracker.clone();
+            tokio::spawn(async move {
+                tracker
+                    .wait("testModule", std::time::Duration::from_mins(1))
+                    .await
+            })
+        };
+        tokio::task::yield_now().await;
+        tracker.edge_ca_renewed();
+
+        let due = tokio::time::timeout(std::time::Duration::from_secs(10), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(1, due.len());
+        assert_eq!(RenewalReason::EdgeCaRenewed, due[0].reason);
+
+        // Due certs are returned without waiting.
+        let due = tracker
+            .wait("testModule", std::time::Duration::from_mins(1))
+            .await;
+        assert_eq!(1, due.len());
+    }
+}
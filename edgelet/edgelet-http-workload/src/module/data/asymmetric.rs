@@ -0,0 +1,332 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Signatures with the private keys of modules' X.509 identities.
+//!
+//! The private keys stay in keyd, which only signs digests with ECDSA and only applies the raw
+//! RSA private key operation. So ECDSA digests are computed here, and messages are encoded for
+//! RSA-PSS here before keyd signs them.
+
+#[cfg(not(test))]
+use aziot_cert_client_async::Client as CertClient;
+#[cfg(not(test))]
+use aziot_key_client_async::Client as KeyClient;
+
+#[cfg(test)]
+use test_common::client::CertClient;
+#[cfg(test)]
+use test_common::client::KeyClient;
+
+/// RSA-PSS salts are as long as the SHA-256 digest.
+const PSS_SALT_LEN: usize = 32;
+
+const SHA256_LEN: usize = 32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+pub(crate) enum SignAlgorithm {
+    /// HMAC-SHA256 with the module's symmetric identity key.
+    #[default]
+    #[serde(rename = "HMACSHA256")]
+    HmacSha256,
+
+    /// ECDSA with SHA-256 and the module's X.509 identity key. Signatures are DER-encoded.
+    #[serde(rename = "ECDSASHA256")]
+    EcdsaSha256,
+
+    /// ECDSA with SHA-384 and the module's X.509 identity key. Signatures are DER-encoded.
+    #[serde(rename = "ECDSASHA384")]
+    EcdsaSha384,
+
+    /// RSA-PSS with SHA-256, MGF1 with SHA-256, a 32-byte salt, and the module's X.509
+    /// identity key.
+    #[serde(rename = "RSAPSSSHA256")]
+    RsaPssSha256,
+}
+
+impl SignAlgorithm {
+    fn message_digest(self) -> openssl::hash::MessageDigest {
+        match self {
+            SignAlgorithm::HmacSha256
+            | SignAlgorithm::EcdsaSha256
+            | SignAlgorithm::RsaPssSha256 => openssl::hash::MessageDigest::sha256(),
+            SignAlgorithm::EcdsaSha384 => openssl::hash::MessageDigest::sha384(),
+        }
+    }
+
+    /// Checks that the algorithm can be used with a module's X.509 identity key.
+    pub(crate) fn check_key(
+        self,
+        key: &openssl::pkey::PKeyRef<openssl::pkey::Public>,
+    ) -> Result<(), http_common::server::Error> {
+        let usable = match self {
+            SignAlgorithm::HmacSha256 => false,
+            SignAlgorithm::EcdsaSha256 | SignAlgorithm::EcdsaSha384 => {
+                key.id() == openssl::pkey::Id::EC
+            }
+            SignAlgorithm::RsaPssSha256 => key.id() == openssl::pkey::Id::RSA,
+        };
+
+        if usable {
+            Ok(())
+        } else {
+            Err(edgelet_http::error::bad_request(
+                "algo can't be used with the module's identity key",
+            ))
+        }
+    }
+}
+
+/// The public key of a module's X.509 identity, and the identity certificate it is from.
+pub(crate) struct IdentityPublicKey {
+    pub(crate) key: openssl::pkey::PKey<openssl::pkey::Public>,
+    pub(crate) certificate: String,
+}
+
+pub(crate) async fn identity_public_key(
+    client: &tokio::sync::Mutex<CertClient>,
+    module_id: &str,
+    auth: &aziot_identity_common::AuthenticationInfo,
+) -> Result<IdentityPublicKey, http_common::server::Error> {
+    let cert_id = auth.cert_id.as_deref().ok_or_else(|| {
+        edgelet_http::error::bad_request("module does not have an X.509 identity")
+    })?;
+
+    let certificate = {
+        let client = client.lock().await;
+
+        client.get_cert(cert_id).await.map_err(|err| {
+            edgelet_http::error::server_error(format!(
+                "failed to get identity certificate for {module_id}: {err}"
+            ))
+        })
+    }?;
+
+    let key = openssl::x509::X509::stack_from_pem(&certificate)
+        .ok()
+        .and_then(|certs| certs.first()?.public_key().ok())
+        .ok_or_else(|| {
+            edgelet_http::error::server_error(format!(
+                "invalid identity certificate for {module_id}"
+            ))
+        })?;
+
+    let certificate = String::from_utf8(certificate).map_err(edgelet_http::error::server_error)?;
+
+    Ok(IdentityPublicKey { key, certificate })
+}
+
+/// Signs `data` with a module's X.509 identity key in keyd.
+pub(crate) async fn sign(
+    client: &KeyClient,
+    key_handle: &aziot_key_common::KeyHandle,
+    algorithm: SignAlgorithm,
+    public_key: &openssl::pkey::PKeyRef<openssl::pkey::Public>,
+    data: &[u8],
+) -> Result<Vec<u8>, http_common::server::Error> {
+    algorithm.check_key(public_key)?;
+
+    match algorithm {
+        SignAlgorithm::HmacSha256 => unreachable!("HMAC keys aren't X.509 identity keys"),
+        SignAlgorithm::EcdsaSha256 | SignAlgorithm::EcdsaSha384 => {
+            let digest = openssl::hash::hash(algorithm.message_digest(), data)
+                .map_err(edgelet_http::error::server_error)?;
+
+            client
+                .sign(key_handle, aziot_key_common::SignMechanism::Ecdsa, &digest)
+                .await
+                .map_err(edgelet_http::error::server_error)
+        }
+        SignAlgorithm::RsaPssSha256 => {
+            let encoded = pss_encode(data, public_key.bits())?;
+
+            client
+                .encrypt(
+                    key_handle,
+                    aziot_key_common::EncryptMechanism::RsaNoPadding,
+                    &encoded,
+                )
+                .await
+                .map_err(edgelet_http::error::server_error)
+        }
+    }
+}
+
+/// Verifies a signature made by [`sign`]. Malformed signatures are reported as not verified.
+pub(crate) fn verify(
+    algorithm: SignAlgorithm,
+    public_key: &openssl::pkey::PKeyRef<openssl::pkey::Public>,
+    data: &[u8],
+    signature: &[u8],
+) -> Result<bool, http_common::server::Error> {
+    algorithm.check_key(public_key)?;
+
+    let mut verifier = openssl::sign::Verifier::new(algorithm.message_digest(), public_key)
+        .map_err(edgelet_http::error::server_error)?;
+
+    if algorithm == SignAlgorithm::RsaPssSha256 {
+        let set_pss = |verifier: &mut openssl::sign::Verifier<'_>| {
+            verifier.set_rsa_padding(openssl::rsa::Padding::PKCS1_PSS)?;
+            verifier.set_rsa_pss_saltlen(openssl::sign::RsaPssSaltlen::DIGEST_LENGTH)?;
+            verifier.set_rsa_mgf1_md(openssl::hash::MessageDigest::sha256())
+        };
+
+        set_pss(&mut verifier).map_err(edgelet_http::error::server_error)?;
+    }
+
+    Ok(verifier.verify_oneshot(signature, data).unwrap_or(false))
+}
+
+/// Encodes `data` with EMSA-PSS (RFC 8017, section 9.1.1) using SHA-256, MGF1 with SHA-256 and
+/// a 32-byte salt. The encoded message is padded to the length of the modulus, so that it can
+/// be signed by the raw RSA private key operation.
+fn pss_encode(data: &[u8], modulus_bits: u32) -> Result<Vec<u8>, http_common::server::Error> {
+    let modulus_bits = usize::try_from(modulus_bits).expect("RSA modulus sizes fit in usize");
+    let em_bits = modulus_bits - 1;
+    let em_len = em_bits.div_ceil(8);
+
+    if em_len < SHA256_LEN + PSS_SALT_LEN + 2 {
+        return Err(edgelet_http::error::server_error(
+            "RSA identity key is too small for RSA-PSS",
+        ));
+    }
+
+    let mut salt = [0; PSS_SALT_LEN];
+    openssl::rand::rand_bytes(&mut salt).map_err(edgelet_http::error::server_error)?;
+
+    let hash = {
+        let mut hasher = openssl::sha::Sha256::new();
+        hasher.update(&[0; 8]);
+        hasher.update(&openssl::sha::sha256(data));
+        hasher.update(&salt);
+        hasher.finish()
+    };
+
+    let db_len = em_len - SHA256_LEN - 1;
+    let mut db = vec![0; db_len];
+    db[db_len - PSS_SALT_LEN - 1] = 0x01;
+    db[db_len - PSS_SALT_LEN..].copy_from_slice(&salt);
+
+    for (byte, mask) in db.iter_mut().zip(mgf1_sha256(&hash, db_len)) {
+        *byte ^= mask;
+    }
+    db[0] &= 0xff >> (8 * em_len - em_bits);
+
+    let mut encoded = vec![0; modulus_bits.div_ceil(8) - em_len];
+    encoded.extend_from_slice(&db);
+    encoded.extend_from_slice(&hash);
+    encoded.push(0xbc);
+
+    Ok(encoded)
+}
+
+fn mgf1_sha256(seed: &[u8], len: usize) -> Vec<u8> {
+    let mut mask = Vec::with_capacity(len + SHA256_LEN);
+    let mut counter: u32 = 0;
+
+    while mask.len() < len {
+        let mut hasher = openssl::sha::Sha256::new();
+        hasher.update(seed);
+        hasher.update(&counter.to_be_bytes());
+        mask.extend_from_slice(&hasher.finish());
+
+        counter += 1;
+    }
+
+    mask.truncate(len);
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SignAlgorithm, pss_encode, verify};
+
+    fn public_key(
+        private_key: &openssl::pkey::PKeyRef<openssl::pkey::Private>,
+    ) -> openssl::pkey::PKey<openssl::pkey::Public> {
+        let public_key = private_key.public_key_to_pem().unwrap();
+
+        openssl::pkey::PKey::public_key_from_pem(&public_key).unwrap()
+    }
+
+    #[test]
+    fn rsa_pss() {
+        for bits in [2048, 3072] {
+            let rsa = openssl::rsa::Rsa::generate(bits).unwrap();
+
+            // Sign the way keyd does, with the raw private key operation.
+            let modulus_len = usize::try_from(rsa.size()).unwrap();
+
+            let encoded = pss_encode(b"data", bits).unwrap();
+            assert_eq!(modulus_len, encoded.len());
+
+            let mut signature = vec![0; modulus_len];
+            rsa.private_encrypt(&encoded, &mut signature, openssl::rsa::Padding::NONE)
+                .unwrap();
+
+            let key = public_key(&openssl::pkey::PKey::from_rsa(rsa).unwrap());
+            assert!(verify(SignAlgorithm::RsaPssSha256, &key, b"data", &signature).unwrap());
+            assert!(!verify(SignAlgorithm::RsaPssSha256, &key, b"other", &signature).unwrap());
+        }
+
+        // Salts are random.
+        assert_ne!(
+            pss_encode(b"data", 2048).unwrap(),
+            pss_encode(b"data", 2048).unwrap()
+        );
+
+        pss_encode(b"data", 512).unwrap_err();
+    }
+
+    #[test]
+    fn ecdsa() {
+        for (curve, algorithm) in [
+            (
+                openssl::nid::Nid::X9_62_PRIME256V1,
+                SignAlgorithm::EcdsaSha256,
+            ),
+            (openssl::nid::Nid::SECP384R1, SignAlgorithm::EcdsaSha384),
+        ] {
+            let group = openssl::ec::EcGroup::from_curve_name(curve).unwrap();
+            let ec = openssl::ec::EcKey::generate(&group).unwrap();
+
+            // Sign the way keyd does, over the digest.
+            let digest = openssl::hash::hash(algorithm.message_digest(), b"data").unwrap();
+            let signature = openssl::ecdsa::EcdsaSig::sign(&digest, &ec)
+                .unwrap()
+                .to_der()
+                .unwrap();
+
+            let key = public_key(&openssl::pkey::PKey::from_ec_key(ec).unwrap());
+            assert!(verify(algorithm, &key, b"data", &signature).unwrap());
+            assert!(!verify(algorithm, &key, b"other", &signature).unwrap());
+
+            // Malformed signatures aren't errors.
+            assert!(!verify(algorithm, &key, b"data", b"signature").unwrap());
+        }
+    }
+
+    #[test]
+    fn check_key() {
+        let rsa =
+            openssl::pkey::PKey::from_rsa(openssl::rsa::Rsa::generate(2048).unwrap()).unwrap();
+        let rsa = public_key(&rsa);
+
+        let group =
+            openssl::ec::EcGroup::from_curve_name(openssl::nid::Nid::X9_62_PRIME256V1).unwrap();
+        let ec = openssl::pkey::PKey::from_ec_key(openssl::ec::EcKey::generate(&group).unwrap())
+            .unwrap();
+        let ec = public_key(&ec);
+
+        SignAlgorithm::RsaPssSha256.check_key(&rsa).unwrap();
+        SignAlgorithm::EcdsaSha256.check_key(&ec).unwrap();
+        SignAlgorithm::EcdsaSha384.check_key(&ec).unwrap();
+
+        for (algorithm, key) in [
+            (SignAlgorithm::RsaPssSha256, &ec),
+            (SignAlgorithm::EcdsaSha256, &rsa),
+            (SignAlgorithm::HmacSha256, &rsa),
+        ] {
+            let err = algorithm.check_key(key).unwrap_err();
+            assert_eq!(http::StatusCode::BAD_REQUEST, err.status_code);
+        }
+    }
+}
@@ -21,14 +21,26 @@ where
 pub(crate) struct DecryptRequest {
     ciphertext: String,
 
+    /// Only set by callers of the original scheme, whose ciphertext has no envelope.
     #[serde(rename = "initializationVector")]
-    iv: String,
+    iv: Option<String>,
+
+    #[serde(rename = "associatedData")]
+    associated_data: Option<String>,
 }
 
 #[derive(Debug, serde::Serialize)]
 #[cfg_attr(test, derive(serde::Deserialize))]
 pub(crate) struct DecryptResponse {
     plaintext: String,
+
+    #[serde(rename = "keyId", skip_serializing_if = "Option::is_none")]
+    key_id: Option<String>,
+
+    /// The position of the chunk in its payload. Modules must check that chunks are decrypted
+    /// in order and that the last one is final, or else a payload may have been truncated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chunk: Option<super::envelope::Chunk>,
 }
 
 #[async_trait::async_trait]
@@ -80,37 +92,131 @@ where
     async fn post(self, body: Option<Self::PostBody>) -> http_common::server::RouteResponse {
         edgelet_http::auth_caller(&self.module_id, self.pid, &self.runtime).await?;
 
-        let (ciphertext, iv) = match body {
-            Some(body) => {
-                let ciphertext = super::base64_decode(body.ciphertext)?;
-                let iv = super::base64_decode(body.iv)?;
-
-                (ciphertext, iv)
+        let Some(body) = body else {
+            return Err(edgelet_http::error::bad_request("missing request body"));
+        };
+        let ciphertext = super::base64_decode(body.ciphertext)?;
+
+        let res = match body.iv {
+            Some(iv) => {
+                if body.associated_data.is_some() {
+                    return Err(edgelet_http::error::bad_request(
+                        "initializationVector can't be used with ciphertext envelopes",
+                    ));
+                }
+                let iv = super::base64_decode(iv)?;
+
+                self.decrypt(&ciphertext, iv).await?
+            }
+            None => {
+                let associated_data = body
+                    .associated_data
+                    .map(super::base64_decode)
+                    .transpose()?
+                    .unwrap_or_default();
+
+                self.decrypt_envelope(&ciphertext, &associated_data).await?
             }
-            None => return Err(edgelet_http::error::bad_request("missing request body")),
         };
 
+        Ok(http_common::server::response::json(
+            hyper::StatusCode::OK,
+            &res,
+        ))
+    }
+
+    type PutBody = serde::de::IgnoredAny;
+}
+
+impl<M> Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    async fn decrypt(
+        &self,
+        ciphertext: &[u8],
+        iv: Vec<u8>,
+    ) -> Result<DecryptResponse, http_common::server::Error> {
         let aad = format!("{}{}", self.module_id, self.gen_id).into_bytes();
         let parameters = aziot_key_common::EncryptMechanism::Aead { iv, aad };
 
         let client = self.client.lock().await;
         let key = super::master_encryption_key(&client).await?;
 
-        match client.decrypt(&key, parameters, &ciphertext).await {
-            Ok(plaintext) => {
-                let engine = base64::engine::general_purpose::STANDARD;
-                let plaintext = base64::Engine::encode(&engine, plaintext);
+        let plaintext = client
+            .decrypt(&key, parameters, ciphertext)
+            .await
+            .map_err(edgelet_http::error::server_error)?;
 
-                let res = DecryptResponse { plaintext };
-                let res = http_common::server::response::json(hyper::StatusCode::OK, &res);
+        let engine = base64::engine::general_purpose::STANDARD;
 
-                Ok(res)
-            }
-            Err(err) => Err(edgelet_http::error::server_error(err)),
-        }
+        Ok(DecryptResponse {
+            plaintext: base64::Engine::encode(&engine, plaintext),
+            key_id: None,
+            chunk: None,
+        })
     }
 
-    type PutBody = serde::de::IgnoredAny;
+    async fn decrypt_envelope(
+        &self,
+        ciphertext: &[u8],
+        associated_data: &[u8],
+    ) -> Result<DecryptResponse, http_common::server::Error> {
+        use super::envelope::{Envelope, Mode};
+
+        let envelope = Envelope::parse(ciphertext)?;
+        let key_id = &envelope.header.key_id;
+
+        let client = self.client.lock().await;
+        let key = super::master_encryption_key(&client).await?;
+
+        let (plaintext, chunk) = match &envelope.header.data_key {
+            None => {
+                let aad = super::envelope::keyd_aad(
+                    &self.module_id,
+                    &self.gen_id,
+                    key_id,
+                    Mode::Direct,
+                    associated_data,
+                );
+                let parameters = aziot_key_common::EncryptMechanism::Aead {
+                    iv: envelope.iv.clone(),
+                    aad,
+                };
+
+                // keyd fails to decrypt ciphertext, a header or associated data that has been
+                // modified, so this is the caller's error.
+                let plaintext = client
+                    .decrypt(&key, parameters, &envelope.ciphertext)
+                    .await
+                    .map_err(|_| {
+                        edgelet_http::error::bad_request("ciphertext could not be authenticated")
+                    })?;
+
+                (plaintext, None)
+            }
+            Some((wrapped_key, chunk)) => {
+                let aad = super::envelope::keyd_aad(
+                    &self.module_id,
+                    &self.gen_id,
+                    key_id,
+                    Mode::Envelope,
+                    &[],
+                );
+                let data_key = super::unwrap_data_key(&client, &key, aad, wrapped_key).await?;
+
+                (envelope.open(&data_key, associated_data)?, Some(*chunk))
+            }
+        };
+
+        let engine = base64::engine::general_purpose::STANDARD;
+
+        Ok(DecryptResponse {
+            plaintext: base64::Engine::encode(&engine, plaintext),
+            key_id: Some(envelope.header.key_id),
+            chunk,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -121,6 +227,8 @@ mod tests {
 
     use edgelet_test_utils::{test_route_err, test_route_ok};
 
+    use crate::module::data::envelope::{Envelope, Header, IV_LEN};
+
     const TEST_PATH: &str = "/modules/testModule/genid/1/decrypt";
 
     #[test]
@@ -152,7 +260,8 @@ mod tests {
             let engine = base64::engine::general_purpose::STANDARD;
             let body = super::DecryptRequest {
                 ciphertext: base64::Engine::encode(&engine, "ciphertext"),
-                iv: base64::Engine::encode(&engine, "iv"),
+                iv: Some(base64::Engine::encode(&engine, "iv")),
+                associated_data: None,
             };
 
             route.post(Some(body)).await
@@ -173,7 +282,8 @@ mod tests {
         // ciphertext must be base64-encoded
         let body = super::DecryptRequest {
             ciphertext: "~".to_string(),
-            iv: base64::Engine::encode(&engine, "~"),
+            iv: Some(base64::Engine::encode(&engine, "~")),
+            associated_data: None,
         };
 
         let route = test_route_ok!(TEST_PATH);
@@ -183,7 +293,8 @@ mod tests {
         // iv must be base64-encoded
         let body = super::DecryptRequest {
             ciphertext: base64::Engine::encode(&engine, "~"),
-            iv: "~".to_string(),
+            iv: Some("~".to_string()),
+            associated_data: None,
         };
 
         let route = test_route_ok!(TEST_PATH);
@@ -193,7 +304,8 @@ mod tests {
         // Response plaintext is base64-encoded
         let body = super::DecryptRequest {
             ciphertext: base64::Engine::encode(&engine, "~"),
-            iv: base64::Engine::encode(&engine, "~"),
+            iv: Some(base64::Engine::encode(&engine, "~")),
+            associated_data: None,
         };
 
         let route = test_route_ok!(TEST_PATH);
@@ -202,4 +314,56 @@ mod tests {
         let response: super::DecryptResponse = serde_json::from_slice(&body).unwrap();
         base64::Engine::decode(&engine, response.plaintext).unwrap();
     }
+
+    #[tokio::test]
+    async fn envelope() {
+        let engine = base64::engine::general_purpose::STANDARD;
+
+        let envelope = Envelope {
+            header: Header {
+                key_id: "state".to_string(),
+                data_key: None,
+            },
+            iv: vec![0; IV_LEN],
+            tag: Vec::new(),
+            ciphertext: b"ciphertext".to_vec(),
+        };
+        let body = super::DecryptRequest {
+            ciphertext: base64::Engine::encode(&engine, envelope.to_bytes()),
+            iv: None,
+            associated_data: Some(base64::Engine::encode(&engine, "aad")),
+        };
+
+        // The key ID is taken from the envelope.
+        let route = test_route_ok!(TEST_PATH);
+        let response = route.post(Some(body)).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let response: super::DecryptResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(Some("state"), response.key_id.as_deref());
+        assert_eq!(None, response.chunk);
+
+        // Ciphertext without an initialization vector must be an envelope.
+        for ciphertext in [&b""[..], b"ciphertext", &[2, 3, 0]] {
+            let body = super::DecryptRequest {
+                ciphertext: base64::Engine::encode(&engine, ciphertext),
+                iv: None,
+                associated_data: None,
+            };
+
+            let route = test_route_ok!(TEST_PATH);
+            let response = route.post(Some(body)).await.unwrap_err();
+            assert_eq!(hyper::StatusCode::BAD_REQUEST, response.status_code);
+        }
+
+        // Associated data can't be used with the original scheme.
+        let body = super::DecryptRequest {
+            ciphertext: base64::Engine::encode(&engine, "ciphertext"),
+            iv: Some(base64::Engine::encode(&engine, "iv")),
+            associated_data: Some(base64::Engine::encode(&engine, "aad")),
+        };
+
+        let route = test_route_ok!(TEST_PATH);
+        let response = route.post(Some(body)).await.unwrap_err();
+        assert_eq!(hyper::StatusCode::BAD_REQUEST, response.status_code);
+    }
 }
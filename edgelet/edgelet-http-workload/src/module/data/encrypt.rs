@@ -21,14 +21,48 @@ where
 pub(crate) struct EncryptRequest {
     plaintext: String,
 
+    /// Only set by callers of the original scheme, whose ciphertext has no envelope.
     #[serde(rename = "initializationVector")]
-    iv: String,
+    iv: Option<String>,
+
+    #[serde(flatten)]
+    envelope: EnvelopeRequest,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub(crate) struct EnvelopeRequest {
+    mode: Option<super::envelope::Mode>,
+
+    #[serde(rename = "keyId")]
+    key_id: Option<String>,
+
+    #[serde(rename = "associatedData")]
+    associated_data: Option<String>,
+
+    /// A wrapped data key returned by a previous request, to encrypt more chunks with it.
+    #[serde(rename = "dataKey")]
+    data_key: Option<String>,
+
+    chunk: Option<super::envelope::Chunk>,
+}
+
+impl EnvelopeRequest {
+    fn is_empty(&self) -> bool {
+        self.mode.is_none()
+            && self.key_id.is_none()
+            && self.associated_data.is_none()
+            && self.data_key.is_none()
+            && self.chunk.is_none()
+    }
 }
 
 #[derive(Debug, serde::Serialize)]
 #[cfg_attr(test, derive(serde::Deserialize))]
 pub(crate) struct EncryptResponse {
     ciphertext: String,
+
+    #[serde(rename = "dataKey", skip_serializing_if = "Option::is_none")]
+    data_key: Option<String>,
 }
 
 #[async_trait::async_trait]
@@ -80,37 +114,167 @@ where
     async fn post(self, body: Option<Self::PostBody>) -> http_common::server::RouteResponse {
         edgelet_http::auth_caller(&self.module_id, self.pid, &self.runtime).await?;
 
-        let (plaintext, iv) = match body {
-            Some(body) => {
-                let plaintext = super::base64_decode(body.plaintext)?;
-                let iv = super::base64_decode(body.iv)?;
-
-                (plaintext, iv)
+        let Some(body) = body else {
+            return Err(edgelet_http::error::bad_request("missing request body"));
+        };
+        let plaintext = super::base64_decode(body.plaintext)?;
+
+        let res = match body.iv {
+            Some(iv) => {
+                if !body.envelope.is_empty() {
+                    return Err(edgelet_http::error::bad_request(
+                        "initializationVector can't be used with ciphertext envelopes",
+                    ));
+                }
+                let iv = super::base64_decode(iv)?;
+
+                self.encrypt(&plaintext, iv).await?
             }
-            None => return Err(edgelet_http::error::bad_request("missing request body")),
+            None => self.encrypt_envelope(&plaintext, body.envelope).await?,
         };
 
+        Ok(http_common::server::response::json(
+            hyper::StatusCode::OK,
+            &res,
+        ))
+    }
+
+    type PutBody = serde::de::IgnoredAny;
+}
+
+impl<M> Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    async fn encrypt(
+        &self,
+        plaintext: &[u8],
+        iv: Vec<u8>,
+    ) -> Result<EncryptResponse, http_common::server::Error> {
         let aad = format!("{}{}", self.module_id, self.gen_id).into_bytes();
         let parameters = aziot_key_common::EncryptMechanism::Aead { iv, aad };
 
         let client = self.client.lock().await;
         let key = super::master_encryption_key(&client).await?;
 
-        match client.encrypt(&key, parameters, &plaintext).await {
-            Ok(ciphertext) => {
-                let engine = base64::engine::general_purpose::STANDARD;
-                let ciphertext = base64::Engine::encode(&engine, ciphertext);
+        let ciphertext = client
+            .encrypt(&key, parameters, plaintext)
+            .await
+            .map_err(edgelet_http::error::server_error)?;
 
-                let res = EncryptResponse { ciphertext };
-                let res = http_common::server::response::json(hyper::StatusCode::OK, &res);
+        let engine = base64::engine::general_purpose::STANDARD;
 
-                Ok(res)
-            }
-            Err(err) => Err(edgelet_http::error::server_error(err)),
-        }
+        Ok(EncryptResponse {
+            ciphertext: base64::Engine::encode(&engine, ciphertext),
+            data_key: None,
+        })
     }
 
-    type PutBody = serde::de::IgnoredAny;
+    async fn encrypt_envelope(
+        &self,
+        plaintext: &[u8],
+        request: EnvelopeRequest,
+    ) -> Result<EncryptResponse, http_common::server::Error> {
+        use super::envelope::{Envelope, Header, Mode};
+
+        let key_id = request
+            .key_id
+            .unwrap_or_else(|| super::envelope::DEFAULT_KEY_ID.to_string());
+        super::envelope::validate_key_id(&key_id)?;
+
+        let associated_data = request
+            .associated_data
+            .map(super::base64_decode)
+            .transpose()?
+            .unwrap_or_default();
+        let wrapped_key = request.data_key.map(super::base64_decode).transpose()?;
+
+        let continues_stream = wrapped_key.is_some() || request.chunk.is_some();
+        let mode = request.mode.unwrap_or(if continues_stream {
+            Mode::Envelope
+        } else {
+            Mode::Direct
+        });
+
+        let client = self.client.lock().await;
+        let key = super::master_encryption_key(&client).await?;
+
+        let envelope = match mode {
+            Mode::Direct => {
+                if continues_stream {
+                    return Err(edgelet_http::error::bad_request(
+                        "dataKey and chunk can only be used in envelope mode",
+                    ));
+                }
+
+                let iv = super::envelope::random_bytes(super::envelope::IV_LEN)?;
+                let aad = super::envelope::keyd_aad(
+                    &self.module_id,
+                    &self.gen_id,
+                    &key_id,
+                    Mode::Direct,
+                    &associated_data,
+                );
+                let parameters = aziot_key_common::EncryptMechanism::Aead {
+                    iv: iv.clone(),
+                    aad,
+                };
+
+                let ciphertext = client
+                    .encrypt(&key, parameters, plaintext)
+                    .await
+                    .map_err(edgelet_http::error::server_error)?;
+
+                Envelope {
+                    header: Header {
+                        key_id,
+                        data_key: None,
+                    },
+                    iv,
+                    tag: Vec::new(),
+                    ciphertext,
+                }
+            }
+            Mode::Envelope => {
+                let aad = super::envelope::keyd_aad(
+                    &self.module_id,
+                    &self.gen_id,
+                    &key_id,
+                    Mode::Envelope,
+                    &[],
+                );
+
+                let (data_key, wrapped_key) = match wrapped_key {
+                    Some(wrapped_key) => {
+                        let data_key =
+                            super::unwrap_data_key(&client, &key, aad, &wrapped_key).await?;
+
+                        (data_key, wrapped_key)
+                    }
+                    None => super::new_data_key(&client, &key, aad).await?,
+                };
+
+                let header = Header {
+                    key_id,
+                    data_key: Some((wrapped_key, request.chunk.unwrap_or_default())),
+                };
+
+                Envelope::seal(&data_key, header, &associated_data, plaintext)?
+            }
+        };
+
+        let engine = base64::engine::general_purpose::STANDARD;
+        let data_key = envelope
+            .header
+            .data_key
+            .as_ref()
+            .map(|(wrapped_key, _)| base64::Engine::encode(&engine, wrapped_key));
+
+        Ok(EncryptResponse {
+            ciphertext: base64::Engine::encode(&engine, envelope.to_bytes()),
+            data_key,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -121,6 +285,8 @@ mod tests {
 
     use edgelet_test_utils::{test_route_err, test_route_ok};
 
+    use crate::module::data::envelope::{Chunk, DEFAULT_KEY_ID, Envelope, Mode};
+
     const TEST_PATH: &str = "/modules/testModule/genid/1/encrypt";
 
     #[test]
@@ -152,7 +318,8 @@ mod tests {
             let engine = base64::engine::general_purpose::STANDARD;
             let body = super::EncryptRequest {
                 plaintext: base64::Engine::encode(&engine, "plaintext"),
-                iv: base64::Engine::encode(&engine, "iv"),
+                iv: Some(base64::Engine::encode(&engine, "iv")),
+                envelope: super::EnvelopeRequest::default(),
             };
 
             route.post(Some(body)).await
@@ -173,7 +340,8 @@ mod tests {
         // plaintext must be base64-encoded
         let body = super::EncryptRequest {
             plaintext: "~".to_string(),
-            iv: base64::Engine::encode(&engine, "~"),
+            iv: Some(base64::Engine::encode(&engine, "~")),
+            envelope: super::EnvelopeRequest::default(),
         };
 
         let route = test_route_ok!(TEST_PATH);
@@ -183,7 +351,8 @@ mod tests {
         // iv must be base64-encoded
         let body = super::EncryptRequest {
             plaintext: base64::Engine::encode(&engine, "~"),
-            iv: "~".to_string(),
+            iv: Some("~".to_string()),
+            envelope: super::EnvelopeRequest::default(),
         };
 
         let route = test_route_ok!(TEST_PATH);
@@ -193,7 +362,8 @@ mod tests {
         // Response ciphertext is base64-encoded
         let body = super::EncryptRequest {
             plaintext: base64::Engine::encode(&engine, "~"),
-            iv: base64::Engine::encode(&engine, "~"),
+            iv: Some(base64::Engine::encode(&engine, "~")),
+            envelope: super::EnvelopeRequest::default(),
         };
 
         let route = test_route_ok!(TEST_PATH);
@@ -202,4 +372,117 @@ mod tests {
         let response: super::EncryptResponse = serde_json::from_slice(&body).unwrap();
         base64::Engine::decode(&engine, response.ciphertext).unwrap();
     }
+
+    async fn encrypt(
+        body: super::EncryptRequest,
+    ) -> Result<(super::EncryptResponse, Envelope), http_common::server::Error> {
+        let route = test_route_ok!(TEST_PATH);
+        let response = route.post(Some(body)).await?;
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let response: super::EncryptResponse = serde_json::from_slice(&body).unwrap();
+
+        let engine = base64::engine::general_purpose::STANDARD;
+        let ciphertext = base64::Engine::decode(&engine, &response.ciphertext).unwrap();
+        let envelope = Envelope::parse(&ciphertext).unwrap();
+
+        Ok((response, envelope))
+    }
+
+    #[tokio::test]
+    async fn envelope() {
+        let engine = base64::engine::general_purpose::STANDARD;
+        let plaintext = base64::Engine::encode(&engine, "plaintext");
+
+        // Ciphertext is in a direct mode envelope unless an initialization vector is given.
+        let body = super::EncryptRequest {
+            plaintext: plaintext.clone(),
+            iv: None,
+            envelope: super::EnvelopeRequest::default(),
+        };
+        let (response, envelope) = encrypt(body).await.unwrap();
+        assert_eq!(Mode::Direct, envelope.header.mode());
+        assert_eq!(DEFAULT_KEY_ID, envelope.header.key_id);
+        assert_eq!(None, response.data_key);
+
+        // Envelope mode returns the wrapped data key to encrypt more chunks with.
+        let body = super::EncryptRequest {
+            plaintext,
+            iv: None,
+            envelope: super::EnvelopeRequest {
+                mode: Some(Mode::Envelope),
+                key_id: Some("state".to_string()),
+                associated_data: Some(base64::Engine::encode(&engine, "aad")),
+                ..Default::default()
+            },
+        };
+        let (response, envelope) = encrypt(body).await.unwrap();
+        assert_eq!("state", envelope.header.key_id);
+
+        let (wrapped_key, chunk) = envelope.header.data_key.unwrap();
+        assert_eq!(Chunk::default(), chunk);
+        assert_eq!(
+            Some(base64::Engine::encode(&engine, wrapped_key)),
+            response.data_key
+        );
+    }
+
+    #[tokio::test]
+    async fn envelope_invalid() {
+        let engine = base64::engine::general_purpose::STANDARD;
+        let plaintext = base64::Engine::encode(&engine, "plaintext");
+
+        for (iv, envelope) in [
+            // Key IDs are validated.
+            (
+                None,
+                super::EnvelopeRequest {
+                    key_id: Some("a/b".to_string()),
+                    ..Default::default()
+                },
+            ),
+            // Associated data must be base64-encoded.
+            (
+                None,
+                super::EnvelopeRequest {
+                    associated_data: Some("~".to_string()),
+                    ..Default::default()
+                },
+            ),
+            // Chunks are only used in envelope mode.
+            (
+                None,
+                super::EnvelopeRequest {
+                    mode: Some(Mode::Direct),
+                    chunk: Some(Chunk::default()),
+                    ..Default::default()
+                },
+            ),
+            // Data keys must be valid.
+            (
+                None,
+                super::EnvelopeRequest {
+                    data_key: Some(base64::Engine::encode(&engine, "short")),
+                    ..Default::default()
+                },
+            ),
+            // Envelope options can't be used with the original scheme.
+            (
+                Some(base64::Engine::encode(&engine, "iv")),
+                super::EnvelopeRequest {
+                    key_id: Some("state".to_string()),
+                    ..Default::default()
+                },
+            ),
+        ] {
+            let body = super::EncryptRequest {
+                plaintext: plaintext.clone(),
+                iv,
+                envelope,
+            };
+
+            let response = encrypt(body).await.unwrap_err();
+            assert_eq!(hyper::StatusCode::BAD_REQUEST, response.status_code);
+        }
+    }
 }
@@ -0,0 +1,419 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Versioned ciphertext envelopes.
+//!
+//! An envelope carries everything besides the key that is needed to decrypt it, so modules only
+//! need to store the envelope. Its binary layout is:
+//!
+//! | Field | Size |
+//! |-------|------|
+//! | version (2) | 1 |
+//! | mode (1: direct, 2: envelope) | 1 |
+//! | key ID length | 1 |
+//! | key ID | variable |
+//! | *envelope mode only:* wrapped data key length | 2, big-endian |
+//! | *envelope mode only:* wrapped data key | variable |
+//! | *envelope mode only:* chunk index | 4, big-endian |
+//! | *envelope mode only:* final chunk (0 or 1) | 1 |
+//! | IV | 12 |
+//! | *envelope mode only:* tag | 16 |
+//! | ciphertext | remainder |
+//!
+//! Everything before the IV is the header, which is authenticated along with the caller's
+//! associated data.
+
+pub(crate) const VERSION: u8 = 2;
+
+pub(crate) const DEFAULT_KEY_ID: &str = "default";
+
+/// Data keys are AES-256-GCM keys.
+pub(crate) const DATA_KEY_LEN: usize = 32;
+
+pub(crate) const IV_LEN: usize = 12;
+
+const TAG_LEN: usize = 16;
+
+const MAX_KEY_ID_LEN: usize = 64;
+
+const MODE_DIRECT: u8 = 1;
+const MODE_ENVELOPE: u8 = 2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum Mode {
+    /// The plaintext is encrypted by keyd with the master encryption key.
+    #[default]
+    Direct,
+
+    /// The plaintext is encrypted with a data key, which is in turn encrypted by keyd with the
+    /// master encryption key. Large payloads can be encrypted in chunks with the same data key.
+    Envelope,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub(crate) struct Chunk {
+    pub(crate) index: u32,
+
+    #[serde(rename = "final")]
+    pub(crate) last: bool,
+}
+
+impl Default for Chunk {
+    /// A payload that isn't split is a single, final chunk.
+    fn default() -> Self {
+        Chunk {
+            index: 0,
+            last: true,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Header {
+    pub(crate) key_id: String,
+
+    /// The wrapped data key and chunk position, for envelope mode.
+    pub(crate) data_key: Option<(Vec<u8>, Chunk)>,
+}
+
+impl Header {
+    pub(crate) fn mode(&self) -> Mode {
+        if self.data_key.is_some() {
+            Mode::Envelope
+        } else {
+            Mode::Direct
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = key_prefix(&self.key_id, self.mode());
+
+        if let Some((wrapped_key, chunk)) = &self.data_key {
+            let wrapped_key_len =
+                u16::try_from(wrapped_key.len()).expect("wrapped data keys are short");
+
+            bytes.extend_from_slice(&wrapped_key_len.to_be_bytes());
+            bytes.extend_from_slice(wrapped_key);
+            bytes.extend_from_slice(&chunk.index.to_be_bytes());
+            bytes.push(u8::from(chunk.last));
+        }
+
+        bytes
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Envelope {
+    pub(crate) header: Header,
+    pub(crate) iv: Vec<u8>,
+
+    /// The tag of a chunk encrypted with a data key. Ciphertext encrypted by keyd includes
+    /// its own tag, so this is empty in direct mode.
+    pub(crate) tag: Vec<u8>,
+
+    pub(crate) ciphertext: Vec<u8>,
+}
+
+impl Envelope {
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&self.iv);
+        bytes.extend_from_slice(&self.tag);
+        bytes.extend_from_slice(&self.ciphertext);
+
+        bytes
+    }
+
+    pub(crate) fn parse(bytes: &[u8]) -> Result<Self, http_common::server::Error> {
+        const INVALID: http_common::server::Error =
+            edgelet_http::error::bad_request("invalid ciphertext");
+
+        let mut reader = Reader(bytes);
+
+        if reader.take(1).ok_or(INVALID)? != [VERSION] {
+            return Err(edgelet_http::error::bad_request(
+                "unsupported ciphertext version",
+            ));
+        }
+
+        let mode = reader.take(1).ok_or(INVALID)?[0];
+
+        let key_id_len = reader.take(1).ok_or(INVALID)?[0];
+        let key_id = reader.take(key_id_len.into()).ok_or(INVALID)?;
+        let key_id = std::str::from_utf8(key_id)
+            .map_err(|_| INVALID)?
+            .to_string();
+
+        let data_key = match mode {
+            MODE_DIRECT => None,
+            MODE_ENVELOPE => {
+                let wrapped_key_len = reader.take(2).ok_or(INVALID)?;
+                let wrapped_key_len = u16::from_be_bytes([wrapped_key_len[0], wrapped_key_len[1]]);
+                let wrapped_key = reader.take(wrapped_key_len.into()).ok_or(INVALID)?.to_vec();
+
+                let index = reader.take(4).ok_or(INVALID)?;
+                let index = u32::from_be_bytes([index[0], index[1], index[2], index[3]]);
+
+                let last = match reader.take(1).ok_or(INVALID)? {
+                    [0] => false,
+                    [1] => true,
+                    _ => return Err(INVALID),
+                };
+
+                Some((wrapped_key, Chunk { index, last }))
+            }
+            _ => return Err(INVALID),
+        };
+
+        let iv = reader.take(IV_LEN).ok_or(INVALID)?.to_vec();
+
+        let tag = if data_key.is_some() {
+            reader.take(TAG_LEN).ok_or(INVALID)?.to_vec()
+        } else {
+            Vec::new()
+        };
+
+        Ok(Envelope {
+            header: Header { key_id, data_key },
+            iv,
+            tag,
+            ciphertext: reader.0.to_vec(),
+        })
+    }
+
+    /// Encrypts a chunk with a data key.
+    pub(crate) fn seal(
+        data_key: &[u8],
+        header: Header,
+        associated_data: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Self, http_common::server::Error> {
+        let iv = random_bytes(IV_LEN)?;
+        let mut tag = vec![0; TAG_LEN];
+
+        let aad = [header.to_bytes().as_slice(), associated_data].concat();
+        let ciphertext = openssl::symm::encrypt_aead(
+            openssl::symm::Cipher::aes_256_gcm(),
+            data_key,
+            Some(&iv),
+            &aad,
+            plaintext,
+            &mut tag,
+        )
+        .map_err(edgelet_http::error::server_error)?;
+
+        Ok(Envelope {
+            header,
+            iv,
+            tag,
+            ciphertext,
+        })
+    }
+
+    /// Decrypts a chunk with its data key, checking that neither it nor the associated data
+    /// have been modified.
+    pub(crate) fn open(
+        &self,
+        data_key: &[u8],
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>, http_common::server::Error> {
+        let aad = [self.header.to_bytes().as_slice(), associated_data].concat();
+
+        openssl::symm::decrypt_aead(
+            openssl::symm::Cipher::aes_256_gcm(),
+            data_key,
+            Some(&self.iv),
+            &aad,
+            &self.ciphertext,
+            &self.tag,
+        )
+        .map_err(|_| edgelet_http::error::bad_request("ciphertext could not be authenticated"))
+    }
+}
+
+/// Associated data for data that keyd encrypts with the master encryption key. This binds the
+/// data to the module, its generation ID, and the key ID, so that it can't be decrypted by
+/// another module or under another key ID.
+pub(crate) fn keyd_aad(
+    module_id: &str,
+    gen_id: &str,
+    key_id: &str,
+    mode: Mode,
+    associated_data: &[u8],
+) -> Vec<u8> {
+    let mut aad = Vec::new();
+
+    for field in [module_id.as_bytes(), gen_id.as_bytes()] {
+        let field_len =
+            u32::try_from(field.len()).expect("module IDs and generation IDs are short");
+
+        aad.extend_from_slice(&field_len.to_be_bytes());
+        aad.extend_from_slice(field);
+    }
+
+    aad.extend_from_slice(&key_prefix(key_id, mode));
+    aad.extend_from_slice(associated_data);
+
+    aad
+}
+
+/// Key IDs label a module's ciphertext. Every key ID is encrypted with the same
+/// `iotedge_master_encryption_id` key; the key ID is only part of the associated data, so
+/// ciphertext under one key ID can't be decrypted under another. Key IDs are not separate keys
+/// and can't be rotated. They are limited to characters that are safe to log.
+pub(crate) fn validate_key_id(key_id: &str) -> Result<(), http_common::server::Error> {
+    let valid = !key_id.is_empty()
+        && key_id.len() <= MAX_KEY_ID_LEN
+        && key_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(edgelet_http::error::bad_request("invalid parameter: keyId"))
+    }
+}
+
+pub(crate) fn random_bytes(len: usize) -> Result<Vec<u8>, http_common::server::Error> {
+    let mut bytes = vec![0; len];
+    openssl::rand::rand_bytes(&mut bytes).map_err(edgelet_http::error::server_error)?;
+
+    Ok(bytes)
+}
+
+fn key_prefix(key_id: &str, mode: Mode) -> Vec<u8> {
+    let key_id_len = u8::try_from(key_id.len()).expect("key IDs are validated");
+    let mode = match mode {
+        Mode::Direct => MODE_DIRECT,
+        Mode::Envelope => MODE_ENVELOPE,
+    };
+
+    [&[VERSION, mode, key_id_len], key_id.as_bytes()].concat()
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+
+        Some(taken)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chunk, DATA_KEY_LEN, Envelope, Header, Mode, keyd_aad, validate_key_id};
+
+    fn chunk_header(index: u32, last: bool) -> Header {
+        Header {
+            key_id: "state".to_string(),
+            data_key: Some((vec![1; 40], Chunk { index, last })),
+        }
+    }
+
+    #[test]
+    fn key_ids() {
+        for key_id in ["default", "state-1.2_a", &"a".repeat(64)] {
+            validate_key_id(key_id).unwrap();
+        }
+
+        for key_id in ["", "a/b", "a b", "é", &"a".repeat(65)] {
+            let err = validate_key_id(key_id).unwrap_err();
+            assert_eq!(http::StatusCode::BAD_REQUEST, err.status_code);
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let direct = Envelope {
+            header: Header {
+                key_id: "default".to_string(),
+                data_key: None,
+            },
+            iv: vec![2; super::IV_LEN],
+            tag: Vec::new(),
+            ciphertext: b"ciphertext".to_vec(),
+        };
+        assert_eq!(direct, Envelope::parse(&direct.to_bytes()).unwrap());
+        assert_eq!(Mode::Direct, direct.header.mode());
+
+        let data_key = vec![3; DATA_KEY_LEN];
+        let chunk = Envelope::seal(&data_key, chunk_header(7, true), b"aad", b"plaintext").unwrap();
+        let parsed = Envelope::parse(&chunk.to_bytes()).unwrap();
+        assert_eq!(chunk, parsed);
+        assert_eq!(Mode::Envelope, parsed.header.mode());
+        assert_eq!(
+            b"plaintext".to_vec(),
+            parsed.open(&data_key, b"aad").unwrap()
+        );
+    }
+
+    #[test]
+    fn tampering() {
+        let data_key = vec![3; DATA_KEY_LEN];
+        let chunk =
+            Envelope::seal(&data_key, chunk_header(0, false), b"aad", b"plaintext").unwrap();
+
+        // Different associated data
+        let err = chunk.open(&data_key, b"other").unwrap_err();
+        assert_eq!(http::StatusCode::BAD_REQUEST, err.status_code);
+
+        // Different data key
+        chunk.open(&[4; DATA_KEY_LEN], b"aad").unwrap_err();
+
+        // Chunks can't be reordered or marked as final.
+        for header in [chunk_header(1, false), chunk_header(0, true)] {
+            let mut moved = Envelope::parse(&chunk.to_bytes()).unwrap();
+            moved.header = header;
+            moved.open(&data_key, b"aad").unwrap_err();
+        }
+        chunk.open(&data_key, b"aad").unwrap();
+
+        // Modified ciphertext
+        let mut bytes = chunk.to_bytes();
+        *bytes.last_mut().unwrap() ^= 1;
+        Envelope::parse(&bytes)
+            .unwrap()
+            .open(&data_key, b"aad")
+            .unwrap_err();
+    }
+
+    #[test]
+    fn invalid() {
+        let chunk = Envelope::seal(&[3; DATA_KEY_LEN], chunk_header(0, true), b"", b"").unwrap();
+        let bytes = chunk.to_bytes();
+
+        // Truncated envelopes
+        for len in 0..bytes.len() {
+            let err = Envelope::parse(&bytes[..len]).unwrap_err();
+            assert_eq!(http::StatusCode::BAD_REQUEST, err.status_code);
+        }
+
+        // Unknown version and mode
+        let mut unknown = bytes.clone();
+        unknown[0] = 1;
+        Envelope::parse(&unknown).unwrap_err();
+
+        let mut unknown = bytes;
+        unknown[1] = 3;
+        Envelope::parse(&unknown).unwrap_err();
+    }
+
+    #[test]
+    fn keyd_aad_binding() {
+        let aad = keyd_aad("module", "1", "default", Mode::Direct, b"");
+
+        // Field boundaries are unambiguous.
+        assert_ne!(aad, keyd_aad("module1", "", "default", Mode::Direct, b""));
+        assert_ne!(aad, keyd_aad("module", "1", "other", Mode::Direct, b""));
+        assert_ne!(aad, keyd_aad("module", "1", "default", Mode::Envelope, b""));
+        assert_ne!(aad, keyd_aad("module", "1", "default", Mode::Direct, b"a"));
+    }
+}
@@ -1,8 +1,12 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+pub(crate) mod asymmetric;
 pub(crate) mod decrypt;
 pub(crate) mod encrypt;
+pub(crate) mod envelope;
+pub(crate) mod public_key;
 pub(crate) mod sign;
+pub(crate) mod verify;
 
 #[cfg(not(test))]
 use aziot_key_client_async::Client as KeyClient;
@@ -35,3 +39,55 @@ async fn master_encryption_key(
             ))
         })
 }
+
+/// Generates a data key and wraps it with the master encryption key. Returns the data key and
+/// the wrapped key, which is the IV followed by the encrypted data key.
+async fn new_data_key(
+    client: &KeyClient,
+    key: &aziot_key_common::KeyHandle,
+    aad: Vec<u8>,
+) -> Result<(Vec<u8>, Vec<u8>), http_common::server::Error> {
+    let data_key = envelope::random_bytes(envelope::DATA_KEY_LEN)?;
+    let iv = envelope::random_bytes(envelope::IV_LEN)?;
+
+    let parameters = aziot_key_common::EncryptMechanism::Aead {
+        iv: iv.clone(),
+        aad,
+    };
+    let encrypted_key = client
+        .encrypt(key, parameters, &data_key)
+        .await
+        .map_err(edgelet_http::error::server_error)?;
+
+    Ok((data_key, [iv, encrypted_key].concat()))
+}
+
+/// Unwraps a data key produced by [`new_data_key`].
+async fn unwrap_data_key(
+    client: &KeyClient,
+    key: &aziot_key_common::KeyHandle,
+    aad: Vec<u8>,
+    wrapped_key: &[u8],
+) -> Result<Vec<u8>, http_common::server::Error> {
+    // Wrapped keys must also fit in an envelope header.
+    if wrapped_key.len() <= envelope::IV_LEN || u16::try_from(wrapped_key.len()).is_err() {
+        return Err(edgelet_http::error::bad_request("invalid data key"));
+    }
+    let (iv, encrypted_key) = wrapped_key.split_at(envelope::IV_LEN);
+
+    let parameters = aziot_key_common::EncryptMechanism::Aead {
+        iv: iv.to_vec(),
+        aad,
+    };
+    // A data key that was modified, or is used under another key ID, fails to decrypt.
+    let data_key = client
+        .decrypt(key, parameters, encrypted_key)
+        .await
+        .map_err(|_| edgelet_http::error::bad_request("invalid data key"))?;
+
+    if data_key.len() == envelope::DATA_KEY_LEN {
+        Ok(data_key)
+    } else {
+        Err(edgelet_http::error::bad_request("invalid data key"))
+    }
+}
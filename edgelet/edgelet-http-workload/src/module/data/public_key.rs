@@ -0,0 +1,149 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+#[cfg(not(test))]
+use aziot_cert_client_async::Client as CertClient;
+#[cfg(not(test))]
+use aziot_identity_client_async::Client as IdentityClient;
+
+#[cfg(test)]
+use test_common::client::CertClient;
+#[cfg(test)]
+use test_common::client::IdentityClient;
+
+pub(crate) struct Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    cert_client: std::sync::Arc<tokio::sync::Mutex<CertClient>>,
+    identity_client: std::sync::Arc<tokio::sync::Mutex<IdentityClient>>,
+    module_id: String,
+    pid: libc::pid_t,
+    runtime: std::sync::Arc<tokio::sync::Mutex<M>>,
+}
+
+#[derive(Debug, serde::Serialize)]
+#[cfg_attr(test, derive(serde::Deserialize))]
+pub(crate) struct PublicKeyResponse {
+    #[serde(rename = "publicKey")]
+    public_key: String,
+
+    certificate: String,
+}
+
+#[async_trait::async_trait]
+impl<M> http_common::server::Route for Route<M>
+where
+    M: edgelet_core::ModuleRuntime + Send + Sync,
+{
+    type ApiVersion = edgelet_http::ApiVersion;
+    fn api_version() -> &'static dyn http_common::DynRangeBounds<Self::ApiVersion> {
+        &((edgelet_http::ApiVersion::V2022_08_03)..)
+    }
+
+    type Service = crate::Service<M>;
+    fn from_uri(
+        service: &Self::Service,
+        path: &str,
+        _query: &[(std::borrow::Cow<'_, str>, std::borrow::Cow<'_, str>)],
+        extensions: &http::Extensions,
+    ) -> Option<Self> {
+        let uri_regex =
+            regex::Regex::new("^/modules/(?P<moduleId>[^/]+)/genid/(?P<genId>[^/]+)/publickey$")
+                .expect("hard-coded regex must compile");
+        let captures = uri_regex.captures(path)?;
+
+        let module_id = &captures["moduleId"];
+        let module_id = percent_encoding::percent_decode_str(module_id)
+            .decode_utf8()
+            .ok()?;
+
+        let pid = extensions.get::<Option<libc::pid_t>>().copied()??;
+
+        Some(Route {
+            cert_client: service.cert_client.clone(),
+            identity_client: service.identity_client.clone(),
+            module_id: module_id.into_owned(),
+            pid,
+            runtime: service.runtime.clone(),
+        })
+    }
+
+    type DeleteBody = serde::de::IgnoredAny;
+
+    /// Returns the public key of the module's X.509 identity, so that third parties can verify
+    /// its signatures, along with the identity certificate that binds the key to the module.
+    async fn get(self) -> http_common::server::RouteResponse {
+        edgelet_http::auth_caller(&self.module_id, self.pid, &self.runtime).await?;
+
+        let auth = super::sign::get_module_auth(self.identity_client, &self.module_id).await?;
+        let public_key =
+            super::asymmetric::identity_public_key(&self.cert_client, &self.module_id, &auth)
+                .await?;
+
+        let pem = public_key
+            .key
+            .public_key_to_pem()
+            .map_err(edgelet_http::error::server_error)?;
+        let pem = String::from_utf8(pem).map_err(edgelet_http::error::server_error)?;
+
+        let res = PublicKeyResponse {
+            public_key: pem,
+            certificate: public_key.certificate,
+        };
+        let res = http_common::server::response::json(hyper::StatusCode::OK, &res);
+
+        Ok(res)
+    }
+
+    type PostBody = serde::de::IgnoredAny;
+
+    type PutBody = serde::de::IgnoredAny;
+}
+
+#[cfg(test)]
+mod tests {
+    use http_common::server::Route;
+
+    use edgelet_test_utils::{test_route_err, test_route_ok};
+
+    const TEST_PATH: &str = "/modules/testModule/genid/1/publickey";
+
+    #[test]
+    fn parse_uri() {
+        // Valid URI
+        let route = test_route_ok!(TEST_PATH);
+        assert_eq!("testModule", &route.module_id);
+        assert_eq!(nix::unistd::getpid().as_raw(), route.pid);
+
+        // Missing module ID
+        test_route_err!("/modules//genid/1/publickey");
+
+        // Missing generation ID
+        test_route_err!("/modules/testModule/genid//publickey");
+
+        // Extra character at beginning of URI
+        test_route_err!(&format!("a{TEST_PATH}"));
+
+        // Extra character at end of URI
+        test_route_err!(&format!("{TEST_PATH}a"));
+    }
+
+    #[tokio::test]
+    async fn auth() {
+        async fn get(
+            route: super::Route<edgelet_test_utils::runtime::Runtime>,
+        ) -> http_common::server::RouteResponse {
+            route.get().await
+        }
+
+        edgelet_test_utils::test_auth_caller!(TEST_PATH, "testModule", get);
+    }
+
+    #[tokio::test]
+    async fn no_x509_identity() {
+        // The test module has a symmetric key identity, which has no public key.
+        let route = test_route_ok!(TEST_PATH);
+        let response = route.get().await.unwrap_err();
+        assert_eq!(hyper::StatusCode::BAD_REQUEST, response.status_code);
+    }
+}
@@ -1,10 +1,14 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+#[cfg(not(test))]
+use aziot_cert_client_async::Client as CertClient;
 #[cfg(not(test))]
 use aziot_identity_client_async::Client as IdentityClient;
 #[cfg(not(test))]
 use aziot_key_client_async::Client as KeyClient;
 
+#[cfg(test)]
+use test_common::client::CertClient;
 #[cfg(test)]
 use test_common::client::IdentityClient;
 #[cfg(test)]
@@ -15,6 +19,7 @@ where
     M: edgelet_core::ModuleRuntime + Send + Sync,
 {
     key_client: std::sync::Arc<tokio::sync::Mutex<KeyClient>>,
+    cert_client: std::sync::Arc<tokio::sync::Mutex<CertClient>>,
     identity_client: std::sync::Arc<tokio::sync::Mutex<IdentityClient>>,
     module_id: String,
     pid: libc::pid_t,
@@ -24,6 +29,9 @@ where
 #[derive(Debug, serde::Deserialize)]
 pub(crate) struct SignRequest {
     data: String,
+
+    #[serde(default)]
+    algo: super::asymmetric::SignAlgorithm,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -63,6 +71,7 @@ where
 
         Some(Route {
             key_client: service.key_client.clone(),
+            cert_client: service.cert_client.clone(),
             identity_client: service.identity_client.clone(),
             module_id: module_id.into_owned(),
             pid,
@@ -76,8 +85,8 @@ where
     async fn post(self, body: Option<Self::PostBody>) -> http_common::server::RouteResponse {
         edgelet_http::auth_caller(&self.module_id, self.pid, &self.runtime).await?;
 
-        let data = match body {
-            Some(body) => super::base64_decode(body.data)?,
+        let (data, algorithm) = match body {
+            Some(body) => (super::base64_decode(body.data)?, body.algo),
             None => {
                 return Err(edgelet_http::error::bad_request(
                     "missing parameter: request body",
@@ -85,18 +94,33 @@ where
             }
         };
 
-        let module_key = get_module_key(self.identity_client, &self.module_id).await?;
-
-        let key_client = self.key_client.lock().await;
-
-        let digest = key_client
-            .sign(
-                &module_key,
-                aziot_key_common::SignMechanism::HmacSha256,
-                &data,
-            )
-            .await
-            .map_err(edgelet_http::error::server_error)?;
+        let digest = if algorithm == super::asymmetric::SignAlgorithm::HmacSha256 {
+            let module_key = get_module_key(self.identity_client, &self.module_id).await?;
+
+            let key_client = self.key_client.lock().await;
+
+            key_client
+                .sign(
+                    &module_key,
+                    aziot_key_common::SignMechanism::HmacSha256,
+                    &data,
+                )
+                .await
+                .map_err(edgelet_http::error::server_error)?
+        } else {
+            let auth = get_module_auth(self.identity_client, &self.module_id).await?;
+            let public_key =
+                super::asymmetric::identity_public_key(&self.cert_client, &self.module_id, &auth)
+                    .await?;
+            let module_key = auth
+                .key_handle
+                .ok_or_else(|| edgelet_http::error::server_error("module identity missing key"))?;
+
+            let key_client = self.key_client.lock().await;
+
+            super::asymmetric::sign(&key_client, &module_key, algorithm, &public_key.key, &data)
+                .await?
+        };
         let engine = base64::engine::general_purpose::STANDARD;
         let digest = base64::Engine::encode(&engine, digest);
 
@@ -109,10 +133,20 @@ where
     type PutBody = serde::de::IgnoredAny;
 }
 
-async fn get_module_key(
+pub(super) async fn get_module_key(
     client: std::sync::Arc<tokio::sync::Mutex<IdentityClient>>,
     module_id: &str,
 ) -> Result<aziot_key_common::KeyHandle, http_common::server::Error> {
+    let auth = get_module_auth(client, module_id).await?;
+
+    auth.key_handle
+        .ok_or_else(|| edgelet_http::error::server_error("module identity missing key"))
+}
+
+pub(super) async fn get_module_auth(
+    client: std::sync::Arc<tokio::sync::Mutex<IdentityClient>>,
+    module_id: &str,
+) -> Result<aziot_identity_common::AuthenticationInfo, http_common::server::Error> {
     let identity = {
         let client = client.lock().await;
 
@@ -130,12 +164,9 @@ async fn get_module_key(
         }
     };
 
-    let auth = identity
+    identity
         .auth
-        .ok_or_else(|| edgelet_http::error::server_error("module identity missing auth"))?;
-
-    auth.key_handle
-        .ok_or_else(|| edgelet_http::error::server_error("module identity missing key"))
+        .ok_or_else(|| edgelet_http::error::server_error("module identity missing auth"))
 }
 
 #[cfg(test)]
@@ -146,6 +177,8 @@ mod tests {
 
     use edgelet_test_utils::{test_route_err, test_route_ok};
 
+    use crate::module::data::asymmetric::SignAlgorithm;
+
     const TEST_PATH: &str = "/modules/testModule/genid/1/sign";
 
     #[test]
@@ -176,6 +209,7 @@ mod tests {
             let engine = base64::engine::general_purpose::STANDARD;
             let body = super::SignRequest {
                 data: base64::Engine::encode(&engine, "data"),
+                algo: SignAlgorithm::HmacSha256,
             };
 
             route.post(Some(body)).await
@@ -194,6 +228,7 @@ mod tests {
         // data must be base64-encoded
         let body = super::SignRequest {
             data: "~".to_string(),
+            algo: SignAlgorithm::HmacSha256,
         };
 
         let route = test_route_ok!(TEST_PATH);
@@ -205,6 +240,7 @@ mod tests {
 
         let body = super::SignRequest {
             data: base64::Engine::encode(&engine, "~"),
+            algo: SignAlgorithm::HmacSha256,
         };
 
         let route = test_route_ok!(TEST_PATH);
@@ -214,6 +250,27 @@ mod tests {
         base64::Engine::decode(&engine, response.digest).unwrap();
     }
 
+    #[tokio::test]
+    async fn asymmetric() {
+        let engine = base64::engine::general_purpose::STANDARD;
+
+        // Asymmetric algorithms need an X.509 identity, which the test module doesn't have.
+        for algo in [
+            SignAlgorithm::EcdsaSha256,
+            SignAlgorithm::EcdsaSha384,
+            SignAlgorithm::RsaPssSha256,
+        ] {
+            let body = super::SignRequest {
+                data: base64::Engine::encode(&engine, "data"),
+                algo,
+            };
+
+            let route = test_route_ok!(TEST_PATH);
+            let response = route.post(Some(body)).await.unwrap_err();
+            assert_eq!(hyper::StatusCode::BAD_REQUEST, response.status_code);
+        }
+    }
+
     #[tokio::test]
     async fn get_module_key() {
         // Identity doesn't exist: fail
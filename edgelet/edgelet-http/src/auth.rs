@@ -35,10 +35,54 @@ pub async fn auth_caller(
     Ok(())
 }
 
+/// Authorizes edgeAgent and processes on the host, but not other modules. Callers that can
+/// reach the management socket from outside a module are trusted as much as edgeAgent.
+#[allow(clippy::module_name_repetitions)]
+pub async fn auth_agent_or_host(
+    pid: libc::pid_t,
+    runtime: &std::sync::Arc<tokio::sync::Mutex<impl edgelet_core::ModuleRuntime>>,
+) -> Result<(), http_common::server::Error> {
+    let runtime = runtime.lock().await;
+
+    let modules = runtime.list_with_details().await.map_err(|err| {
+        log::info!("Auth failed: could not list modules: {err}");
+
+        crate::error::FORBIDDEN
+    })?;
+
+    for (module, state) in modules {
+        // Modules that aren't running have no processes to check.
+        let module_name = edgelet_core::Module::name(&module);
+        let running = matches!(
+            state.status(),
+            edgelet_core::ModuleStatus::Running | edgelet_core::ModuleStatus::Unhealthy
+        );
+        if module_name == "edgeAgent" || !running {
+            continue;
+        }
+
+        let module_pids = runtime.module_top(module_name).await.map_err(|err| {
+            log::info!("Auth failed: could not get processes of {module_name}: {err}");
+
+            crate::error::FORBIDDEN
+        })?;
+
+        if module_pids.contains(&pid) {
+            log::info!(
+                "Only edgeAgent and the host are authorized for this endpoint; pid {pid} belongs to {module_name}."
+            );
+
+            return Err(crate::error::FORBIDDEN);
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 #[allow(clippy::semicolon_if_nothing_returned)]
 mod tests {
-    use super::{auth_agent, auth_caller};
+    use super::{auth_agent, auth_agent_or_host, auth_caller};
 
     fn assert_is_forbidden(res: Result<(), http_common::server::Error>) {
         let res = res.unwrap_err();
@@ -92,4 +136,32 @@ mod tests {
         assert!(auth_caller("testModule", 1001, &runtime).await.is_ok());
         assert_is_forbidden(auth_caller("testModule", 1000, &runtime).await);
     }
+
+    #[tokio::test]
+    async fn auth_agent_or_host_pids() {
+        let mut runtime = edgelet_test_utils::runtime::Runtime::default();
+        runtime
+            .module_auth
+            .insert("edgeAgent".to_string(), vec![1000]);
+        runtime
+            .module_auth
+            .insert("testModule".to_string(), vec![1001]);
+
+        let runtime = std::sync::Arc::new(tokio::sync::Mutex::new(runtime));
+
+        // edgeAgent and processes outside of modules are authorized.
+        assert!(auth_agent_or_host(1000, &runtime).await.is_ok());
+        assert!(auth_agent_or_host(1002, &runtime).await.is_ok());
+
+        // Other modules are not.
+        assert_is_forbidden(auth_agent_or_host(1001, &runtime).await);
+
+        // Runtime errors should cause auth to return 403 errors.
+        runtime
+            .lock()
+            .await
+            .module_auth
+            .insert("runtimeError".to_string(), vec![]);
+        assert_is_forbidden(auth_agent_or_host(1002, &runtime).await);
+    }
 }
@@ -16,7 +16,8 @@ pub const fn bad_request(message: &'static str) -> Error {
     }
 }
 
-/// Produce an HTTP error response provided a runtime-dependent error.
+/// Produce an HTTP error response provided a runtime-dependent error. The message includes
+/// the error's causes, which say why the operation failed.
 #[allow(clippy::module_name_repetitions)]
 pub fn runtime_error<M>(_runtime: &M, error: &anyhow::Error) -> http_common::server::Error
 where
@@ -24,7 +25,7 @@ where
 {
     http_common::server::Error {
         status_code: <M as edgelet_core::ModuleRuntime>::error_code(error),
-        message: Cow::Owned(error.to_string()),
+        message: Cow::Owned(format!("{error:#}")),
     }
 }
 
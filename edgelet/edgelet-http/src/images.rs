@@ -0,0 +1,31 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct LoadImagesResponse {
+    pub images: Vec<String>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct ListImagesResponse {
+    pub images: Vec<edgelet_core::ImageUsage>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct PruneImagesResponse {
+    pub images: Vec<edgelet_core::PrunedImage>,
+}
+
+/// One line of the stream returned when following a module's image pull.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct ModulePullProgress {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<edgelet_core::PullProgress>,
+
+    /// Set on the last line, once the pull has finished.
+    #[serde(default)]
+    pub done: bool,
+
+    /// Why the pull failed, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
@@ -2,17 +2,31 @@
 
 mod auth;
 pub mod error;
+mod images;
+mod metrics;
 mod modules;
+mod snapshots;
 mod version;
 
-pub use auth::{auth_agent, auth_caller};
+pub use auth::{auth_agent, auth_agent_or_host, auth_caller};
+
+pub use metrics::Instrumented;
 
 // Common types shared between management and workload APIs.
 pub use modules::{ListModulesResponse, ModuleConfig, ModuleDetails, ModuleStatus};
 
+// HTTP bodies for running commands in modules.
+pub use modules::ExecRequest;
+
 // HTTP bodies that represent module specs.
 pub use modules::ModuleSpec;
 
+// HTTP bodies for managing images.
+pub use images::{ListImagesResponse, LoadImagesResponse, ModulePullProgress, PruneImagesResponse};
+
+// HTTP bodies for saving and restoring snapshots of the deployed modules.
+pub use snapshots::{ListSnapshotsResponse, SaveSnapshotRequest};
+
 pub use version::ApiVersion;
 
 /// Search a query string for the provided key.
@@ -0,0 +1,119 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+/// Wraps an API service so that every request it serves is recorded in
+/// [`edgelet_core::metrics`].
+#[derive(Clone)]
+pub struct Instrumented<S> {
+    api: &'static str,
+    inner: S,
+}
+
+impl<S> Instrumented<S> {
+    pub fn new(api: &'static str, inner: S) -> Self {
+        Instrumented { api, inner }
+    }
+}
+
+impl<S, ReqBody, ResBody> hyper::service::Service<hyper::Request<ReqBody>> for Instrumented<S>
+where
+    S: hyper::service::Service<hyper::Request<ReqBody>, Response = hyper::Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn call(&self, req: hyper::Request<ReqBody>) -> Self::Future {
+        let api = self.api;
+        let method = req.method().to_string();
+        let path = req.uri().path().to_owned();
+        let start = std::time::Instant::now();
+
+        let res = self.inner.call(req);
+
+        Box::pin(async move {
+            let res = res.await;
+
+            if let Ok(res) = &res {
+                let status = res.status();
+
+                // Paths that don't match any route are not templated, so they are grouped
+                // together to keep the number of series bounded.
+                let route = if status == hyper::StatusCode::NOT_FOUND
+                    || status == hyper::StatusCode::METHOD_NOT_ALLOWED
+                {
+                    "unmatched".to_owned()
+                } else {
+                    route_template(&path)
+                };
+
+                edgelet_core::metrics().record_request(
+                    api,
+                    &method,
+                    &route,
+                    status.as_u16(),
+                    start.elapsed(),
+                );
+            }
+
+            res
+        })
+    }
+}
+
+/// Replaces the module, identity and snapshot names and generation IDs in a request path with
+/// placeholders.
+fn route_template(path: &str) -> String {
+    let mut template = Vec::new();
+    let mut placeholder = None;
+
+    for segment in path.split('/') {
+        if let Some(placeholder) = placeholder.take()
+            && !segment.is_empty()
+        {
+            template.push(placeholder);
+            continue;
+        }
+
+        placeholder = match segment {
+            "modules" | "identities" | "snapshots" => Some("{name}"),
+            "genid" => Some("{genid}"),
+            _ => None,
+        };
+        template.push(segment);
+    }
+
+    template.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::route_template;
+
+    #[test]
+    fn templates() {
+        assert_eq!("/modules", route_template("/modules"));
+        assert_eq!("/modules/{name}", route_template("/modules/edgeHub"));
+        assert_eq!(
+            "/modules/{name}/logs",
+            route_template("/modules/edgeHub/logs")
+        );
+        assert_eq!(
+            "/modules/{name}/genid/{genid}/sign",
+            route_template("/modules/edgeHub/genid/1234/sign")
+        );
+        assert_eq!("/identities/", route_template("/identities/"));
+        assert_eq!("/identities/{name}", route_template("/identities/edgeHub"));
+        assert_eq!("/snapshots", route_template("/snapshots"));
+        assert_eq!(
+            "/snapshots/{name}/restore",
+            route_template("/snapshots/known-good/restore")
+        );
+        assert_eq!(
+            "/systeminfo/resources",
+            route_template("/systeminfo/resources")
+        );
+    }
+}
@@ -68,6 +68,13 @@ pub struct RuntimeStatus {
     pub description: Option<String>,
 }
 
+/// Request to run a command in a module. The response is a stream of
+/// `edgelet_core::ExecOutput`, one line of JSON each.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct ExecRequest {
+    pub command: Vec<String>,
+}
+
 impl ModuleSpec {
     pub fn name(&self) -> &str {
         &self.name
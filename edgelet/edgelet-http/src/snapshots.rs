@@ -0,0 +1,13 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+/// Request to save the running modules as a snapshot. The response is the saved
+/// `edgelet_core::SnapshotInfo`.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct SaveSnapshotRequest {
+    pub name: String,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct ListSnapshotsResponse {
+    pub snapshots: Vec<edgelet_core::SnapshotInfo>,
+}
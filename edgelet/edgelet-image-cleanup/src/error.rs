@@ -3,6 +3,9 @@ pub enum ImageCleanupError {
     #[error("image id was unexpectedly found to be empty")]
     GetImageId(),
 
+    #[error("could not get the bootstrap Edge Agent image id")]
+    GetBootstrapImageId(),
+
     #[error("error while trying to get running modules: {0:?}")]
     ListRunningModules(#[source] anyhow::Error),
 
@@ -1,11 +1,13 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use std::{collections::HashSet, time::Duration};
+use std::path::{Path, PathBuf};
+use std::{collections::HashMap, collections::HashSet, time::Duration};
 
 use chrono::Timelike;
-use edgelet_core::{ModuleRegistry, ModuleRuntime};
+use edgelet_core::{ImagePruneRequest, ModuleRegistry, ModuleRuntime, PrunedImage};
 use edgelet_docker::ImagePruneData;
-use edgelet_settings::base::image::ImagePruneSettings;
+use edgelet_settings::DockerConfig;
+use edgelet_settings::base::image::{DiskPressureSettings, ImagePruneSettings};
 
 use crate::error::ImageCleanupError;
 
@@ -13,34 +15,60 @@ const TOTAL_MINS_IN_DAY: u64 = 1440;
 
 /// <summary>
 /// This method is the main controller loop for image garbage collection.
-/// [Note: It delegates the actual image deletion to remove_unused_images()]
-/// - If GC is not enabled, it will simply return a future that never completes.
+/// [Note: It delegates the actual image deletion to remove_unused_images() and free_disk_space()]
+/// - If GC is not enabled, it will only serve requests from `prune_requests`.
 /// - If GC is enabled, it will sleep till the first occurrence of the
 ///   'cleanup time' that has been specified by the user.
 ///   After waking up, it'll try to get the bootstrap image ID [if it doesn't
 ///   already have it from a previous run], and then calls remove_unused_images()
 ///   Finally, it puts itself back to sleep till it's time for the next run.
-pub async fn image_garbage_collect(
+/// - If disk pressure watermarks are configured and `data_root` is known, it also
+///   wakes up when disk usage of `data_root` reaches the high watermark, and then
+///   calls free_disk_space() instead.
+/// - Requests from `prune_requests` run remove_unused_images() immediately, and are
+///   answered with the images that were (or, in a dry run, would be) removed.
+pub async fn image_garbage_collect<M>(
     edge_agent_bootstrap: String,
     settings: ImagePruneSettings,
-    runtime: &edgelet_docker::DockerModuleRuntime<http_common::Connector>,
+    data_root: Option<PathBuf>,
+    runtime: &M,
     image_use_data: ImagePruneData,
-) -> Result<(), ImageCleanupError> {
+    mut prune_requests: tokio::sync::mpsc::UnboundedReceiver<ImagePruneRequest>,
+) -> Result<(), ImageCleanupError>
+where
+    M: ModuleRuntime<Config = DockerConfig>,
+{
     log::info!("Starting image garbage collection task...");
 
-    if !settings.is_enabled() {
-        return std::future::pending().await;
-    }
-
     let cleanup_time_in_mins = &mut settings.cleanup_time();
 
-    let diff_in_secs: u64 = get_sleep_time_mins(*cleanup_time_in_mins) * 60;
-    tokio::time::sleep(Duration::from_secs(diff_in_secs)).await;
+    let mut next_scheduled_run = settings.is_enabled().then(|| {
+        let diff_in_secs: u64 = get_sleep_time_mins(*cleanup_time_in_mins) * 60;
+        tokio::time::Instant::now() + Duration::from_secs(diff_in_secs)
+    });
+
+    let mut disk_monitor = match (settings.disk_pressure(), data_root) {
+        (Some(_), _) if !settings.is_enabled() => None,
+        (Some(disk_pressure), Some(data_root)) => Some(DiskMonitor::new(disk_pressure, data_root)),
+        (Some(_), None) => {
+            log::warn!(
+                "Container engine data root is unknown; image garbage collection will not be triggered by disk usage"
+            );
+            None
+        }
+        (None, _) => None,
+    };
 
     let mut bootstrap_image_id_option = None;
     let mut is_bootstrap_image_deleted: bool = false;
 
     loop {
+        let trigger = tokio::select! {
+            () = sleep_until(next_scheduled_run) => Trigger::Scheduled,
+            usage = wait_for_disk_pressure(disk_monitor.as_mut()) => Trigger::DiskPressure(usage),
+            Some(request) = prune_requests.recv() => Trigger::OnDemand(request),
+        };
+
         // Try to get the bootstrap image id if we failed on the last(/all previous) run(s)
         if bootstrap_image_id_option.is_none() {
             // bootstrap edge agent image should never be deleted
@@ -56,35 +84,268 @@ pub async fn image_garbage_collect(
             }
         }
 
-        if bootstrap_image_id_option.is_some()
-            || (bootstrap_image_id_option.is_none() && is_bootstrap_image_deleted)
-        {
-            remove_unused_images(
-                runtime,
-                image_use_data.clone(),
-                bootstrap_image_id_option.clone(),
-            )
-            .await?;
+        let can_prune = bootstrap_image_id_option.is_some()
+            || (bootstrap_image_id_option.is_none() && is_bootstrap_image_deleted);
+
+        match trigger {
+            Trigger::Scheduled => {
+                if can_prune {
+                    remove_unused_images(
+                        runtime,
+                        image_use_data.clone(),
+                        bootstrap_image_id_option.clone(),
+                        false,
+                    )
+                    .await?;
+                }
+
+                // sleep till it's time to wake up based on recurrence (and on current time post-last-execution to avoid time drift)
+                let recurrence = settings.cleanup_recurrence();
+                let delay = recurrence
+                    .checked_sub(Duration::from_secs(
+                        (TOTAL_MINS_IN_DAY - get_sleep_time_mins(*cleanup_time_in_mins)) * 60,
+                    ))
+                    .unwrap_or_default();
+                next_scheduled_run = Some(tokio::time::Instant::now() + delay);
+            }
+            Trigger::DiskPressure(usage) => {
+                if can_prune && let Some(disk_monitor) = &disk_monitor {
+                    // Disk usage is checked again at the next tick, so a failed run is retried
+                    // rather than stopping garbage collection.
+                    if let Err(e) = free_disk_space(
+                        runtime,
+                        &image_use_data,
+                        bootstrap_image_id_option.clone(),
+                        disk_monitor,
+                        usage,
+                    )
+                    .await
+                    {
+                        log::error!("Could not free disk space: {e}");
+                    }
+                }
+            }
+            Trigger::OnDemand(request) => {
+                // Failures are reported to the requester rather than stopping garbage collection.
+                let result = if can_prune {
+                    remove_unused_images(
+                        runtime,
+                        image_use_data.clone(),
+                        bootstrap_image_id_option.clone(),
+                        request.dry_run,
+                    )
+                    .await
+                } else {
+                    Err(ImageCleanupError::GetBootstrapImageId())
+                };
+
+                if request.reply.send(result.map_err(Into::into)).is_err() {
+                    log::warn!("Image prune request was cancelled before it completed");
+                }
+            }
+        }
+    }
+}
+
+enum Trigger {
+    Scheduled,
+    DiskPressure(u8),
+    OnDemand(ImagePruneRequest),
+}
+
+/// Sleeps until `deadline`. Never completes if there is no deadline.
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+struct DiskMonitor {
+    data_root: PathBuf,
+    high_watermark: u8,
+    low_watermark: u8,
+    interval: tokio::time::Interval,
+}
+
+impl DiskMonitor {
+    fn new(settings: &DiskPressureSettings, data_root: PathBuf) -> Self {
+        log::info!(
+            "Image garbage collection will run when disk usage of {} reaches {}%",
+            data_root.display(),
+            settings.high_watermark(),
+        );
+
+        let mut interval = tokio::time::interval(settings.check_interval());
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        DiskMonitor {
+            data_root,
+            high_watermark: settings.high_watermark(),
+            low_watermark: settings.low_watermark(),
+            interval,
         }
+    }
 
-        // sleep till it's time to wake up based on recurrence (and on current time post-last-execution to avoid time drift)
-        let recurrence = settings.cleanup_recurrence();
-        if let Some(delay) = recurrence.checked_sub(Duration::from_secs(
-            (TOTAL_MINS_IN_DAY - get_sleep_time_mins(*cleanup_time_in_mins)) * 60,
-        )) {
-            tokio::time::sleep(delay).await;
+    fn usage(&self) -> Option<u8> {
+        disk_usage(&self.data_root)
+    }
+}
+
+/// Waits until disk usage reaches the high watermark and returns the usage. Never completes
+/// if there is no disk monitor.
+async fn wait_for_disk_pressure(disk_monitor: Option<&mut DiskMonitor>) -> u8 {
+    let Some(disk_monitor) = disk_monitor else {
+        return std::future::pending().await;
+    };
+
+    loop {
+        disk_monitor.interval.tick().await;
+
+        match disk_monitor.usage() {
+            Some(usage) if usage >= disk_monitor.high_watermark => return usage,
+            Some(_) => (),
+            None => log::warn!(
+                "Could not determine disk usage of {}",
+                disk_monitor.data_root.display()
+            ),
         }
     }
 }
 
-async fn remove_unused_images(
-    runtime: &edgelet_docker::DockerModuleRuntime<http_common::Connector>,
+async fn remove_unused_images<M>(
+    runtime: &M,
     image_use_data: ImagePruneData,
     bootstrap_image_id_option: Option<String>,
-) -> Result<(), ImageCleanupError> {
-    log::info!("Image Garbage Collection starting scheduled run");
+    dry_run: bool,
+) -> Result<Vec<PrunedImage>, ImageCleanupError>
+where
+    M: ModuleRuntime<Config = DockerConfig>,
+{
+    if dry_run {
+        log::info!("Image Garbage Collection starting dry run");
+    } else {
+        log::info!("Image Garbage Collection starting scheduled run");
+    }
+
+    let in_use_image_ids =
+        get_in_use_image_ids(runtime, &image_use_data, bootstrap_image_id_option).await?;
+
+    let image_map = if dry_run {
+        image_use_data.prunable_images(in_use_image_ids)
+    } else {
+        image_use_data.prune_images_from_file(in_use_image_ids)
+    }
+    .map_err(ImageCleanupError::PruneImages)?;
+
+    // sizes are only reported, so pruning goes ahead without them
+    let image_sizes = match ModuleRegistry::image_sizes(runtime.registry()).await {
+        Ok(image_sizes) => image_sizes,
+        Err(e) => {
+            log::warn!("Could not get image sizes: {e}");
+            HashMap::new()
+        }
+    };
 
-    let bootstrap_img_id = bootstrap_image_id_option.clone().unwrap_or_default();
+    let current_time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+
+    // delete images
+    let mut pruned_images = Vec::with_capacity(image_map.len());
+    for (key, last_used) in image_map {
+        if !dry_run {
+            if let Err(e) = ModuleRegistry::remove(runtime.registry(), &key).await {
+                log::error!("Could not delete image {key} : {e}");
+                continue;
+            }
+
+            edgelet_core::metrics().record_image_gc_removal();
+        }
+
+        pruned_images.push(PrunedImage {
+            size: image_sizes.get(&key).copied(),
+            id: key,
+            last_used_secs: current_time.saturating_sub(last_used).as_secs(),
+        });
+    }
+
+    // least recently used first
+    pruned_images.sort_by(|image1, image2| {
+        image2
+            .last_used_secs
+            .cmp(&image1.last_used_secs)
+            .then_with(|| image1.id.cmp(&image2.id))
+    });
+
+    Ok(pruned_images)
+}
+
+// Removes unused images, least recently used first, until disk usage drops to the low watermark.
+// Unlike a scheduled run, this ignores image_age_cleanup_threshold.
+async fn free_disk_space<M>(
+    runtime: &M,
+    image_use_data: &ImagePruneData,
+    bootstrap_image_id_option: Option<String>,
+    disk_monitor: &DiskMonitor,
+    usage: u8,
+) -> Result<(), ImageCleanupError>
+where
+    M: ModuleRuntime<Config = DockerConfig>,
+{
+    log::info!(
+        "Image Garbage Collection starting run because disk usage of {} is {usage}%",
+        disk_monitor.data_root.display(),
+    );
+
+    let in_use_image_ids =
+        get_in_use_image_ids(runtime, image_use_data, bootstrap_image_id_option).await?;
+
+    let images = image_use_data
+        .least_recently_used_images(&in_use_image_ids)
+        .map_err(ImageCleanupError::PruneImages)?;
+
+    let mut usage = usage;
+    for image_id in images {
+        if usage <= disk_monitor.low_watermark {
+            break;
+        }
+
+        if let Err(e) = ModuleRegistry::remove(runtime.registry(), &image_id).await {
+            log::error!("Could not delete image {image_id} : {e}");
+            continue;
+        }
+
+        edgelet_core::metrics().record_image_gc_removal();
+        if let Err(e) = image_use_data.forget_image(&image_id) {
+            log::warn!("Could not remove image {image_id} from garbage collection state: {e}");
+        }
+
+        // Keep the last known usage if it can't be determined, so that removal continues.
+        usage = disk_monitor.usage().unwrap_or(usage);
+    }
+
+    if usage > disk_monitor.low_watermark {
+        log::warn!(
+            "Disk usage of {} is still {usage}% after removing all unused images",
+            disk_monitor.data_root.display(),
+        );
+    }
+
+    Ok(())
+}
+
+// Returns the IDs of the images used by modules on the device, of the bootstrap edge agent
+// image, and of the images kept by the retention rules, none of which should be deleted.
+async fn get_in_use_image_ids<M>(
+    runtime: &M,
+    image_use_data: &ImagePruneData,
+    bootstrap_image_id_option: Option<String>,
+) -> Result<HashSet<String>, ImageCleanupError>
+where
+    M: ModuleRuntime<Config = DockerConfig>,
+{
+    let bootstrap_img_id = bootstrap_image_id_option.unwrap_or_default();
 
     // track images associated with extant containers
     let modules = ModuleRuntime::list_with_details(runtime)
@@ -93,7 +354,7 @@ async fn remove_unused_images(
     let mut in_use_image_ids: HashSet<String> = HashSet::new();
     if !bootstrap_img_id.is_empty() {
         // the bootstrap edge agent image should never be deleted.
-        in_use_image_ids.insert(bootstrap_img_id.clone());
+        in_use_image_ids.insert(bootstrap_img_id);
     }
 
     for module in modules {
@@ -105,18 +366,16 @@ async fn remove_unused_images(
         in_use_image_ids.insert(id.to_string());
     }
 
-    let image_map = image_use_data
-        .prune_images_from_file(in_use_image_ids)
+    // images kept by the retention rules are treated as being in use
+    let images = ModuleRuntime::list_images(runtime)
+        .await
+        .map_err(ImageCleanupError::ListImages)?;
+    let retained_image_ids = image_use_data
+        .retained_image_ids(&images)
         .map_err(ImageCleanupError::PruneImages)?;
+    in_use_image_ids.extend(retained_image_ids);
 
-    // delete images
-    for key in image_map.keys() {
-        if let Err(e) = ModuleRegistry::remove(runtime, key).await {
-            log::error!("Could not delete image {key} : {e}");
-        }
-    }
-
-    Ok(())
+    Ok(in_use_image_ids)
 }
 
 /* ================================================ HELPER METHODS ================================================ */
@@ -133,10 +392,13 @@ async fn remove_unused_images(
 //        no                         no                (Implicit) Docker Engine API error; update persistence file but
 //                                                     do not prune images to ensure EA bootstrap isn't deleted
 
-async fn get_bootstrap_image_id(
-    runtime: &edgelet_docker::DockerModuleRuntime<http_common::Connector>,
+async fn get_bootstrap_image_id<M>(
+    runtime: &M,
     edge_agent_bootstrap: String,
-) -> Result<(Option<String>, bool), ImageCleanupError> {
+) -> Result<(Option<String>, bool), ImageCleanupError>
+where
+    M: ModuleRuntime,
+{
     let image_name_to_id = ModuleRuntime::list_images(runtime)
         .await
         .map_err(ImageCleanupError::ListImages)?;
@@ -156,6 +418,31 @@ async fn get_bootstrap_image_id(
     ))
 }
 
+// Returns the usage, in percent, of the disk that holds `path`. That is the disk mounted at the
+// longest mount point that `path` is under.
+fn disk_usage(path: &Path) -> Option<u8> {
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    let disk = disks
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())?;
+
+    usage_percent(disk.total_space(), disk.available_space())
+}
+
+fn usage_percent(total_space: u64, available_space: u64) -> Option<u8> {
+    if total_space == 0 {
+        return None;
+    }
+
+    let used_space = u128::from(total_space.saturating_sub(available_space));
+    // Round up so that usage just above a watermark counts as reaching it.
+    let usage = (used_space * 100).div_ceil(u128::from(total_space));
+
+    u8::try_from(usage).ok()
+}
+
 fn get_sleep_time_mins(cleanup_mins: u64) -> u64 {
     let current_hour = chrono::Local::now().hour();
     let current_minute = chrono::Local::now().minute();
@@ -170,7 +457,7 @@ fn get_sleep_time_mins(cleanup_mins: u64) -> u64 {
 
 #[cfg(test)]
 mod tests {
-    use super::get_sleep_time_mins;
+    use super::{get_sleep_time_mins, usage_percent};
     use chrono::Timelike;
 
     const TOTAL_MINS_IN_DAY: u64 = 1440;
@@ -193,4 +480,14 @@ mod tests {
 
         assert!(answer == result);
     }
+
+    #[test]
+    fn test_usage_percent() {
+        assert_eq!(usage_percent(0, 0), None);
+        assert_eq!(usage_percent(1000, 1000), Some(0));
+        assert_eq!(usage_percent(1000, 250), Some(75));
+        assert_eq!(usage_percent(1000, 249), Some(76));
+        assert_eq!(usage_percent(1000, 0), Some(100));
+        assert_eq!(usage_percent(u64::MAX, 0), Some(100));
+    }
 }
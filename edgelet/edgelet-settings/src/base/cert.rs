@@ -0,0 +1,29 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+/// Names and key usages that modules may add to the server certificates they request from
+/// the workload API, beyond the module ID and common name that every server certificate has.
+///
+/// A requested name is allowed if any rule that matches the module allows it. Modules that
+/// no rule matches can't request additional names.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ModuleCertRule {
+    /// Globs matched against module names.
+    pub modules: Vec<String>,
+
+    /// Globs matched against DNS names, ignoring case. `*` matches within a single label,
+    /// so `*.contoso.local` allows `a.contoso.local` but not `a.b.contoso.local`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dns_names: Vec<String>,
+
+    /// IP addresses, or ranges of them in CIDR notation such as `10.0.0.0/8`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ip_addresses: Vec<String>,
+
+    /// Globs matched against URIs. `*` matches within a single path segment.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uris: Vec<String>,
+
+    /// Whether the certificates may also be used for client authentication.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub client_auth: bool,
+}
@@ -1,4 +1,5 @@
 // Copyright (c) Microsoft. All rights reserved.
+use std::num::NonZeroUsize;
 use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
@@ -26,6 +27,95 @@ pub struct ImagePruneSettings {
     // is image garbage collection enabled
     #[serde(default = "default_enabled")]
     enabled: bool,
+    /// disk usage watermarks that trigger garbage collection between scheduled runs
+    #[serde(
+        default,
+        deserialize_with = "validate_disk_pressure",
+        skip_serializing_if = "Option::is_none"
+    )]
+    disk_pressure: Option<DiskPressureSettings>,
+    /// images that garbage collection keeps even when they are unused
+    #[serde(default, skip_serializing_if = "RetentionSettings::is_default")]
+    retention: RetentionSettings,
+}
+
+/// Disk usage watermarks for the container engine's data root, in percent of the disk's size.
+/// When usage reaches `high_watermark`, unused images are removed least recently used first
+/// until usage drops to `low_watermark`.
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct DiskPressureSettings {
+    high_watermark: u8,
+    low_watermark: u8,
+    /// how often disk usage is checked
+    #[serde(default = "default_disk_check_interval", with = "humantime_serde")]
+    check_interval: Duration,
+}
+
+/// Rules for unused images that garbage collection keeps, so that they don't have to be
+/// downloaded again.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub struct RetentionSettings {
+    /// globs of image names, such as "mcr.microsoft.com/azureiotedge-*", that are never removed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    protected_images: Vec<String>,
+    /// number of most recently used images to keep for each repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keep_versions: Option<NonZeroUsize>,
+    /// whether to keep the images that modules used before their current image
+    #[serde(default)]
+    keep_previous_deployment: bool,
+}
+
+impl RetentionSettings {
+    pub fn new(
+        protected_images: Vec<String>,
+        keep_versions: Option<NonZeroUsize>,
+        keep_previous_deployment: bool,
+    ) -> Self {
+        RetentionSettings {
+            protected_images,
+            keep_versions,
+            keep_previous_deployment,
+        }
+    }
+
+    pub fn protected_images(&self) -> &[String] {
+        &self.protected_images
+    }
+
+    pub fn keep_versions(&self) -> Option<NonZeroUsize> {
+        self.keep_versions
+    }
+
+    pub fn keep_previous_deployment(&self) -> bool {
+        self.keep_previous_deployment
+    }
+
+    pub fn is_default(value: &Self) -> bool {
+        value == &Self::default()
+    }
+}
+
+impl DiskPressureSettings {
+    pub fn new(high_watermark: u8, low_watermark: u8, check_interval: Duration) -> Self {
+        DiskPressureSettings {
+            high_watermark,
+            low_watermark,
+            check_interval,
+        }
+    }
+
+    pub fn high_watermark(&self) -> u8 {
+        self.high_watermark
+    }
+
+    pub fn low_watermark(&self) -> u8 {
+        self.low_watermark
+    }
+
+    pub fn check_interval(&self) -> Duration {
+        self.check_interval
+    }
 }
 
 impl ImagePruneSettings {
@@ -40,9 +130,23 @@ impl ImagePruneSettings {
             image_age_cleanup_threshold,
             cleanup_time,
             enabled,
+            disk_pressure: None,
+            retention: RetentionSettings::default(),
         }
     }
 
+    #[must_use]
+    pub fn with_retention(mut self, retention: RetentionSettings) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    #[must_use]
+    pub fn with_disk_pressure(mut self, disk_pressure: DiskPressureSettings) -> Self {
+        self.disk_pressure = Some(disk_pressure);
+        self
+    }
+
     pub fn cleanup_recurrence(&self) -> Duration {
         self.cleanup_recurrence
     }
@@ -59,6 +163,14 @@ impl ImagePruneSettings {
         self.enabled
     }
 
+    pub fn disk_pressure(&self) -> Option<&DiskPressureSettings> {
+        self.disk_pressure.as_ref()
+    }
+
+    pub fn retention(&self) -> &RetentionSettings {
+        &self.retention
+    }
+
     pub fn is_default(value: &Self) -> bool {
         value == &Self::default()
     }
@@ -78,6 +190,11 @@ fn default_enabled() -> bool {
     true
 }
 
+// 5 minutes
+fn default_disk_check_interval() -> Duration {
+    Duration::from_mins(5)
+}
+
 fn validate_recurrence<'de, D>(de: D) -> Result<Duration, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -96,6 +213,34 @@ where
     Ok(recurrence)
 }
 
+fn validate_disk_pressure<'de, D>(de: D) -> Result<Option<DiskPressureSettings>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let disk_pressure = DiskPressureSettings::deserialize(de)?;
+
+    if disk_pressure.high_watermark > 100
+        || disk_pressure.low_watermark >= disk_pressure.high_watermark
+    {
+        return Err(<D::Error as serde::de::Error>::invalid_value(
+            serde::de::Unexpected::Other(&format!(
+                "high_watermark = {}, low_watermark = {}",
+                disk_pressure.high_watermark, disk_pressure.low_watermark
+            )),
+            &"watermarks where low_watermark < high_watermark <= 100",
+        ));
+    }
+
+    if disk_pressure.check_interval.is_zero() {
+        return Err(<D::Error as serde::de::Error>::invalid_value(
+            serde::de::Unexpected::Other("0s"),
+            &"non-zero check_interval",
+        ));
+    }
+
+    Ok(Some(disk_pressure))
+}
+
 mod hhmm_as_minutes {
     use chrono::Timelike;
     use serde::{Deserialize, Serialize};
@@ -136,6 +281,8 @@ impl Default for ImagePruneSettings {
             image_age_cleanup_threshold: default_image_age_cleanup_threshold(),
             cleanup_time: 0,
             enabled: default_enabled(),
+            disk_pressure: None,
+            retention: RetentionSettings::default(),
         }
     }
 }
@@ -1,6 +1,7 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 pub mod aziot;
+pub mod cert;
 pub mod image;
 pub mod module;
 pub mod uri;
@@ -15,6 +16,7 @@ pub trait RuntimeSettings {
     fn edge_ca_key(&self) -> Option<&str>;
     fn edge_ca_auto_renew(&self) -> &Option<cert_renewal::AutoRenewConfig>;
     fn edge_ca_subject(&self) -> &Option<aziot_certd_config::CertSubject>;
+    fn edge_ca_key_algorithm(&self) -> Option<KeyAlgorithm>;
 
     fn trust_bundle_cert(&self) -> Option<&str>;
     fn manifest_trust_bundle_cert(&self) -> Option<&str>;
@@ -25,6 +27,8 @@ pub trait RuntimeSettings {
 
     fn allow_elevated_docker_permissions(&self) -> bool;
 
+    fn allow_module_exec(&self) -> bool;
+
     fn iotedge_max_requests(&self) -> &IotedgeMaxRequests;
 
     fn agent(&self) -> &module::Settings<Self::ModuleConfig>;
@@ -40,6 +44,8 @@ pub trait RuntimeSettings {
     fn additional_info(&self) -> &std::collections::BTreeMap<String, String>;
 
     fn image_garbage_collection(&self) -> &image::ImagePruneSettings;
+
+    fn module_cert_policy(&self) -> &[cert::ModuleCertRule];
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
@@ -48,6 +54,8 @@ pub struct EdgeCa {
     pub cert: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_algorithm: Option<KeyAlgorithm>,
 
     // This enum has one value variant and one table variant. It must be placed
     // after all values and before all tables.
@@ -64,6 +72,50 @@ impl EdgeCa {
     }
 }
 
+/// Algorithm of a generated key pair.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum KeyAlgorithm {
+    #[default]
+    #[serde(rename = "rsa-2048")]
+    Rsa2048,
+    #[serde(rename = "rsa-3072")]
+    Rsa3072,
+    #[serde(rename = "rsa-4096")]
+    Rsa4096,
+    #[serde(rename = "ec-p256")]
+    EcP256,
+    #[serde(rename = "ec-p384")]
+    EcP384,
+    #[serde(rename = "ed25519")]
+    Ed25519,
+}
+
+impl KeyAlgorithm {
+    /// The name of the algorithm in aziot-keyd, if keyd can generate keys with it.
+    pub fn keyd_name(self) -> Option<&'static str> {
+        match self {
+            KeyAlgorithm::Rsa2048 => Some("rsa-2048"),
+            KeyAlgorithm::Rsa4096 => Some("rsa-4096"),
+            KeyAlgorithm::EcP256 => Some("ec-p256"),
+            KeyAlgorithm::EcP384 => Some("ec-p384"),
+            KeyAlgorithm::Rsa3072 | KeyAlgorithm::Ed25519 => None,
+        }
+    }
+}
+
+impl std::fmt::Display for KeyAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            KeyAlgorithm::Rsa2048 => "rsa-2048",
+            KeyAlgorithm::Rsa3072 => "rsa-3072",
+            KeyAlgorithm::Rsa4096 => "rsa-4096",
+            KeyAlgorithm::EcP256 => "ec-p256",
+            KeyAlgorithm::EcP384 => "ec-p384",
+            KeyAlgorithm::Ed25519 => "ed25519",
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct IotedgeMaxRequests {
     pub management: usize,
@@ -104,6 +156,11 @@ pub struct Settings<ModuleConfig> {
     #[serde(default = "default_allow_elevated_docker_permissions")]
     pub allow_elevated_docker_permissions: bool,
 
+    /// Whether commands can be run in modules and files copied to and from them through the
+    /// management API.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_module_exec: bool,
+
     #[serde(default, skip_serializing_if = "IotedgeMaxRequests::is_default")]
     pub iotedge_max_requests: IotedgeMaxRequests,
 
@@ -130,6 +187,9 @@ pub struct Settings<ModuleConfig> {
 
     #[serde(default, skip_serializing_if = "image::ImagePruneSettings::is_default")]
     pub image_garbage_collection: image::ImagePruneSettings,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub module_cert_policy: Vec<cert::ModuleCertRule>,
 }
 
 pub(crate) fn default_allow_elevated_docker_permissions() -> bool {
@@ -160,6 +220,10 @@ impl<T: Clone> RuntimeSettings for Settings<T> {
         &self.edge_ca.subject
     }
 
+    fn edge_ca_key_algorithm(&self) -> Option<KeyAlgorithm> {
+        self.edge_ca.key_algorithm
+    }
+
     fn trust_bundle_cert(&self) -> Option<&str> {
         self.trust_bundle_cert.as_deref()
     }
@@ -183,6 +247,10 @@ impl<T: Clone> RuntimeSettings for Settings<T> {
         self.allow_elevated_docker_permissions
     }
 
+    fn allow_module_exec(&self) -> bool {
+        self.allow_module_exec
+    }
+
     fn agent(&self) -> &module::Settings<Self::ModuleConfig> {
         &self.agent
     }
@@ -214,4 +282,8 @@ impl<T: Clone> RuntimeSettings for Settings<T> {
     fn image_garbage_collection(&self) -> &image::ImagePruneSettings {
         &self.image_garbage_collection
     }
+
+    fn module_cert_policy(&self) -> &[cert::ModuleCertRule] {
+        &self.module_cert_policy
+    }
 }
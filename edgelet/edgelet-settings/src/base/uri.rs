@@ -28,6 +28,7 @@ impl Default for Connect {
             management_uri: management_uri
                 .parse()
                 .expect("failed to parse management uri"),
+            metrics_uri: None,
         }
     }
 }
@@ -36,6 +37,11 @@ impl Default for Connect {
 pub struct Listen {
     pub workload_uri: url::Url,
     pub management_uri: url::Url,
+
+    /// Where to serve Prometheus metrics about aziot-edged. Metrics are not served if
+    /// this is not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_uri: Option<url::Url>,
 }
 
 impl Listen {
@@ -62,6 +68,10 @@ impl Listen {
     pub fn management_uri(&self) -> &url::Url {
         &self.management_uri
     }
+
+    pub fn metrics_uri(&self) -> Option<&url::Url> {
+        self.metrics_uri.as_ref()
+    }
 }
 
 impl Default for Listen {
@@ -76,6 +86,7 @@ impl Default for Listen {
             management_uri: management_uri
                 .parse()
                 .expect("failed to parse management uri"),
+            metrics_uri: None,
         }
     }
 }
@@ -1,20 +1,212 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use std::convert::TryInto;
+use std::time::Duration;
 
-#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub struct Settings {
     #[serde(default)]
     pub max_retries: MaxRetries,
+
+    /// Time between watchdog runs while the watchdog is not reporting errors.
+    #[serde(
+        default = "default_period",
+        skip_serializing_if = "is_default_period",
+        with = "humantime_serde"
+    )]
+    pub period: Duration,
+
+    /// Upper bound on a random delay added to every period, so that a fleet of devices
+    /// does not run in lockstep.
+    #[serde(
+        default,
+        skip_serializing_if = "Duration::is_zero",
+        with = "humantime_serde"
+    )]
+    pub jitter: Duration,
+
+    /// Upper bound on the period, which doubles with each consecutive watchdog error.
+    #[serde(
+        default = "default_max_period",
+        skip_serializing_if = "is_default_max_period",
+        with = "humantime_serde"
+    )]
+    pub max_period: Duration,
+
+    /// Errors are forgotten once the watchdog has gone this long without one.
+    #[serde(
+        default = "default_reset_window",
+        skip_serializing_if = "is_default_reset_window",
+        with = "humantime_serde"
+    )]
+    pub reset_window: Duration,
+
+    /// Consecutive Edge Agent failures after which the agent is reported as crash looping.
+    #[serde(
+        default = "default_crash_loop_threshold",
+        skip_serializing_if = "is_default_crash_loop_threshold"
+    )]
+    pub crash_loop_threshold: u32,
+
+    #[serde(default, skip_serializing_if = "HealthPolicy::is_default")]
+    pub health: HealthPolicy,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            max_retries: MaxRetries::default(),
+            period: default_period(),
+            jitter: Duration::ZERO,
+            max_period: default_max_period(),
+            reset_window: default_reset_window(),
+            crash_loop_threshold: default_crash_loop_threshold(),
+            health: HealthPolicy::default(),
+        }
+    }
 }
 
 impl Settings {
     pub fn max_retries(&self) -> MaxRetries {
         self.max_retries
     }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    pub fn reset_window(&self) -> Duration {
+        self.reset_window
+    }
+
+    pub fn crash_loop_threshold(&self) -> u32 {
+        self.crash_loop_threshold
+    }
+
+    pub fn health(&self) -> &HealthPolicy {
+        &self.health
+    }
+
+    /// Period before the next run after `errors` consecutive watchdog errors.
+    pub fn backoff_period(&self, errors: u32) -> Duration {
+        let factor = 2_u32.saturating_pow(errors);
+
+        self.period.saturating_mul(factor).min(self.max_period)
+    }
+}
+
+fn default_period() -> Duration {
+    Duration::from_mins(1)
+}
+
+fn default_max_period() -> Duration {
+    Duration::from_mins(15)
+}
+
+fn default_reset_window() -> Duration {
+    Duration::from_mins(30)
+}
+
+fn default_crash_loop_threshold() -> u32 {
+    3
+}
+
+// NOTE: Reference required by serde
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_default_period(period: &Duration) -> bool {
+    *period == default_period()
+}
+
+// NOTE: Reference required by serde
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_default_max_period(max_period: &Duration) -> bool {
+    *max_period == default_max_period()
+}
+
+// NOTE: Reference required by serde
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_default_reset_window(reset_window: &Duration) -> bool {
+    *reset_window == default_reset_window()
+}
+
+// NOTE: Reference required by serde
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_default_crash_loop_threshold(threshold: &u32) -> bool {
+    *threshold == default_crash_loop_threshold()
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+/// Restart policy for modules whose health check reports them as unhealthy.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct HealthPolicy {
+    /// Restart running modules that report unhealthy.
+    #[serde(default)]
+    pub restart_unhealthy: bool,
+
+    /// Modules the policy applies to. All modules if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modules: Vec<String>,
+
+    /// Time to wait after a restart before restarting the same module again. Doubles with
+    /// each consecutive restart up to `max_backoff`.
+    #[serde(default = "default_initial_backoff", with = "humantime_serde")]
+    pub initial_backoff: Duration,
+
+    /// Upper bound on the backoff. A module that has not been unhealthy for this long
+    /// after its last restart starts over at `initial_backoff`.
+    #[serde(default = "default_max_backoff", with = "humantime_serde")]
+    pub max_backoff: Duration,
+
+    /// Consecutive restarts after which a module that stays unhealthy is left alone.
+    #[serde(default)]
+    pub max_restarts: MaxRetries,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        HealthPolicy {
+            restart_unhealthy: false,
+            modules: Vec::new(),
+            initial_backoff: default_initial_backoff(),
+            max_backoff: default_max_backoff(),
+            max_restarts: MaxRetries::default(),
+        }
+    }
+}
+
+impl HealthPolicy {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn applies_to(&self, module: &str) -> bool {
+        self.restart_unhealthy
+            && (self.modules.is_empty() || self.modules.iter().any(|m| m == module))
+    }
+
+    /// Backoff before the next restart of a module that has already been restarted
+    /// `restarts` times in a row.
+    pub fn backoff(&self, restarts: u32) -> Duration {
+        let factor = 2_u32.saturating_pow(restarts.saturating_sub(1));
+
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+fn default_initial_backoff() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_max_backoff() -> Duration {
+    Duration::from_mins(10)
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub enum MaxRetries {
     #[default]
     Infinite,
@@ -125,6 +317,53 @@ impl serde::Serialize for MaxRetries {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_period() {
+        let settings = super::Settings {
+            period: Duration::from_secs(30),
+            max_period: Duration::from_secs(300),
+            ..Default::default()
+        };
+
+        assert_eq!(settings.backoff_period(0), Duration::from_secs(30));
+        assert_eq!(settings.backoff_period(1), Duration::from_secs(60));
+        assert_eq!(settings.backoff_period(3), Duration::from_secs(240));
+        assert_eq!(settings.backoff_period(4), Duration::from_secs(300));
+        assert_eq!(settings.backoff_period(u32::MAX), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn health_policy_backoff() {
+        let policy = super::HealthPolicy {
+            restart_unhealthy: true,
+            initial_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(60),
+            ..Default::default()
+        };
+
+        assert_eq!(policy.backoff(0), Duration::from_secs(10));
+        assert_eq!(policy.backoff(1), Duration::from_secs(10));
+        assert_eq!(policy.backoff(2), Duration::from_secs(20));
+        assert_eq!(policy.backoff(3), Duration::from_secs(40));
+        assert_eq!(policy.backoff(4), Duration::from_secs(60));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn health_policy_applies_to() {
+        let mut policy = super::HealthPolicy::default();
+        assert!(!policy.applies_to("edgeHub"));
+
+        policy.restart_unhealthy = true;
+        assert!(policy.applies_to("edgeHub"));
+
+        policy.modules = vec!["edgeHub".to_string()];
+        assert!(policy.applies_to("edgeHub"));
+        assert!(!policy.applies_to("edgeAgent"));
+    }
+
     #[test]
     fn max_retries_cmp() {
         let max_retries = super::MaxRetries::Infinite;
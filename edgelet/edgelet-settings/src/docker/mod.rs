@@ -35,6 +35,15 @@ impl Settings {
 
         init::agent_spec(&mut settings)?;
 
+        // Fail early rather than on every attempt to create edgeAgent.
+        let agent_image = settings.base.agent.config().image();
+        if settings.moby_runtime.policy.require_digest && !agent_image.contains('@') {
+            return Err(format!(
+                "edgeAgent image {agent_image:?} must be referenced by digest because moby_runtime.policy.require_digest is set"
+            )
+            .into());
+        }
+
         Ok(settings)
     }
 
@@ -75,6 +84,10 @@ impl crate::RuntimeSettings for Settings {
         self.base.edge_ca_subject()
     }
 
+    fn edge_ca_key_algorithm(&self) -> Option<crate::base::KeyAlgorithm> {
+        self.base.edge_ca_key_algorithm()
+    }
+
     fn trust_bundle_cert(&self) -> Option<&str> {
         self.base.trust_bundle_cert()
     }
@@ -99,6 +112,10 @@ impl crate::RuntimeSettings for Settings {
         self.base.allow_elevated_docker_permissions()
     }
 
+    fn allow_module_exec(&self) -> bool {
+        self.base.allow_module_exec()
+    }
+
     fn agent(&self) -> &crate::module::Settings<Self::ModuleConfig> {
         self.base.agent()
     }
@@ -130,6 +147,10 @@ impl crate::RuntimeSettings for Settings {
     fn image_garbage_collection(&self) -> &crate::base::image::ImagePruneSettings {
         self.base.image_garbage_collection()
     }
+
+    fn module_cert_policy(&self) -> &[crate::base::cert::ModuleCertRule] {
+        self.base.module_cert_policy()
+    }
 }
 
 #[cfg(test)]
@@ -149,12 +170,16 @@ mod tests {
     // Test files.
     static BAD_SETTINGS: &str = "test-files/bad_sample_settings.toml";
     static BAD_SETTINGS_CONTENT_TRUST: &str = "test-files/bad_settings_content_trust.toml";
+    static BAD_SETTINGS_DISK_PRESSURE: &str = "test-files/bad_settings_disk_pressure.toml";
+    static BAD_SETTINGS_REQUIRE_DIGEST: &str = "test-files/bad_settings_require_digest.toml";
 
     static GOOD_SETTINGS: &str = "test-files/sample_settings.toml";
     static GOOD_SETTINGS_CASE_SENSITIVE: &str = "test-files/case_sensitive.toml";
     static GOOD_SETTINGS_CONTENT_TRUST: &str = "test-files/sample_settings_content_trust.toml";
     static GOOD_SETTINGS_NETWORK: &str = "test-files/sample_settings.network.toml";
+    static GOOD_SETTINGS_PODMAN: &str = "test-files/sample_settings_podman.toml";
     static GOOD_SETTINGS_IMAGE_GC: &str = "test-files/sample_settings_image_gc.toml";
+    static GOOD_SETTINGS_REQUIRE_DIGEST: &str = "test-files/sample_settings_require_digest.toml";
 
     #[test]
     fn err_no_file() {
@@ -210,6 +235,60 @@ mod tests {
         let settings = Settings::new().unwrap();
         let watchdog_settings = settings.watchdog();
         assert_eq!(watchdog_settings.max_retries(), 3);
+        assert_eq!(watchdog_settings.period(), Duration::from_mins(2));
+        assert_eq!(watchdog_settings.jitter(), Duration::from_secs(10));
+        assert_eq!(watchdog_settings.reset_window(), Duration::from_mins(30));
+        assert_eq!(watchdog_settings.crash_loop_threshold(), 3);
+
+        let health = watchdog_settings.health();
+        assert!(health.applies_to("edgeHub"));
+        assert!(!health.applies_to("edgeAgent"));
+        assert_eq!(health.initial_backoff, Duration::from_mins(1));
+        assert_eq!(health.max_backoff, Duration::from_mins(10));
+        assert_eq!(health.max_restarts, 5);
+    }
+
+    #[test]
+    fn listen() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS);
+            std::env::set_var("AZIOT_EDGED_CONFIG_DIR", CONFIG_DIR);
+        }
+
+        let settings = Settings::new().unwrap();
+        assert_eq!(
+            settings.listen().metrics_uri().map(url::Url::as_str),
+            Some("http://127.0.0.1:9600/")
+        );
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS_NETWORK);
+        }
+
+        let settings = Settings::new().unwrap();
+        assert!(settings.listen().metrics_uri().is_none());
+    }
+
+    #[test]
+    fn allow_module_exec() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS);
+            std::env::set_var("AZIOT_EDGED_CONFIG_DIR", CONFIG_DIR);
+        }
+
+        let settings = Settings::new().unwrap();
+        assert!(settings.allow_module_exec());
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS_NETWORK);
+        }
+
+        let settings = Settings::new().unwrap();
+        assert!(!settings.allow_module_exec());
     }
 
     #[test]
@@ -253,6 +332,215 @@ mod tests {
         };
     }
 
+    #[test]
+    fn container_engine() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS);
+            std::env::set_var("AZIOT_EDGED_CONFIG_DIR", CONFIG_DIR);
+        }
+
+        let settings = Settings::new().unwrap();
+        assert_eq!(
+            settings.moby_runtime().engine(),
+            crate::ContainerEngine::Moby
+        );
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS_PODMAN);
+        }
+
+        let settings = Settings::new().unwrap();
+        assert_eq!(
+            settings.moby_runtime().engine(),
+            crate::ContainerEngine::Podman
+        );
+        assert_eq!(
+            settings.moby_runtime().uri().to_string(),
+            "unix:///run/podman/podman.sock"
+        );
+    }
+
+    #[test]
+    fn module_limits() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS);
+            std::env::set_var("AZIOT_EDGED_CONFIG_DIR", CONFIG_DIR);
+        }
+
+        let settings = Settings::new().unwrap();
+        let module_limits = settings.moby_runtime().module_limits();
+
+        assert_eq!(
+            module_limits.for_module("edgeAgent"),
+            crate::ResourceLimits {
+                memory: Some(536_870_912),
+                cpu_shares: None,
+                pids: Some(256),
+                log_size: Some(10_485_760),
+            }
+        );
+        assert_eq!(
+            module_limits.for_module("edgeHub"),
+            crate::ResourceLimits {
+                memory: Some(1_073_741_824),
+                cpu_shares: Some(512),
+                pids: Some(256),
+                log_size: Some(10_485_760),
+            }
+        );
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS_PODMAN);
+        }
+
+        let settings = Settings::new().unwrap();
+        assert!(settings.moby_runtime().module_limits().is_default());
+    }
+
+    #[test]
+    fn policy() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS);
+            std::env::set_var("AZIOT_EDGED_CONFIG_DIR", CONFIG_DIR);
+        }
+
+        let settings = Settings::new().unwrap();
+        let policy = settings.moby_runtime().policy();
+
+        assert!(!policy.require_digest);
+        assert_eq!(
+            policy.bind_mounts,
+            crate::PolicyRule {
+                allow: Some(vec!["/var/lib/modules/**".to_owned()]),
+                deny: vec![],
+            }
+        );
+        assert_eq!(
+            policy.capabilities,
+            crate::PolicyRule {
+                allow: None,
+                deny: vec!["SYS_ADMIN".to_owned()],
+            }
+        );
+        assert!(policy.devices.is_default());
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS_PODMAN);
+        }
+
+        let settings = Settings::new().unwrap();
+        assert!(settings.moby_runtime().policy().is_default());
+    }
+
+    #[test]
+    fn registries() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS);
+            std::env::set_var("AZIOT_EDGED_CONFIG_DIR", CONFIG_DIR);
+        }
+
+        let settings = Settings::new().unwrap();
+
+        assert_eq!(
+            settings.moby_runtime().registry_mirrors(),
+            [
+                crate::RegistryMirror {
+                    registry: "docker.io".to_owned(),
+                    mirrors: vec!["cache.contoso.local:5000/docker-hub".to_owned()],
+                    fallback: true,
+                },
+                crate::RegistryMirror {
+                    registry: "mcr.microsoft.com".to_owned(),
+                    mirrors: vec!["cache.contoso.local:5000/mcr".to_owned()],
+                    fallback: false,
+                },
+            ]
+        );
+        assert_eq!(
+            settings.moby_runtime().credential_providers(),
+            [
+                crate::CredentialProvider {
+                    registries: vec!["cache.contoso.local:5000".to_owned()],
+                    source: crate::CredentialSource::DockerConfig(
+                        "/etc/aziot/edged/registry-auth.json".into()
+                    ),
+                },
+                crate::CredentialProvider {
+                    registries: vec!["*.dkr.ecr.*.amazonaws.com".to_owned()],
+                    source: crate::CredentialSource::Helper(
+                        "/usr/bin/docker-credential-ecr-login".into()
+                    ),
+                },
+            ]
+        );
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS_PODMAN);
+        }
+
+        let settings = Settings::new().unwrap();
+        assert!(settings.moby_runtime().registry_mirrors().is_empty());
+        assert!(settings.moby_runtime().credential_providers().is_empty());
+    }
+
+    #[test]
+    fn pulls() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS);
+            std::env::set_var("AZIOT_EDGED_CONFIG_DIR", CONFIG_DIR);
+        }
+
+        let settings = Settings::new().unwrap();
+
+        assert_eq!(
+            settings.moby_runtime().pulls(),
+            &crate::PullSettings {
+                max_concurrent: std::num::NonZeroUsize::new(1),
+                max_bandwidth: std::num::NonZeroU64::new(262_144),
+                retries: 5,
+                initial_backoff: std::time::Duration::from_secs(30),
+                max_backoff: std::time::Duration::from_mins(5),
+            }
+        );
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS_PODMAN);
+        }
+
+        let settings = Settings::new().unwrap();
+        assert!(settings.moby_runtime().pulls().is_default());
+    }
+
+    #[test]
+    fn require_digest() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", GOOD_SETTINGS_REQUIRE_DIGEST);
+            std::env::set_var("AZIOT_EDGED_CONFIG_DIR", CONFIG_DIR);
+        }
+
+        let settings = Settings::new().unwrap();
+        assert!(settings.moby_runtime().policy().require_digest);
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", BAD_SETTINGS_REQUIRE_DIGEST);
+        }
+
+        let settings = Settings::new();
+        assert!(settings.is_err());
+    }
+
     #[test]
     fn networking_create_options() {
         let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
@@ -319,6 +607,32 @@ mod tests {
             Duration::from_secs(1440 * 60 * 3)
         );
         assert_eq!(image_prune_settings.cleanup_time(), 600);
+
+        let disk_pressure = image_prune_settings.disk_pressure().unwrap();
+        assert_eq!(disk_pressure.high_watermark(), 90);
+        assert_eq!(disk_pressure.low_watermark(), 75);
+        assert_eq!(disk_pressure.check_interval(), Duration::from_mins(5));
+
+        let retention = image_prune_settings.retention();
+        assert_eq!(
+            retention.protected_images(),
+            ["mcr.microsoft.com/azureiotedge-*"]
+        );
+        assert_eq!(retention.keep_versions(), std::num::NonZeroUsize::new(2));
+        assert!(retention.keep_previous_deployment());
+    }
+
+    #[test]
+    fn image_garbage_collection_bad_disk_pressure() {
+        let _env_lock = ENV_LOCK.lock().expect("env lock poisoned");
+
+        unsafe {
+            std::env::set_var("AZIOT_EDGED_CONFIG", BAD_SETTINGS_DISK_PRESSURE);
+            std::env::set_var("AZIOT_EDGED_CONFIG_DIR", CONFIG_DIR);
+        }
+
+        let settings = Settings::new();
+        assert!(settings.is_err());
     }
 
     #[test]
@@ -341,6 +655,10 @@ mod tests {
             Duration::from_hours(24)
         );
         assert_eq!(image_gc_settings.cleanup_time(), 0);
+        assert!(image_gc_settings.disk_pressure().is_none());
+        assert!(crate::base::image::RetentionSettings::is_default(
+            image_gc_settings.retention()
+        ));
     }
 
     #[test]
@@ -373,6 +691,17 @@ mod tests {
         } else {
             panic!();
         }
+
+        let servers = settings
+            .moby_runtime()
+            .content_trust()
+            .map(crate::docker::runtime::ContentTrust::servers)
+            .unwrap();
+        assert_eq!(
+            servers.get("docker.io").map(url::Url::as_str),
+            Some("https://notary.docker.io/")
+        );
+        assert!(!servers.contains_key("contoso1.azurcr.io"));
     }
 
     #[test]
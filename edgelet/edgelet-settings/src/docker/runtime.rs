@@ -5,8 +5,26 @@ pub struct MobyRuntime {
     pub uri: url::Url,
     pub network: crate::docker::network::MobyNetwork,
 
+    #[serde(default, skip_serializing_if = "ContainerEngine::is_default")]
+    pub engine: ContainerEngine,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content_trust: Option<ContentTrust>,
+
+    #[serde(default, skip_serializing_if = "ModuleLimits::is_default")]
+    pub module_limits: ModuleLimits,
+
+    #[serde(default, skip_serializing_if = "CreateOptionsPolicy::is_default")]
+    pub policy: CreateOptionsPolicy,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub registry_mirrors: Vec<RegistryMirror>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub credential_providers: Vec<CredentialProvider>,
+
+    #[serde(default, skip_serializing_if = "PullSettings::is_default")]
+    pub pulls: PullSettings,
 }
 
 impl MobyRuntime {
@@ -18,19 +36,367 @@ impl MobyRuntime {
         &self.network
     }
 
+    pub fn engine(&self) -> ContainerEngine {
+        self.engine
+    }
+
     pub fn content_trust(&self) -> Option<&ContentTrust> {
         self.content_trust.as_ref()
     }
+
+    pub fn module_limits(&self) -> &ModuleLimits {
+        &self.module_limits
+    }
+
+    pub fn policy(&self) -> &CreateOptionsPolicy {
+        &self.policy
+    }
+
+    pub fn registry_mirrors(&self) -> &[RegistryMirror] {
+        &self.registry_mirrors
+    }
+
+    pub fn credential_providers(&self) -> &[CredentialProvider] {
+        &self.credential_providers
+    }
+
+    pub fn pulls(&self) -> &PullSettings {
+        &self.pulls
+    }
+}
+
+/// The container engine listening on `uri`. Engines other than Moby are driven through
+/// their Docker-compatible API.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerEngine {
+    #[default]
+    Moby,
+    Podman,
+}
+
+impl ContainerEngine {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl std::fmt::Display for ContainerEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContainerEngine::Moby => f.write_str("moby"),
+            ContainerEngine::Podman => f.write_str("podman"),
+        }
+    }
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub struct ContentTrust {
     #[serde(default)]
     pub ca_certs: Option<std::collections::BTreeMap<String, String>>,
+
+    /// Notary servers of registries whose trust data is not served from the registry's own
+    /// hostname, e.g. `https://notary.docker.io` for `docker.io`.
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub servers: std::collections::BTreeMap<String, url::Url>,
 }
 
 impl ContentTrust {
     pub fn ca_certs(&self) -> Option<&std::collections::BTreeMap<String, String>> {
         self.ca_certs.as_ref()
     }
+
+    pub fn servers(&self) -> &std::collections::BTreeMap<String, url::Url> {
+        &self.servers
+    }
+}
+
+/// Caps on the resources modules may use. The top-level limits apply to every module;
+/// entries in `modules` override them for individual modules.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ModuleLimits {
+    #[serde(flatten)]
+    pub default: ResourceLimits,
+
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub modules: std::collections::BTreeMap<String, ResourceLimits>,
+}
+
+impl ModuleLimits {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The limits that apply to `module`.
+    pub fn for_module(&self, module: &str) -> ResourceLimits {
+        match self.modules.get(module) {
+            Some(limits) => ResourceLimits {
+                memory: limits.memory.or(self.default.memory),
+                cpu_shares: limits.cpu_shares.or(self.default.cpu_shares),
+                pids: limits.pids.or(self.default.pids),
+                log_size: limits.log_size.or(self.default.log_size),
+            },
+            None => self.default.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ResourceLimits {
+    /// Maximum memory in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<u64>,
+
+    /// Maximum relative CPU weight.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_shares: Option<u64>,
+
+    /// Maximum number of processes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pids: Option<u64>,
+
+    /// Maximum size in bytes of a container log file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_size: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Restrictions on the images and create options that deployments may use.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct CreateOptionsPolicy {
+    /// Globs matched against the host path of bind mounts.
+    #[serde(default, skip_serializing_if = "PolicyRule::is_default")]
+    pub bind_mounts: PolicyRule,
+
+    /// Globs matched against the host path of devices.
+    #[serde(default, skip_serializing_if = "PolicyRule::is_default")]
+    pub devices: PolicyRule,
+
+    #[serde(default, skip_serializing_if = "PolicyRule::is_default")]
+    pub network_modes: PolicyRule,
+
+    /// Capability names, with or without the `CAP_` prefix.
+    #[serde(default, skip_serializing_if = "PolicyRule::is_default")]
+    pub capabilities: PolicyRule,
+
+    /// Globs matched against the registry hostname of module images.
+    #[serde(default, skip_serializing_if = "PolicyRule::is_default")]
+    pub registries: PolicyRule,
+
+    /// Namespaces that modules may share with the host: `pid`, `ipc`, `uts`, `userns` and
+    /// `cgroupns`. None are allowed unless `allow` is set.
+    #[serde(default, skip_serializing_if = "PolicyRule::is_default")]
+    pub host_namespaces: PolicyRule,
+
+    /// Security options, such as `seccomp=unconfined`. Privileged mode counts as the option
+    /// `privileged`, and setting `MaskedPaths` or `ReadonlyPaths` as `systempaths=unconfined`.
+    /// Only `no-new-privileges` is allowed unless `allow` is set.
+    #[serde(default, skip_serializing_if = "PolicyRule::is_default")]
+    pub security_options: PolicyRule,
+
+    /// Globs matched against the containers whose volumes modules may mount with
+    /// `VolumesFrom`. None are allowed unless `allow` is set.
+    #[serde(default, skip_serializing_if = "PolicyRule::is_default")]
+    pub volumes_from: PolicyRule,
+
+    /// Whether module images, including edgeAgent's, must be referenced by digest rather
+    /// than by tag. Pulled images are checked against the digest before modules are created.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub require_digest: bool,
+}
+
+impl CreateOptionsPolicy {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Values matching `deny` are rejected. If `allow` is set, values must also match it;
+/// otherwise every value that isn't denied is allowed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct PolicyRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow: Option<Vec<String>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+}
+
+impl PolicyRule {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Pull-through caches for a registry. Images from `registry` that are referenced by tag
+/// are pulled from each mirror in turn, then from `registry` itself if `fallback` is set.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct RegistryMirror {
+    /// Registry hostname as it appears in image names. Images without one are from
+    /// `docker.io`.
+    pub registry: String,
+
+    /// Mirror hostnames, optionally followed by a path that prefixes the repository, such
+    /// as `cache.contoso.local:5000/docker-hub`.
+    pub mirrors: Vec<String>,
+
+    #[serde(
+        default = "default_fallback",
+        skip_serializing_if = "is_default_fallback"
+    )]
+    pub fallback: bool,
+}
+
+fn default_fallback() -> bool {
+    true
+}
+
+// NOTE: Reference required by serde
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_default_fallback(fallback: &bool) -> bool {
+    *fallback
+}
+
+/// Where to get credentials for registries that a module's deployment has none for. The
+/// first provider that has credentials for the registry is used.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct CredentialProvider {
+    /// Globs matched against the registry hostname.
+    pub registries: Vec<String>,
+
+    #[serde(flatten)]
+    pub source: CredentialSource,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialSource {
+    /// A Docker client `config.json` with credentials in its `auths` section. It is read
+    /// for every pull, so it may be updated while aziot-edged runs.
+    DockerConfig(std::path::PathBuf),
+
+    /// A Docker credential helper such as `docker-credential-ecr-login`, run as
+    /// `<helper> get` with the registry on stdin.
+    Helper(std::path::PathBuf),
+}
+
+/// Limits on image pulls, for devices on slow or metered connections.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct PullSettings {
+    /// Maximum number of images pulled at the same time. Further pulls wait for one of
+    /// them to finish.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent: Option<std::num::NonZeroUsize>,
+
+    /// Maximum average download rate of each pull, in bytes per second. Pulls are routed
+    /// through a local proxy that enforces it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bandwidth: Option<std::num::NonZeroU64>,
+
+    /// Number of times a failed pull is retried. Each retry is a new pull, which like any pull
+    /// skips the layers the container engine already has.
+    #[serde(default)]
+    pub retries: u32,
+
+    /// Time to wait before the first retry. Doubles with each retry up to `max_backoff`.
+    #[serde(default = "default_pull_initial_backoff", with = "humantime_serde")]
+    pub initial_backoff: std::time::Duration,
+
+    #[serde(default = "default_pull_max_backoff", with = "humantime_serde")]
+    pub max_backoff: std::time::Duration,
+}
+
+fn default_pull_initial_backoff() -> std::time::Duration {
+    std::time::Duration::from_secs(10)
+}
+
+fn default_pull_max_backoff() -> std::time::Duration {
+    std::time::Duration::from_mins(5)
+}
+
+impl Default for PullSettings {
+    fn default() -> Self {
+        PullSettings {
+            max_concurrent: None,
+            max_bandwidth: None,
+            retries: 0,
+            initial_backoff: default_pull_initial_backoff(),
+            max_backoff: default_pull_max_backoff(),
+        }
+    }
+}
+
+impl PullSettings {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Backoff before retry number `retry`, counting from 1.
+    pub fn backoff(&self, retry: u32) -> std::time::Duration {
+        let factor = 2_u32.saturating_pow(retry.saturating_sub(1));
+
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ModuleLimits, PullSettings, ResourceLimits};
+
+    #[test]
+    fn module_limits_for_module() {
+        let limits = ModuleLimits {
+            default: ResourceLimits {
+                memory: Some(512),
+                pids: Some(100),
+                ..Default::default()
+            },
+            modules: [(
+                "edgeHub".to_owned(),
+                ResourceLimits {
+                    memory: Some(1024),
+                    log_size: Some(10),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+
+        assert_eq!(
+            ResourceLimits {
+                memory: Some(1024),
+                cpu_shares: None,
+                pids: Some(100),
+                log_size: Some(10),
+            },
+            limits.for_module("edgeHub")
+        );
+        assert_eq!(limits.default, limits.for_module("edgeAgent"));
+
+        assert!(ModuleLimits::default().for_module("edgeHub").is_empty());
+    }
+
+    #[test]
+    fn pull_backoff() {
+        let pulls = PullSettings {
+            initial_backoff: std::time::Duration::from_secs(10),
+            max_backoff: std::time::Duration::from_secs(60),
+            ..Default::default()
+        };
+
+        let backoffs: Vec<_> = (1..=5)
+            .map(|retry| pulls.backoff(retry).as_secs())
+            .collect();
+        assert_eq!(vec![10, 20, 40, 60, 60], backoffs);
+
+        assert_eq!(std::time::Duration::from_secs(60), pulls.backoff(u32::MAX));
+    }
 }
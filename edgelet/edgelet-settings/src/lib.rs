@@ -13,7 +13,10 @@ pub use crate::docker::{
     CONFIG_FILE_DEFAULT, Settings,
     config::{DockerConfig, UPSTREAM_PARENT_KEYWORD},
     network::{Ipam, MobyNetwork},
-    runtime::{ContentTrust, MobyRuntime},
+    runtime::{
+        ContainerEngine, ContentTrust, CreateOptionsPolicy, CredentialProvider, CredentialSource,
+        MobyRuntime, ModuleLimits, PolicyRule, PullSettings, RegistryMirror, ResourceLimits,
+    },
 };
 
 /// ID of the device CA cert in certd and private key in keyd.
@@ -53,13 +53,29 @@ impl edgelet_core::ModuleRegistry for ModuleRegistry {
 
     // The fuctions below aren't used in tests.
 
-    async fn pull(&self, _config: &Self::Config) -> anyhow::Result<()> {
+    async fn pull(
+        &self,
+        _config: &Self::Config,
+        _progress: Option<tokio::sync::mpsc::UnboundedSender<edgelet_core::PullProgress>>,
+    ) -> anyhow::Result<()> {
         unimplemented!()
     }
 
     async fn remove(&self, _name: &str) -> anyhow::Result<()> {
         unimplemented!()
     }
+
+    async fn load(&self, _archive: Incoming, _digest: Option<&str>) -> anyhow::Result<Vec<String>> {
+        unimplemented!()
+    }
+
+    async fn image_sizes(&self) -> anyhow::Result<std::collections::HashMap<String, u64>> {
+        unimplemented!()
+    }
+
+    async fn image_usage(&self) -> anyhow::Result<Vec<edgelet_core::ImageUsage>> {
+        unimplemented!()
+    }
 }
 
 pub struct Runtime {
@@ -103,6 +119,28 @@ impl edgelet_core::ModuleRuntime for Runtime {
         }
     }
 
+    async fn list_with_details(
+        &self,
+    ) -> anyhow::Result<Vec<(Self::Module, edgelet_core::ModuleRuntimeState)>> {
+        let modules = self
+            .module_auth
+            .keys()
+            .filter(|name| *name != "default")
+            .map(|name| {
+                let module = Module {
+                    name: name.clone(),
+                    ..Default::default()
+                };
+                let state = edgelet_core::ModuleRuntimeState::default()
+                    .with_status(edgelet_core::ModuleStatus::Running);
+
+                (module, state)
+            })
+            .collect();
+
+        Ok(modules)
+    }
+
     // The functions below aren't used in tests.
 
     async fn create(
@@ -151,12 +189,6 @@ impl edgelet_core::ModuleRuntime for Runtime {
         unimplemented!()
     }
 
-    async fn list_with_details(
-        &self,
-    ) -> anyhow::Result<Vec<(Self::Module, edgelet_core::ModuleRuntimeState)>> {
-        unimplemented!()
-    }
-
     async fn list_images(&self) -> anyhow::Result<std::collections::HashMap<String, String>> {
         unimplemented!()
     }
@@ -177,6 +209,44 @@ impl edgelet_core::ModuleRuntime for Runtime {
         unimplemented!()
     }
 
+    async fn module_stats(&self, _id: &str) -> anyhow::Result<edgelet_core::ModuleStats> {
+        unimplemented!()
+    }
+
+    async fn exec(
+        &self,
+        _id: &str,
+        _command: Vec<String>,
+    ) -> anyhow::Result<tokio::sync::mpsc::Receiver<edgelet_core::ExecOutput>> {
+        unimplemented!()
+    }
+
+    async fn copy_from(&self, _id: &str, _path: &str) -> anyhow::Result<Incoming> {
+        unimplemented!()
+    }
+
+    async fn copy_to(&self, _id: &str, _path: &str, _archive: Incoming) -> anyhow::Result<()> {
+        unimplemented!()
+    }
+
+    async fn events(
+        &self,
+    ) -> anyhow::Result<tokio::sync::mpsc::Receiver<edgelet_core::ModuleEvent>> {
+        unimplemented!()
+    }
+
+    async fn save_snapshot(&self, _name: &str) -> anyhow::Result<edgelet_core::SnapshotInfo> {
+        unimplemented!()
+    }
+
+    async fn list_snapshots(&self) -> anyhow::Result<Vec<edgelet_core::SnapshotInfo>> {
+        unimplemented!()
+    }
+
+    async fn restore_snapshot(&self, _name: &str) -> anyhow::Result<()> {
+        unimplemented!()
+    }
+
     fn registry(&self) -> &Self::ModuleRegistry {
         unimplemented!()
     }
@@ -6,9 +6,12 @@ pub struct Settings {
     pub edge_ca_key: Option<String>,
     pub edge_ca_auto_renew: Option<cert_renewal::AutoRenewConfig>,
     pub edge_ca_subject: Option<aziot_certd_config::CertSubject>,
+    pub edge_ca_key_algorithm: Option<edgelet_settings::base::KeyAlgorithm>,
 
     pub trust_bundle: Option<String>,
     pub manifest_trust_bundle: Option<String>,
+
+    pub module_cert_policy: Vec<edgelet_settings::base::cert::ModuleCertRule>,
 }
 
 impl edgelet_settings::RuntimeSettings for Settings {
@@ -30,6 +33,10 @@ impl edgelet_settings::RuntimeSettings for Settings {
         &self.edge_ca_subject
     }
 
+    fn edge_ca_key_algorithm(&self) -> Option<edgelet_settings::base::KeyAlgorithm> {
+        self.edge_ca_key_algorithm
+    }
+
     fn trust_bundle_cert(&self) -> Option<&str> {
         self.trust_bundle.as_deref()
     }
@@ -56,6 +63,10 @@ impl edgelet_settings::RuntimeSettings for Settings {
         unimplemented!()
     }
 
+    fn allow_module_exec(&self) -> bool {
+        unimplemented!()
+    }
+
     fn iotedge_max_requests(&self) -> &edgelet_settings::IotedgeMaxRequests {
         unimplemented!()
     }
@@ -91,4 +102,8 @@ impl edgelet_settings::RuntimeSettings for Settings {
     fn image_garbage_collection(&self) -> &edgelet_settings::base::image::ImagePruneSettings {
         unimplemented!()
     }
+
+    fn module_cert_policy(&self) -> &[edgelet_settings::base::cert::ModuleCertRule] {
+        &self.module_cert_policy
+    }
 }
@@ -0,0 +1,45 @@
+use anyhow::anyhow;
+
+use edgelet_settings::CreateOptionsPolicy;
+
+use crate::check::{Check, CheckResult, Checker, CheckerMeta};
+
+#[derive(Default, serde::Serialize)]
+pub(crate) struct ContainerCreatePolicy {
+    policy: Option<CreateOptionsPolicy>,
+}
+
+#[async_trait::async_trait]
+impl Checker for ContainerCreatePolicy {
+    fn meta(&self) -> CheckerMeta {
+        CheckerMeta {
+            id: "container-create-policy",
+            description: "production readiness: container create options policy",
+        }
+    }
+
+    async fn execute(&mut self, check: &mut Check) -> CheckResult {
+        self.inner_execute(check)
+    }
+}
+
+impl ContainerCreatePolicy {
+    fn inner_execute(&mut self, check: &mut Check) -> CheckResult {
+        let Some(settings) = &check.settings else {
+            return CheckResult::Skipped;
+        };
+
+        let policy = settings.moby_runtime().policy();
+        self.policy = Some(policy.clone());
+
+        if policy.is_default() {
+            return CheckResult::Warning(anyhow!(
+                "No container create options policy is configured, so deployments can bind mount any host path, \
+                 use any device, network mode or capability, and pull from any registry.\n\
+                 Configure [moby_runtime.policy] in /etc/aziot/config.toml to restrict them."
+            ));
+        }
+
+        CheckResult::Ok
+    }
+}
@@ -2,6 +2,7 @@ mod aziot_edged_version;
 mod check_agent_image;
 mod connect_management_uri;
 mod container_connect_upstream;
+mod container_create_policy;
 mod container_engine_dns;
 mod container_engine_installed;
 mod container_engine_ipv6;
@@ -18,6 +19,7 @@ pub(crate) use self::aziot_edged_version::AziotEdgedVersion;
 pub(crate) use self::check_agent_image::CheckAgentImage;
 pub(crate) use self::connect_management_uri::ConnectManagementUri;
 pub(crate) use self::container_connect_upstream::get_host_container_upstream_tests;
+pub(crate) use self::container_create_policy::ContainerCreatePolicy;
 pub(crate) use self::container_engine_dns::ContainerEngineDns;
 pub(crate) use self::container_engine_installed::ContainerEngineInstalled;
 pub(crate) use self::container_engine_ipv6::ContainerEngineIPv6;
@@ -82,6 +84,7 @@ pub(crate) fn built_in_checks() -> [(&'static str, Vec<Box<dyn Checker>>); 2] {
                 Box::<ContainerEngineDns>::default(),
                 Box::<ContainerEngineIPv6>::default(),
                 Box::<ContainerEngineLogrotate>::default(),
+                Box::<ContainerCreatePolicy>::default(),
                 Box::<EdgeAgentStorageMounted>::default(),
                 Box::<EdgeHubStorageMounted>::default(),
                 Box::<CheckAgentImage>::default(),
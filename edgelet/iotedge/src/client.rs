@@ -2,21 +2,29 @@ use std::{collections::HashMap, time::Duration};
 
 use anyhow::Context;
 use bytes::Bytes;
-use http_body_util::Empty;
+use http_body_util::{BodyExt as _, Empty, Full};
 use hyper::{Uri, body::Incoming};
 use url::Url;
 
 use edgelet_core::{
-    LogOptions, Module, ModuleRegistry, ModuleRuntime, ModuleRuntimeState, SystemInfo,
+    ExecOutput, ImageUsage, LogOptions, Module, ModuleEvent, ModuleRegistry, ModuleRuntime,
+    ModuleRuntimeState, ModuleStats, PrunedImage, PullProgress, SnapshotInfo, SystemInfo,
     SystemResources, UrlExt,
 };
-use edgelet_http::{ListModulesResponse, ModuleDetails};
+use edgelet_http::{
+    ExecRequest, ListModulesResponse, ListSnapshotsResponse, LoadImagesResponse, ModuleDetails,
+    PruneImagesResponse, SaveSnapshotRequest,
+};
 use edgelet_settings::module::Settings as ModuleSpec;
 use http_common::{Connector, ErrorBody, HttpRequest};
 
 use crate::error::Error;
 
 const API_VERSION: &str = "2020-07-07";
+const API_VERSION_2022_08_03: &str = "2022-08-03";
+
+/// Content type of the archives that are streamed to aziot-edged.
+const UPLOAD_CONTENT_TYPE: &str = "application/x-tar";
 
 #[derive(serde::Serialize, Clone)]
 pub struct MgmtConfig {}
@@ -46,6 +54,169 @@ impl MgmtClient {
         Ok(Self { connector, host })
     }
 
+    /// Runs image garbage collection now and returns the images that were removed, or in a
+    /// dry run, would be removed.
+    pub async fn prune_images(&self, dry_run: bool) -> anyhow::Result<Vec<PrunedImage>> {
+        let path = format!("/images/prune?api-version={API_VERSION_2022_08_03}&dryRun={dry_run}");
+        let uri = self.get_uri(&path)?;
+
+        let request: HttpRequest<(), _> = HttpRequest::post(self.connector.clone(), &uri, None);
+
+        let response = request
+            .json_response()
+            .await
+            .context(Error::ModuleRuntime)?;
+        let response = response
+            .parse_expect_ok::<PruneImagesResponse, ErrorBody<'_>>()
+            .context(Error::ModuleRuntime)?;
+
+        Ok(response.images)
+    }
+
+    /// Uploads the image archive at `archive` and loads its images. If `digest` is set, one of
+    /// the loaded images must have that digest.
+    pub async fn load_images(
+        &self,
+        archive: &std::path::Path,
+        digest: Option<&str>,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut query = ::url::form_urlencoded::Serializer::new(String::new());
+        query.append_pair("api-version", API_VERSION_2022_08_03);
+        if let Some(digest) = digest {
+            query.append_pair("digest", digest);
+        }
+        let uri = self.get_uri(&format!("/images/load?{}", query.finish()))?;
+
+        let mut file = std::fs::File::open(archive)
+            .with_context(|| Error::Misc(format!("could not open {}", archive.display())))?;
+        let body = upload_body(move |writer| std::io::copy(&mut file, writer).map(drop));
+
+        let req = hyper::Request::builder()
+            .method(hyper::Method::POST)
+            .uri(uri)
+            .header(hyper::header::CONTENT_TYPE, UPLOAD_CONTENT_TYPE)
+            .body(body)
+            .expect("could not build hyper::Request");
+        let client = self.connector.clone().into_client();
+        let resp = client.request(req).await.context(Error::ModuleRuntime)?;
+
+        let (hyper::http::response::Parts { status, .. }, body) = resp.into_parts();
+        let body = body
+            .collect()
+            .await
+            .context(Error::ModuleRuntime)?
+            .to_bytes();
+
+        if status.is_success() {
+            let response: LoadImagesResponse =
+                serde_json::from_slice(&body).context(Error::ModuleRuntime)?;
+
+            Ok(response.images)
+        } else {
+            let message = serde_json::from_slice::<ErrorBody<'_>>(&body)
+                .map_or_else(|_| status.to_string(), |body| body.message.into_owned());
+
+            Err(Error::Misc(format!("Could not load images: {message}")).into())
+        }
+    }
+
+    /// Pulls the image a module was created with again. Returns once the pull has finished.
+    pub async fn pull_module(&self, module: &str) -> anyhow::Result<()> {
+        let path = format!("/modules/{module}/pull?api-version={API_VERSION_2022_08_03}");
+        let uri = self.get_uri(&path)?;
+
+        let request: HttpRequest<(), _> = HttpRequest::post(self.connector.clone(), &uri, None);
+
+        request
+            .no_content_response()
+            .await
+            .context(Error::ModuleRuntime)?;
+
+        Ok(())
+    }
+
+    /// Copies the file or directory at `source` into the directory at `path` in a module. The
+    /// tar archive of `source` is built here and streamed to aziot-edged as it's written.
+    pub async fn copy_to_module(
+        &self,
+        id: &str,
+        path: &str,
+        source: &std::path::Path,
+    ) -> anyhow::Result<()> {
+        let query = ::url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("api-version", API_VERSION_2022_08_03)
+            .append_pair("path", path)
+            .finish();
+        let uri = self.get_uri(&format!("/modules/{id}/archive?{query}"))?;
+
+        let metadata = std::fs::metadata(source)
+            .with_context(|| Error::Misc(format!("could not find {}", source.display())))?;
+        let name = source
+            .file_name()
+            .ok_or_else(|| Error::Misc(format!("{} has no file name", source.display())))?
+            .to_owned();
+
+        let source = source.to_owned();
+        let body = upload_body(move |writer| {
+            let mut archive = tar::Builder::new(writer);
+            archive.follow_symlinks(false);
+
+            if metadata.is_dir() {
+                archive.append_dir_all(&name, &source)?;
+            } else {
+                archive.append_path_with_name(&source, &name)?;
+            }
+
+            archive.finish()
+        });
+
+        let req = hyper::Request::builder()
+            .method(hyper::Method::PUT)
+            .uri(uri)
+            .header(hyper::header::CONTENT_TYPE, UPLOAD_CONTENT_TYPE)
+            .body(body)
+            .expect("could not build hyper::Request");
+        let client = self.connector.clone().into_client();
+        let resp = client.request(req).await.context(Error::ModuleRuntime)?;
+
+        let (hyper::http::response::Parts { status, .. }, body) = resp.into_parts();
+        if status.is_success() {
+            Ok(())
+        } else {
+            let body = body
+                .collect()
+                .await
+                .context(Error::ModuleRuntime)?
+                .to_bytes();
+            let message = serde_json::from_slice::<ErrorBody<'_>>(&body)
+                .map_or_else(|_| status.to_string(), |body| body.message.into_owned());
+
+            Err(Error::Misc(format!("Could not copy to module {id}: {message}")).into())
+        }
+    }
+
+    /// Follows the next pull of a module's image, or the one in progress. Returns the stream
+    /// of progress updates, one line of JSON each.
+    pub async fn follow_pull(&self, module: &str) -> anyhow::Result<Incoming> {
+        let path = format!("/modules/{module}/pull?api-version={API_VERSION_2022_08_03}");
+        let uri = self.get_uri(&path)?;
+
+        let req = hyper::Request::builder()
+            .method(hyper::Method::GET)
+            .uri(uri)
+            .body(Empty::<Bytes>::new())
+            .expect("could not build hyper::Request");
+        let client = self.connector.clone().into_client();
+        let resp = client.request(req).await.context(Error::ModuleRuntime)?;
+
+        let (hyper::http::response::Parts { status, .. }, body) = resp.into_parts();
+        if status.is_success() {
+            Ok(body)
+        } else {
+            Err(Error::Misc(format!("Bad status code when following pull: {status}")).into())
+        }
+    }
+
     fn get_uri(&self, path: &str) -> anyhow::Result<String> {
         let host_str = format!("unix://{}:0{path}", self.host);
         let uri: std::result::Result<Uri, _> = host_str.parse();
@@ -184,6 +355,179 @@ impl ModuleRuntime for MgmtClient {
         unimplemented!()
     }
 
+    async fn module_stats(&self, id: &str) -> anyhow::Result<ModuleStats> {
+        let path = format!("/modules/{id}/stats?api-version={API_VERSION_2022_08_03}");
+        let uri = self.get_uri(&path)?;
+
+        let request: HttpRequest<(), _> = HttpRequest::get(self.connector.clone(), &uri);
+
+        let response = request
+            .json_response()
+            .await
+            .context(Error::ModuleRuntime)?;
+        let response = response
+            .parse_expect_ok::<ModuleStats, ErrorBody<'_>>()
+            .context(Error::ModuleRuntime)?;
+
+        Ok(response)
+    }
+
+    async fn exec(
+        &self,
+        id: &str,
+        command: Vec<String>,
+    ) -> anyhow::Result<tokio::sync::mpsc::Receiver<ExecOutput>> {
+        let path = format!("/modules/{id}/exec?api-version={API_VERSION_2022_08_03}");
+        let uri = self.get_uri(&path)?;
+
+        let body = serde_json::to_vec(&ExecRequest { command }).context(Error::ModuleRuntime)?;
+        let req = hyper::Request::builder()
+            .method(hyper::Method::POST)
+            .uri(uri)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(body)))
+            .expect("could not build hyper::Request");
+        let client = self.connector.clone().into_client();
+        let resp = client.request(req).await.context(Error::ModuleRuntime)?;
+
+        let (hyper::http::response::Parts { status, .. }, mut body) = resp.into_parts();
+        if !status.is_success() {
+            let body = body
+                .collect()
+                .await
+                .context(Error::ModuleRuntime)?
+                .to_bytes();
+            let message = serde_json::from_slice::<ErrorBody<'_>>(&body)
+                .map_or_else(|_| status.to_string(), |body| body.message.into_owned());
+
+            return Err(Error::Misc(format!("Could not run command: {message}")).into());
+        }
+
+        // Each output of the command is one line of JSON.
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+
+        tokio::spawn(async move {
+            let mut buf = Vec::new();
+
+            while let Some(frame) = body.frame().await {
+                let data = match frame {
+                    Ok(frame) => match frame.into_data() {
+                        Ok(data) => data,
+                        Err(_) => continue,
+                    },
+                    Err(err) => {
+                        let _ = tx.send(ExecOutput::Error(err.to_string())).await;
+                        return;
+                    }
+                };
+                buf.extend_from_slice(&data);
+
+                while let Some(end) = buf.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = buf.drain(..=end).collect();
+                    let output = serde_json::from_slice(&line)
+                        .unwrap_or_else(|err| ExecOutput::Error(err.to_string()));
+
+                    if tx.send(output).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+
+    async fn copy_from(&self, id: &str, path: &str) -> anyhow::Result<Incoming> {
+        let query = ::url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("api-version", API_VERSION_2022_08_03)
+            .append_pair("path", path)
+            .finish();
+        let uri = self.get_uri(&format!("/modules/{id}/archive?{query}"))?;
+
+        let req = hyper::Request::builder()
+            .method(hyper::Method::GET)
+            .uri(uri)
+            .body(Empty::<Bytes>::new())
+            .expect("could not build hyper::Request");
+        let client = self.connector.clone().into_client();
+        let resp = client.request(req).await.context(Error::ModuleRuntime)?;
+
+        let (hyper::http::response::Parts { status, .. }, body) = resp.into_parts();
+        if status.is_success() {
+            Ok(body)
+        } else {
+            let body = body
+                .collect()
+                .await
+                .context(Error::ModuleRuntime)?
+                .to_bytes();
+            let message = serde_json::from_slice::<ErrorBody<'_>>(&body)
+                .map_or_else(|_| status.to_string(), |body| body.message.into_owned());
+
+            Err(Error::Misc(format!("Could not copy {path} from module {id}: {message}")).into())
+        }
+    }
+
+    async fn copy_to(&self, _id: &str, _path: &str, _archive: Incoming) -> anyhow::Result<()> {
+        unimplemented!()
+    }
+
+    async fn events(&self) -> anyhow::Result<tokio::sync::mpsc::Receiver<ModuleEvent>> {
+        unimplemented!()
+    }
+
+    async fn save_snapshot(&self, name: &str) -> anyhow::Result<SnapshotInfo> {
+        let path = format!("/snapshots?api-version={API_VERSION_2022_08_03}");
+        let uri = self.get_uri(&path)?;
+
+        let body = SaveSnapshotRequest {
+            name: name.to_owned(),
+        };
+
+        let request = HttpRequest::post(self.connector.clone(), &uri, Some(body));
+
+        let response = request
+            .json_response()
+            .await
+            .context(Error::ModuleRuntime)?;
+        let response = response
+            .parse_expect_ok::<SnapshotInfo, ErrorBody<'_>>()
+            .context(Error::ModuleRuntime)?;
+
+        Ok(response)
+    }
+
+    async fn list_snapshots(&self) -> anyhow::Result<Vec<SnapshotInfo>> {
+        let path = format!("/snapshots?api-version={API_VERSION_2022_08_03}");
+        let uri = self.get_uri(&path)?;
+
+        let request: HttpRequest<(), _> = HttpRequest::get(self.connector.clone(), &uri);
+
+        let response = request
+            .json_response()
+            .await
+            .context(Error::ModuleRuntime)?;
+        let response = response
+            .parse_expect_ok::<ListSnapshotsResponse, ErrorBody<'_>>()
+            .context(Error::ModuleRuntime)?;
+
+        Ok(response.snapshots)
+    }
+
+    async fn restore_snapshot(&self, name: &str) -> anyhow::Result<()> {
+        let path = format!("/snapshots/{name}/restore?api-version={API_VERSION_2022_08_03}");
+        let uri = self.get_uri(&path)?;
+
+        let request: HttpRequest<(), _> = HttpRequest::post(self.connector.clone(), &uri, None);
+
+        request
+            .no_content_response()
+            .await
+            .context(Error::ModuleRuntime)?;
+
+        Ok(())
+    }
+
     fn registry(&self) -> &Self::ModuleRegistry {
         unimplemented!()
     }
@@ -216,13 +560,29 @@ impl Module for MgmtModule {
 impl ModuleRegistry for MgmtClient {
     type Config = MgmtConfig;
 
-    async fn pull(&self, _config: &Self::Config) -> anyhow::Result<()> {
+    async fn pull(
+        &self,
+        _config: &Self::Config,
+        _progress: Option<tokio::sync::mpsc::UnboundedSender<PullProgress>>,
+    ) -> anyhow::Result<()> {
         Ok(())
     }
 
     async fn remove(&self, _name: &str) -> anyhow::Result<()> {
         Ok(())
     }
+
+    async fn load(&self, _archive: Incoming, _digest: Option<&str>) -> anyhow::Result<Vec<String>> {
+        unimplemented!()
+    }
+
+    async fn image_sizes(&self) -> anyhow::Result<HashMap<String, u64>> {
+        unimplemented!()
+    }
+
+    async fn image_usage(&self) -> anyhow::Result<Vec<ImageUsage>> {
+        unimplemented!()
+    }
 }
 
 impl MgmtModule {
@@ -239,3 +599,57 @@ impl MgmtModule {
         Self { details, image }
     }
 }
+
+/// Returns a request body that streams what `write` writes. `write` runs on a blocking thread,
+/// and an error it returns fails the request.
+fn upload_body<F>(write: F) -> UploadBody
+where
+    F: FnOnce(&mut dyn std::io::Write) -> std::io::Result<()> + Send + 'static,
+{
+    let (tx, rx) = tokio::sync::mpsc::channel(4);
+
+    tokio::task::spawn_blocking(move || {
+        let mut writer = std::io::BufWriter::with_capacity(64 * 1024, ChannelWriter(tx.clone()));
+
+        if let Err(err) = write(&mut writer).and_then(|()| std::io::Write::flush(&mut writer)) {
+            drop(writer);
+            let _ = tx.blocking_send(Err(err));
+        }
+    });
+
+    UploadBody(rx)
+}
+
+struct ChannelWriter(tokio::sync::mpsc::Sender<std::io::Result<Bytes>>);
+
+impl std::io::Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Request body that streams the data sent by a `ChannelWriter`.
+struct UploadBody(tokio::sync::mpsc::Receiver<std::io::Result<Bytes>>);
+
+impl hyper::body::Body for UploadBody {
+    type Data = Bytes;
+    type Error = std::io::Error;
+
+    fn poll_frame(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<hyper::body::Frame<Self::Data>, Self::Error>>> {
+        self.get_mut()
+            .0
+            .poll_recv(cx)
+            .map(|data| data.map(|data| data.map(hyper::body::Frame::data)))
+    }
+}
@@ -215,6 +215,7 @@ async fn execute_inner(
     let super_config::Config {
         trust_bundle_cert,
         allow_elevated_docker_permissions,
+        allow_module_exec,
         auto_reprovisioning_mode,
         imported_master_encryption_key,
         additional_info,
@@ -227,6 +228,7 @@ async fn execute_inner(
         edge_ca,
         moby_runtime,
         image_garbage_collection,
+        module_cert_policy,
     } = toml::from_str(config).map_err(|err| format!("could not parse config file: {err}"))?;
 
     let aziotctl_common::config::apply::RunOutput {
@@ -325,11 +327,17 @@ async fn execute_inner(
     let edge_ca = edge_ca.unwrap_or(super_config::EdgeCa::Quickstart {
         auto_generated_edge_ca_expiry_days: 90,
         auto_renew: cert_renewal::AutoRenewConfig::default(),
+        key_algorithm: None,
         subject: None,
     });
 
     let edge_ca_config = match edge_ca {
-        super_config::EdgeCa::Issued { cert } => {
+        super_config::EdgeCa::Issued {
+            key_algorithm,
+            cert,
+        } => {
+            let key_algorithm = edge_ca_key_algorithm(key_algorithm)?;
+
             match cert.method {
                 common_config::super_config::CertIssuanceMethod::Est { url, auth } => {
                     let mut aziotcs_principal = aziot_keyd_config::Principal {
@@ -381,6 +389,7 @@ async fn execute_inner(
                         cert: Some(edgelet_settings::AZIOT_EDGED_CA_ALIAS.to_owned()),
                         key: Some(edgelet_settings::AZIOT_EDGED_CA_ALIAS.to_owned()),
                         auto_renew: Some(auto_renew),
+                        key_algorithm,
                         subject: cert.subject,
                     }
                 }
@@ -409,6 +418,7 @@ async fn execute_inner(
                         cert: Some(edgelet_settings::AZIOT_EDGED_CA_ALIAS.to_owned()),
                         key: Some(edgelet_settings::AZIOT_EDGED_CA_ALIAS.to_owned()),
                         auto_renew: Some(auto_renew),
+                        key_algorithm,
 
                         // The cert subject override is set in certd's settings and does not need
                         // to be set in Edge daemon.
@@ -431,6 +441,7 @@ async fn execute_inner(
                         cert: Some(edgelet_settings::AZIOT_EDGED_CA_ALIAS.to_owned()),
                         key: Some(edgelet_settings::AZIOT_EDGED_CA_ALIAS.to_owned()),
                         auto_renew: Some(auto_renew),
+                        key_algorithm,
 
                         // The cert subject override is set in certd's settings and does not need
                         // to be set in Edge daemon.
@@ -454,14 +465,18 @@ async fn execute_inner(
                 cert: Some(edgelet_settings::AZIOT_EDGED_CA_ALIAS.to_owned()),
                 key: Some(edgelet_settings::AZIOT_EDGED_CA_ALIAS.to_owned()),
                 auto_renew: None,
+                key_algorithm: None,
                 subject: None,
             }
         }
         super_config::EdgeCa::Quickstart {
             auto_generated_edge_ca_expiry_days,
             auto_renew,
+            key_algorithm,
             subject,
         } => {
+            let key_algorithm = edge_ca_key_algorithm(key_algorithm)?;
+
             set_quickstart_ca(
                 &mut keyd_config,
                 &mut certd_config,
@@ -474,6 +489,7 @@ async fn execute_inner(
                 cert: None,
                 key: None,
                 auto_renew: Some(auto_renew),
+                key_algorithm,
                 subject: None,
             }
         }
@@ -549,6 +565,8 @@ async fn execute_inner(
 
             allow_elevated_docker_permissions: allow_elevated_docker_permissions.unwrap_or(true),
 
+            allow_module_exec,
+
             iotedge_max_requests,
 
             agent,
@@ -561,22 +579,36 @@ async fn execute_inner(
             endpoints: Default::default(),
 
             image_garbage_collection,
+
+            module_cert_policy,
         },
 
         moby_runtime: {
             let super_config::MobyRuntime {
                 uri,
                 network,
+                engine,
                 content_trust,
+                module_limits,
+                policy,
+                registry_mirrors,
+                credential_providers,
+                pulls,
             } = moby_runtime;
 
             edgelet_settings::MobyRuntime {
                 uri,
                 network,
+                engine,
+                module_limits,
+                policy,
+                registry_mirrors,
+                credential_providers,
+                pulls,
                 content_trust: content_trust
                     .map(
                         |content_trust| -> Result<_, std::borrow::Cow<'static, str>> {
-                            let super_config::ContentTrust { ca_certs } = content_trust;
+                            let super_config::ContentTrust { ca_certs, servers } = content_trust;
 
                             Ok(edgelet_settings::ContentTrust {
                                 ca_certs: ca_certs
@@ -596,6 +628,7 @@ async fn execute_inner(
                                         Ok(new_ca_certs)
                                     })
                                     .transpose()?,
+                                servers,
                             })
                         },
                     )
@@ -653,6 +686,22 @@ async fn execute_inner(
     })
 }
 
+// The Edge CA key is generated by keyd, which supports fewer algorithms than module certs.
+fn edge_ca_key_algorithm(
+    key_algorithm: Option<edgelet_settings::base::KeyAlgorithm>,
+) -> Result<Option<edgelet_settings::base::KeyAlgorithm>, std::borrow::Cow<'static, str>> {
+    if let Some(key_algorithm) = key_algorithm
+        && key_algorithm.keyd_name().is_none()
+    {
+        return Err(format!(
+            "edge_ca.key_algorithm {key_algorithm} is not supported for the Edge CA; use rsa-2048, rsa-4096, ec-p256 or ec-p384"
+        )
+        .into());
+    }
+
+    Ok(key_algorithm)
+}
+
 fn set_quickstart_ca(
     keyd_config: &mut aziot_keyd_config::Config,
     certd_config: &mut aziot_certd_config::Config,
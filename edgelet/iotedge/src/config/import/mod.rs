@@ -346,6 +346,7 @@ fn execute_inner(
                     Some(super_config::EdgeCa::Quickstart {
                         auto_generated_edge_ca_expiry_days: auto_generated_ca_lifetime_days.into(),
                         auto_renew: cert_renewal::AutoRenewConfig::default(),
+                        key_algorithm: None,
                         subject: None,
                     }),
                     None,
@@ -359,6 +360,8 @@ fn execute_inner(
     let config = super_config::Config {
         allow_elevated_docker_permissions: None,
 
+        allow_module_exec: false,
+
         trust_bundle_cert,
 
         auto_reprovisioning_mode,
@@ -491,6 +494,7 @@ fn execute_inner(
             edgelet_settings::uri::Listen {
                 workload_uri,
                 management_uri,
+                metrics_uri: None,
             }
         },
 
@@ -505,6 +509,7 @@ fn execute_inner(
                         edgelet_settings::watchdog::MaxRetries::Num(num)
                     }
                 },
+                ..Default::default()
             }
         },
 
@@ -557,6 +562,15 @@ fn execute_inner(
                     }
                 },
 
+                // Old configs predate engine selection and always targeted Moby.
+                engine: edgelet_settings::ContainerEngine::Moby,
+
+                module_limits: edgelet_settings::ModuleLimits::default(),
+                policy: edgelet_settings::CreateOptionsPolicy::default(),
+                registry_mirrors: Vec::new(),
+                credential_providers: Vec::new(),
+                pulls: edgelet_settings::PullSettings::default(),
+
                 content_trust: content_trust
                     .map(
                         |content_trust| -> Result<_, std::borrow::Cow<'static, str>> {
@@ -582,6 +596,7 @@ fn execute_inner(
                                         Ok(new_ca_certs)
                                     })
                                     .transpose()?,
+                                servers: Default::default(),
                             })
                         },
                     )
@@ -589,6 +604,7 @@ fn execute_inner(
             }
         },
         image_garbage_collection: ImagePruneSettings::default(),
+        module_cert_policy: Vec::new(),
     };
 
     let config =
@@ -35,6 +35,8 @@ To reconfigure IoT Edge, run:
     let config = super_config::Config {
         allow_elevated_docker_permissions: None,
 
+        allow_module_exec: false,
+
         trust_bundle_cert: None,
 
         auto_reprovisioning_mode:
@@ -96,6 +98,8 @@ To reconfigure IoT Edge, run:
         moby_runtime: Default::default(),
 
         image_garbage_collection: Default::default(),
+
+        module_cert_policy: Vec::new(),
     };
     let config = toml::to_string(&config)
         .map_err(|err| format!("could not serialize system config: {err}"))?;
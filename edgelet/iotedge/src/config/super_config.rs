@@ -15,6 +15,9 @@ pub struct Config {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub allow_elevated_docker_permissions: Option<bool>,
 
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_module_exec: bool,
+
     #[serde(default = "edgelet_settings::base::aziot::AutoReprovisioningMode::default")]
     pub auto_reprovisioning_mode: edgelet_settings::base::aziot::AutoReprovisioningMode,
 
@@ -61,6 +64,9 @@ pub struct Config {
 
     #[serde(default, skip_serializing_if = "image::ImagePruneSettings::is_default")]
     pub image_garbage_collection: image::ImagePruneSettings,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub module_cert_policy: Vec<edgelet_settings::base::cert::ModuleCertRule>,
 }
 
 pub fn default_agent() -> edgelet_settings::ModuleSpec<edgelet_settings::DockerConfig> {
@@ -86,6 +92,9 @@ pub fn default_agent() -> edgelet_settings::ModuleSpec<edgelet_settings::DockerC
 #[serde(untagged)]
 pub enum EdgeCa {
     Issued {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key_algorithm: Option<edgelet_settings::base::KeyAlgorithm>,
+
         #[serde(flatten)]
         cert: Box<common_config::super_config::CertIssuanceOptions>,
     },
@@ -102,6 +111,9 @@ pub enum EdgeCa {
         )]
         auto_renew: cert_renewal::AutoRenewConfig,
 
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key_algorithm: Option<edgelet_settings::base::KeyAlgorithm>,
+
         #[serde(flatten, skip_serializing_if = "Option::is_none")]
         subject: Option<aziot_certd_config::CertSubject>,
     },
@@ -111,8 +123,32 @@ pub enum EdgeCa {
 pub struct MobyRuntime {
     pub uri: Url,
     pub network: edgelet_settings::MobyNetwork,
+    #[serde(
+        default,
+        skip_serializing_if = "edgelet_settings::ContainerEngine::is_default"
+    )]
+    pub engine: edgelet_settings::ContainerEngine,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content_trust: Option<ContentTrust>,
+    #[serde(
+        default,
+        skip_serializing_if = "edgelet_settings::ModuleLimits::is_default"
+    )]
+    pub module_limits: edgelet_settings::ModuleLimits,
+    #[serde(
+        default,
+        skip_serializing_if = "edgelet_settings::CreateOptionsPolicy::is_default"
+    )]
+    pub policy: edgelet_settings::CreateOptionsPolicy,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub registry_mirrors: Vec<edgelet_settings::RegistryMirror>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub credential_providers: Vec<edgelet_settings::CredentialProvider>,
+    #[serde(
+        default,
+        skip_serializing_if = "edgelet_settings::PullSettings::is_default"
+    )]
+    pub pulls: edgelet_settings::PullSettings,
 }
 
 impl Default for MobyRuntime {
@@ -126,7 +162,13 @@ impl Default for MobyRuntime {
             network: edgelet_settings::MobyNetwork::Name(
                 edgelet_settings::DEFAULT_NETWORKID.to_owned(),
             ),
+            engine: edgelet_settings::ContainerEngine::default(),
             content_trust: None,
+            module_limits: edgelet_settings::ModuleLimits::default(),
+            policy: edgelet_settings::CreateOptionsPolicy::default(),
+            registry_mirrors: Vec::new(),
+            credential_providers: Vec::new(),
+            pulls: edgelet_settings::PullSettings::default(),
         }
     }
 }
@@ -134,4 +176,6 @@ impl Default for MobyRuntime {
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct ContentTrust {
     pub ca_certs: Option<BTreeMap<String, Url>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub servers: BTreeMap<String, Url>,
 }
@@ -0,0 +1,176 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use anyhow::Context;
+use bytes::{Buf, Bytes};
+use http_body_util::BodyExt;
+
+use edgelet_core::ModuleRuntime;
+
+use crate::MgmtClient;
+use crate::error::Error;
+
+/// A path given to `iotedge cp`: either on the device, or in a module as `MODULE:PATH`.
+pub enum CopyPath {
+    Local(PathBuf),
+    Module { module: String, path: String },
+}
+
+impl CopyPath {
+    pub fn parse(s: &str) -> Self {
+        // As with docker cp, paths that start with / or . are always on the device, so that
+        // paths that contain a colon can be given.
+        if !s.starts_with('/')
+            && !s.starts_with('.')
+            && let Some((module, path)) = s.split_once(':')
+            && !module.is_empty()
+        {
+            CopyPath::Module {
+                module: module.to_owned(),
+                path: path.to_owned(),
+            }
+        } else {
+            CopyPath::Local(s.into())
+        }
+    }
+}
+
+pub struct CopyFiles {
+    source: CopyPath,
+    destination: CopyPath,
+    client: MgmtClient,
+}
+
+impl CopyFiles {
+    pub fn new(source: CopyPath, destination: CopyPath, client: MgmtClient) -> Self {
+        CopyFiles {
+            source,
+            destination,
+            client,
+        }
+    }
+
+    pub async fn execute(self) -> anyhow::Result<()> {
+        match (self.source, self.destination) {
+            (CopyPath::Module { module, path }, CopyPath::Local(destination)) => {
+                let archive = self
+                    .client
+                    .copy_from(&module, &path)
+                    .await
+                    .with_context(|| format!("Could not copy {path} from module {module}"))?;
+
+                if destination.as_os_str() == "-" {
+                    write_archive(archive).await
+                } else {
+                    extract_archive(archive, destination).await
+                }
+            }
+
+            (CopyPath::Local(source), CopyPath::Module { module, path }) => {
+                // The archive is named after the source, so paths such as . are resolved first.
+                let source = source.canonicalize().with_context(|| {
+                    Error::Misc(format!("Could not find {}", source.display()))
+                })?;
+
+                self.client
+                    .copy_to_module(&module, &path, &source)
+                    .await
+                    .with_context(|| {
+                        format!("Could not copy {} to module {module}", source.display())
+                    })?;
+
+                Ok(())
+            }
+
+            _ => Err(Error::Misc(
+                "Exactly one of the source and destination must be a path in a module, such as edgeHub:/tmp"
+                    .to_owned(),
+            )
+            .into()),
+        }
+    }
+}
+
+/// Writes a tar archive to stdout as it's received.
+async fn write_archive(mut archive: hyper::body::Incoming) -> anyhow::Result<()> {
+    let mut stdout = std::io::stdout();
+
+    while let Some(frame) = archive.frame().await {
+        let frame = frame.context(Error::ModuleRuntime)?;
+        if let Ok(data) = frame.into_data() {
+            stdout.write_all(&data).context(Error::WriteToStdout)?;
+        }
+    }
+
+    stdout.flush().context(Error::WriteToStdout)?;
+
+    Ok(())
+}
+
+/// Extracts a tar archive into a directory as it's received.
+async fn extract_archive(
+    mut archive: hyper::body::Incoming,
+    destination: PathBuf,
+) -> anyhow::Result<()> {
+    if !destination.is_dir() {
+        return Err(Error::Misc(format!(
+            "{} is not a directory; give an existing directory or - to write the tar archive to stdout",
+            destination.display()
+        ))
+        .into());
+    }
+
+    let (tx, rx) = tokio::sync::mpsc::channel(4);
+    let extract = tokio::task::spawn_blocking(move || {
+        tar::Archive::new(ChannelReader {
+            rx,
+            data: Bytes::new(),
+        })
+        .unpack(&destination)
+        .with_context(|| Error::Misc(format!("Could not extract into {}", destination.display())))
+    });
+
+    while let Some(frame) = archive.frame().await {
+        let data = match frame {
+            Ok(frame) => match frame.into_data() {
+                Ok(data) => Ok(data),
+                Err(_) => continue,
+            },
+            Err(err) => Err(std::io::Error::other(err)),
+        };
+
+        // Extraction stopped early, and reports why.
+        if tx.send(data).await.is_err() {
+            break;
+        }
+    }
+    drop(tx);
+
+    extract.await.context("extraction task panicked")?
+}
+
+/// Reads the data received from a channel. An error received from the channel ends the data
+/// with that error rather than looking like the end of the data.
+struct ChannelReader {
+    rx: tokio::sync::mpsc::Receiver<std::io::Result<Bytes>>,
+    data: Bytes,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.data.is_empty() {
+            match self.rx.blocking_recv() {
+                Some(data) => self.data = data?,
+                None => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.data.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data.advance(len);
+
+        Ok(len)
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io::Write;
+
+use anyhow::Context;
+
+use edgelet_core::{ExecOutput, ModuleRuntime};
+
+use crate::error::Error;
+
+pub struct Exec<M> {
+    module: String,
+    command: Vec<String>,
+    runtime: M,
+}
+
+impl<M> Exec<M> {
+    pub fn new(module: String, command: Vec<String>, runtime: M) -> Self {
+        Exec {
+            module,
+            command,
+            runtime,
+        }
+    }
+}
+
+impl<M> Exec<M>
+where
+    M: ModuleRuntime,
+{
+    /// Runs the command and returns its exit code.
+    pub async fn execute(self) -> anyhow::Result<i32> {
+        let mut output = self
+            .runtime
+            .exec(&self.module, self.command)
+            .await
+            .with_context(|| format!("Could not run command in module {}", self.module))?;
+
+        let mut stdout = std::io::stdout();
+        let mut stderr = std::io::stderr();
+
+        while let Some(output) = output.recv().await {
+            match output {
+                ExecOutput::Stdout(data) => {
+                    stdout
+                        .write_all(data.as_bytes())
+                        .and_then(|()| stdout.flush())
+                        .context(Error::WriteToStdout)?;
+                }
+                ExecOutput::Stderr(data) => {
+                    // There is nowhere left to report a failure to write to stderr.
+                    let _ = stderr.write_all(data.as_bytes());
+                }
+                ExecOutput::Exit(code) => {
+                    // Exit codes that don't fit a process exit code are reported as a failure.
+                    return Ok(i32::try_from(code).unwrap_or(1));
+                }
+                ExecOutput::Error(err) => {
+                    return Err(Error::Misc(format!(
+                        "Could not read the output of the command: {err}"
+                    ))
+                    .into());
+                }
+            }
+        }
+
+        Err(
+            Error::Misc("aziot-edged ended the stream before the command finished".to_owned())
+                .into(),
+        )
+    }
+}
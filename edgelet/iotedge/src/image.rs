@@ -0,0 +1,123 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use chrono_humanize::{Accuracy, HumanTime, Tense};
+use tabwriter::TabWriter;
+
+use crate::MgmtClient;
+use crate::error::Error;
+use crate::stats::format_bytes;
+
+pub struct ImportImage<W> {
+    archive: PathBuf,
+    digest: Option<String>,
+    client: MgmtClient,
+    output: Arc<Mutex<W>>,
+}
+
+impl<W> ImportImage<W> {
+    pub fn new(archive: PathBuf, digest: Option<String>, client: MgmtClient, output: W) -> Self {
+        ImportImage {
+            archive,
+            digest,
+            client,
+            output: Arc::new(Mutex::new(output)),
+        }
+    }
+}
+
+impl<W> ImportImage<W>
+where
+    W: Write + Send,
+{
+    pub async fn execute(&self) -> anyhow::Result<()> {
+        let images = self
+            .client
+            .load_images(&self.archive, self.digest.as_deref())
+            .await?;
+
+        let write = self.output.clone();
+        let mut w = write.lock().unwrap();
+        for image in images {
+            writeln!(w, "Loaded {image}").context(Error::WriteToStdout)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct PruneImages<W> {
+    dry_run: bool,
+    client: MgmtClient,
+    output: Arc<Mutex<TabWriter<W>>>,
+}
+
+impl<W> PruneImages<W>
+where
+    W: Write,
+{
+    pub fn new(dry_run: bool, client: MgmtClient, output: W) -> Self {
+        let tab = TabWriter::new(output).minwidth(15);
+        PruneImages {
+            dry_run,
+            client,
+            output: Arc::new(Mutex::new(tab)),
+        }
+    }
+
+    pub async fn execute(self) -> anyhow::Result<()> {
+        let images = self.client.prune_images(self.dry_run).await?;
+
+        let write = self.output.clone();
+        let mut w = write.lock().unwrap();
+
+        if images.is_empty() {
+            writeln!(w, "No images to remove").context(Error::WriteToStdout)?;
+            w.flush().context(Error::WriteToStdout)?;
+            return Ok(());
+        }
+
+        if self.dry_run {
+            writeln!(w, "Images that would be removed:").context(Error::WriteToStdout)?;
+        } else {
+            writeln!(w, "Removed images:").context(Error::WriteToStdout)?;
+        }
+
+        writeln!(w, "IMAGE ID\tLAST USED\tSIZE").context(Error::WriteToStdout)?;
+        let mut total_size = 0;
+        for image in images {
+            let last_used = HumanTime::from(
+                i64::try_from(image.last_used_secs)
+                    .ok()
+                    .and_then(chrono::Duration::try_seconds)
+                    .unwrap_or(chrono::Duration::MAX),
+            )
+            .to_text_en(Accuracy::Rough, Tense::Past);
+            let size = image
+                .size
+                .map_or_else(|| "unknown".to_owned(), format_bytes);
+            total_size += image.size.unwrap_or_default();
+
+            writeln!(w, "{}\t{last_used}\t{size}", image.id).context(Error::WriteToStdout)?;
+        }
+        w.flush().context(Error::WriteToStdout)?;
+
+        if self.dry_run {
+            writeln!(
+                w,
+                "\nTotal space that would be reclaimed: {}",
+                format_bytes(total_size)
+            )
+        } else {
+            writeln!(w, "\nTotal space reclaimed: {}", format_bytes(total_size))
+        }
+        .context(Error::WriteToStdout)?;
+        w.flush().context(Error::WriteToStdout)?;
+
+        Ok(())
+    }
+}
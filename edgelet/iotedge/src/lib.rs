@@ -5,20 +5,32 @@ use serde::Deserialize;
 mod check;
 mod client;
 pub mod config;
+mod cp;
 mod error;
+mod exec;
+mod image;
 mod list;
 mod logs;
+mod pull;
 mod restart;
+mod snapshot;
+mod stats;
 mod support_bundle;
 mod system;
 mod version;
 
 pub use crate::check::{Check, OutputFormat};
 pub use crate::client::{MgmtClient, MgmtModule};
+pub use crate::cp::{CopyFiles, CopyPath};
 pub use crate::error::{Error, FetchLatestVersionsReason};
+pub use crate::exec::Exec;
+pub use crate::image::{ImportImage, PruneImages};
 pub use crate::list::List;
 pub use crate::logs::Logs;
+pub use crate::pull::Pull;
 pub use crate::restart::Restart;
+pub use crate::snapshot::{ListSnapshots, RestoreSnapshot, SaveSnapshot};
+pub use crate::stats::Stats;
 pub use crate::support_bundle::SupportBundleCommand;
 pub use crate::system::System;
 pub use crate::version::Version;
@@ -101,6 +101,15 @@ fn humanize_status(status: &ModuleStatus) -> String {
 
             "Up".to_string()
         }
+        ModuleStatusEnum::Unhealthy => {
+            if let Some(start_time) = &status.start_time
+                && let Ok(time) = DateTime::parse_from_rfc3339(start_time)
+            {
+                return format!("Up {} (unhealthy)", format_time(time, Tense::Present));
+            }
+
+            "Up (unhealthy)".to_string()
+        }
     }
 }
 
@@ -14,8 +14,9 @@ use edgelet_core::{LogOptions, LogTail, parse_since};
 use support_bundle::OutputLocation;
 
 use iotedge::{
-    Check, Error, List, Logs, MgmtClient, OutputFormat, Restart, SupportBundleCommand, System,
-    Version,
+    Check, CopyFiles, CopyPath, Error, Exec, ImportImage, List, ListSnapshots, Logs, MgmtClient,
+    OutputFormat, PruneImages, Pull, Restart, RestoreSnapshot, SaveSnapshot, Stats,
+    SupportBundleCommand, System, Version,
 };
 
 #[tokio::main]
@@ -261,6 +262,94 @@ async fn run() -> anyhow::Result<()> {
                         .index(1),
                 ),
         )
+        .subcommand(
+            Command::new("image")
+                .about("Manage module images")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("import")
+                        .about("Load the images in an image archive, such as one created by docker save, without pulling them from a registry")
+                        .arg(
+                            Arg::new("ARCHIVE")
+                                .help("Path of the image archive, which is uploaded to aziot-edged")
+                                .required(true)
+                                .value_parser(clap::value_parser!(PathBuf))
+                                .index(1),
+                        )
+                        .arg(
+                            Arg::new("digest")
+                                .help("Digest that one of the loaded images must have, such as sha256:...")
+                                .long("digest")
+                                .num_args(1)
+                                .value_name("DIGEST"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("prune")
+                        .about("Remove unused images now, as scheduled image garbage collection would")
+                        .arg(
+                            Arg::new("dry-run")
+                                .long("dry-run")
+                                .num_args(0)
+                                .help("List the images that would be removed without removing them"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("snapshot")
+                .about("Save the running modules and recreate them later, such as after a deployment breaks the device's connectivity")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("save")
+                        .about("Save the specs and images of the running modules, replacing any snapshot of the same name")
+                        .arg(
+                            Arg::new("NAME")
+                                .help("Sets the name of the snapshot")
+                                .required(true)
+                                .index(1),
+                        ),
+                )
+                .subcommand(Command::new("list").about("List saved snapshots"))
+                .subcommand(
+                    Command::new("restore")
+                        .about("Replace all modules with the modules in a snapshot, recreated from the images they were running when it was saved")
+                        .arg(
+                            Arg::new("NAME")
+                                .help("Sets the name of the snapshot to restore")
+                                .required(true)
+                                .index(1),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("pull")
+                .about("Pull the image a module was created with again and show the progress of the pull")
+                .arg(
+                    Arg::new("MODULE")
+                        .help("Sets the module identity to pull the image of")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Display the CPU, memory, network and block IO usage of modules")
+                .arg(
+                    Arg::new("MODULE")
+                        .help("Sets the module identities to show stats for. Defaults to all modules")
+                        .num_args(0..)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("follow")
+                        .short('f')
+                        .long("follow")
+                        .num_args(0)
+                        .help("Keep refreshing the stats"),
+                ),
+        )
         .subcommand(
             Command::new("logs")
                 .about("Fetch the logs of a module")
@@ -301,6 +390,39 @@ async fn run() -> anyhow::Result<()> {
                         .help("Follow output log"),
                 ),
         )
+        .subcommand(
+            Command::new("exec")
+                .about("Run a command in a module and exit with its exit code. Requires allow_module_exec in config.toml. Example: iotedge exec edgeHub -- ls /tmp")
+                .arg(
+                    Arg::new("MODULE")
+                        .help("Sets the module identity to run the command in")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("COMMAND")
+                        .help("The command and its arguments")
+                        .required(true)
+                        .last(true)
+                        .num_args(1..),
+                ),
+        )
+        .subcommand(
+            Command::new("cp")
+                .about("Copy files between the device and a module. Requires allow_module_exec in config.toml. Example: iotedge cp edgeHub:/tmp/file.txt .")
+                .arg(
+                    Arg::new("SOURCE")
+                        .help("MODULE:PATH to copy from a module, or a path on the device to copy to a module")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("DESTINATION")
+                        .help("A directory on the device to extract into, or - to write a tar archive to stdout, when copying from a module. MODULE:DIRECTORY when copying to a module")
+                        .required(true)
+                        .index(2),
+                ),
+        )
         .subcommand(
             Command::new("system")
                 .about("Manage system services for IoT Edge.")
@@ -512,6 +634,78 @@ async fn run() -> anyhow::Result<()> {
             .execute()
             .await
         }
+        ("image", args) => match args
+            .subcommand()
+            .expect("Command::subcommand_required was set, but ArgMatches::subcommand was None")
+        {
+            ("import", args) => {
+                let archive = args
+                    .get_one::<PathBuf>("ARCHIVE")
+                    .expect("arg is required")
+                    .clone();
+                let digest = args.get_one::<String>("digest").cloned();
+
+                ImportImage::new(archive, digest, runtime()?, io::stdout())
+                    .execute()
+                    .await
+            }
+            ("prune", args) => {
+                PruneImages::new(args.get_flag("dry-run"), runtime()?, io::stdout())
+                    .execute()
+                    .await
+            }
+            (command, _) => {
+                eprintln!("Unknown image subcommand: {command}");
+                std::process::exit(1);
+            }
+        },
+        ("snapshot", args) => match args
+            .subcommand()
+            .expect("Command::subcommand_required was set, but ArgMatches::subcommand was None")
+        {
+            ("save", args) => {
+                SaveSnapshot::new(
+                    args.get_one::<String>("NAME").unwrap().clone(),
+                    runtime()?,
+                    io::stdout(),
+                )
+                .execute()
+                .await
+            }
+            ("list", _) => ListSnapshots::new(runtime()?, io::stdout()).execute().await,
+            ("restore", args) => {
+                RestoreSnapshot::new(
+                    args.get_one::<String>("NAME").unwrap().clone(),
+                    runtime()?,
+                    io::stdout(),
+                )
+                .execute()
+                .await
+            }
+            (command, _) => {
+                eprintln!("Unknown snapshot subcommand: {command}");
+                std::process::exit(1);
+            }
+        },
+        ("pull", args) => {
+            Pull::new(
+                args.get_one::<String>("MODULE").unwrap().clone(),
+                runtime()?,
+                io::stdout(),
+            )
+            .execute()
+            .await
+        }
+        ("stats", args) => {
+            let modules = args
+                .get_many::<String>("MODULE")
+                .map_or_else(Vec::new, |modules| modules.cloned().collect());
+            let follow = args.get_flag("follow");
+
+            Stats::new(modules, follow, runtime()?, io::stdout())
+                .execute()
+                .await
+        }
         ("logs", args) => {
             let id = args.get_one::<String>("MODULE").unwrap().clone();
             let follow = args.get_flag("follow");
@@ -542,6 +736,29 @@ async fn run() -> anyhow::Result<()> {
 
             Logs::new(id, options, runtime()?).execute().await
         }
+        ("exec", args) => {
+            let module = args.get_one::<String>("MODULE").unwrap().clone();
+            let command = args
+                .get_many::<String>("COMMAND")
+                .expect("arg is required")
+                .cloned()
+                .collect();
+
+            let code = Exec::new(module, command, runtime()?).execute().await?;
+            if code != 0 {
+                process::exit(code);
+            }
+
+            Ok(())
+        }
+        ("cp", args) => {
+            let source = CopyPath::parse(args.get_one::<String>("SOURCE").unwrap());
+            let destination = CopyPath::parse(args.get_one::<String>("DESTINATION").unwrap());
+
+            CopyFiles::new(source, destination, runtime()?)
+                .execute()
+                .await
+        }
         ("system", args) => (match args
             .subcommand()
             .expect("Command::subcommand_required was set, but ArgMatches::subcommand was None")
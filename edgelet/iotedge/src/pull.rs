@@ -0,0 +1,188 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::Context;
+use http_body_util::BodyExt;
+
+use edgelet_core::PullProgress;
+use edgelet_http::ModulePullProgress;
+
+use crate::MgmtClient;
+use crate::error::Error;
+use crate::stats::format_bytes;
+
+const BAR_WIDTH: usize = 30;
+
+pub struct Pull<W> {
+    module: String,
+    client: MgmtClient,
+    output: W,
+}
+
+impl<W> Pull<W>
+where
+    W: Write,
+{
+    pub fn new(module: String, client: MgmtClient, output: W) -> Self {
+        Pull {
+            module,
+            client,
+            output,
+        }
+    }
+
+    pub async fn execute(self) -> anyhow::Result<()> {
+        let Pull {
+            module,
+            client,
+            mut output,
+        } = self;
+
+        // Follow the module's pulls before starting this one, so that none of its progress is
+        // missed.
+        let mut body = client.follow_pull(&module).await?;
+
+        writeln!(output, "Pulling the image for module {module}...")
+            .context(Error::WriteToStdout)?;
+        output.flush().context(Error::WriteToStdout)?;
+
+        // The request returns once the pull has finished, after its last progress update was
+        // sent. An error starting the pull, such as an unknown module, ends the command.
+        let pull = client.pull_module(&module);
+        tokio::pin!(pull);
+        let mut pulled = false;
+
+        let mut layers = Layers::default();
+        let mut buf = Vec::new();
+
+        loop {
+            let frame = tokio::select! {
+                result = &mut pull, if !pulled => {
+                    if let Err(err) = result {
+                        writeln!(output).context(Error::WriteToStdout)?;
+                        return Err(err);
+                    }
+
+                    pulled = true;
+                    continue;
+                }
+                frame = body.frame() => frame,
+            };
+
+            let Some(frame) = frame else {
+                break;
+            };
+            let frame = frame.context(Error::ModuleRuntime)?;
+            let Ok(data) = frame.into_data() else {
+                continue;
+            };
+            buf.extend_from_slice(&data);
+
+            while let Some(end) = buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buf.drain(..=end).collect();
+                let update: ModulePullProgress =
+                    serde_json::from_slice(&line).context(Error::ModuleRuntime)?;
+
+                if let Some(progress) = &update.progress {
+                    layers.update(progress);
+                    write!(output, "\r{}", layers.render()).context(Error::WriteToStdout)?;
+                    output.flush().context(Error::WriteToStdout)?;
+                }
+
+                if update.done {
+                    writeln!(output).context(Error::WriteToStdout)?;
+
+                    return match update.error {
+                        Some(err) => Err(Error::Misc(format!(
+                            "Pulling the image for module {module} failed: {err}"
+                        ))
+                        .into()),
+                        None => {
+                            writeln!(output, "Pulled the image for module {module}")
+                                .context(Error::WriteToStdout)?;
+                            Ok(())
+                        }
+                    };
+                }
+            }
+        }
+
+        writeln!(output).context(Error::WriteToStdout)?;
+        Err(Error::Misc("aziot-edged ended the stream before the pull finished".to_owned()).into())
+    }
+}
+
+/// The state of each layer of the image being pulled, keyed by layer ID.
+#[derive(Default)]
+struct Layers(BTreeMap<String, Layer>);
+
+#[derive(Default)]
+struct Layer {
+    downloaded: u64,
+    size: Option<u64>,
+    extracting: bool,
+    complete: bool,
+}
+
+impl Layers {
+    fn update(&mut self, progress: &PullProgress) {
+        // Progress that is not about a layer, such as the resolved digest, has no ID or is
+        // not one of the layer states below.
+        let Some(id) = &progress.id else {
+            return;
+        };
+
+        match progress.status.as_str() {
+            "Pulling fs layer" | "Waiting" => {
+                self.0.entry(id.clone()).or_default();
+            }
+            "Downloading" => {
+                let layer = self.0.entry(id.clone()).or_default();
+                layer.downloaded = progress.current.unwrap_or(layer.downloaded);
+                layer.size = progress.total.or(layer.size);
+            }
+            "Verifying Checksum" | "Download complete" | "Extracting" => {
+                let layer = self.0.entry(id.clone()).or_default();
+                if let Some(size) = layer.size {
+                    layer.downloaded = size;
+                }
+                layer.extracting = progress.status == "Extracting";
+            }
+            "Pull complete" | "Already exists" => {
+                let layer = self.0.entry(id.clone()).or_default();
+                if let Some(size) = layer.size {
+                    layer.downloaded = size;
+                }
+                layer.extracting = false;
+                layer.complete = true;
+            }
+            _ => (),
+        }
+    }
+
+    fn render(&self) -> String {
+        let downloaded: u64 = self.0.values().map(|layer| layer.downloaded).sum();
+        let size: u64 = self.0.values().filter_map(|layer| layer.size).sum();
+        let extracting = self.0.values().filter(|layer| layer.extracting).count();
+        let complete = self.0.values().filter(|layer| layer.complete).count();
+
+        let percent = if size == 0 {
+            0
+        } else {
+            (downloaded.saturating_mul(100) / size).min(100)
+        };
+        let filled = usize::try_from(percent).unwrap_or_default() * BAR_WIDTH / 100;
+
+        // Trailing spaces clear what is left of a longer previous line.
+        format!(
+            "[{}{}] {percent:>3}% {} / {}, {extracting} extracting, {complete}/{} layers complete    ",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            format_bytes(downloaded),
+            format_bytes(size),
+            self.0.len(),
+        )
+    }
+}
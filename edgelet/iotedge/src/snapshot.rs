@@ -0,0 +1,142 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use chrono::Utc;
+use chrono_humanize::{Accuracy, HumanTime, Tense};
+use tabwriter::TabWriter;
+
+use edgelet_core::ModuleRuntime;
+
+use crate::error::Error;
+
+pub struct SaveSnapshot<M, W> {
+    name: String,
+    runtime: M,
+    output: Arc<Mutex<W>>,
+}
+
+impl<M, W> SaveSnapshot<M, W> {
+    pub fn new(name: String, runtime: M, output: W) -> Self {
+        SaveSnapshot {
+            name,
+            runtime,
+            output: Arc::new(Mutex::new(output)),
+        }
+    }
+}
+
+impl<M, W> SaveSnapshot<M, W>
+where
+    M: ModuleRuntime,
+    W: Write + Send,
+{
+    pub async fn execute(&self) -> anyhow::Result<()> {
+        let snapshot = self
+            .runtime
+            .save_snapshot(&self.name)
+            .await
+            .with_context(|| format!("Could not save snapshot {}", self.name))?;
+
+        let write = self.output.clone();
+        let mut w = write.lock().unwrap();
+        writeln!(
+            w,
+            "Saved snapshot {} of {}",
+            snapshot.name,
+            snapshot.modules.join(", ")
+        )
+        .context(Error::WriteToStdout)?;
+
+        Ok(())
+    }
+}
+
+pub struct ListSnapshots<M, W> {
+    runtime: M,
+    output: Arc<Mutex<TabWriter<W>>>,
+}
+
+impl<M, W> ListSnapshots<M, W>
+where
+    W: Write,
+{
+    pub fn new(runtime: M, output: W) -> Self {
+        let tab = TabWriter::new(output).minwidth(15);
+        ListSnapshots {
+            runtime,
+            output: Arc::new(Mutex::new(tab)),
+        }
+    }
+}
+
+impl<M, W> ListSnapshots<M, W>
+where
+    M: ModuleRuntime,
+    W: Write + Send,
+{
+    pub async fn execute(&self) -> anyhow::Result<()> {
+        let snapshots = self
+            .runtime
+            .list_snapshots()
+            .await
+            .context("Could not list snapshots")?;
+
+        let write = self.output.clone();
+        let mut w = write.lock().unwrap();
+
+        writeln!(w, "NAME\tCREATED\tMODULES").context(Error::WriteToStdout)?;
+        for snapshot in snapshots {
+            let created = HumanTime::from(Utc::now().signed_duration_since(snapshot.created))
+                .to_text_en(Accuracy::Rough, Tense::Past);
+
+            writeln!(
+                w,
+                "{}\t{created}\t{}",
+                snapshot.name,
+                snapshot.modules.join(", ")
+            )
+            .context(Error::WriteToStdout)?;
+        }
+        w.flush().context(Error::WriteToStdout)?;
+
+        Ok(())
+    }
+}
+
+pub struct RestoreSnapshot<M, W> {
+    name: String,
+    runtime: M,
+    output: Arc<Mutex<W>>,
+}
+
+impl<M, W> RestoreSnapshot<M, W> {
+    pub fn new(name: String, runtime: M, output: W) -> Self {
+        RestoreSnapshot {
+            name,
+            runtime,
+            output: Arc::new(Mutex::new(output)),
+        }
+    }
+}
+
+impl<M, W> RestoreSnapshot<M, W>
+where
+    M: ModuleRuntime,
+    W: Write + Send,
+{
+    pub async fn execute(&self) -> anyhow::Result<()> {
+        self.runtime
+            .restore_snapshot(&self.name)
+            .await
+            .with_context(|| format!("Could not restore snapshot {}", self.name))?;
+
+        let write = self.output.clone();
+        let mut w = write.lock().unwrap();
+        writeln!(w, "Restored snapshot {}", self.name).context(Error::WriteToStdout)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io::Write;
+
+use anyhow::Context;
+use tabwriter::TabWriter;
+
+use edgelet_core::{Module, ModuleRuntime, ModuleStats};
+
+use crate::error::Error;
+
+pub struct Stats<M, W> {
+    modules: Vec<String>,
+    follow: bool,
+    runtime: M,
+    output: TabWriter<W>,
+}
+
+impl<M, W> Stats<M, W>
+where
+    W: Write,
+{
+    pub fn new(modules: Vec<String>, follow: bool, runtime: M, output: W) -> Self {
+        let output = TabWriter::new(output).minwidth(15);
+        Stats {
+            modules,
+            follow,
+            runtime,
+            output,
+        }
+    }
+}
+
+impl<M, W> Stats<M, W>
+where
+    M: ModuleRuntime,
+    W: Write,
+{
+    pub async fn execute(mut self) -> anyhow::Result<()> {
+        let modules = if self.modules.is_empty() {
+            let mut modules: Vec<_> = self
+                .runtime
+                .list()
+                .await
+                .context(Error::ModuleRuntime)?
+                .iter()
+                .map(|module| module.name().to_owned())
+                .collect();
+            modules.sort();
+            modules
+        } else {
+            std::mem::take(&mut self.modules)
+        };
+
+        loop {
+            let mut stats = Vec::with_capacity(modules.len());
+            for module in &modules {
+                let module_stats = self
+                    .runtime
+                    .module_stats(module)
+                    .await
+                    .context(Error::ModuleRuntime)?;
+                stats.push(module_stats);
+            }
+
+            self.write(&stats)?;
+
+            // Each query takes about a second, so there is no need to wait between
+            // refreshes.
+            if !self.follow {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, stats: &[ModuleStats]) -> anyhow::Result<()> {
+        let w = &mut self.output;
+
+        writeln!(
+            w,
+            "NAME\tCPU %\tMEM USAGE / LIMIT\tMEM %\tNET I/O\tBLOCK I/O\tPIDS"
+        )
+        .context(Error::WriteToStdout)?;
+
+        for stats in stats {
+            #[allow(clippy::cast_precision_loss)]
+            let memory_percent = if stats.memory_limit == 0 {
+                0.0
+            } else {
+                stats.memory_usage as f64 / stats.memory_limit as f64 * 100.0
+            };
+
+            writeln!(
+                w,
+                "{}\t{:.2}%\t{} / {}\t{:.2}%\t{} / {}\t{} / {}\t{}",
+                stats.name,
+                stats.cpu_percent,
+                format_bytes(stats.memory_usage),
+                format_bytes(stats.memory_limit),
+                memory_percent,
+                format_bytes(stats.network_rx_bytes),
+                format_bytes(stats.network_tx_bytes),
+                format_bytes(stats.block_read_bytes),
+                format_bytes(stats.block_write_bytes),
+                stats.pids,
+            )
+            .context(Error::WriteToStdout)?;
+        }
+
+        // Separate refreshes when following.
+        if self.follow {
+            writeln!(w).context(Error::WriteToStdout)?;
+        }
+
+        w.flush().context(Error::WriteToStdout)?;
+
+        Ok(())
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes}B")
+    } else {
+        format!("{value:.1}{}", UNITS[unit])
+    }
+}